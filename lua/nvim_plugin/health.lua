@@ -0,0 +1,13 @@
+-- `:checkhealth nvim_plugin`, reporting what `health()` found.
+local M = {}
+
+function M.check()
+  for _, section in ipairs(require("nvim_plugin").health()) do
+    vim.health.start(section.name)
+    for _, item in ipairs(section.items) do
+      vim.health[item.level](item.message)
+    end
+  end
+end
+
+return M
@@ -0,0 +1,131 @@
+//! Viewers allowed to use every chat command whatever their badges, and
+//! viewers blocked from all of them, managed with `:TwitchAccess`. Both
+//! lists are kept in the plugin state.
+
+use std::collections::BTreeSet;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::{tr, tr_with},
+    ui::float,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 4] = ["allow", "block", "remove", "list"];
+
+const STATE_KEY: &str = "access";
+
+/// Kept on the plugin as well, since every chat command is checked
+/// against them.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Lists {
+    #[serde(default)]
+    allowed: BTreeSet<String>,
+    #[serde(default)]
+    blocked: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Past the badge checks.
+    Allowed,
+    Blocked,
+    /// Up to the badges.
+    Badges,
+}
+
+/// Reads the lists from the plugin state.
+pub fn read(plugin: &mut Plugin) {
+    plugin.access = plugin.state.get(STATE_KEY).unwrap_or_default();
+}
+
+pub fn of(plugin: &Plugin, user: &str) -> Access {
+    let lists = &plugin.access;
+    let user = user.to_lowercase();
+
+    if lists.blocked.contains(&user) {
+        Access::Blocked
+    } else if lists.allowed.contains(&user) {
+        Access::Allowed
+    } else {
+        Access::Badges
+    }
+}
+
+/// Runs `:TwitchAccess allow|block|remove <user>` or `:TwitchAccess list`.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    let (command, user) = match args {
+        [] => return show(plugin),
+        [command] if command == "list" => return show(plugin),
+        [command, user] if SUBCOMMANDS[..3].contains(&command.as_str()) => (
+            command.as_str(),
+            user.trim_start_matches('@').to_lowercase(),
+        ),
+        _ => {
+            api::notify(&tr("access.usage"), LogLevel::Error, &NotifyOpts::default())?;
+            return Ok(());
+        }
+    };
+
+    let lists = &mut plugin.access;
+    lists.allowed.remove(&user);
+    lists.blocked.remove(&user);
+
+    let key = match command {
+        "allow" => {
+            lists.allowed.insert(user.clone());
+            "access.allowed"
+        }
+        "block" => {
+            lists.blocked.insert(user.clone());
+            "access.blocked"
+        }
+        _ => "access.removed",
+    };
+
+    plugin.state.set(STATE_KEY, &plugin.access);
+
+    // Written right away like imported commands, these are deliberate.
+    if let Err(e) = plugin.state.flush() {
+        plugin.errors.push("State", &e);
+    }
+
+    api::notify(
+        &tr_with(key, &[("user", &user)]),
+        LogLevel::Info,
+        &NotifyOpts::default(),
+    )?;
+
+    Ok(())
+}
+
+fn show(plugin: &Plugin) -> Result<()> {
+    let lists = &plugin.access;
+
+    let mut lines = Vec::new();
+
+    for (title, users) in [
+        (tr("access.allowed_title"), &lists.allowed),
+        (tr("access.blocked_title"), &lists.blocked),
+    ] {
+        if users.is_empty() {
+            continue;
+        }
+        lines.push(format!(
+            "{}: {}",
+            title,
+            users.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    if lines.is_empty() {
+        lines.push(tr("access.none"));
+    }
+
+    float::show(&tr("access.title"), lines)
+}
@@ -0,0 +1,134 @@
+//! A countdown to the next scheduled ad break, and `:TwitchAd` to snooze it
+//! or start one right away.
+
+use chrono::{TimeDelta, Utc};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    commands,
+    i18n::tr_with,
+    twitch::{
+        ads::{AdAction, AdSchedule, MAX_LENGTH},
+        Outgoing,
+    },
+    ui::StatusFloat,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 2] = ["snooze", "run"];
+
+/// How long before a break the countdown shows up.
+const WARNING: TimeDelta = TimeDelta::minutes(5);
+
+pub struct Ads {
+    schedule: Option<AdSchedule>,
+    /// Whether the coming break was announced in accessibility mode, which
+    /// has no countdown.
+    warned: bool,
+    countdown: StatusFloat,
+}
+
+impl Default for Ads {
+    fn default() -> Self {
+        Ads {
+            schedule: None,
+            warned: false,
+            // Above the raffle counter.
+            countdown: StatusFloat::new(1),
+        }
+    }
+}
+
+impl Ads {
+    pub fn update(&mut self, mut schedule: AdSchedule) {
+        // A break that already began doesn't count down again when the
+        // schedule still reports it.
+        if schedule
+            .next_at
+            .is_some_and(|next_at| next_at <= Utc::now())
+        {
+            schedule.next_at = None;
+        }
+
+        let next_at = self.schedule.as_ref().and_then(|schedule| schedule.next_at);
+
+        if schedule.next_at != next_at {
+            self.warned = false;
+        }
+
+        self.schedule = Some(schedule);
+    }
+}
+
+/// Runs `:TwitchAd snooze` or `:TwitchAd run <length>`.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    let action = match args {
+        [command] if command == "snooze" => AdAction::Snooze,
+        [command, length] if command == "run" => match length.parse() {
+            Ok(length) if (1..=MAX_LENGTH).contains(&length) => AdAction::Run(length),
+            _ => return usage(),
+        },
+        _ => return usage(),
+    };
+
+    let _ = plugin.outgoing.send(Outgoing::Ad(action));
+
+    Ok(())
+}
+
+/// Updates the countdown, called every second or so.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let Some(schedule) = &mut plugin.ads.schedule else {
+        return Ok(());
+    };
+    let Some(next_at) = schedule.next_at else {
+        return plugin.ads.countdown.close();
+    };
+
+    let left = next_at - Utc::now();
+
+    // Announced once EventSub tells it began, cleared until the schedule
+    // brings the following break.
+    if left <= TimeDelta::zero() {
+        schedule.next_at = None;
+        return plugin.ads.countdown.close();
+    }
+
+    if left > WARNING {
+        return plugin.ads.countdown.close();
+    }
+
+    let seconds = left.num_seconds();
+    let text = format!(
+        "📺 {}",
+        tr_with(
+            "ads.countdown",
+            &[
+                ("left", &format!("{}:{:02}", seconds / 60, seconds % 60)),
+                ("length", &schedule.duration),
+                ("snoozes", &schedule.snoozes),
+            ],
+        )
+    );
+
+    match plugin.config.accessibility.enabled {
+        true if plugin.ads.warned => Ok(()),
+        true => {
+            plugin.ads.warned = true;
+            commands::announce(plugin, &text)
+        }
+        false => {
+            let zindex = plugin.chat.zindex();
+            plugin.ads.countdown.show(&text, zindex)
+        }
+    }
+}
+
+fn usage() -> Result<()> {
+    let message = tr_with("ads.usage", &[("max", &MAX_LENGTH)]);
+    api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! `approve = true`: chat commands that change the editor wait in a float
+//! listing exactly what each would do, until the streamer approves it with
+//! `y` or rejects it with `n`. Several can be decided at once by selecting
+//! their lines, all of them rejected with `X`, and approved only with
+//! `:TwitchApprovals approve_all`.
+
+use std::collections::VecDeque;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+use crate::{
+    commands::{self, Command},
+    i18n::{tr, tr_with},
+    redemptions,
+    twitch::Outgoing,
+    ui::float,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 4] = ["approve", "reject", "approve_all", "reject_all"];
+
+/// Commands beyond this many waiting are dropped.
+const MAX_PENDING: usize = 20;
+
+struct Held {
+    channel: String,
+    author: String,
+    /// What the command would do, like `colorscheme gruvbox`.
+    action: String,
+    command: Command,
+}
+
+#[derive(Default)]
+pub struct Approvals {
+    /// The oldest first, in the order of the float's lines.
+    pending: VecDeque<Held>,
+    window: Option<(Window, Buffer)>,
+}
+
+/// What `command` would change in the editor, if it is one that waits for
+/// approval.
+pub fn action(plugin: &Plugin, command: &Command) -> Option<(String, String, String)> {
+    let (channel, author, action) = match command {
+        Command::ColorScheme {
+            channel,
+            author,
+            name,
+            background,
+            ..
+        } => (
+            channel,
+            author,
+            match background {
+                Some(background) => format!("colorscheme {} --bg={}", name.trim(), background),
+                None => format!("colorscheme {}", name.trim()),
+            },
+        ),
+        Command::TryThis {
+            channel,
+            author,
+            name,
+            ..
+        } => (channel, author, format!("trythis {}", name.trim())),
+        Command::Highlight {
+            channel,
+            author,
+            group,
+            fg,
+            bg,
+            ..
+        } => (
+            channel,
+            author,
+            match bg {
+                Some(bg) => format!("hl {} {} {}", group, fg, bg),
+                None => format!("hl {} {}", group, fg),
+            },
+        ),
+        Command::Goto {
+            channel,
+            author,
+            file,
+            line,
+            ..
+        } => (channel, author, format!("goto {} {}", file, line)),
+        Command::SetOption {
+            channel,
+            author,
+            name,
+            value,
+            ..
+        } => (channel, author, format!("opt {} {}", name, value)),
+        Command::Font {
+            channel,
+            author,
+            font,
+            ..
+        } => (channel, author, format!("font {}", font)),
+        Command::Snippet {
+            channel,
+            author,
+            name,
+            ..
+        } => (channel, author, format!("snippet {}", name)),
+        Command::Redemption {
+            channel,
+            user,
+            reward,
+            ..
+        } => (channel, user, redemptions::action(plugin, reward)?),
+        _ => return None,
+    };
+
+    Some((channel.clone(), author.clone(), action))
+}
+
+/// Holds `command` back for approval if it needs it, returning it again if
+/// it doesn't.
+pub fn hold(plugin: &mut Plugin, command: Command) -> Result<Option<Command>> {
+    if !plugin.config.approve {
+        return Ok(Some(command));
+    }
+    let Some((channel, author, action)) = action(plugin, &command) else {
+        return Ok(Some(command));
+    };
+
+    if plugin.approvals.pending.len() < MAX_PENDING {
+        plugin.approvals.pending.push_back(Held {
+            channel,
+            author,
+            action,
+            command,
+        });
+        render(plugin)?;
+    }
+
+    Ok(None)
+}
+
+/// Runs `:[range]TwitchApprovals [approve|reject|approve_all|reject_all]`.
+/// In the float the range picks the commands, elsewhere it is the oldest
+/// one. Without a subcommand the float is opened again.
+pub fn run(plugin: &mut Plugin, args: Option<&str>, lines: (usize, usize)) -> Result<()> {
+    let count = plugin.approvals.pending.len();
+    let picked = match &plugin.approvals.window {
+        Some((window, _)) if *window == api::get_current_win() => {
+            lines.0.saturating_sub(1)..lines.1.min(count)
+        }
+        _ => 0..count.min(1),
+    };
+
+    match args.map(str::trim).unwrap_or_default() {
+        "" => render(plugin),
+        "approve" => decide(plugin, picked, true),
+        "reject" => decide(plugin, picked, false),
+        "approve_all" => decide(plugin, 0..count, true),
+        "reject_all" => decide(plugin, 0..count, false),
+        _ => notify(&tr("approvals.usage"), LogLevel::Error),
+    }
+}
+
+fn decide(plugin: &mut Plugin, picked: std::ops::Range<usize>, approved: bool) -> Result<()> {
+    if picked.is_empty() {
+        return notify(&tr("approvals.nothing_pending"), LogLevel::Info);
+    }
+
+    let decided: Vec<Held> = plugin.approvals.pending.drain(picked).collect();
+
+    // Closed first, so what the commands open ends up in the window the
+    // float was opened from.
+    close(plugin)?;
+
+    for held in decided {
+        match approved {
+            true => commands::execute(plugin, held.command),
+            false => {
+                let text = tr_with(
+                    "approvals.rejected",
+                    &[("user", &held.author), ("action", &held.action)],
+                );
+                let _ = plugin.outgoing.send(Outgoing::Reply {
+                    channel: held.channel,
+                    text,
+                });
+            }
+        }
+    }
+
+    match plugin.approvals.pending.is_empty() {
+        true => Ok(()),
+        false => render(plugin),
+    }
+}
+
+/// Shows what is waiting, in the open float if there is one so it doesn't
+/// take the cursor again.
+fn render(plugin: &mut Plugin) -> Result<()> {
+    let mut lines: Vec<String> = plugin
+        .approvals
+        .pending
+        .iter()
+        .map(|held| format!("{}: {}", held.author, held.action))
+        .collect();
+    if lines.is_empty() {
+        return notify(&tr("approvals.nothing_pending"), LogLevel::Info);
+    }
+    lines.push(String::new());
+    lines.push(tr("approvals.hint"));
+
+    if let Some((window, buffer)) = &mut plugin.approvals.window {
+        if window.is_valid() && buffer.is_valid() {
+            buffer.set_lines(.., false, lines.iter().map(String::as_str))?;
+            let rows = api::get_option_value::<u32>("lines", &OptionOpts::default())?;
+            window.set_height((lines.len() as u32).min(rows.saturating_sub(4)).max(1))?;
+            return Ok(());
+        }
+    }
+
+    let keys = [
+        (Mode::Normal, "y", "<cmd>TwitchApprovals approve<cr>"),
+        (Mode::Normal, "n", "<cmd>TwitchApprovals reject<cr>"),
+        (Mode::Visual, "y", ":TwitchApprovals approve<cr>"),
+        (Mode::Visual, "n", ":TwitchApprovals reject<cr>"),
+        (Mode::Normal, "X", "<cmd>TwitchApprovals reject_all<cr>"),
+    ];
+    plugin.approvals.window = Some(float::confirm(
+        &tr("approvals.title"),
+        lines,
+        plugin.config.window.focus,
+        &keys,
+    )?);
+
+    Ok(())
+}
+
+fn close(plugin: &mut Plugin) -> Result<()> {
+    if let Some((window, _)) = plugin.approvals.window.take() {
+        if window.is_valid() {
+            window.close(true)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Every change chat made to the editor, who made it and whether it went
+//! through. `:TwitchAudit` lists them and `:TwitchUndo` reverts the latest
+//! one that can be reverted and wasn't yet.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Object, Result,
+};
+
+use crate::{
+    commands,
+    i18n::{tr, tr_with},
+    keymaps, options, suggest, Plugin,
+};
+
+/// Older entries are dropped, and can no longer be undone.
+const MAX_ENTRIES: usize = 500;
+
+/// How a change is reverted.
+pub enum Undo {
+    /// Back to this colorscheme, `None` if none was loaded.
+    Colorscheme(Option<String>),
+    /// The latest `!hl` change, kept in the `HighlightLog`.
+    Highlight,
+    /// The latest mapping accepted from chat, kept in `Keymaps`.
+    Keymap,
+    /// Back to what an option was before `!opt` or `!font`.
+    Option { name: String, previous: Object },
+    /// Back to the buffer and cursor a window had before a file was opened
+    /// in it.
+    Jump {
+        window: Window,
+        buffer: Buffer,
+        cursor: (usize, usize),
+    },
+    /// Back to the `lines` an accepted `!suggest` replaced with `count`
+    /// new ones from line `start` on, 0-based.
+    Edit {
+        buffer: Buffer,
+        start: usize,
+        lines: Vec<String>,
+        count: usize,
+    },
+}
+
+impl Undo {
+    /// Where the current window is, for changes that open a file in it.
+    pub fn jump() -> Result<Self> {
+        let window = api::get_current_win();
+
+        Ok(Undo::Jump {
+            buffer: window.get_buf()?,
+            cursor: window.get_cursor()?,
+            window,
+        })
+    }
+}
+
+struct Entry {
+    timestamp: DateTime<Local>,
+    channel: String,
+    author: String,
+    /// What was done, like `colorscheme gruvbox`.
+    action: String,
+    /// Why it failed, if it did.
+    error: Option<String>,
+    undo: Option<Undo>,
+    undone: bool,
+}
+
+/// The changes of this session, oldest first.
+#[derive(Default)]
+pub struct Audit {
+    entries: VecDeque<Entry>,
+}
+
+impl Audit {
+    /// Records a change `author` made from chat and how it went. Failed
+    /// changes are never undone.
+    pub fn record(
+        &mut self,
+        channel: &str,
+        author: &str,
+        action: String,
+        result: &Result<()>,
+        undo: Option<Undo>,
+    ) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(Entry {
+            timestamp: Local::now(),
+            channel: channel.to_owned(),
+            author: author.to_owned(),
+            action,
+            error: result.as_ref().err().map(ToString::to_string),
+            undo: result.is_ok().then_some(undo).flatten(),
+            undone: false,
+        });
+    }
+}
+
+/// Runs `:TwitchUndo`, reverting the latest change chat made that can be.
+pub fn undo(plugin: &mut Plugin) -> Result<()> {
+    let Some(index) = plugin
+        .audit
+        .entries
+        .iter()
+        .rposition(|entry| entry.undo.is_some() && !entry.undone)
+    else {
+        return notify(&tr("audit.nothing_to_undo"), LogLevel::Info);
+    };
+
+    let entry = &mut plugin.audit.entries[index];
+    let message = tr_with(
+        "audit.undone",
+        &[("action", &entry.action), ("user", &entry.author)],
+    );
+
+    // Taken even if reverting fails, it would only fail again.
+    match entry.undo.take() {
+        Some(Undo::Colorscheme(previous)) => {
+            // Back for good, so no `!trythis` switches away from it later.
+            plugin.trials.clear();
+            commands::apply_colorscheme(plugin, previous.as_deref().unwrap_or("default"))?;
+        }
+        Some(Undo::Highlight) => plugin.highlights.revert_last()?,
+        Some(Undo::Keymap) => keymaps::revert_last(plugin)?,
+        Some(Undo::Option { name, previous }) => options::restore(&name, previous)?,
+        Some(Undo::Edit {
+            buffer,
+            start,
+            lines,
+            count,
+        }) => suggest::restore(buffer, start, lines, count)?,
+        // Left be if the window or buffer is gone by now.
+        Some(Undo::Jump {
+            mut window,
+            buffer,
+            cursor: (line, col),
+        }) if window.is_valid() && buffer.is_valid() => {
+            window.set_buf(&buffer)?;
+            window.set_cursor(line.min(buffer.line_count()?.max(1)), col)?;
+        }
+        Some(Undo::Jump { .. }) | None => (),
+    }
+
+    plugin.audit.entries[index].undone = true;
+    notify(&message, LogLevel::Info)
+}
+
+/// Runs `:TwitchAudit`, opening the changes in a read-only scratch buffer.
+pub fn show(plugin: &Plugin) -> Result<()> {
+    let entries = &plugin.audit.entries;
+    let width = |column: fn(&Entry) -> usize| entries.iter().map(column).max().unwrap_or(0);
+    let channels = width(|entry| entry.channel.len() + 1);
+    let authors = width(|entry| entry.author.len());
+    let actions = width(|entry| entry.action.chars().count());
+
+    let mut lines: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let result = match (&entry.error, entry.undone) {
+                (Some(error), _) => tr_with("audit.failed", &[("error", error)]),
+                (None, true) => tr("audit.was_undone"),
+                (None, false) => tr("audit.ok"),
+            };
+
+            format!(
+                "{}  {:<channels$}  {:<authors$}  {:<actions$}  {}",
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                format!("#{}", entry.channel),
+                entry.author,
+                entry.action,
+                result,
+            )
+        })
+        .collect();
+
+    if lines.is_empty() {
+        lines.push(tr("audit.none"));
+    }
+
+    let mut buffer = api::create_buf(false, true)?;
+    buffer.set_lines(.., false, lines.iter().map(String::as_str))?;
+
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    api::set_option_value("modifiable", false, &opts)?;
+    api::set_option_value("filetype", "twitchaudit", &opts)?;
+
+    api::command("split")?;
+    api::get_current_win().set_buf(&buffer)?;
+    api::command("normal! G")?;
+
+    // Syntax belongs to the current buffer, so it is set once it is shown.
+    api::command(r"syntax match TwitchTimestamp /\v^\S+ \S+/")?;
+
+    Ok(())
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
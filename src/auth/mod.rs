@@ -0,0 +1,77 @@
+mod store;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+};
+
+use serde::{Deserialize, Serialize};
+
+pub use store::{clear, load, save, Backend};
+
+use crate::{config::AccountOptions, paths::Paths};
+
+/// The account `:TwitchLogin <nick>` stores, which also reads the chat.
+pub const MAIN: &str = "main";
+
+/// The nick and OAuth token used for authenticated connections.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub login: String,
+    pub token: String,
+    /// Trades an expired token for a new one, when the token came with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn new(login: &str, token: &str) -> Self {
+        Credentials {
+            login: login.to_lowercase(),
+            token: token.trim().trim_start_matches("oauth:").to_owned(),
+            refresh_token: None,
+        }
+    }
+
+    /// Credentials from the environment, the login is looked up when the
+    /// token is validated.
+    pub fn from_env(token: &str) -> Self {
+        Credentials::new("", token)
+    }
+}
+
+/// The credentials of each of `accounts`, from its `token_env` variable when
+/// that is set and from the store otherwise. Accounts without any are left
+/// out, the errors of those that couldn't be read are returned with them.
+///
+/// Blocks, since the keychain backends run on their own executor.
+pub fn load_all(
+    accounts: &BTreeMap<String, AccountOptions>,
+    paths: &Paths,
+) -> (HashMap<String, Credentials>, Vec<String>) {
+    let mut loaded = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (account, options) in accounts {
+        let token = options
+            .token_env
+            .as_ref()
+            .and_then(|name| env::var(name).ok())
+            .filter(|token| !token.trim().is_empty());
+
+        if let Some(token) = token {
+            loaded.insert(account.clone(), Credentials::from_env(&token));
+            continue;
+        }
+
+        match load(paths, account) {
+            Ok(Some(credentials)) => {
+                loaded.insert(account.clone(), credentials);
+            }
+            Ok(None) => (),
+            Err(message) => errors.push(format!("{}: {}", account, message)),
+        }
+    }
+
+    (loaded, errors)
+}
@@ -0,0 +1,137 @@
+//! Credentials live in the OS keychain (Secret Service, Keychain or
+//! Credential Manager). When no keychain is available they are kept in a file
+//! encrypted with a key derived from the machine and user, which keeps the
+//! token out of plain sight but is no substitute for a real keychain.
+
+use std::{env, fs, io, path::Path};
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use keyring::Entry;
+use sha2::{Digest, Sha256};
+
+use super::Credentials;
+use crate::paths::Paths;
+
+const SERVICE: &str = "nvim-twitch";
+/// The keyring entry of the main account, from before there were others.
+const MAIN_ENTRY: &str = "default";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Backend {
+    Keyring,
+    EncryptedFile,
+}
+
+pub fn load(paths: &Paths, account: &str) -> Result<Option<Credentials>, String> {
+    let secret = match entry(account).and_then(|entry| entry.get_password()) {
+        Ok(secret) => Some(secret),
+        Err(_) => read_file(&paths.credentials_file(account))?,
+    };
+
+    secret
+        .map(|secret| serde_json::from_str(&secret).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+pub fn save(paths: &Paths, account: &str, credentials: &Credentials) -> Result<Backend, String> {
+    let secret = serde_json::to_string(credentials).map_err(|e| e.to_string())?;
+
+    if let Ok(()) = entry(account).and_then(|entry| entry.set_password(&secret)) {
+        return Ok(Backend::Keyring);
+    }
+
+    write_file(&paths.credentials_file(account), &secret)?;
+
+    Ok(Backend::EncryptedFile)
+}
+
+/// Removes the account's credentials from every backend.
+pub fn clear(paths: &Paths, account: &str) -> Result<(), String> {
+    match entry(account).and_then(|entry| entry.delete_credential()) {
+        Ok(())
+        | Err(keyring::Error::NoEntry)
+        | Err(keyring::Error::NoStorageAccess(_))
+        | Err(keyring::Error::PlatformFailure(_)) => (),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    match fs::remove_file(paths.credentials_file(account)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.to_string()),
+        _ => (),
+    }
+
+    Ok(())
+}
+
+fn entry(account: &str) -> keyring::Result<Entry> {
+    match account {
+        super::MAIN => Entry::new(SERVICE, MAIN_ENTRY),
+        account => Entry::new(SERVICE, account),
+    }
+}
+
+fn cipher() -> ChaCha20Poly1305 {
+    let machine = fs::read_to_string("/etc/machine-id")
+        .or_else(|_| fs::read_to_string("/var/lib/dbus/machine-id"))
+        .unwrap_or_default();
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(SERVICE);
+    hasher.update(machine.trim());
+    hasher.update(user);
+
+    ChaCha20Poly1305::new(Key::from_slice(&hasher.finalize()))
+}
+
+fn read_file(path: &Path) -> Result<Option<String>, String> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    if data.len() < NONCE_LEN {
+        return Err(format!("{} is corrupted", path.display()));
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+    let plaintext = cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| format!("{} could not be decrypted", path.display()))?;
+
+    String::from_utf8(plaintext)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+fn write_file(path: &Path, secret: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher()
+        .encrypt(&nonce, secret.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+
+    fs::write(path, data).map_err(|e| e.to_string())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,411 @@
+//! `backend = "websocket"`: chat from clients on `127.0.0.1:<port>` rather
+//! than from Twitch, so OBS scripts, overlays and tests can drive the plugin
+//! without a live connection. Clients either open a WebSocket and send text
+//! messages, or write lines over plain TCP. Each message is a JSON object as
+//! taken by `inject()`, `{ author, text, channel }`, or plain text said by
+//! `local`. Whatever the plugin sends to chat goes back to every client the
+//! same way, as `{ account, channel, text, action }`.
+//!
+//! Every web page open in a browser can reach the loopback interface, so a
+//! client has to show the `backend_token` first: as `?token=` or an
+//! `Authorization: Bearer` header on the WebSocket upgrade, or as the first
+//! line over TCP. Upgrades from a browser page are refused unless its origin
+//! is in `backend_origins`. Whatever a client claims, its messages count as
+//! a viewer's without badges.
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    protocol::WebSocketConfig,
+    Message,
+};
+
+use super::{ChatBackend, ChatEvent};
+use crate::twitch::ChatMessage;
+
+/// The author of messages sent as plain text.
+const LOCAL_AUTHOR: &str = "local";
+
+/// Longer messages and lines close the connection, no chat message comes
+/// near it.
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// What a client has to show before it is listened to.
+#[derive(Debug, Clone)]
+pub struct Admission {
+    pub token: String,
+    /// Origins of the browser pages allowed to connect, such as
+    /// `http://localhost:8080`.
+    pub origins: Vec<String>,
+}
+
+pub struct LocalBackend {
+    listener: TcpListener,
+    /// Where messages that don't name a channel are said.
+    channel: String,
+    admission: Admission,
+    /// What every client read, kept here so receiving never ends.
+    events: (UnboundedSender<ChatEvent>, UnboundedReceiver<ChatEvent>),
+    /// One per client, dropped once the client is gone.
+    clients: Vec<UnboundedSender<String>>,
+    joined: bool,
+}
+
+#[derive(Serialize)]
+struct Sent<'a> {
+    account: &'a str,
+    channel: String,
+    text: String,
+    action: bool,
+}
+
+impl LocalBackend {
+    /// Listens on `port` on the loopback interface only, for the clients
+    /// that pass `admission`.
+    pub async fn bind(port: u16, channel: String, admission: Admission) -> Result<Self, String> {
+        if admission.token.is_empty() {
+            return Err("set backend_token, which clients have to send first".to_owned());
+        }
+
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| format!("can't listen on port {}: {}", port, e))?;
+
+        Ok(LocalBackend {
+            listener,
+            channel,
+            admission,
+            events: mpsc::unbounded_channel(),
+            clients: Vec::new(),
+            joined: false,
+        })
+    }
+}
+
+impl ChatBackend for LocalBackend {
+    /// Joined right away, then whatever the clients send. The listener stays
+    /// up, so this never ends.
+    async fn next_event(&mut self) -> Option<ChatEvent> {
+        if !self.joined {
+            self.joined = true;
+            return Some(ChatEvent::Joined);
+        }
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    // A failed accept, say with too many open files, leaves
+                    // the listener be.
+                    let Ok((stream, _)) = accepted else {
+                        continue;
+                    };
+
+                    let (client, replies) = mpsc::unbounded_channel();
+                    self.clients.push(client);
+                    let client = Client {
+                        channel: self.channel.clone(),
+                        events: self.events.0.clone(),
+                    };
+                    tokio::spawn(serve(stream, client, self.admission.clone(), replies));
+                }
+                Some(event) = self.events.1.recv() => return Some(event),
+            }
+        }
+    }
+
+    async fn send(
+        &mut self,
+        account: &str,
+        channel: String,
+        text: String,
+        action: bool,
+    ) -> Result<(), String> {
+        let sent = Sent {
+            account,
+            channel,
+            text,
+            action,
+        };
+        let line = serde_json::to_string(&sent).map_err(|e| e.to_string())?;
+
+        self.clients
+            .retain(|client| client.send(line.clone()).is_ok());
+
+        Ok(())
+    }
+
+    /// Every account is there, nothing is sent anywhere real.
+    fn can_send_as(&self, _account: &str) -> bool {
+        true
+    }
+}
+
+/// Reads one client until it goes away, as WebSocket if it starts with a
+/// handshake and as lines otherwise.
+async fn serve(
+    stream: TcpStream,
+    client: Client,
+    admission: Admission,
+    replies: UnboundedReceiver<String>,
+) {
+    let mut start = [0; 4];
+    let websocket = matches!(stream.peek(&mut start).await, Ok(4) if &start == b"GET ");
+
+    let _ = match websocket {
+        true => client.websocket(stream, &admission, replies).await,
+        false => client.lines(stream, &admission, replies).await,
+    };
+}
+
+struct Client {
+    channel: String,
+    events: UnboundedSender<ChatEvent>,
+}
+
+impl Client {
+    async fn lines(
+        &self,
+        stream: TcpStream,
+        admission: &Admission,
+        mut replies: UnboundedReceiver<String>,
+    ) -> Result<(), String> {
+        let (read, mut write) = stream.into_split();
+        let mut read = BufReader::new(read);
+        let mut partial = Vec::new();
+
+        let first = match next_line(&mut read, &mut partial).await {
+            Ok(first) => first,
+            Err(reason) => return hang_up(&mut write, reason).await,
+        };
+        if !first.is_some_and(|line| token_matches(line.trim(), &admission.token)) {
+            return write_line(&mut write, &error("the first line must be the token")).await;
+        }
+
+        loop {
+            tokio::select! {
+                line = next_line(&mut read, &mut partial) => {
+                    let line = match line {
+                        Ok(Some(line)) => line,
+                        Ok(None) => return Ok(()),
+                        Err(reason) => return hang_up(&mut write, reason).await,
+                    };
+
+                    if let Some(error) = self.received(&line) {
+                        write_line(&mut write, &error).await?;
+                    }
+                }
+                Some(reply) = replies.recv() => write_line(&mut write, &reply).await?,
+            }
+        }
+    }
+
+    // The callback's error type is tungstenite's to choose.
+    #[allow(clippy::result_large_err)]
+    async fn websocket(
+        &self,
+        stream: TcpStream,
+        admission: &Admission,
+        mut replies: UnboundedReceiver<String>,
+    ) -> Result<(), String> {
+        let config = WebSocketConfig::default()
+            .max_message_size(Some(MAX_MESSAGE))
+            .max_frame_size(Some(MAX_MESSAGE));
+        let socket = tokio_tungstenite::accept_hdr_async_with_config(
+            stream,
+            |request: &Request, response: Response| admit(request, response, admission),
+            Some(config),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let (mut write, mut read) = socket.split();
+
+        loop {
+            tokio::select! {
+                message = read.next() => {
+                    let Some(message) = message else {
+                        return Ok(());
+                    };
+
+                    // Pings are answered while reading, binary messages
+                    // mean nothing here.
+                    match message.map_err(|e| e.to_string())? {
+                        Message::Text(text) => {
+                            if let Some(error) = self.received(text.as_str()) {
+                                write.send(Message::text(error)).await.map_err(|e| e.to_string())?;
+                            }
+                        }
+                        Message::Close(_) => return Ok(()),
+                        _ => (),
+                    }
+                }
+                Some(reply) = replies.recv() => {
+                    write.send(Message::text(reply)).await.map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+
+    /// Passes a message on as said in chat, or returns why it can't be, for
+    /// the client. Only the author, text, channel, color and emote codes
+    /// are taken from it, nothing that would give it more rights.
+    fn received(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return None;
+        }
+
+        let mut message = match text.starts_with('{') {
+            true => match serde_json::from_str::<ChatMessage>(text) {
+                Ok(sent) => ChatMessage {
+                    channel: sent.channel,
+                    color: sent.color,
+                    emotes: sent.emotes,
+                    ..said(&sent.author, &sent.text)
+                },
+                Err(e) => return Some(error(&e.to_string())),
+            },
+            false => said(LOCAL_AUTHOR, text),
+        };
+
+        if message.channel.is_empty() {
+            message.channel = self.channel.clone();
+        }
+
+        let _ = self.events.send(ChatEvent::Message(Box::new(message)));
+
+        None
+    }
+}
+
+/// Completes the upgrade for clients with the token, from no browser page
+/// or an allowed one.
+#[allow(clippy::result_large_err)]
+fn admit(
+    request: &Request,
+    response: Response,
+    admission: &Admission,
+) -> Result<Response, ErrorResponse> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    if let Some(origin) = header("origin") {
+        if !admission.origins.iter().any(|allowed| allowed == origin) {
+            return Err(refuse(StatusCode::FORBIDDEN, "origin not allowed"));
+        }
+    }
+
+    let bearer = header("authorization").and_then(|value| value.strip_prefix("Bearer "));
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+
+    match bearer.or(query) {
+        Some(token) if token_matches(token, &admission.token) => Ok(response),
+        _ => Err(refuse(StatusCode::UNAUTHORIZED, "missing or wrong token")),
+    }
+}
+
+fn refuse(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_owned()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Compares in the same time whatever the bytes, so the token can't be
+/// guessed one character at a time.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn error(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn said(author: &str, text: &str) -> ChatMessage {
+    ChatMessage {
+        id: String::new(),
+        channel: String::new(),
+        author: author.to_owned(),
+        color: None,
+        text: text.to_owned(),
+        badges: Vec::new(),
+        emotes: Vec::new(),
+        emote_spans: Vec::new(),
+        bits: None,
+        reply: None,
+        paid: None,
+        source: None,
+        risk: None,
+    }
+}
+
+/// Reads the next line over plain TCP, without its line ending. What was
+/// read so far is kept in `partial`, so this can be cancelled and picked up
+/// again. A line longer than [`MAX_MESSAGE`] is an error, before it is even
+/// read to the end.
+async fn next_line(
+    read: &mut (impl AsyncBufRead + Unpin),
+    partial: &mut Vec<u8>,
+) -> Result<Option<String>, String> {
+    // One byte more than allowed, to tell a line that just fits from one
+    // that goes on.
+    let left = (MAX_MESSAGE + 1).saturating_sub(partial.len());
+    (&mut *read)
+        .take(left as u64)
+        .read_until(b'\n', partial)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut line = std::mem::take(partial);
+    match line.last() {
+        Some(b'\n') => {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        _ if line.len() > MAX_MESSAGE => {
+            return Err(format!("lines can be {} bytes at most", MAX_MESSAGE))
+        }
+        // The client is gone, with or without a last line.
+        None => return Ok(None),
+        Some(_) => {}
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| "lines must be UTF-8".to_owned())
+}
+
+/// Tells the client why it is dropped, if it still listens.
+async fn hang_up(write: &mut (impl AsyncWrite + Unpin), reason: String) -> Result<(), String> {
+    let _ = write_line(write, &error(&reason)).await;
+    Err(reason)
+}
+
+async fn write_line(write: &mut (impl AsyncWrite + Unpin), line: &str) -> Result<(), String> {
+    write
+        .write_all(format!("{}\n", line).as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    write.flush().await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,283 @@
+//! Where chat comes from. A [`ChatBackend`] turns whatever its platform
+//! sends into [`ChatEvent`]s, and the [`Relay`] hands those to the Neovim
+//! side as the same commands whichever backend they came from, so a new
+//! platform only has to implement the trait. Twitch's is in
+//! `twitch/backend.rs`, [`LocalBackend`] reads local clients instead, and
+//! tests use `twitch::MockChat`.
+
+mod local;
+
+use std::future::Future;
+
+use futures_util::{stream::FuturesOrdered, StreamExt};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Semaphore,
+};
+
+use crate::{
+    commands::{Command, CommandSender, SharedSyntax},
+    config::Config,
+    moderation::Moderator,
+    twitch::{parse_command, ChatMessage, GiftTrains, Lookups},
+    ui::PluginError,
+};
+
+pub use local::{Admission, LocalBackend};
+
+/// Messages scored at once while moderation is on.
+const SCORING: usize = 4;
+
+/// Messages waiting to be scored, beyond which they are passed on in their
+/// turn without a score, as when the endpoint fails.
+const MAX_UNSCORED: usize = 200;
+
+/// Something that happened in chat, whatever the platform.
+#[derive(Debug)]
+pub enum ChatEvent {
+    /// Joined a channel, again after reconnecting too.
+    Joined,
+    /// Boxed, it is far larger than the rest.
+    Message(Box<ChatMessage>),
+    Subscription {
+        channel: String,
+        user: String,
+        months: u64,
+        resub: bool,
+    },
+    Raid {
+        channel: String,
+        from: String,
+        viewers: u64,
+    },
+    /// A sub gifted to `recipient`, by no one in particular if `gifter` is
+    /// unset.
+    Gift {
+        channel: String,
+        gifter: Option<String>,
+        tier: String,
+        recipient: String,
+    },
+    /// `count` subs gifted at once, before each of them arrives as a
+    /// `Gift`.
+    MysteryGift {
+        channel: String,
+        gifter: Option<String>,
+        count: u64,
+        tier: String,
+    },
+    Milestone {
+        channel: String,
+        user: String,
+        category: String,
+        value: u64,
+    },
+    /// A private message to the logged in account, not tied to a channel.
+    Whisper { from: String, text: String },
+}
+
+/// A chat connection. The connection loop races both methods against its
+/// timers, so `next_event` has to be cancel safe: dropping it before it
+/// returned must not lose an event.
+pub trait ChatBackend {
+    /// The next event, `None` once the connection is gone and has to be
+    /// made again.
+    fn next_event(&mut self) -> impl Future<Output = Option<ChatEvent>> + Send;
+
+    /// Sends `text` to `channel` as `account`, as an action (`/me`) if
+    /// `action` is set.
+    fn send(
+        &mut self,
+        account: &str,
+        channel: String,
+        text: String,
+        action: bool,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+
+    /// Whether there are credentials to send as `account`.
+    fn can_send_as(&self, account: &str) -> bool;
+}
+
+/// Runs one connection on a task of its own, so a panic anywhere in its
+/// backend or relay ends that connection like a dropped one, to be made
+/// again after the backoff, rather than the whole connection thread.
+pub async fn supervised<T: Send + 'static>(
+    connection: impl Future<Output = T> + Send + 'static,
+) -> Result<T, String> {
+    tokio::spawn(connection)
+        .await
+        .map_err(|e| match e.try_into_panic() {
+            Ok(panic) => format!("the connection panicked: {}", crate::panic_message(&*panic)),
+            Err(e) => e.to_string(),
+        })
+}
+
+/// Turns events into the commands the Neovim side dispatches.
+pub struct Relay {
+    sender: CommandSender,
+    syntax: SharedSyntax,
+    /// Where messages are scored in order when moderation is on.
+    scoring: Option<UnboundedSender<ChatMessage>>,
+    gifts: GiftTrains,
+    lookups: Lookups,
+}
+
+impl Relay {
+    /// Starts the scoring task with moderation on, so it has to be called
+    /// on the connection's runtime then.
+    pub fn new(config: &Config, syntax: SharedSyntax, sender: CommandSender) -> Self {
+        let scoring = match config.moderation.enabled.then(|| Moderator::new(config)) {
+            Some(Ok(moderator)) => {
+                let (scoring, unscored) = mpsc::unbounded_channel();
+                tokio::spawn(score(
+                    moderator,
+                    unscored,
+                    SharedSyntax::clone(&syntax),
+                    sender.clone(),
+                ));
+                Some(scoring)
+            }
+            Some(Err(message)) => {
+                sender.send(Command::Error(PluginError::error("Moderation", message)));
+                None
+            }
+            None => None,
+        };
+
+        Relay {
+            scoring,
+            gifts: GiftTrains::new(sender.clone()),
+            lookups: Lookups::default(),
+            sender,
+            syntax,
+        }
+    }
+
+    /// Has Helix look up the authors of the messages, see `twitch/metadata.rs`.
+    pub fn looking_up(mut self, lookups: Lookups) -> Self {
+        self.lookups = lookups;
+        self
+    }
+
+    pub fn forward(&self, event: ChatEvent) {
+        match event {
+            ChatEvent::Joined => self.sender.send(Command::Connected),
+            ChatEvent::Message(message) => self.chat(*message),
+            ChatEvent::Subscription {
+                channel,
+                user,
+                months,
+                resub,
+            } => self.sender.send(Command::Subscription {
+                channel,
+                user,
+                months,
+                resub,
+            }),
+            ChatEvent::Raid {
+                channel,
+                from,
+                viewers,
+            } => self.sender.send(Command::Raid {
+                channel,
+                from,
+                viewers,
+            }),
+            ChatEvent::Gift {
+                channel,
+                gifter,
+                tier,
+                recipient,
+            } => self.gifts.gift(channel, gifter, tier, recipient),
+            ChatEvent::MysteryGift {
+                channel,
+                gifter,
+                count,
+                tier,
+            } => self.gifts.announce(channel, gifter, count, tier),
+            ChatEvent::Milestone {
+                channel,
+                user,
+                category,
+                value,
+            } => self.sender.send(Command::Milestone {
+                channel,
+                user,
+                category,
+                value,
+            }),
+            // Never parsed for chat commands, a whisper isn't said in chat.
+            ChatEvent::Whisper { from, text } => self.sender.send(Command::Whisper {
+                user: from,
+                text,
+                sent: false,
+            }),
+        }
+    }
+
+    fn chat(&self, message: ChatMessage) {
+        self.lookups.seen(&message);
+
+        let Some(scoring) = &self.scoring else {
+            send_chat(message, None, &self.syntax, &self.sender);
+            return;
+        };
+
+        // Only fails once the runtime is going down.
+        let _ = scoring.send(message);
+    }
+}
+
+/// Scores the messages from `unscored` a few at a time, so one slow request
+/// doesn't hold up the rest of the chat, and passes them on in the order
+/// they arrived, the ones that came too far behind to be scored included.
+async fn score(
+    moderator: Moderator,
+    mut unscored: UnboundedReceiver<ChatMessage>,
+    syntax: SharedSyntax,
+    sender: CommandSender,
+) {
+    let requests = Semaphore::new(SCORING);
+    let mut scoring = FuturesOrdered::new();
+
+    loop {
+        tokio::select! {
+            message = unscored.recv() => {
+                let Some(mut message) = message else {
+                    return;
+                };
+                let (moderator, sender, requests) = (&moderator, &sender, &requests);
+                let behind = scoring.len() >= MAX_UNSCORED;
+
+                scoring.push_back(async move {
+                    if !behind {
+                        let _request = requests.acquire().await;
+                        message.risk = moderator.score(&message.text, sender).await;
+                    }
+                    message
+                });
+            }
+            Some(message) = scoring.next() => {
+                send_chat(message, Some(&moderator), &syntax, &sender);
+            }
+        }
+    }
+}
+
+/// Passes `message` on, along with its chat command unless `moderator`
+/// hides it.
+fn send_chat(
+    message: ChatMessage,
+    moderator: Option<&Moderator>,
+    syntax: &SharedSyntax,
+    sender: &CommandSender,
+) {
+    let command = parse_command(&message, &syntax.read().unwrap())
+        .filter(|_| !moderator.is_some_and(|moderator| moderator.hides(message.risk)));
+
+    sender.send(Command::Chat(message));
+
+    if let Some(command) = command {
+        sender.send(command);
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    commands,
+    i18n::tr_with,
+    twitch::{parse_command, ChatMessage},
+    ui::RenderStats,
+    Plugin,
+};
+
+const DEFAULT_COUNT: usize = 1000;
+
+/// Anything slower than this would have missed a frame at 60fps.
+const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
+/// Replays a synthetic burst through the whole pipeline, once message by
+/// message and once as a single batch like busy chat is handled, and
+/// reports the throughput of both along with where the time went.
+pub fn run(plugin: &mut Plugin, count: Option<usize>) -> Result<()> {
+    let count = count.unwrap_or(DEFAULT_COUNT);
+
+    let single = burst(plugin, count, false);
+    let batched = burst(plugin, count, true)?;
+    let single = single?;
+
+    let report = tr_with(
+        "bench.report",
+        &[
+            ("count", &count),
+            ("total", &seconds(single.total)),
+            ("rate", &rate(count, single.total)),
+            ("set_lines", &seconds(single.stats.set_lines)),
+            ("extmarks", &seconds(single.stats.extmarks)),
+            ("dropped", &single.dropped_frames),
+        ],
+    );
+    let report = format!(
+        "{}\n{}",
+        report,
+        tr_with(
+            "bench.batched",
+            &[
+                ("total", &seconds(batched.total)),
+                ("rate", &rate(count, batched.total)),
+                ("set_lines", &seconds(batched.stats.set_lines)),
+                ("extmarks", &seconds(batched.stats.extmarks)),
+            ],
+        )
+    );
+
+    api::notify(&report, LogLevel::Info, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+struct Burst {
+    total: Duration,
+    stats: RenderStats,
+    /// Messages that took longer than a frame, when handled one by one.
+    dropped_frames: usize,
+}
+
+fn burst(plugin: &mut Plugin, count: usize, batched: bool) -> Result<Burst> {
+    let channel = plugin.config.channel.clone();
+    let prefix = plugin.config.syntax.prefix.clone();
+
+    let messages = (0..count).map(|i| ChatMessage {
+        id: String::new(),
+        channel: channel.clone(),
+        author: format!("bench_user_{}", i % 50),
+        color: None,
+        text: format!(
+            "{}nvim benchmark message number {} with some filler text",
+            prefix, i
+        ),
+        badges: Vec::new(),
+        emotes: Vec::new(),
+        emote_spans: Vec::new(),
+        bits: None,
+        reply: None,
+        paid: None,
+        source: None,
+        risk: None,
+    });
+
+    plugin.chat.take_stats();
+
+    let mut dropped_frames = 0;
+    let start = Instant::now();
+
+    if batched {
+        plugin.chat.begin_batch();
+    }
+
+    for message in messages {
+        let frame = Instant::now();
+
+        if let Some(command) = parse_command(&message, &plugin.config.syntax) {
+            commands::dispatch(plugin, command);
+        }
+
+        if frame.elapsed() > FRAME_BUDGET {
+            dropped_frames += 1;
+        }
+    }
+
+    plugin.chat.end_batch()?;
+
+    Ok(Burst {
+        total: start.elapsed(),
+        stats: plugin.chat.take_stats(),
+        dropped_frames,
+    })
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+fn rate(count: usize, total: Duration) -> String {
+    format!("{:.0}", count as f64 / total.as_secs_f64())
+}
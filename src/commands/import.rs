@@ -0,0 +1,113 @@
+//! Reads the command exports of other chat bots into text commands.
+//!
+//! Nightbot exports `{ "commands": [{ "name": "!discord", "message": "...",
+//! "coolDown": 30 }] }`, StreamElements a list of `{ "command": "discord",
+//! "reply": "...", "cooldown": { "global": 30 } }`. Their `$(user)` and
+//! `${user}` variables become `{user}`, anything else is kept as written.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::text::TextCommand;
+
+#[derive(Deserialize)]
+struct NightbotExport {
+    commands: Vec<NightbotCommand>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NightbotCommand {
+    name: String,
+    message: String,
+    #[serde(default)]
+    cool_down: u64,
+}
+
+#[derive(Deserialize)]
+struct StreamElementsCommand {
+    command: String,
+    reply: String,
+    #[serde(default = "enabled")]
+    enabled: bool,
+    #[serde(default)]
+    cooldown: StreamElementsCooldown,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct StreamElementsCooldown {
+    #[serde(default)]
+    global: u64,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+pub fn read(path: &Path) -> Result<BTreeMap<String, TextCommand>, String> {
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+
+    let commands = if value.get("commands").is_some() {
+        nightbot(value)?
+    } else if value.is_array() {
+        stream_elements(value)?
+    } else {
+        return Err("not a Nightbot or StreamElements command export".to_owned());
+    };
+
+    Ok(commands)
+}
+
+fn nightbot(value: Value) -> Result<BTreeMap<String, TextCommand>, String> {
+    let export = NightbotExport::deserialize(value).map_err(|e| e.to_string())?;
+
+    Ok(export
+        .commands
+        .into_iter()
+        .map(|command| {
+            (
+                name(&command.name),
+                TextCommand {
+                    response: variables(&command.message),
+                    cooldown_secs: command.cool_down,
+                },
+            )
+        })
+        .collect())
+}
+
+fn stream_elements(value: Value) -> Result<BTreeMap<String, TextCommand>, String> {
+    let commands = Vec::<StreamElementsCommand>::deserialize(value).map_err(|e| e.to_string())?;
+
+    let mut imported = BTreeMap::new();
+
+    for command in commands.into_iter().filter(|command| command.enabled) {
+        let text = TextCommand {
+            response: variables(&command.reply),
+            cooldown_secs: command.cooldown.global,
+        };
+
+        for alias in &command.aliases {
+            imported.insert(name(alias), text.clone());
+        }
+
+        imported.insert(name(&command.command), text);
+    }
+
+    Ok(imported)
+}
+
+fn name(name: &str) -> String {
+    name.trim().trim_start_matches('!').to_lowercase()
+}
+
+fn variables(text: &str) -> String {
+    text.replace("$(user)", "{user}")
+        .replace("${user}", "{user}")
+        .replace("${sender}", "{user}")
+}
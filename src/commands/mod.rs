@@ -0,0 +1,1448 @@
+pub mod import;
+pub mod registry;
+pub mod syntax;
+pub mod text;
+pub mod throttle;
+
+pub use syntax::{SharedSyntax, Syntax};
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::Local;
+use nvim_oxi::{
+    api::{
+        self,
+        opts::*,
+        types::{CmdInfos, LogLevel},
+    },
+    libuv::AsyncHandle,
+    serde::Serializer,
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    approvals,
+    audit::Undo,
+    config::{ChannelConfig, DisplayMode, EmoteStyle, Output, RenderMode},
+    display,
+    events::Event,
+    filter, flood, gifts, help,
+    hooks::{self, Executed, Failure, Hook},
+    i18n::{tr, tr_with},
+    ignore, keymaps, location, logging, metrics, options, raffle, raid, redemptions, replay,
+    schedule, snippets,
+    sounds::{self, SoundEvent},
+    spotlight, suggest, transcript, trythis,
+    twitch::{
+        ads::AdSchedule,
+        args::{schema_of, ArgError},
+        cheer,
+        emotes::{self, EmoteSpan},
+        polls::{Choice, Poll, Prediction},
+        BadgeSets, ChatMessage, ConnectionStatus, GiftTrain, LiveStream, Outgoing, ReplyParent,
+        Viewer,
+    },
+    ui::{
+        alert, banner,
+        celebration::{self, Celebration},
+        float, MessageView, PlainLog, PluginError,
+    },
+    viewers, votes, webhooks, whispers, Plugin,
+};
+
+/// How much of a reply the list of colorschemes may take up.
+const MAX_OPTIONS_LENGTH: usize = 350;
+
+/// Where the last colorscheme chat switched to is kept, so the next session
+/// starts with it.
+pub const COLORSCHEME_KEY: &str = "colorscheme";
+
+/// The chat commands viewers can use, without the leading `!`.
+pub const CHAT_COMMANDS: [&str; 17] = [
+    "nvim",
+    "colorscheme",
+    "trythis",
+    "hl",
+    "goto",
+    "vote",
+    "keymap",
+    "banner",
+    "opt",
+    "font",
+    "snippet",
+    "remind",
+    "suggest",
+    "where",
+    "focus",
+    "uptime",
+    "help",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    /// Every chat message, whether or not it is a command.
+    Chat(ChatMessage),
+    Message {
+        #[serde(default)]
+        id: String,
+        channel: String,
+        author: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+        #[serde(default)]
+        badges: Vec<String>,
+        text: String,
+        /// Where each emote is in `text`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        emotes: Vec<EmoteSpan>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bits: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply: Option<ReplyParent>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        risk: Option<f64>,
+    },
+    #[serde(rename = "colorscheme")]
+    ColorScheme {
+        channel: String,
+        #[serde(default)]
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        name: String,
+        /// `--bg=dark` or `--bg=light`, set as `background` first.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        background: Option<String>,
+    },
+    /// `!trythis <colorscheme>`, switching back after `trythis.duration`.
+    #[serde(rename = "trythis")]
+    TryThis {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        name: String,
+    },
+    /// `!hl <group> <fg> [bg]`, setting the colors of one highlight group.
+    Highlight {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        group: String,
+        fg: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bg: Option<String>,
+    },
+    /// `!goto <file>:<line>`.
+    Goto {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        file: String,
+        line: usize,
+    },
+    /// `!vote <option>`, the option's name or number.
+    Vote {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        choice: String,
+    },
+    /// `!keymap <lhs> <rhs>`, a normal mode mapping for the streamer to
+    /// accept.
+    Keymap {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        lhs: String,
+        rhs: String,
+    },
+    /// `!suggest <line>[-<line>] <replacement>`, replacing lines `start`
+    /// to `end` (1-based) of the buffer on screen once the streamer accepts
+    /// the diff.
+    Suggest {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        start: usize,
+        end: usize,
+        lines: Vec<String>,
+    },
+    /// `!banner <text>`, typed out in large letters across the editor.
+    Banner {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        text: String,
+    },
+    /// `!opt <name> <value>`, setting one of the options chat may set.
+    #[serde(rename = "option")]
+    SetOption {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        name: String,
+        value: String,
+    },
+    /// `!font <font> [size]`, setting `guifont`.
+    Font {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        font: String,
+    },
+    /// `!snippet <name>`, inserting one of the `snippets` at the cursor.
+    Snippet {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        name: String,
+    },
+    /// `!remind 10m stretch`, a notification for the streamer once
+    /// `seconds` went by.
+    Remind {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        seconds: u64,
+        text: String,
+    },
+    /// `!where`, answered with the file, line and git branch on screen.
+    Where {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+    },
+    /// `!uptime`, answered with how long the stream is live.
+    Uptime {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+    },
+    /// `!help [command]`, answered with the chat commands or how `command`
+    /// is used.
+    Help {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+    },
+    /// `!focus <number>`, a vote for one of the buffers of a
+    /// `:TwitchFocusVote`.
+    Focus {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        number: usize,
+    },
+    /// A known chat `command` whose arguments didn't parse, answered with
+    /// the `error` and how it is used.
+    Malformed {
+        channel: String,
+        author: String,
+        #[serde(default)]
+        badges: Vec<String>,
+        command: String,
+        error: ArgError,
+    },
+    /// A `:TwitchPoll` chat vote ended, `winner` is unset on a tie.
+    VoteEnded {
+        title: String,
+        choices: Vec<Choice>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        winner: Option<String>,
+    },
+    Connected,
+    /// Dispatched on the Neovim side once `:TwitchDisconnect` stopped the
+    /// connection.
+    Disconnected,
+    Reconnecting {
+        reason: String,
+        /// How many times in a row connecting failed, starting at 1.
+        #[serde(default)]
+        attempt: u32,
+        /// Seconds until the next attempt.
+        #[serde(default)]
+        retry_in: u64,
+    },
+    /// `resub` when the viewer shared a renewed subscription.
+    Subscription {
+        channel: String,
+        user: String,
+        months: u64,
+        #[serde(default)]
+        resub: bool,
+    },
+    Raid {
+        channel: String,
+        from: String,
+        viewers: u64,
+    },
+    /// A viewer milestone such as a watch streak, `category` as Twitch
+    /// names it, e.g. `watch-streak`.
+    Milestone {
+        channel: String,
+        user: String,
+        category: String,
+        value: u64,
+    },
+    Summary {
+        text: String,
+    },
+    /// A poll was created or its votes changed.
+    Poll(Poll),
+    /// A prediction was created, changed or ended.
+    Prediction(Prediction),
+    /// A raid out of the channel was started and goes ahead after the
+    /// countdown.
+    RaidStarted {
+        to: String,
+    },
+    RaidCanceled,
+    /// Followed channels that are live, offered as raid targets.
+    LiveChannels {
+        channels: Vec<String>,
+    },
+    /// The ad schedule changed.
+    AdSchedule(AdSchedule),
+    /// An ad break began, `automatic` if Twitch ran it on schedule.
+    AdBreak {
+        length: u32,
+        automatic: bool,
+    },
+    /// A whisper from `user`, or one `sent` to them with `:TwitchWhisper`.
+    Whisper {
+        user: String,
+        text: String,
+        #[serde(default)]
+        sent: bool,
+    },
+    /// A stream marker was created, `position` seconds into the stream.
+    Marker {
+        position: u64,
+        description: String,
+    },
+    /// A channel point reward was redeemed, `input` is empty for rewards
+    /// that don't ask for any.
+    Redemption {
+        channel: String,
+        user: String,
+        reward: String,
+        cost: u64,
+        #[serde(default)]
+        input: String,
+    },
+    /// A burst of gifted subs, collected into one.
+    GiftSubs(GiftTrain),
+    /// Badge titles fetched from Helix or read from the cache.
+    Badges(BadgeSets),
+    /// The accounts the streamer blocked on Twitch, in lowercase.
+    BlockedUsers {
+        users: Vec<String>,
+    },
+    /// Chatters looked up on Helix.
+    Viewers {
+        viewers: Vec<Viewer>,
+    },
+    /// The channel went live or offline, or its broadcast changed.
+    Stream {
+        #[serde(default)]
+        stream: Option<LiveStream>,
+    },
+    /// A chat command of a `:TwitchReplay`, run again without the checks it
+    /// passed when it was recorded.
+    Replayed {
+        command: Box<Command>,
+    },
+    Error(PluginError),
+}
+
+impl Command {
+    /// The channel, chat command, and the name and badges of whoever used
+    /// it, if this came from chat.
+    pub fn chat_origin(&self) -> Option<(&str, &str, &str, &[String])> {
+        match self {
+            Command::Message {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "nvim", author, badges)),
+            Command::ColorScheme {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "colorscheme", author, badges)),
+            Command::TryThis {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "trythis", author, badges)),
+            Command::Highlight {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "hl", author, badges)),
+            Command::Goto {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "goto", author, badges)),
+            Command::Vote {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "vote", author, badges)),
+            Command::Keymap {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "keymap", author, badges)),
+            Command::Suggest {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "suggest", author, badges)),
+            Command::Banner {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "banner", author, badges)),
+            Command::SetOption {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "opt", author, badges)),
+            Command::Font {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "font", author, badges)),
+            Command::Snippet {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "snippet", author, badges)),
+            Command::Remind {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "remind", author, badges)),
+            Command::Where {
+                channel,
+                author,
+                badges,
+            } => Some((channel, "where", author, badges)),
+            Command::Uptime {
+                channel,
+                author,
+                badges,
+            } => Some((channel, "uptime", author, badges)),
+            Command::Help {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "help", author, badges)),
+            Command::Focus {
+                channel,
+                author,
+                badges,
+                ..
+            } => Some((channel, "focus", author, badges)),
+            // Held to the command's permissions and cooldowns, so the
+            // replies can't be used to flood the chat.
+            Command::Malformed {
+                channel,
+                author,
+                badges,
+                command,
+                ..
+            } => Some((channel, command, author, badges)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandPayload {
+    pub command: Command,
+}
+
+/// Which lane of the [`Queue`] a command waits in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// Alerts, connection changes, results from Helix and the broadcaster's
+    /// own chat commands. Rare and they matter, so they go first.
+    High,
+    /// Chat, and the commands viewers send in it.
+    Low,
+}
+
+impl Command {
+    pub fn priority(&self) -> Priority {
+        let badges = match self {
+            Command::Chat(_) => return Priority::Low,
+            command => match command.chat_origin() {
+                Some((_, _, _, badges)) => badges,
+                None => return Priority::High,
+            },
+        };
+
+        match badges
+            .iter()
+            .any(|badge| badge.split('/').next() == Some("broadcaster"))
+        {
+            true => Priority::High,
+            false => Priority::Low,
+        }
+    }
+}
+
+/// The commands sent but not dispatched yet, shared by both threads. It
+/// holds at most `limit` of them: once full, chat is dropped, while a high
+/// priority command takes the place of the oldest chat still waiting and is
+/// only dropped itself when there is none.
+#[derive(Debug)]
+pub struct Queue {
+    lanes: Mutex<Lanes>,
+    limit: AtomicUsize,
+    /// How many commands didn't fit.
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Lanes {
+    high: VecDeque<CommandPayload>,
+    low: VecDeque<CommandPayload>,
+}
+
+impl Queue {
+    pub fn new(limit: usize) -> Self {
+        Queue {
+            lanes: Mutex::default(),
+            limit: AtomicUsize::new(limit),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        let lanes = self.lanes.lock().unwrap();
+        lanes.high.len() + lanes.low.len()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Adds `command` if there is room for it, or can be made. Returns
+    /// whether it was added.
+    fn push(&self, command: Command) -> bool {
+        let priority = command.priority();
+        let mut lanes = self.lanes.lock().unwrap();
+
+        if lanes.high.len() + lanes.low.len() >= self.limit.load(Ordering::Relaxed) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            metrics::MESSAGES_DROPPED.inc();
+
+            if priority == Priority::Low || lanes.low.pop_front().is_none() {
+                return false;
+            }
+        }
+
+        let lane = match priority {
+            Priority::High => &mut lanes.high,
+            Priority::Low => &mut lanes.low,
+        };
+        lane.push_back(CommandPayload { command });
+
+        true
+    }
+
+    /// Takes every command waiting, the high priority ones first and each
+    /// lane in the order it was sent. Wakeups are coalesced, so one
+    /// callback may stand for many sends.
+    pub fn drain(&self) -> Vec<CommandPayload> {
+        let mut lanes = self.lanes.lock().unwrap();
+        let mut payloads: Vec<CommandPayload> = lanes.high.drain(..).collect();
+        payloads.extend(lanes.low.drain(..));
+
+        payloads
+    }
+}
+
+/// Sends commands from the Twitch thread and wakes up the Neovim loop to
+/// process them.
+#[derive(Clone)]
+pub struct CommandSender {
+    handle: AsyncHandle,
+    queue: Arc<Queue>,
+}
+
+impl CommandSender {
+    pub fn new(handle: AsyncHandle, queue: Arc<Queue>) -> Self {
+        CommandSender { handle, queue }
+    }
+
+    pub fn send(&self, command: Command) {
+        if self.queue.push(command) {
+            // Only fails once Neovim is exiting, when there is no one left
+            // to tell.
+            let _ = self.handle.send();
+        }
+    }
+}
+
+/// Dispatches what was drained from the [`Queue`] together, with a single
+/// update of the chat buffer when rendering is batched.
+pub fn dispatch_batch(plugin: &mut Plugin, payloads: Vec<CommandPayload>) -> Result<()> {
+    if plugin.render_mode == RenderMode::Batched {
+        plugin.chat.begin_batch();
+    }
+    for payload in payloads {
+        dispatch(plugin, payload.command);
+    }
+    plugin.chat.end_batch()?;
+
+    // Neovim would otherwise only redraw once it's idle again.
+    if plugin.render_mode == RenderMode::Immediate {
+        api::command("redraw")?;
+    }
+
+    Ok(())
+}
+
+pub fn dispatch(plugin: &mut Plugin, command: Command) {
+    if let Some((channel, name, author, badges)) = command.chat_origin() {
+        if !registry::admit(plugin, channel, name, author, badges) {
+            metrics::MESSAGES_DROPPED.inc();
+            return;
+        }
+
+        if !matches!(command, Command::Malformed { .. }) {
+            plugin.stats.record_command(name);
+        }
+    }
+
+    match approvals::hold(plugin, command) {
+        Ok(Some(command)) => execute(plugin, command),
+        Ok(None) => (),
+        Err(e) => finish(plugin, "Approvals", Err(e)),
+    }
+}
+
+/// Runs a command that passed the checks in [`dispatch`], and was approved
+/// if it had to be.
+pub fn execute(plugin: &mut Plugin, command: Command) {
+    // Told to `on_command_executed()` once the command went through.
+    let executed = command
+        .chat_origin()
+        .map(|(channel, name, author, _)| (channel.to_owned(), name.to_owned(), author.to_owned()));
+
+    let event = Event::new(command.clone());
+
+    webhooks::fire(plugin, &event);
+
+    let (context, result) = match command {
+        Command::Message {
+            id,
+            channel,
+            author,
+            color,
+            badges,
+            text,
+            emotes,
+            bits,
+            reply,
+            source,
+            risk,
+        } => {
+            let key = message_count_key(&channel);
+            let count = plugin.state.get::<u64>(&key).unwrap_or(0);
+            plugin.state.set(&key, count + 1);
+
+            // Only hidden from the window, the message stays in the mod view.
+            if risk.is_some_and(|risk| risk >= plugin.config.moderation.threshold) {
+                metrics::MESSAGES_DROPPED.inc();
+                return;
+            }
+
+            if !flood::admit(plugin, &author) {
+                metrics::MESSAGES_DROPPED.inc();
+                return;
+            }
+
+            let (text, emotes) = match filter::apply(plugin, text, emotes) {
+                Ok(Some(filtered)) => filtered,
+                Ok(None) => {
+                    metrics::MESSAGES_DROPPED.inc();
+                    return;
+                }
+                Err(e) => return finish(plugin, "Filter", Err(e)),
+            };
+
+            let ChannelConfig { window, output, .. } = plugin.config.for_channel(&channel);
+
+            // Before the cheers are found, as their offsets are in the text
+            // that's shown.
+            let (text, emotes) = match window.emotes {
+                EmoteStyle::Text => (text, emotes),
+                EmoteStyle::Placeholder | EmoteStyle::Image => emotes::placeholders(&text, &emotes),
+            };
+
+            let view = MessageView {
+                id,
+                author,
+                color,
+                badges: plugin.badges.titles(&channel, &badges),
+                cheers: match bits {
+                    Some(_) => cheer::find(&text),
+                    None => Vec::new(),
+                },
+                emotes,
+                text,
+                reply,
+                source,
+                received: Local::now(),
+            };
+
+            let result = if output == Output::Buffer {
+                let echo = plugin.config.accessibility.echo;
+                let limit = plugin.config.limits.buffer;
+                let result = plugin
+                    .channel_logs
+                    .entry(channel)
+                    .or_insert_with_key(|channel| PlainLog::for_channel(channel, limit))
+                    .append(&view.plain_line(), echo);
+
+                if result.is_ok() {
+                    metrics::MESSAGES_RENDERED.inc();
+                }
+
+                result
+            } else if !plugin.config.accessibility.enabled
+                && plugin.config.display.mode == DisplayMode::Queue
+            {
+                // Counted as rendered once it is its turn.
+                plugin.display.push(&plugin.config.display, view, window);
+                display::tick(plugin)
+            } else {
+                let shown = if plugin.config.accessibility.enabled {
+                    let echo = plugin.config.accessibility.echo;
+                    plugin.plain.append(&view.plain_line(), echo)
+                } else if plugin.config.display.mode == DisplayMode::Annotate {
+                    plugin
+                        .annotations
+                        .show(&view, &window, &plugin.config.display.annotations)
+                } else {
+                    plugin
+                        .chat
+                        .set_options(window)
+                        .and_then(|()| plugin.chat.show_message(view))
+                };
+
+                if shown.is_ok() {
+                    metrics::MESSAGES_RENDERED.inc();
+                }
+
+                shown
+            };
+
+            ("Message", result)
+        }
+        Command::Badges(badges) => {
+            plugin.badges = badges;
+            return;
+        }
+        Command::BlockedUsers { users } => {
+            plugin.ignored.blocked = users.into_iter().collect();
+            return;
+        }
+        Command::Viewers { viewers } => {
+            plugin.viewers.update(viewers);
+            return;
+        }
+        Command::Stream { stream } => {
+            plugin.viewers.stream = Some(stream);
+            return;
+        }
+        Command::Replayed { command } => return execute(plugin, *command),
+        Command::Chat(message) => {
+            if let Some(paid) = &message.paid {
+                let result = match plugin.config.accessibility.enabled {
+                    true => announce(
+                        plugin,
+                        &format!("[{}] {}: {}", paid.amount(), message.author, message.text),
+                    ),
+                    false => plugin.pinned.pin(&message.author, &message.text, paid),
+                };
+
+                if let Err(e) = result {
+                    plugin.errors.push("Hype Chat", &e);
+                }
+            }
+
+            let ignored = ignore::ignored(plugin, &message.author);
+
+            registry::respond(plugin, &message);
+
+            if !ignored {
+                if let Err(e) = filter::enforce(plugin, &message) {
+                    plugin.errors.push("Filter", &e);
+                }
+                if let Err(e) = raffle::enter(plugin, &message) {
+                    plugin.errors.push("Raffle", &e);
+                }
+            }
+
+            plugin.hooks.fire(Hook::Message, &event);
+            plugin.sentiment.push(&message.text);
+            plugin.stats.record(&message);
+            transcript::message(plugin, &message);
+            plugin.history.push(message);
+            return;
+        }
+        Command::ColorScheme {
+            channel,
+            author,
+            name,
+            background,
+            ..
+        } => {
+            let result = colorscheme(plugin, channel, &author, name.trim(), background.as_deref());
+            ("Colorscheme", result)
+        }
+        Command::TryThis {
+            channel,
+            author,
+            name,
+            ..
+        } => {
+            let result = trythis::try_colorscheme(plugin, channel, &author, name.trim());
+            ("Colorscheme", result)
+        }
+        Command::Highlight {
+            ref channel,
+            ref author,
+            ref group,
+            ref fg,
+            ref bg,
+            ..
+        } => {
+            let result = plugin.highlights.apply(author, group, fg, bg.as_deref());
+            let action = match bg {
+                Some(bg) => format!("hl {} {} {}", group, fg, bg),
+                None => format!("hl {} {}", group, fg),
+            };
+            plugin
+                .audit
+                .record(channel, author, action, &result, Some(Undo::Highlight));
+
+            let result = result.and_then(|()| {
+                let text = tr_with("confirm.highlight", &[("group", group), ("user", author)]);
+                confirm(plugin, channel.clone(), text);
+                emit("TwitchHighlight", &event)
+            });
+            ("Highlight", result)
+        }
+        Command::Goto {
+            channel,
+            author,
+            file,
+            line,
+            ..
+        } => {
+            let result = spotlight::goto(plugin, channel, &author, &file, line);
+            ("Goto", result)
+        }
+        Command::Vote {
+            channel,
+            author,
+            choice,
+            ..
+        } => ("Vote", votes::cast(plugin, &channel, &author, &choice)),
+        Command::Focus {
+            channel,
+            author,
+            number,
+            ..
+        } => ("Vote", votes::focus(plugin, &channel, &author, number)),
+        Command::Keymap {
+            channel,
+            author,
+            lhs,
+            rhs,
+            ..
+        } => (
+            "Keymap",
+            keymaps::propose(plugin, channel, author, lhs, rhs),
+        ),
+        Command::Suggest {
+            channel,
+            author,
+            start,
+            end,
+            lines,
+            ..
+        } => (
+            "Suggest",
+            suggest::propose(plugin, channel, author, (start, end), lines),
+        ),
+        Command::SetOption {
+            channel,
+            author,
+            name,
+            value,
+            ..
+        } => (
+            "Option",
+            options::set(plugin, channel, &author, &name, &value),
+        ),
+        Command::Font {
+            channel,
+            author,
+            font,
+            ..
+        } => ("Option", options::font(plugin, channel, &author, &font)),
+        Command::Snippet {
+            channel,
+            author,
+            name,
+            ..
+        } => ("Snippet", snippets::insert(plugin, channel, &author, &name)),
+        Command::Remind {
+            channel,
+            author,
+            seconds,
+            text,
+            ..
+        } => (
+            "Schedule",
+            schedule::remind(plugin, channel, &author, seconds, text),
+        ),
+        Command::Where {
+            channel, author, ..
+        } => ("Where", location::reply(plugin, channel, &author)),
+        Command::Uptime {
+            channel, author, ..
+        } => ("Uptime", viewers::uptime(plugin, channel, &author)),
+        Command::Help {
+            channel,
+            author,
+            command,
+            ..
+        } => (
+            "Help",
+            help::reply(plugin, channel, &author, command.as_deref()),
+        ),
+        Command::Malformed {
+            channel,
+            author,
+            command,
+            error,
+            ..
+        } => {
+            let usage = schema_of(&command)
+                .map(|schema| schema.usage(&format!("{}{}", plugin.config.syntax.prefix, command)))
+                .unwrap_or_default();
+            let text = tr_with(
+                "args.reply",
+                &[
+                    ("user", &author),
+                    ("error", &error.message()),
+                    ("usage", &usage),
+                ],
+            );
+            let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+            return;
+        }
+        Command::Banner { ref text, .. } => {
+            let options = &plugin.config.banner;
+            let result = match plugin.config.accessibility.enabled {
+                true => plugin.plain.append(text, plugin.config.accessibility.echo),
+                false => banner::show(text, options.step, options.duration, plugin.chat.zindex()),
+            };
+            ("Banner", result.and_then(|()| emit("TwitchBanner", &event)))
+        }
+        Command::Redemption {
+            ref channel,
+            ref user,
+            ref reward,
+            ..
+        } => {
+            plugin.stats.record_alert("redemption");
+
+            let result = redemptions::run(plugin, channel, user, reward, &event)
+                .and_then(|()| emit("TwitchRedemption", &event));
+            ("Redemption", result)
+        }
+        Command::VoteEnded { .. } => {
+            if let Some(on_result) = plugin.votes.on_result.clone() {
+                let data = event.serialize(Serializer::new());
+
+                // Called once the plugin is no longer borrowed, like the
+                // registered commands.
+                nvim_oxi::schedule(move |_| -> Result<()> {
+                    if let Err(message) = hooks::guarded(on_result, data?) {
+                        hooks::fail(Failure {
+                            context: "Vote".to_owned(),
+                            message,
+                            command: None,
+                        });
+                    }
+                    Ok(())
+                });
+            }
+
+            ("Vote", emit("TwitchVoteEnded", &event))
+        }
+        Command::Subscription {
+            user,
+            months,
+            resub,
+            ..
+        } => {
+            plugin.stats.record_alert("subscription");
+            sounds::play(plugin, SoundEvent::Sub);
+
+            let kind = match resub {
+                true => Celebration::Resub,
+                false => Celebration::Sub,
+            };
+            let result = celebrate(plugin, kind, &[("user", &user), ("months", &months)])
+                .and_then(|()| emit("TwitchSubscription", &event));
+            ("Event", result)
+        }
+        Command::Raid { from, viewers, .. } => {
+            plugin.stats.record_alert("raid");
+            sounds::play(plugin, SoundEvent::Raid);
+
+            let result = celebrate(
+                plugin,
+                Celebration::Raid,
+                &[("user", &from), ("viewers", &viewers)],
+            )
+            .and_then(|()| emit("TwitchRaid", &event));
+            ("Event", result)
+        }
+        Command::Milestone {
+            user,
+            category,
+            value,
+            ..
+        } => {
+            plugin.stats.record_alert("milestone");
+
+            let text = match category.as_str() {
+                "watch-streak" => {
+                    tr_with("alerts.watch_streak", &[("user", &user), ("count", &value)])
+                }
+                _ => tr_with(
+                    "alerts.milestone",
+                    &[("user", &user), ("category", &category), ("count", &value)],
+                ),
+            };
+
+            let result = announce(plugin, &text).and_then(|()| emit("TwitchMilestone", &event));
+            ("Event", result)
+        }
+        Command::GiftSubs(train) => {
+            plugin.stats.record_alert("gift_subs");
+
+            let result = announce(plugin, &format!("🎁 {}", gifts::summary(&train)))
+                .and_then(|()| emit("TwitchGiftSubs", &event));
+            plugin.gifts.push(train);
+
+            ("Event", result)
+        }
+        Command::Summary { text } => (
+            "Summary",
+            float::show(
+                &tr("summary.title"),
+                text.lines().map(str::to_owned).collect(),
+            ),
+        ),
+        Command::Poll(poll) => {
+            let zindex = plugin.chat.zindex();
+            let result = plugin
+                .polls
+                .show_poll(poll, zindex)
+                .and_then(|shown| match shown {
+                    true => emit("TwitchPoll", &event),
+                    false => Ok(()),
+                });
+            ("Polls", result)
+        }
+        Command::Prediction(prediction) => {
+            let zindex = plugin.chat.zindex();
+            let result = plugin
+                .polls
+                .show_prediction(prediction, zindex)
+                .and_then(|shown| match shown {
+                    true => emit("TwitchPrediction", &event),
+                    false => Ok(()),
+                });
+            ("Polls", result)
+        }
+        Command::RaidStarted { ref to } => {
+            let result = raid::started(plugin, to).and_then(|()| emit("TwitchRaidOut", &event));
+            ("Raid", result)
+        }
+        Command::RaidCanceled => {
+            let result = raid::canceled(plugin).and_then(|()| emit("TwitchRaidCanceled", &event));
+            ("Raid", result)
+        }
+        Command::LiveChannels { channels } => {
+            plugin.raid.live = channels;
+            return;
+        }
+        Command::AdSchedule(schedule) => {
+            plugin.ads.update(schedule);
+            return;
+        }
+        Command::AdBreak { length, .. } => {
+            let text = tr_with("ads.running", &[("length", &length)]);
+            let result = announce(plugin, &format!("📺 {}", text))
+                .and_then(|()| emit("TwitchAdBreak", &event));
+            ("Ads", result)
+        }
+        Command::Marker {
+            position,
+            ref description,
+        } => {
+            let at = format!(
+                "{}:{:02}:{:02}",
+                position / 3600,
+                position / 60 % 60,
+                position % 60
+            );
+            let message = match description.is_empty() {
+                true => tr_with("marker.created", &[("at", &at)]),
+                false => tr_with(
+                    "marker.created_with",
+                    &[("at", &at), ("description", description)],
+                ),
+            };
+
+            let result = api::notify(&message, LogLevel::Info, &NotifyOpts::default())
+                .map_err(Into::into)
+                .and_then(|_| emit("TwitchMarker", &event));
+            ("Marker", result)
+        }
+        Command::Whisper {
+            ref user,
+            ref text,
+            sent,
+        } => {
+            // Muting a viewer mutes their whispers too.
+            if !sent && filter::muted(plugin, user) {
+                return;
+            }
+
+            let result =
+                whispers::show(user, text, sent).and_then(|()| emit("TwitchWhisper", &event));
+            ("Whisper", result)
+        }
+        Command::Connected => {
+            logging::info("Connection", "connected");
+            plugin.status = ConnectionStatus::Connected;
+            plugin.hooks.fire(Hook::Connect, &event);
+            let result =
+                emit("TwitchConnected", &event).and_then(|()| emit("TwitchStatusChanged", &event));
+            ("Event", result)
+        }
+        Command::Disconnected => {
+            logging::info("Connection", "disconnected");
+            plugin.status = ConnectionStatus::Disconnected;
+            plugin.hooks.fire(Hook::Disconnect, &event);
+            let result = emit("TwitchDisconnected", &event)
+                .and_then(|()| emit("TwitchStatusChanged", &event));
+            ("Event", result)
+        }
+        Command::Reconnecting {
+            reason,
+            attempt,
+            retry_in,
+        } => {
+            logging::warn(
+                "Connection",
+                format!(
+                    "reconnecting in {}s (attempt {}): {}",
+                    retry_in, attempt, reason
+                ),
+            );
+            plugin.status = ConnectionStatus::Reconnecting {
+                reason,
+                attempt,
+                retry_in,
+            };
+            let result = emit("TwitchReconnecting", &event)
+                .and_then(|()| emit("TwitchStatusChanged", &event));
+            ("Event", result)
+        }
+        Command::Error(error) => {
+            plugin.errors.report(error);
+            return;
+        }
+    };
+
+    if let Some((_, command, author)) = &executed {
+        transcript::command(plugin, author, command, &event, &result);
+    }
+
+    if let (Some((channel, command, author)), Ok(())) = (&executed, &result) {
+        let executed = Executed {
+            command,
+            channel,
+            author,
+        };
+        logging::debug(
+            "Command",
+            format!("{} ran {} in #{}", author, command, channel),
+        );
+        plugin.hooks.fire(Hook::CommandExecuted, &executed);
+        replay::command(plugin, &event);
+
+        // Every message would be too many sounds.
+        if command != "nvim" {
+            sounds::play(plugin, SoundEvent::Command);
+        }
+    }
+
+    finish(plugin, context, result);
+}
+
+fn finish(plugin: &mut Plugin, context: &str, result: Result<()>) {
+    if let Err(e) = result {
+        plugin.errors.push(context, &e);
+    }
+}
+
+/// The state key counting every chat message seen in `channel`.
+pub fn message_count_key(channel: &str) -> String {
+    format!("messages.{}", channel)
+}
+
+/// The installed colorschemes `colorschemes` allows, all of them when it is
+/// empty.
+pub fn allowed_colorschemes(plugin: &Plugin) -> Result<Vec<String>> {
+    let installed: Vec<String> = api::call_function("getcompletion", ("", "color"))?;
+
+    Ok(installed
+        .into_iter()
+        .filter(|installed| {
+            plugin.config.colorschemes.is_empty()
+                || plugin
+                    .config
+                    .colorschemes
+                    .iter()
+                    .any(|allowed| allowed == installed)
+        })
+        .collect())
+}
+
+/// Switches to `name` if it is installed and allowed, otherwise tells chat
+/// which ones are. The `background` is set first, as colorschemes with a
+/// dark and a light variant pick by it.
+fn colorscheme(
+    plugin: &mut Plugin,
+    channel: String,
+    author: &str,
+    name: &str,
+    background: Option<&str>,
+) -> Result<()> {
+    if !colorscheme_allowed(plugin, &channel, author, name)? {
+        return Ok(());
+    }
+
+    // Chosen for good, so no `!trythis` switches away from it later.
+    plugin.trials.clear();
+
+    let previous = api::get_var::<String>("colors_name").ok();
+    let result = match background {
+        Some(background) => {
+            let opts = OptionOpts::builder().scope(OptionScope::Global).build();
+            api::set_option_value("background", background, &opts)
+                .map_err(Into::into)
+                .and_then(|()| apply_colorscheme(plugin, name))
+        }
+        None => apply_colorscheme(plugin, name),
+    };
+    let action = match background {
+        Some(background) => format!("colorscheme {} --bg={}", name, background),
+        None => format!("colorscheme {}", name),
+    };
+    plugin.audit.record(
+        &channel,
+        author,
+        action,
+        &result,
+        Some(Undo::Colorscheme(previous)),
+    );
+    result?;
+
+    let text = tr_with("confirm.colorscheme", &[("name", &name), ("user", &author)]);
+    confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+/// Whether chat may switch to `name`, telling `author` what it may switch
+/// to instead if not.
+pub fn colorscheme_allowed(
+    plugin: &Plugin,
+    channel: &str,
+    author: &str,
+    name: &str,
+) -> Result<bool> {
+    let allowed = allowed_colorschemes(plugin)?;
+    let allowed: Vec<&str> = allowed.iter().map(String::as_str).collect();
+
+    // Names come from `getcompletion()`, so this also keeps out anything
+    // that could end the command, like `|` or a newline.
+    if !allowed.contains(&name) {
+        let mut options = String::new();
+
+        for (i, allowed) in allowed.iter().enumerate() {
+            // Well under the 500 characters of a chat message.
+            if options.len() + allowed.len() > MAX_OPTIONS_LENGTH {
+                options.push_str(&tr_with(
+                    "colorscheme.more",
+                    &[("count", &(allowed.len() - i))],
+                ));
+                break;
+            }
+            if !options.is_empty() {
+                options.push_str(", ");
+            }
+            options.push_str(allowed);
+        }
+
+        let text = tr_with(
+            "colorscheme.unknown",
+            &[("user", &author), ("name", &name), ("options", &options)],
+        );
+        let _ = plugin.outgoing.send(Outgoing::Reply {
+            channel: channel.to_owned(),
+            text,
+        });
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Switches to the colorscheme `name` and remembers it for the next session.
+/// It has to be one of [`allowed_colorschemes`].
+pub fn apply_colorscheme(plugin: &mut Plugin, name: &str) -> Result<()> {
+    switch_colorscheme(name)?;
+    plugin.state.set(COLORSCHEME_KEY, name);
+    Ok(())
+}
+
+/// Switches to the colorscheme `name` without remembering it, for those
+/// that only last a while.
+pub fn switch_colorscheme(name: &str) -> Result<()> {
+    // Structured, so the name is only ever the argument.
+    let infos = CmdInfos::builder().cmd("colorscheme").args([name]).build();
+    api::cmd(&infos, &CmdOpts::default())?;
+    Ok(())
+}
+
+/// Tells chat that a chat command went through, unless `confirm` is off.
+pub fn confirm(plugin: &Plugin, channel: String, text: String) {
+    if plugin.config.confirm {
+        let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+    }
+}
+
+/// Shows an alert, or appends it to the plain buffer in accessibility mode.
+pub fn announce(plugin: &mut Plugin, text: &str) -> Result<()> {
+    match plugin.config.accessibility.enabled {
+        true => plugin.plain.append(text, plugin.config.accessibility.echo),
+        false => alert::show(text, plugin.chat.zindex()),
+    }
+}
+
+/// Shows a sub or raid in its own float, from the `celebrations.templates`
+/// or the translated text. In accessibility mode it goes to the plain buffer
+/// like alerts.
+fn celebrate(plugin: &mut Plugin, kind: Celebration, args: &[(&str, &dyn Display)]) -> Result<()> {
+    let options = &plugin.config.celebrations;
+    if !options.enabled {
+        return Ok(());
+    }
+
+    let text = match options.templates.get(kind.name()) {
+        Some(template) => args.iter().fold(template.clone(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), &value.to_string())
+        }),
+        None => tr_with(&format!("alerts.{}", kind.name()), args),
+    };
+
+    match plugin.config.accessibility.enabled {
+        true => plugin.plain.append(&text, plugin.config.accessibility.echo),
+        false => celebration::show(
+            kind,
+            &text,
+            options.banners,
+            options.duration,
+            plugin.chat.zindex(),
+        ),
+    }
+}
+
+fn emit(pattern: &str, event: &Event) -> Result<()> {
+    let data = event.serialize(Serializer::new())?;
+
+    let opts = ExecAutocmdsOpts::builder()
+        .patterns(pattern)
+        .data(data)
+        .build();
+
+    api::exec_autocmds(["User"], &opts)?;
+
+    Ok(())
+}
@@ -0,0 +1,338 @@
+//! Every chat command: the built-in ones, the text commands and those
+//! registered from Lua with `require("nvim_plugin").register_command()`.
+//! Whichever it is, a command is let through here, by who may use it and
+//! how often, and the text and Lua ones are answered here as well.
+//!
+//! The built-in commands are parsed on the connection thread, where Lua
+//! callbacks can't go, and take their permission and cooldown from the
+//! config.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use nvim_oxi::{
+    conversion::FromObject,
+    serde::{Deserializer, Serializer},
+    Dictionary, Function, Object,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{text::TextCommand, CHAT_COMMANDS};
+use crate::{
+    access::{self, Access},
+    config::{self, ChannelConfig, Config, CooldownOptions},
+    filter,
+    hooks::{self, Executed, Failure, Hook},
+    i18n::tr_with,
+    ignore,
+    twitch::{ChatMessage, Outgoing, Viewer},
+    Plugin,
+};
+
+/// Cooldowns beyond this are most likely a mistake, e.g. milliseconds.
+const MAX_COOLDOWN: u64 = 24 * 60 * 60;
+
+struct LuaCommand {
+    /// Badge sets allowed to use the command, empty for everyone.
+    permission: Vec<String>,
+    cooldown: Duration,
+    callback: Function<Object, Object>,
+}
+
+/// The text and Lua commands, next to the built-in ones. A Lua command
+/// hides a text command of the same name.
+#[derive(Default)]
+pub struct CommandRegistry {
+    text: BTreeMap<String, TextCommand>,
+    lua: BTreeMap<String, LuaCommand>,
+}
+
+/// Who may use a command and how often.
+pub struct Policy<'a> {
+    /// Badge sets allowed to use it, empty for everyone.
+    pub permission: &'a [String],
+    pub cooldown: Option<CooldownOptions>,
+}
+
+#[derive(Deserialize)]
+struct Options {
+    #[serde(default)]
+    permission: Vec<String>,
+    #[serde(default)]
+    cooldown: u64,
+}
+
+/// What the callback is called with.
+#[derive(Serialize)]
+struct Context<'a> {
+    channel: &'a str,
+    author: &'a str,
+    badges: &'a [String],
+    /// The text after the command name.
+    args: &'a str,
+    /// What Helix told about the author, once they were looked up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    viewer: Option<&'a Viewer>,
+}
+
+impl CommandRegistry {
+    /// Registers `name` from a Lua table with a `callback` function along
+    /// with optional `permission` and `cooldown` (seconds) entries.
+    pub fn register(&mut self, name: &str, opts: Dictionary) -> Result<(), String> {
+        let name = name.trim().to_lowercase();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("command names can't be empty or contain spaces".to_owned());
+        }
+        if CHAT_COMMANDS.contains(&name.as_str()) {
+            return Err(format!("{} is a built-in command", name));
+        }
+
+        let callback = opts
+            .get("callback")
+            .cloned()
+            .ok_or_else(|| format!("{} needs a callback", name))
+            .and_then(|callback| {
+                Function::from_object(callback)
+                    .map_err(|_| "callback must be a function".to_owned())
+            })?;
+
+        let opts = opts
+            .into_iter()
+            .filter(|(key, _)| key.to_string_lossy() != "callback")
+            .collect::<Dictionary>();
+        let options = Options::deserialize(Deserializer::new(Object::from(opts)))
+            .map_err(|e| e.to_string())?;
+
+        if options.cooldown > MAX_COOLDOWN {
+            return Err(format!("cooldown can be at most {} seconds", MAX_COOLDOWN));
+        }
+
+        self.lua.insert(
+            name,
+            LuaCommand {
+                permission: options.permission,
+                cooldown: Duration::from_secs(options.cooldown),
+                callback,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Returns whether there was such a command.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.lua.remove(&name.trim().to_lowercase()).is_some()
+    }
+
+    /// Replaces the text commands with those kept in the plugin state.
+    pub fn set_text(&mut self, commands: BTreeMap<String, TextCommand>) {
+        self.text = commands;
+    }
+
+    /// Every command chat can use in a channel: the built-in ones it
+    /// enables first, then the others by name.
+    pub fn names(&self, channel: &ChannelConfig) -> Vec<&str> {
+        let others: BTreeSet<&str> = self
+            .text
+            .keys()
+            .chain(self.lua.keys())
+            .map(String::as_str)
+            .filter(|name| !CHAT_COMMANDS.contains(name))
+            .collect();
+
+        CHAT_COMMANDS
+            .iter()
+            .copied()
+            .filter(|name| channel.command_enabled(name))
+            .chain(others)
+            .collect()
+    }
+
+    /// Who may use `name` and how often, unset if there is no such command.
+    pub fn policy<'a>(&'a self, config: &'a Config, name: &str) -> Option<Policy<'a>> {
+        if CHAT_COMMANDS.contains(&name) {
+            return Some(Policy {
+                permission: config.permissions.get(name).map_or(&[], Vec::as_slice),
+                cooldown: config.cooldowns.get(name).cloned(),
+            });
+        }
+
+        if let Some(command) = self.lua.get(name) {
+            return Some(Policy {
+                permission: &command.permission,
+                cooldown: shared(command.cooldown),
+            });
+        }
+
+        self.text.get(name).map(|command| Policy {
+            permission: &[],
+            cooldown: shared(Duration::from_secs(command.cooldown_secs)),
+        })
+    }
+}
+
+/// A cooldown for everyone together, as text and Lua commands have.
+fn shared(cooldown: Duration) -> Option<CooldownOptions> {
+    (!cooldown.is_zero()).then_some(CooldownOptions {
+        burst: 1,
+        command: cooldown,
+        user: Duration::ZERO,
+    })
+}
+
+/// Whether `author` may run `name` in `channel` right now: the command is
+/// there and enabled, they may use it and it isn't cooling down. With
+/// `notify_cooldowns` whoever has to wait is told so once.
+pub fn admit(
+    plugin: &mut Plugin,
+    channel: &str,
+    name: &str,
+    author: &str,
+    badges: &[String],
+) -> bool {
+    if filter::muted(plugin, author) || ignore::ignored(plugin, author) {
+        return false;
+    }
+    if CHAT_COMMANDS.contains(&name) && !plugin.config.for_channel(channel).command_enabled(name) {
+        return false;
+    }
+
+    let Some(policy) = plugin.registry.policy(&plugin.config, name) else {
+        return false;
+    };
+    let permitted = match access::of(plugin, author) {
+        Access::Allowed => true,
+        Access::Blocked => false,
+        Access::Badges => config::badges_allowed(policy.permission, badges),
+    };
+    let Some(cooldown) = policy.cooldown.filter(|_| permitted) else {
+        return permitted;
+    };
+
+    let Err(cooling) = plugin.throttle.take(&cooldown, name, author) else {
+        return true;
+    };
+
+    if plugin.config.notify_cooldowns && !cooling.told {
+        let text = tr_with(
+            "cooldown.waiting",
+            &[
+                ("user", &author),
+                (
+                    "command",
+                    &format!("{}{}", plugin.config.syntax.prefix, name),
+                ),
+                ("seconds", &cooling.left.as_secs().max(1)),
+            ],
+        );
+        let _ = plugin.outgoing.send(Outgoing::Reply {
+            channel: channel.to_owned(),
+            text,
+        });
+    }
+
+    false
+}
+
+/// Answers the text or Lua command `message` starts with, if its author is
+/// let through.
+pub fn respond(plugin: &mut Plugin, message: &ChatMessage) {
+    let mut split = message.text.trim().splitn(2, ' ');
+    let Some(name) = split
+        .next()
+        .and_then(|word| plugin.config.syntax.command(word))
+    else {
+        return;
+    };
+    let args = split.next().unwrap_or_default().trim();
+
+    let registry = &plugin.registry;
+    if CHAT_COMMANDS.contains(&name.as_str())
+        || !(registry.lua.contains_key(&name) || registry.text.contains_key(&name))
+    {
+        return;
+    }
+    if !admit(
+        plugin,
+        &message.channel,
+        &name,
+        &message.author,
+        &message.badges,
+    ) {
+        return;
+    }
+
+    plugin.stats.record_command(&name);
+    plugin.hooks.fire(
+        Hook::CommandExecuted,
+        &Executed {
+            command: &name,
+            channel: &message.channel,
+            author: &message.author,
+        },
+    );
+
+    if let Some(command) = plugin.registry.lua.get(&name) {
+        let callback = command.callback.clone();
+        call(plugin, callback, message, &name, args);
+    } else if let Some(command) = plugin.registry.text.get(&name) {
+        let text = command.response(&plugin.viewers, &message.author);
+        let _ = plugin.outgoing.send(Outgoing::Reply {
+            channel: message.channel.clone(),
+            text,
+        });
+    }
+}
+
+/// Calls a Lua command's callback. A string it returns is sent back to the
+/// channel.
+fn call(
+    plugin: &Plugin,
+    callback: Function<Object, Object>,
+    message: &ChatMessage,
+    name: &str,
+    args: &str,
+) {
+    let context = Context {
+        channel: &message.channel,
+        author: &message.author,
+        badges: &message.badges,
+        args,
+        viewer: plugin.viewers.get(&message.author),
+    };
+    let Ok(context) = context.serialize(Serializer::new()) else {
+        return;
+    };
+
+    let outgoing = plugin.outgoing.clone();
+    let channel = message.channel.clone();
+    let author = message.author.clone();
+    let action = format!("{} {}", name, args).trim_end().to_owned();
+
+    // Called once the plugin is no longer borrowed, the callback may well
+    // use the Lua API itself.
+    nvim_oxi::schedule(move |_| -> nvim_oxi::Result<()> {
+        let reply = match hooks::guarded(callback, context) {
+            Ok(reply) => reply,
+            Err(message) => {
+                hooks::fail(Failure {
+                    context: format!("Lua command {}", action),
+                    message,
+                    command: Some((channel, author, action)),
+                });
+                return Ok(());
+            }
+        };
+
+        if let Ok(text) = String::from_object(reply) {
+            if !text.is_empty() {
+                let _ = outgoing.send(Outgoing::Reply { channel, text });
+            }
+        }
+
+        Ok(())
+    });
+}
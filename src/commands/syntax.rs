@@ -0,0 +1,44 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::config::DEFAULT_PREFIX;
+
+/// The syntax as the connection thread reads it, replaced by
+/// `:TwitchReloadConfig` without reconnecting.
+pub type SharedSyntax = Arc<RwLock<Syntax>>;
+
+/// How chat commands are written: the prefix they start with and the
+/// other names they go by, e.g. `!cs` for `!colorscheme`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Syntax {
+    pub prefix: String,
+    /// Lowercased alias to the command it stands for, without the prefix.
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax {
+            prefix: DEFAULT_PREFIX.to_owned(),
+            aliases: BTreeMap::new(),
+        }
+    }
+}
+
+impl Syntax {
+    /// The lowercased name of the command `word` invokes, with aliases
+    /// resolved, or `None` if it doesn't start with the prefix. Letters in
+    /// the prefix match either case too.
+    pub fn command(&self, word: &str) -> Option<String> {
+        let name = word
+            .get(..self.prefix.len())
+            .filter(|head| head.eq_ignore_ascii_case(&self.prefix))
+            .map(|_| &word[self.prefix.len()..])
+            .filter(|name| !name.is_empty())?
+            .to_lowercase();
+
+        Some(self.aliases.get(&name).cloned().unwrap_or(name))
+    }
+}
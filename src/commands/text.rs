@@ -0,0 +1,47 @@
+//! Chat commands that answer with a fixed text, like `!discord`. They are
+//! kept in the plugin state so they survive restarts, and answered from
+//! the [`CommandRegistry`](super::registry::CommandRegistry).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{state::State, viewers::Viewers, Plugin};
+
+const STATE_KEY: &str = "text_commands";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextCommand {
+    /// May contain `{user}`, replaced with the name of whoever used it,
+    /// `{followage}` with how long they follow and `{uptime}` with how long
+    /// the stream is live.
+    pub response: String,
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
+impl TextCommand {
+    /// The response for `author`, with its variables filled in.
+    pub fn response(&self, viewers: &Viewers, author: &str) -> String {
+        self.response
+            .replace("{user}", author)
+            .replace("{followage}", &viewers.followage(author))
+            .replace("{uptime}", &viewers.uptime())
+    }
+}
+
+pub fn load(state: &State) -> BTreeMap<String, TextCommand> {
+    state.get(STATE_KEY).unwrap_or_default()
+}
+
+/// Adds or replaces `commands` and returns how many there are now.
+pub fn insert(plugin: &mut Plugin, commands: BTreeMap<String, TextCommand>) -> usize {
+    let mut stored = load(&plugin.state);
+    stored.extend(commands);
+
+    plugin.state.set(STATE_KEY, &stored);
+    let count = stored.len();
+    plugin.registry.set_text(stored);
+
+    count
+}
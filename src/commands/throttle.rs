@@ -0,0 +1,190 @@
+//! Token buckets that keep chat from running the same command over and
+//! over, one per command and one per viewer and command.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::CooldownOptions;
+
+/// Where the buckets are kept between sessions.
+pub const STATE_KEY: &str = "cooldowns";
+
+/// Viewers are forgotten once this many are tracked and their buckets
+/// have refilled.
+const MAX_USERS: usize = 1000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// The cooldown it was last taken from with, so it can be refilled
+    /// while sweeping without knowing which command it is for.
+    burst: u32,
+    refill: Duration,
+}
+
+impl Bucket {
+    fn full(burst: u32, refill: Duration, now: Instant) -> Self {
+        Bucket {
+            tokens: f64::from(burst),
+            updated: now,
+            burst,
+            refill,
+        }
+    }
+
+    /// Refills the bucket and returns how long until it has a token.
+    fn wait(&mut self, burst: u32, refill: Duration, now: Instant) -> Duration {
+        (self.burst, self.refill) = (burst, refill);
+        self.refill(now);
+
+        match self.tokens >= 1.0 {
+            true => Duration::ZERO,
+            false => refill.mul_f64(1.0 - self.tokens),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let burst = f64::from(self.burst);
+        self.tokens = match self.refill.is_zero() {
+            true => burst,
+            false => {
+                let refilled =
+                    now.duration_since(self.updated).as_secs_f64() / self.refill.as_secs_f64();
+                (self.tokens + refilled).min(burst)
+            }
+        };
+        self.updated = now;
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= f64::from(self.burst)
+    }
+}
+
+#[derive(Default)]
+pub struct Throttle {
+    commands: HashMap<String, Bucket>,
+    users: HashMap<(String, String), Bucket>,
+    /// Viewers told about a cooldown, so they are told once per cooldown
+    /// rather than on every try.
+    told: HashSet<(String, String)>,
+}
+
+/// A bucket as kept in the plugin state. `Instant`s don't survive a
+/// restart, so it is saved with the wall clock time it was last updated.
+#[derive(Serialize, Deserialize)]
+pub struct SavedBucket {
+    command: String,
+    /// Unset for the bucket shared by everyone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    tokens: f64,
+    updated: DateTime<Local>,
+    #[serde(default)]
+    burst: u32,
+    /// In seconds.
+    #[serde(default)]
+    refill: f64,
+}
+
+/// Why a command was held back.
+pub struct Cooling {
+    pub left: Duration,
+    /// Whether the viewer was already told about this cooldown.
+    pub told: bool,
+}
+
+impl Throttle {
+    /// Takes a token for `user` running `command` if both buckets have one.
+    pub fn take(
+        &mut self,
+        options: &CooldownOptions,
+        command: &str,
+        user: &str,
+    ) -> Result<(), Cooling> {
+        let now = Instant::now();
+
+        if self.users.len() > MAX_USERS {
+            self.users.retain(|_, bucket| !bucket.is_full(now));
+            let users = &self.users;
+            self.told.retain(|key| users.contains_key(key));
+        }
+
+        let key = (command.to_owned(), user.to_lowercase());
+
+        let shared = self
+            .commands
+            .entry(command.to_owned())
+            .or_insert_with(|| Bucket::full(options.burst, options.command, now));
+        let own = self
+            .users
+            .entry(key.clone())
+            .or_insert_with(|| Bucket::full(options.burst, options.user, now));
+
+        let left = shared
+            .wait(options.burst, options.command, now)
+            .max(own.wait(options.burst, options.user, now));
+
+        if !left.is_zero() {
+            let told = !self.told.insert(key);
+            return Err(Cooling { left, told });
+        }
+
+        shared.tokens -= 1.0;
+        own.tokens -= 1.0;
+        self.told.remove(&key);
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Vec<SavedBucket> {
+        let (now, wall) = (Instant::now(), Local::now());
+        let saved = |command: &str, user: Option<&str>, bucket: &Bucket| SavedBucket {
+            command: command.to_owned(),
+            user: user.map(str::to_owned),
+            tokens: bucket.tokens,
+            updated: chrono::Duration::from_std(now.duration_since(bucket.updated))
+                .map_or(wall, |age| wall - age),
+            burst: bucket.burst,
+            refill: bucket.refill.as_secs_f64(),
+        };
+
+        self.commands
+            .iter()
+            .map(|(command, bucket)| saved(command, None, bucket))
+            .chain(
+                self.users
+                    .iter()
+                    .map(|((command, user), bucket)| saved(command, Some(user), bucket)),
+            )
+            .collect()
+    }
+
+    /// Puts back the buckets of an earlier session, so restarting Neovim
+    /// doesn't end a cooldown.
+    pub fn restore(&mut self, saved: Vec<SavedBucket>) {
+        let (now, wall) = (Instant::now(), Local::now());
+
+        for bucket in saved {
+            let age = (wall - bucket.updated).to_std().unwrap_or_default();
+            let restored = Bucket {
+                tokens: bucket.tokens,
+                // Counted from now if the clock can't go back that far.
+                updated: now.checked_sub(age).unwrap_or(now),
+                burst: bucket.burst,
+                refill: Duration::try_from_secs_f64(bucket.refill).unwrap_or_default(),
+            };
+
+            match bucket.user {
+                Some(user) => self.users.insert((bucket.command, user), restored),
+                None => self.commands.insert(bucket.command, restored),
+            };
+        }
+    }
+}
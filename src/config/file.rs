@@ -0,0 +1,40 @@
+use std::{fs, io, path::PathBuf, time::SystemTime};
+
+use nvim_oxi::{Array, Dictionary, Object};
+use toml::Value;
+
+pub fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Reads the config file as a table in the same shape `setup()` receives.
+/// A missing file is not an error.
+pub fn read(path: &PathBuf) -> Result<Option<Object>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|e| e.message().to_owned())?;
+
+    Ok(Some(to_object(Value::Table(table))))
+}
+
+fn to_object(value: Value) -> Object {
+    match value {
+        Value::String(s) => Object::from(s),
+        Value::Integer(n) => Object::from(n),
+        Value::Float(n) => Object::from(n),
+        Value::Boolean(b) => Object::from(b),
+        Value::Datetime(date) => Object::from(date.to_string()),
+        Value::Array(values) => Object::from(Array::from_iter(values.into_iter().map(to_object))),
+        Value::Table(table) => Object::from(Dictionary::from_iter(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, to_object(value))),
+        )),
+    }
+}
@@ -0,0 +1,2666 @@
+pub mod file;
+mod validate;
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use nvim_oxi::{api, Array, Dictionary, Object, ObjectKind};
+
+use crate::{
+    auth,
+    commands::{Syntax, CHAT_COMMANDS},
+    events,
+    i18n::{self, tr},
+    logging::Level,
+    paths::{self, PathOverrides},
+    ui::ErrorLog,
+};
+pub use validate::Diagnostic;
+use validate::{join, Validator};
+
+pub const DEFAULT_PREFIX: &str = "!";
+
+/// The colors Twitch gives viewers who never picked one.
+const DEFAULT_PALETTE: [&str; 15] = [
+    "#ff0000", "#0000ff", "#008000", "#b22222", "#ff7f50", "#9acd32", "#ff4500", "#2e8b57",
+    "#daa520", "#d2691e", "#5f9ea0", "#1e90ff", "#ff69b4", "#8a2be2", "#00ff7f",
+];
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub channel: String,
+    /// The application the `:TwitchLogin` token was issued for, needed for
+    /// Helix API calls such as fetching badges.
+    pub client_id: Option<String>,
+    /// The UI language, `None` follows `v:lang`.
+    pub locale: Option<String>,
+    pub window: WindowOptions,
+    /// Chat commands that are acted on, e.g. `"colorscheme"`.
+    pub commands: Vec<String>,
+    /// What chat commands start with, `!` as in `!nvim`, and the other
+    /// names they go by.
+    pub syntax: Syntax,
+    /// Whether `!colorscheme` and `!hl` are confirmed in chat.
+    pub confirm: bool,
+    /// Whether chat commands that change the editor wait for the streamer
+    /// to approve them.
+    pub approve: bool,
+    /// The colorschemes `!colorscheme` may switch to, any installed one
+    /// when empty.
+    pub colorschemes: Vec<String>,
+    /// The templates `!snippet <name>` inserts at the cursor, keyed by
+    /// name.
+    pub snippets: BTreeMap<String, String>,
+    /// Shown over and over while the editor is open.
+    pub announcements: Vec<Announcement>,
+    /// Rate limits for each chat command, commands left out have none.
+    pub cooldowns: BTreeMap<String, CooldownOptions>,
+    /// Whether viewers are told in chat when a command is cooling down.
+    pub notify_cooldowns: bool,
+    /// The badge sets allowed to use each chat command, e.g. `moderator`.
+    /// Commands left out are open to everyone.
+    pub permissions: BTreeMap<String, Vec<String>>,
+    /// Extra channels to join, each with its own overrides.
+    pub channels: BTreeMap<String, ChannelOverrides>,
+    pub paths: PathOverrides,
+    pub llm: LlmOptions,
+    pub moderation: ModerationOptions,
+    pub metrics: MetricsOptions,
+    pub webhooks: Vec<WebhookOptions>,
+    /// Identities messages can be sent as, keyed by the name given to
+    /// `:TwitchLogin`. `main` is always there.
+    pub accounts: BTreeMap<String, AccountOptions>,
+    /// The account automated replies are sent as, `main` when unset.
+    pub bot: Option<String>,
+    pub shared_chat: SharedChatFilter,
+    /// Where chat comes from.
+    pub backend: BackendKind,
+    /// The local port the `websocket` backend listens on.
+    pub port: u16,
+    /// What clients of the `websocket` backend have to send before they
+    /// are listened to. Without one the backend doesn't start.
+    pub backend_token: String,
+    /// Browser pages allowed to connect to the `websocket` backend, by
+    /// origin. Clients outside a browser send none and need no entry.
+    pub backend_origins: Vec<String>,
+    pub render: RenderMode,
+    /// How long commands arriving after one another are collected before
+    /// they are handled together, in `batched` rendering.
+    pub batch_window: Duration,
+    /// How long a Lua callback may run before it is stopped.
+    pub callback_timeout: Duration,
+    /// The normal mode mapping that jumps into the chat window and back.
+    pub focus_key: Option<String>,
+    /// The least severe lines written to the log, `:TwitchLog <level>`
+    /// switches at runtime.
+    pub log_level: Level,
+    pub limits: LimitOptions,
+    pub accessibility: AccessibilityOptions,
+    pub raffle: RaffleOptions,
+    pub display: DisplayOptions,
+    pub filter: FilterOptions,
+    pub ignore: IgnoreOptions,
+    pub flood: FloodOptions,
+    pub sounds: SoundOptions,
+    pub redemptions: RedemptionOptions,
+    pub celebrations: CelebrationOptions,
+    pub trythis: TryThisOptions,
+    pub banner: BannerOptions,
+    /// Highlight groups set over the plugin's defaults, keyed by name.
+    pub highlights: BTreeMap<String, HighlightOverride>,
+}
+
+/// A `highlights` entry, either a link to another group or colors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HighlightOverride {
+    pub link: Option<String>,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// What `!nvim` messages are checked against before they are shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterOptions {
+    /// Vim regexes, matched ignoring case. Messages matching any are dropped.
+    pub blocklist: Vec<String>,
+    /// Whether links are replaced with `[link]`.
+    pub strip_links: bool,
+    /// Characters kept of longer messages, `None` keeps them whole.
+    pub max_length: Option<usize>,
+    /// What is done on Twitch about chat messages matching a rule, the
+    /// first one that matches counts.
+    pub actions: Vec<FilterAction>,
+    /// Only logs the `actions` rather than taking them.
+    pub dry_run: bool,
+}
+
+/// A `filter.actions` rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterAction {
+    pub rule: FilterRule,
+    pub action: ModAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterRule {
+    /// A Vim regex, matched ignoring case.
+    Pattern(String),
+    /// Any message with a link.
+    Links,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModAction {
+    /// Deletes the message.
+    Delete,
+    /// Times the viewer out for as many seconds.
+    Timeout(u32),
+}
+
+/// Accounts whose messages never run a command or reach the chat window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnoreOptions {
+    /// Whether the well-known bots like Nightbot are ignored.
+    pub known_bots: bool,
+    /// Logins, in lowercase.
+    pub names: Vec<String>,
+    /// Vim regexes matched against logins, ignoring case.
+    pub patterns: Vec<String>,
+    /// Whether the accounts the streamer blocked on Twitch are ignored.
+    pub twitch_blocks: bool,
+}
+
+/// Shown in an alert every `every`, and said in chat as well with `chat`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub text: String,
+    pub every: Duration,
+    pub chat: bool,
+}
+
+/// When chat counts as flooding, and the slow mode it brings on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloodOptions {
+    pub enabled: bool,
+    /// `!nvim` messages a second from one viewer that count as a flood.
+    pub per_user: usize,
+    /// The same from everyone together.
+    pub global: usize,
+    /// The wait between two messages of a viewer in slow mode.
+    pub slow: Duration,
+    /// How long chat has to stay calm for slow mode to end again.
+    pub calm: Duration,
+}
+
+/// Sounds played on events, in builds with the `sounds` feature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundOptions {
+    /// The player and its arguments, with `{file}` and `{volume}` (0 to
+    /// 100) filled in. One of a few common players when unset.
+    pub player: Option<Vec<String>>,
+    /// Scales the volume of every sound.
+    pub volume: f64,
+    /// Keyed by `command`, `raid` or `sub`.
+    pub events: BTreeMap<String, Sound>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    /// Expanded like `expand()` does, so `~` works.
+    pub file: String,
+    /// From 0 to 1.
+    pub volume: f64,
+}
+
+/// The floats shown for subs, resubs and raids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CelebrationOptions {
+    pub enabled: bool,
+    /// Whether an ASCII-art banner is shown above the text.
+    pub banners: bool,
+    /// How long each float stays up.
+    pub duration: Duration,
+    /// Texts replacing the translated ones, keyed by `sub`, `resub` or
+    /// `raid`. `{user}`, `{months}` and `{viewers}` are filled in.
+    pub templates: BTreeMap<String, String>,
+}
+
+/// `!trythis`, trying out a colorscheme for a while.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TryThisOptions {
+    /// How long until the previous colorscheme comes back.
+    pub duration: Duration,
+}
+
+/// `!banner`, large letters across the top of the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BannerOptions {
+    /// How long each letter takes to appear.
+    pub step: Duration,
+    /// How long the banner stays up once it is all there.
+    pub duration: Duration,
+}
+
+/// Channel point redemptions, received over EventSub.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RedemptionOptions {
+    pub enabled: bool,
+    /// Keyed by reward title in lowercase.
+    pub actions: BTreeMap<String, RedemptionAction>,
+}
+
+/// What a redemption does in the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedemptionAction {
+    /// Switches to the next allowed colorscheme.
+    Colorscheme,
+    /// Opens a random file under the working directory.
+    RandomFile,
+    /// An Ex command, without the leading `:`.
+    Command(String),
+}
+
+/// How `!nvim` messages take turns in the chat window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayOptions {
+    pub mode: DisplayMode,
+    /// How long each message stays up before the next one in `queue` mode.
+    pub min_duration: Duration,
+    /// Messages waiting in `queue` mode before `overflow` applies.
+    pub max_depth: usize,
+    pub overflow: Overflow,
+    pub annotations: AnnotationOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DisplayMode {
+    /// Every message is appended to the chat log right away.
+    #[default]
+    Log,
+    /// One message at a time, the others wait their turn.
+    Queue,
+    /// Each message as virtual text next to the cursor, for a while.
+    Annotate,
+}
+
+/// Messages shown in `annotate` mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationOptions {
+    /// The highlight group of the message text.
+    pub highlight: String,
+    pub duration: Duration,
+    /// Annotations at a time, the oldest goes to make room.
+    pub max: usize,
+}
+
+/// What happens to a message that arrives while the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Overflow {
+    /// The longest waiting message makes room.
+    #[default]
+    DropOldest,
+    /// The new message is dropped.
+    DropNewest,
+}
+
+/// A token bucket for one chat command: `burst` uses at once, refilled one
+/// every `command` for everyone together and every `user` for each viewer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CooldownOptions {
+    pub burst: u32,
+    pub command: Duration,
+    pub user: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaffleOptions {
+    /// Tickets a subscriber enters with, everyone else gets one.
+    pub sub_luck: u32,
+}
+
+/// Plain output for screen readers: chat and alerts are appended to a normal
+/// buffer instead of being shown in floats.
+#[derive(Debug, Clone, Default)]
+pub struct AccessibilityOptions {
+    pub enabled: bool,
+    /// Also `:echomsg` every line.
+    pub echo: bool,
+}
+
+/// Caps on what is kept in memory, so long streams don't slowly grow the
+/// Neovim process. The oldest entries go first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitOptions {
+    /// Chat messages kept for summaries, threads and the mod view.
+    pub history: usize,
+    /// Entries in `:TwitchErrors`.
+    pub errors: usize,
+    /// Distinct chatters, emotes and words each in the session stats.
+    pub stats: usize,
+    /// Chat messages waiting to be handled before new ones are dropped.
+    pub pending: usize,
+    /// Lines kept in each plain text chat buffer.
+    pub buffer: usize,
+    /// Chatters whose Helix lookup is kept.
+    pub viewers: usize,
+}
+
+/// How chat messages reach the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RenderMode {
+    /// Everything that arrived since the last tick is drawn in one go.
+    #[default]
+    Batched,
+    /// Messages are handled as soon as they arrive, without waiting for
+    /// `batch_window`, each added on its own and drawn right away.
+    Immediate,
+}
+
+impl RenderMode {
+    pub const NAMES: [&'static str; 2] = ["batched", "immediate"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "batched" => Some(RenderMode::Batched),
+            "immediate" => Some(RenderMode::Immediate),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Batched => "batched",
+            RenderMode::Immediate => "immediate",
+        }
+    }
+}
+
+/// What chat is read from and sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BackendKind {
+    #[default]
+    Twitch,
+    /// Clients on `127.0.0.1:<port>` instead, for overlays and tests.
+    WebSocket,
+}
+
+/// Which messages of a Shared Chat session are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SharedChatFilter {
+    #[default]
+    All,
+    /// Only those sent in our own channel.
+    Own,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountOptions {
+    /// Messages allowed per 30 seconds, Twitch allows 20 or 100 as a
+    /// moderator.
+    pub rate_limit: u32,
+    /// The environment variable holding the account's OAuth token, used
+    /// instead of the stored credentials when it is set.
+    pub token_env: Option<String>,
+}
+
+/// POSTs every event whose type is listed in `events` to `url`.
+#[derive(Debug, Clone)]
+pub struct WebhookOptions {
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+/// Serves Prometheus metrics on `http://<address>/metrics` when enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsOptions {
+    pub enabled: bool,
+    pub address: SocketAddr,
+}
+
+impl MetricsOptions {
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.enabled.then_some(self.address)
+    }
+}
+
+/// Scores messages with the `llm` endpoint and hides those at or above
+/// `threshold` from the chat window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModerationOptions {
+    pub enabled: bool,
+    pub threshold: f64,
+}
+
+/// An OpenAI-compatible chat completion endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmOptions {
+    pub endpoint: String,
+    pub model: String,
+    /// The environment variable holding the API key, so the key itself never
+    /// ends up in a config file.
+    pub api_key_env: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowOptions {
+    pub layout: Layout,
+    pub width: u32,
+    pub height: u32,
+    /// The corner of the editor the window sits in, or its center.
+    pub anchor: Anchor,
+    /// Rows and columns the window is moved from its `anchor`.
+    pub row: i32,
+    pub col: i32,
+    pub border: Border,
+    /// The window's `winblend`, `None` follows the global option.
+    pub winblend: Option<u32>,
+    /// Stacking order of the chat, alert and pinned floats.
+    pub zindex: u32,
+    pub collision: Collision,
+    pub scope: WindowScope,
+    /// How long the chat log may grow before the oldest messages go.
+    pub max_lines: usize,
+    /// Whether each message starts with the time it arrived.
+    pub timestamps: bool,
+    /// Whether authors are shown in their Twitch chat color rather than
+    /// `TwitchAuthor`.
+    pub author_colors: bool,
+    /// The `#rrggbb` colors for authors without a Twitch color, each always
+    /// getting the same one by their name. Empty leaves them `TwitchAuthor`.
+    pub palette: Vec<String>,
+    /// Whether message text is wrapped by the plugin, to `indent` the
+    /// lines after the first, rather than by Neovim.
+    pub wrap: bool,
+    pub indent: usize,
+    /// Messages wrapped onto more lines than this are cut off, 0 never cuts
+    /// them.
+    pub max_height: usize,
+    pub emotes: EmoteStyle,
+    /// How long the chat window stays open after the last message, `None`
+    /// keeps it open.
+    pub dismiss_after: Option<Duration>,
+    /// Whether the window fades out through `winblend` before it closes.
+    pub fade: bool,
+    /// Messages taller than this many lines start out folded in the
+    /// sidebar, 0 never folds them.
+    pub fold_lines: usize,
+    pub focus: Focus,
+}
+
+/// What kind of window the chat log is shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Layout {
+    #[default]
+    Float,
+    /// A vertical split along the left or right edge of the editor, which
+    /// stays open until it is toggled.
+    Sidebar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Anchor {
+    #[default]
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Border {
+    None,
+    Single,
+    Double,
+    #[default]
+    Rounded,
+    Solid,
+    Shadow,
+}
+
+impl Border {
+    /// The columns and rows the border adds around the window.
+    pub fn size(self) -> (u32, u32) {
+        match self {
+            Border::None => (0, 0),
+            // Only to the right and below.
+            Border::Shadow => (1, 1),
+            _ => (2, 2),
+        }
+    }
+}
+
+/// How emotes are shown in the chat window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EmoteStyle {
+    /// As sent, highlighted with `TwitchEmote`.
+    Text,
+    /// As `:Kappa:`, highlighted with `TwitchEmote`.
+    #[default]
+    Placeholder,
+    /// As placeholders that fire `User TwitchEmote`, for image plugins to
+    /// draw over.
+    Image,
+}
+
+/// Where the chat window lives when there are several tabpages.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WindowScope {
+    /// One window that follows into whichever tabpage is entered.
+    #[default]
+    Global,
+    /// A window of its own in each tabpage, opened and closed separately.
+    Tab,
+}
+
+/// Whether a message opening the chat window moves the cursor into it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Focus {
+    #[default]
+    Always,
+    /// Leave the cursor where it is.
+    Never,
+    /// Only in normal mode, after a few seconds without typing or moving
+    /// the cursor.
+    Idle,
+}
+
+/// What the plugin's floats do while a completion menu or the command line
+/// is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Collision {
+    /// Stay at the configured `zindex`.
+    #[default]
+    Stack,
+    /// Drop below every other float until it is done.
+    Lower,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WindowOverrides {
+    pub layout: Option<Layout>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub anchor: Option<Anchor>,
+    pub row: Option<i32>,
+    pub col: Option<i32>,
+    pub border: Option<Border>,
+    pub winblend: Option<Option<u32>>,
+    pub zindex: Option<u32>,
+    pub collision: Option<Collision>,
+    pub scope: Option<WindowScope>,
+    pub max_lines: Option<usize>,
+    pub timestamps: Option<bool>,
+    pub author_colors: Option<bool>,
+    pub palette: Option<Vec<String>>,
+    pub wrap: Option<bool>,
+    pub indent: Option<usize>,
+    pub max_height: Option<usize>,
+    pub emotes: Option<EmoteStyle>,
+    pub dismiss_after: Option<Option<Duration>>,
+    pub fade: Option<bool>,
+    pub fold_lines: Option<usize>,
+    pub focus: Option<Focus>,
+}
+
+/// Options set for a single channel. Anything left unset falls back to the
+/// top-level value.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelOverrides {
+    pub window: WindowOverrides,
+    pub commands: Option<Vec<String>>,
+    pub output: Option<Output>,
+}
+
+/// Where a channel's `!nvim` messages go.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Output {
+    /// The chat window, shared by every channel routed to it.
+    #[default]
+    Window,
+    /// A listed buffer of the channel's own, one line per message.
+    Buffer,
+}
+
+/// The options that apply to one channel after layering its overrides on top
+/// of the top-level config.
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    pub window: WindowOptions,
+    pub commands: Vec<String>,
+    pub output: Output,
+}
+
+impl ChannelConfig {
+    pub fn command_enabled(&self, name: &str) -> bool {
+        self.commands.iter().any(|command| command == name)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            channel: "mikerimebot".to_owned(),
+            client_id: None,
+            locale: None,
+            window: WindowOptions::default(),
+            commands: CHAT_COMMANDS.iter().map(|&name| name.to_owned()).collect(),
+            syntax: Syntax::default(),
+            confirm: true,
+            approve: false,
+            colorschemes: Vec::new(),
+            snippets: BTreeMap::new(),
+            announcements: Vec::new(),
+            cooldowns: BTreeMap::from([
+                (
+                    "colorscheme".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(30),
+                    },
+                ),
+                (
+                    "trythis".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(30),
+                        user: Duration::from_secs(120),
+                    },
+                ),
+                (
+                    "banner".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(60),
+                        user: Duration::from_secs(300),
+                    },
+                ),
+                (
+                    "hl".to_owned(),
+                    CooldownOptions {
+                        burst: 3,
+                        command: Duration::from_secs(2),
+                        user: Duration::from_secs(10),
+                    },
+                ),
+                (
+                    "opt".to_owned(),
+                    CooldownOptions {
+                        burst: 2,
+                        command: Duration::from_secs(5),
+                        user: Duration::from_secs(30),
+                    },
+                ),
+                (
+                    "font".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(30),
+                        user: Duration::from_secs(120),
+                    },
+                ),
+                (
+                    "snippet".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(60),
+                    },
+                ),
+                (
+                    "remind".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(5),
+                        user: Duration::from_secs(60),
+                    },
+                ),
+                (
+                    "where".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(60),
+                    },
+                ),
+                (
+                    "uptime".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(60),
+                    },
+                ),
+                (
+                    "help".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(60),
+                    },
+                ),
+                (
+                    "goto".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(10),
+                        user: Duration::from_secs(30),
+                    },
+                ),
+                (
+                    "keymap".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(30),
+                        user: Duration::from_secs(120),
+                    },
+                ),
+                (
+                    "suggest".to_owned(),
+                    CooldownOptions {
+                        burst: 1,
+                        command: Duration::from_secs(30),
+                        user: Duration::from_secs(120),
+                    },
+                ),
+            ]),
+            notify_cooldowns: false,
+            permissions: [
+                "hl", "goto", "keymap", "suggest", "banner", "opt", "font", "snippet",
+            ]
+            .into_iter()
+            .map(|command| {
+                let badges = ["broadcaster", "moderator", "vip"].map(str::to_owned);
+                (command.to_owned(), badges.to_vec())
+            })
+            .collect(),
+            channels: BTreeMap::new(),
+            paths: PathOverrides::default(),
+            llm: LlmOptions::default(),
+            moderation: ModerationOptions::default(),
+            metrics: MetricsOptions::default(),
+            webhooks: Vec::new(),
+            accounts: BTreeMap::from([(auth::MAIN.to_owned(), AccountOptions::default())]),
+            bot: None,
+            shared_chat: SharedChatFilter::default(),
+            backend: BackendKind::default(),
+            port: 9001,
+            backend_token: String::new(),
+            backend_origins: Vec::new(),
+            render: RenderMode::default(),
+            batch_window: Duration::from_millis(50),
+            callback_timeout: Duration::from_secs(1),
+            focus_key: None,
+            log_level: Level::default(),
+            limits: LimitOptions::default(),
+            accessibility: AccessibilityOptions::default(),
+            display: DisplayOptions::default(),
+            filter: FilterOptions::default(),
+            ignore: IgnoreOptions::default(),
+            flood: FloodOptions::default(),
+            sounds: SoundOptions::default(),
+            redemptions: RedemptionOptions::default(),
+            celebrations: CelebrationOptions::default(),
+            trythis: TryThisOptions::default(),
+            banner: BannerOptions::default(),
+            highlights: BTreeMap::new(),
+            raffle: RaffleOptions::default(),
+        }
+    }
+}
+
+impl Default for AccountOptions {
+    fn default() -> Self {
+        AccountOptions {
+            rate_limit: 20,
+            token_env: None,
+        }
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            mode: DisplayMode::default(),
+            min_duration: Duration::from_secs(3),
+            max_depth: 20,
+            overflow: Overflow::default(),
+            annotations: AnnotationOptions::default(),
+        }
+    }
+}
+
+impl Default for AnnotationOptions {
+    fn default() -> Self {
+        AnnotationOptions {
+            highlight: "TwitchAnnotation".to_owned(),
+            duration: Duration::from_secs(8),
+            max: 3,
+        }
+    }
+}
+
+impl Default for CelebrationOptions {
+    fn default() -> Self {
+        CelebrationOptions {
+            enabled: true,
+            banners: false,
+            duration: Duration::from_secs(8),
+            templates: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for TryThisOptions {
+    fn default() -> Self {
+        TryThisOptions {
+            duration: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Default for BannerOptions {
+    fn default() -> Self {
+        BannerOptions {
+            step: Duration::from_millis(80),
+            duration: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        FilterOptions {
+            blocklist: Vec::new(),
+            strip_links: true,
+            max_length: None,
+            actions: Vec::new(),
+            dry_run: false,
+        }
+    }
+}
+
+impl Default for IgnoreOptions {
+    fn default() -> Self {
+        IgnoreOptions {
+            known_bots: true,
+            names: Vec::new(),
+            patterns: Vec::new(),
+            twitch_blocks: true,
+        }
+    }
+}
+
+impl Default for FloodOptions {
+    fn default() -> Self {
+        FloodOptions {
+            enabled: false,
+            per_user: 3,
+            global: 20,
+            slow: Duration::from_secs(10),
+            calm: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Default for SoundOptions {
+    fn default() -> Self {
+        SoundOptions {
+            player: None,
+            volume: 1.0,
+            events: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for RaffleOptions {
+    fn default() -> Self {
+        RaffleOptions { sub_luck: 1 }
+    }
+}
+
+impl Default for LimitOptions {
+    fn default() -> Self {
+        LimitOptions {
+            history: 5000,
+            errors: 1000,
+            stats: 10_000,
+            pending: 10_000,
+            buffer: 10_000,
+            viewers: 10_000,
+        }
+    }
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        MetricsOptions {
+            enabled: false,
+            address: SocketAddr::from(([127, 0, 0, 1], 9184)),
+        }
+    }
+}
+
+impl Default for ModerationOptions {
+    fn default() -> Self {
+        ModerationOptions {
+            enabled: false,
+            threshold: 0.8,
+        }
+    }
+}
+
+impl Default for LlmOptions {
+    fn default() -> Self {
+        LlmOptions {
+            endpoint: "https://api.openai.com/v1/chat/completions".to_owned(),
+            model: "gpt-4o-mini".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+        }
+    }
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        WindowOptions {
+            layout: Layout::default(),
+            width: 40,
+            height: 10,
+            anchor: Anchor::default(),
+            row: 0,
+            col: 0,
+            border: Border::default(),
+            winblend: None,
+            zindex: 50,
+            collision: Collision::default(),
+            scope: WindowScope::default(),
+            max_lines: 1000,
+            timestamps: true,
+            author_colors: true,
+            palette: DEFAULT_PALETTE
+                .iter()
+                .map(|&color| color.to_owned())
+                .collect(),
+            wrap: true,
+            indent: 2,
+            max_height: 10,
+            emotes: EmoteStyle::default(),
+            dismiss_after: None,
+            fade: true,
+            fold_lines: 4,
+            focus: Focus::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Builds the config from the defaults, then the config files, then the
+    /// `setup()` table. Invalid values are reported and keep their previous
+    /// value rather than being rejected outright.
+    pub fn load(opts: &Object) -> (Config, Vec<Diagnostic>) {
+        let mut config = Config::default();
+        let mut diagnostics = Vec::new();
+
+        for path in paths::config_files() {
+            match file::read(&path) {
+                Ok(Some(table)) => diagnostics.extend(config.merge(table)),
+                Ok(None) => (),
+                Err(message) => diagnostics.push(Diagnostic {
+                    path: path.display().to_string(),
+                    message,
+                }),
+            }
+        }
+
+        diagnostics.extend(config.merge(opts.clone()));
+
+        (config, diagnostics)
+    }
+
+    /// Whether `other` changes something the connection was started with,
+    /// which it only picks up by reconnecting.
+    pub fn connection_changed(&self, other: &Config) -> bool {
+        self.backend != other.backend
+            || self.port != other.port
+            || self.backend_token != other.backend_token
+            || self.backend_origins != other.backend_origins
+            || self.channels() != other.channels()
+            || self.client_id != other.client_id
+            || self.accounts != other.accounts
+            || self.paths != other.paths
+            || self.llm != other.llm
+            || self.moderation != other.moderation
+            || self.metrics != other.metrics
+            || self.shared_chat != other.shared_chat
+            || self.redemptions.enabled != other.redemptions.enabled
+            || self.ignore.twitch_blocks != other.ignore.twitch_blocks
+    }
+
+    /// The primary channel along with every channel that has overrides.
+    pub fn channels(&self) -> HashSet<String> {
+        let mut channels: HashSet<_> = self.channels.keys().cloned().collect();
+        channels.insert(self.channel.clone());
+        channels
+    }
+
+    pub fn for_channel(&self, channel: &str) -> ChannelConfig {
+        let overrides = self.channels.get(channel);
+
+        ChannelConfig {
+            window: match overrides {
+                Some(overrides) => self.window.layered(&overrides.window),
+                None => self.window.clone(),
+            },
+            commands: overrides
+                .and_then(|overrides| overrides.commands.clone())
+                .unwrap_or_else(|| self.commands.clone()),
+            output: overrides
+                .and_then(|overrides| overrides.output)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn merge(&mut self, opts: Object) -> Vec<Diagnostic> {
+        let mut validator = Validator::default();
+
+        if !opts.is_nil() {
+            if let Some(table) = validator.table("setup", opts) {
+                self.apply(table, &mut validator, "");
+            }
+        }
+
+        validator.diagnostics
+    }
+
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "channel" => {
+                    if let Some(channel) = validator.string(&path, value) {
+                        self.channel = channel;
+                    }
+                }
+                "client_id" => {
+                    if let Some(client_id) = validator.string(&path, value) {
+                        self.client_id = Some(client_id);
+                    }
+                }
+                "locale" => {
+                    let Some(locale) = validator.string(&path, value) else {
+                        continue;
+                    };
+
+                    if i18n::locales().any(|known| known == locale) {
+                        self.locale = Some(locale);
+                    } else {
+                        let known = i18n::locales().collect::<Vec<_>>().join(", ");
+                        validator.error(&path, format!("must be one of {}", known));
+                    }
+                }
+                "window" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.window.apply(table, validator, &path);
+                    }
+                }
+                "commands" => {
+                    if let Some(commands) = commands(validator, &path, value) {
+                        self.commands = commands;
+                    }
+                }
+                "prefix" => match validator.string(&path, value) {
+                    Some(prefix)
+                        if !prefix.is_empty()
+                            && prefix.chars().count() <= 3
+                            && !prefix.contains(char::is_whitespace) =>
+                    {
+                        self.syntax.prefix = prefix;
+                    }
+                    Some(_) => validator.error(&path, "must be 1 to 3 characters without spaces"),
+                    None => (),
+                },
+                "aliases" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_aliases(table, validator, &path);
+                    }
+                }
+                "confirm" => {
+                    if let Some(confirm) = validator.boolean(&path, value) {
+                        self.confirm = confirm;
+                    }
+                }
+                "approve" => {
+                    if let Some(approve) = validator.boolean(&path, value) {
+                        self.approve = approve;
+                    }
+                }
+                "permissions" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_permissions(table, validator, &path);
+                    }
+                }
+                "colorschemes" => {
+                    if let Some(colorschemes) = validator.strings(&path, value) {
+                        self.colorschemes = colorschemes;
+                    }
+                }
+                "snippets" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_snippets(table, validator, &path);
+                    }
+                }
+                "announcements" => {
+                    if let Some(list) = validator.list(&path, value) {
+                        self.announcements = announcements(list, validator, &path);
+                    }
+                }
+                "cooldowns" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_cooldowns(table, validator, &path);
+                    }
+                }
+                "notify_cooldowns" => {
+                    if let Some(notify) = validator.boolean(&path, value) {
+                        self.notify_cooldowns = notify;
+                    }
+                }
+                "channels" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_channels(table, validator, &path);
+                    }
+                }
+                "paths" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.paths.apply(table, validator, &path);
+                    }
+                }
+                "llm" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.llm.apply(table, validator, &path);
+                    }
+                }
+                "moderation" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.moderation.apply(table, validator, &path);
+                    }
+                }
+                "metrics" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.metrics.apply(table, validator, &path);
+                    }
+                }
+                "webhooks" => {
+                    if let Some(list) = validator.list(&path, value) {
+                        self.webhooks = webhooks(list, validator, &path);
+                    }
+                }
+                "bot" => match validator.string(&path, value) {
+                    Some(bot) if valid_account(&bot) => {
+                        self.accounts.entry(bot.clone()).or_default();
+                        self.bot = Some(bot);
+                    }
+                    Some(_) => validator.error(&path, "names may only contain a-z, 0-9, - and _"),
+                    None => (),
+                },
+                "shared_chat" => match validator.string(&path, value).as_deref() {
+                    Some("all") => self.shared_chat = SharedChatFilter::All,
+                    Some("own") => self.shared_chat = SharedChatFilter::Own,
+                    Some(_) => validator.error(&path, "must be one of all, own"),
+                    None => (),
+                },
+                "backend" => match validator.string(&path, value).as_deref() {
+                    Some("twitch") => self.backend = BackendKind::Twitch,
+                    Some("websocket") => self.backend = BackendKind::WebSocket,
+                    Some(_) => validator.error(&path, "must be one of twitch, websocket"),
+                    None => (),
+                },
+                "port" => {
+                    if let Some(port) = validator.integer(&path, value, 1, 65535) {
+                        self.port = port as u16;
+                    }
+                }
+                "backend_token" => {
+                    if let Some(token) = validator.string(&path, value) {
+                        self.backend_token = token;
+                    }
+                }
+                "backend_origins" => {
+                    if let Some(origins) = validator.strings(&path, value) {
+                        self.backend_origins = origins;
+                    }
+                }
+                "log_level" => {
+                    let Some(name) = validator.string(&path, value) else {
+                        continue;
+                    };
+
+                    match Level::parse(&name) {
+                        Some(level) => self.log_level = level,
+                        None => validator
+                            .error(&path, format!("must be one of {}", Level::NAMES.join(", "))),
+                    }
+                }
+                "render" => {
+                    let Some(name) = validator.string(&path, value) else {
+                        continue;
+                    };
+
+                    match RenderMode::parse(&name) {
+                        Some(mode) => self.render = mode,
+                        None => validator.error(
+                            &path,
+                            format!("must be one of {}", RenderMode::NAMES.join(", ")),
+                        ),
+                    }
+                }
+                "batch_window" => {
+                    if let Some(seconds) = validator.number(&path, value, 0.0, 1.0) {
+                        self.batch_window = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "callback_timeout" => {
+                    if let Some(seconds) = validator.number(&path, value, 0.01, 60.0) {
+                        self.callback_timeout = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "focus_key" => match validator.string(&path, value) {
+                    Some(key) if key.is_empty() => self.focus_key = None,
+                    Some(key) => self.focus_key = Some(key),
+                    None => (),
+                },
+                "limits" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.limits.apply(table, validator, &path);
+                    }
+                }
+                "accounts" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_accounts(table, validator, &path);
+                    }
+                }
+                "accessibility" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.accessibility.apply(table, validator, &path);
+                    }
+                }
+                "raffle" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.raffle.apply(table, validator, &path);
+                    }
+                }
+                "display" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.display.apply(table, validator, &path);
+                    }
+                }
+                "filter" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.filter.apply(table, validator, &path);
+                    }
+                }
+                "ignore" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.ignore.apply(table, validator, &path);
+                    }
+                }
+                "flood" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.flood.apply(table, validator, &path);
+                    }
+                }
+                "sounds" if !cfg!(feature = "sounds") => {
+                    validator.error(&path, "needs the plugin built with --features sounds");
+                }
+                "sounds" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.sounds.apply(table, validator, &path);
+                    }
+                }
+                "celebrations" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.celebrations.apply(table, validator, &path);
+                    }
+                }
+                "trythis" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.trythis.apply(table, validator, &path);
+                    }
+                }
+                "banner" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.banner.apply(table, validator, &path);
+                    }
+                }
+                "redemptions" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.redemptions.apply(table, validator, &path);
+                    }
+                }
+                "highlights" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_highlights(table, validator, &path);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+
+    fn apply_accounts(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let account = key.to_string_lossy().into_owned();
+            let path = join(path, &account);
+
+            if !valid_account(&account) {
+                validator.error(&path, "names may only contain a-z, 0-9, - and _");
+                continue;
+            }
+
+            if let Some(table) = validator.table(&path, value) {
+                self.accounts
+                    .entry(account)
+                    .or_default()
+                    .apply(table, validator, &path);
+            }
+        }
+    }
+
+    fn apply_snippets(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let name = key.to_string_lossy().to_lowercase();
+            let path = join(path, &name);
+
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                validator.error(&path, "must be a word without spaces");
+                continue;
+            }
+
+            if let Some(template) = validator.string(&path, value) {
+                self.snippets.insert(name, template);
+            }
+        }
+    }
+
+    fn apply_aliases(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let alias = key.to_string_lossy().to_lowercase();
+            let path = join(path, &alias);
+
+            if alias.is_empty() || alias.contains(char::is_whitespace) {
+                validator.error(&path, "must be a word without spaces");
+                continue;
+            }
+            // Registered and text commands can't be checked this early, so
+            // only the built-in ones are kept from being hidden.
+            if CHAT_COMMANDS.contains(&alias.as_str()) {
+                validator.error(&path, "is already a chat command");
+                continue;
+            }
+
+            match validator.string(&path, value) {
+                Some(command) if !command.is_empty() && !command.contains(char::is_whitespace) => {
+                    self.syntax.aliases.insert(alias, command.to_lowercase());
+                }
+                Some(_) => validator.error(&path, "must name a command without the prefix"),
+                None => (),
+            }
+        }
+    }
+
+    fn apply_permissions(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let command = key.to_string_lossy().into_owned();
+            let path = join(path, &command);
+
+            if !CHAT_COMMANDS.contains(&command.as_str()) {
+                validator.error(
+                    &path,
+                    format!(
+                        "is not a chat command (expected one of {})",
+                        CHAT_COMMANDS.join(", ")
+                    ),
+                );
+                continue;
+            }
+
+            if let Some(badges) = validator.strings(&path, value) {
+                self.permissions.insert(command, badges);
+            }
+        }
+    }
+
+    fn apply_cooldowns(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let command = key.to_string_lossy().into_owned();
+            let path = join(path, &command);
+
+            if !CHAT_COMMANDS.contains(&command.as_str()) {
+                validator.error(
+                    &path,
+                    format!(
+                        "is not a chat command (expected one of {})",
+                        CHAT_COMMANDS.join(", ")
+                    ),
+                );
+                continue;
+            }
+
+            // Left out settings start from no limit rather than the default,
+            // so `{ user = 60 }` means just that.
+            let mut options = CooldownOptions {
+                burst: 1,
+                command: Duration::ZERO,
+                user: Duration::ZERO,
+            };
+
+            if let Some(table) = validator.table(&path, value) {
+                options.apply(table, validator, &path);
+                self.cooldowns.insert(command, options);
+            }
+        }
+    }
+
+    fn apply_highlights(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let group = key.to_string_lossy().into_owned();
+            let path = join(path, &group);
+
+            // Only the plugin's own groups, so a typo can't restyle the
+            // editor.
+            if !group.starts_with("Twitch") {
+                validator.error(&path, "is not an nvim-twitch highlight group");
+                continue;
+            }
+
+            // `TwitchBadge = "Special"` is short for `{ link = "Special" }`.
+            if value.kind() == ObjectKind::String {
+                if let Some(link) = validator.string(&path, value) {
+                    let link = Some(link);
+                    self.highlights.insert(
+                        group,
+                        HighlightOverride {
+                            link,
+                            ..Default::default()
+                        },
+                    );
+                }
+                continue;
+            }
+
+            if let Some(table) = validator.table(&path, value) {
+                let mut highlight = HighlightOverride::default();
+                highlight.apply(table, validator, &path);
+                self.highlights.insert(group, highlight);
+            }
+        }
+    }
+
+    fn apply_channels(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            // Twitch channel names are case-insensitive and always sent in
+            // lowercase.
+            let channel = key.to_string_lossy().to_lowercase();
+            let path = join(path, &channel);
+
+            if let Some(table) = validator.table(&path, value) {
+                self.channels
+                    .entry(channel)
+                    .or_default()
+                    .apply(table, validator, &path);
+            }
+        }
+    }
+}
+
+impl WindowOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        let mut overrides = WindowOverrides::default();
+        overrides.apply(table, validator, path);
+
+        *self = self.layered(&overrides);
+    }
+
+    fn layered(&self, overrides: &WindowOverrides) -> WindowOptions {
+        WindowOptions {
+            layout: overrides.layout.unwrap_or(self.layout),
+            width: overrides.width.unwrap_or(self.width),
+            height: overrides.height.unwrap_or(self.height),
+            anchor: overrides.anchor.unwrap_or(self.anchor),
+            row: overrides.row.unwrap_or(self.row),
+            col: overrides.col.unwrap_or(self.col),
+            border: overrides.border.unwrap_or(self.border),
+            winblend: overrides.winblend.unwrap_or(self.winblend),
+            zindex: overrides.zindex.unwrap_or(self.zindex),
+            collision: overrides.collision.unwrap_or(self.collision),
+            scope: overrides.scope.unwrap_or(self.scope),
+            max_lines: overrides.max_lines.unwrap_or(self.max_lines),
+            timestamps: overrides.timestamps.unwrap_or(self.timestamps),
+            author_colors: overrides.author_colors.unwrap_or(self.author_colors),
+            palette: overrides
+                .palette
+                .clone()
+                .unwrap_or_else(|| self.palette.clone()),
+            wrap: overrides.wrap.unwrap_or(self.wrap),
+            indent: overrides.indent.unwrap_or(self.indent),
+            max_height: overrides.max_height.unwrap_or(self.max_height),
+            emotes: overrides.emotes.unwrap_or(self.emotes),
+            dismiss_after: overrides.dismiss_after.unwrap_or(self.dismiss_after),
+            fade: overrides.fade.unwrap_or(self.fade),
+            fold_lines: overrides.fold_lines.unwrap_or(self.fold_lines),
+            focus: overrides.focus.unwrap_or(self.focus),
+        }
+    }
+}
+
+impl WindowOverrides {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "layout" => match validator.string(&path, value).as_deref() {
+                    Some("float") => self.layout = Some(Layout::Float),
+                    Some("sidebar") => self.layout = Some(Layout::Sidebar),
+                    Some(_) => validator.error(&path, "must be one of float, sidebar"),
+                    None => (),
+                },
+                "width" => {
+                    if let Some(width) = validator.integer(&path, value, 3, 500) {
+                        self.width = Some(width as u32);
+                    }
+                }
+                "height" => {
+                    if let Some(height) = validator.integer(&path, value, 3, 200) {
+                        self.height = Some(height as u32);
+                    }
+                }
+                "anchor" => match validator.string(&path, value).as_deref() {
+                    Some("center") => self.anchor = Some(Anchor::Center),
+                    Some("top_left") => self.anchor = Some(Anchor::TopLeft),
+                    Some("top_right") => self.anchor = Some(Anchor::TopRight),
+                    Some("bottom_left") => self.anchor = Some(Anchor::BottomLeft),
+                    Some("bottom_right") => self.anchor = Some(Anchor::BottomRight),
+                    Some(_) => validator.error(
+                        &path,
+                        "must be one of center, top_left, top_right, bottom_left, bottom_right",
+                    ),
+                    None => (),
+                },
+                "row" => {
+                    if let Some(row) = validator.integer(&path, value, -200, 200) {
+                        self.row = Some(row as i32);
+                    }
+                }
+                "col" => {
+                    if let Some(col) = validator.integer(&path, value, -500, 500) {
+                        self.col = Some(col as i32);
+                    }
+                }
+                "border" => match validator.string(&path, value).as_deref() {
+                    Some("none") => self.border = Some(Border::None),
+                    Some("single") => self.border = Some(Border::Single),
+                    Some("double") => self.border = Some(Border::Double),
+                    Some("rounded") => self.border = Some(Border::Rounded),
+                    Some("solid") => self.border = Some(Border::Solid),
+                    Some("shadow") => self.border = Some(Border::Shadow),
+                    Some(_) => validator.error(
+                        &path,
+                        "must be one of none, single, double, rounded, solid, shadow",
+                    ),
+                    None => (),
+                },
+                "winblend" => {
+                    if let Some(winblend) = validator.integer(&path, value, 0, 100) {
+                        self.winblend = Some(Some(winblend as u32));
+                    }
+                }
+                "zindex" => {
+                    if let Some(zindex) = validator.integer(&path, value, 1, 1000) {
+                        self.zindex = Some(zindex as u32);
+                    }
+                }
+                "collision" => match validator.string(&path, value).as_deref() {
+                    Some("stack") => self.collision = Some(Collision::Stack),
+                    Some("lower") => self.collision = Some(Collision::Lower),
+                    Some(_) => validator.error(&path, "must be one of stack, lower"),
+                    None => (),
+                },
+                "scope" => match validator.string(&path, value).as_deref() {
+                    Some("global") => self.scope = Some(WindowScope::Global),
+                    Some("tab") => self.scope = Some(WindowScope::Tab),
+                    Some(_) => validator.error(&path, "must be one of global, tab"),
+                    None => (),
+                },
+                "max_lines" => {
+                    if let Some(max_lines) = validator.integer(&path, value, 10, 100_000) {
+                        self.max_lines = Some(max_lines as usize);
+                    }
+                }
+                "timestamps" => {
+                    if let Some(timestamps) = validator.boolean(&path, value) {
+                        self.timestamps = Some(timestamps);
+                    }
+                }
+                "author_colors" => {
+                    if let Some(author_colors) = validator.boolean(&path, value) {
+                        self.author_colors = Some(author_colors);
+                    }
+                }
+                "palette" => {
+                    if let Some(palette) = validator.strings(&path, value) {
+                        match palette.iter().all(|color| is_hex_color(color)) {
+                            true => {
+                                self.palette =
+                                    Some(palette.iter().map(|color| color.to_lowercase()).collect())
+                            }
+                            false => validator.error(&path, "must be a list of #rrggbb colors"),
+                        }
+                    }
+                }
+                "wrap" => {
+                    if let Some(wrap) = validator.boolean(&path, value) {
+                        self.wrap = Some(wrap);
+                    }
+                }
+                "indent" => {
+                    if let Some(indent) = validator.integer(&path, value, 0, 20) {
+                        self.indent = Some(indent as usize);
+                    }
+                }
+                "max_height" => {
+                    if let Some(max_height) = validator.integer(&path, value, 0, 1000) {
+                        self.max_height = Some(max_height as usize);
+                    }
+                }
+                "emotes" => match validator.string(&path, value).as_deref() {
+                    Some("text") => self.emotes = Some(EmoteStyle::Text),
+                    Some("placeholder") => self.emotes = Some(EmoteStyle::Placeholder),
+                    Some("image") => self.emotes = Some(EmoteStyle::Image),
+                    Some(_) => validator.error(&path, "must be one of text, placeholder, image"),
+                    None => (),
+                },
+                // In seconds, 0 never dismisses.
+                "dismiss_after" => {
+                    if let Some(seconds) = validator.integer(&path, value, 0, 3600) {
+                        self.dismiss_after =
+                            Some((seconds > 0).then(|| Duration::from_secs(seconds as u64)));
+                    }
+                }
+                "fade" => {
+                    if let Some(fade) = validator.boolean(&path, value) {
+                        self.fade = Some(fade);
+                    }
+                }
+                "fold_lines" => {
+                    if let Some(fold_lines) = validator.integer(&path, value, 0, 1000) {
+                        self.fold_lines = Some(fold_lines as usize);
+                    }
+                }
+                "focus" => match validator.string(&path, value).as_deref() {
+                    Some("always") => self.focus = Some(Focus::Always),
+                    Some("never") => self.focus = Some(Focus::Never),
+                    Some("idle") => self.focus = Some(Focus::Idle),
+                    Some(_) => validator.error(&path, "must be one of always, never, idle"),
+                    None => (),
+                },
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl HighlightOverride {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "link" => {
+                    if let Some(link) = validator.string(&path, value) {
+                        self.link = Some(link);
+                    }
+                }
+                "fg" => {
+                    if let Some(fg) = validator.string(&path, value) {
+                        self.fg = Some(fg);
+                    }
+                }
+                "bg" => {
+                    if let Some(bg) = validator.string(&path, value) {
+                        self.bg = Some(bg);
+                    }
+                }
+                "bold" => {
+                    if let Some(bold) = validator.boolean(&path, value) {
+                        self.bold = bold;
+                    }
+                }
+                "italic" => {
+                    if let Some(italic) = validator.boolean(&path, value) {
+                        self.italic = italic;
+                    }
+                }
+                "underline" => {
+                    if let Some(underline) = validator.boolean(&path, value) {
+                        self.underline = underline;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl LlmOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            let slot = match key.as_str() {
+                "endpoint" => &mut self.endpoint,
+                "model" => &mut self.model,
+                "api_key_env" => &mut self.api_key_env,
+                _ => {
+                    validator.unknown(&path);
+                    continue;
+                }
+            };
+
+            if let Some(value) = validator.string(&path, value) {
+                *slot = value;
+            }
+        }
+    }
+}
+
+impl ModerationOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "enabled" => {
+                    if let Some(enabled) = validator.boolean(&path, value) {
+                        self.enabled = enabled;
+                    }
+                }
+                "threshold" => {
+                    if let Some(threshold) = validator.number(&path, value, 0.0, 1.0) {
+                        self.threshold = threshold;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl RaffleOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "sub_luck" => {
+                    if let Some(luck) = validator.integer(&path, value, 1, 10) {
+                        self.sub_luck = luck as u32;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl FilterOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "blocklist" => {
+                    if let Some(patterns) = patterns(value, validator, &path) {
+                        self.blocklist = patterns;
+                    }
+                }
+                "strip_links" => {
+                    if let Some(strip) = validator.boolean(&path, value) {
+                        self.strip_links = strip;
+                    }
+                }
+                // 0 keeps messages whole.
+                "max_length" => {
+                    if let Some(max) = validator.integer(&path, value, 0, 500) {
+                        self.max_length = (max > 0).then_some(max as usize);
+                    }
+                }
+                "actions" => {
+                    if let Some(list) = validator.list(&path, value) {
+                        self.actions = filter_actions(list, validator, &path);
+                    }
+                }
+                "dry_run" => {
+                    if let Some(dry_run) = validator.boolean(&path, value) {
+                        self.dry_run = dry_run;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl IgnoreOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "known_bots" => {
+                    if let Some(known) = validator.boolean(&path, value) {
+                        self.known_bots = known;
+                    }
+                }
+                "names" => {
+                    if let Some(names) = validator.strings(&path, value) {
+                        self.names = names
+                            .iter()
+                            .map(|name| name.trim_start_matches('@').to_lowercase())
+                            .collect();
+                    }
+                }
+                "patterns" => {
+                    if let Some(patterns) = patterns(value, validator, &path) {
+                        self.patterns = patterns;
+                    }
+                }
+                "twitch_blocks" => {
+                    if let Some(blocks) = validator.boolean(&path, value) {
+                        self.twitch_blocks = blocks;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+fn filter_actions(list: Array, validator: &mut Validator, path: &str) -> Vec<FilterAction> {
+    let mut actions = Vec::new();
+
+    for (i, value) in list.into_iter().enumerate() {
+        let path = format!("{}[{}]", path, i + 1);
+
+        let Some(table) = validator.table(&path, value) else {
+            continue;
+        };
+
+        let mut rule = None;
+        let mut action = None;
+        // Ten minutes, like `/timeout`.
+        let mut seconds = 600;
+
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(&path, &key);
+
+            match key.as_str() {
+                "pattern" => match validator.string(&path, value) {
+                    Some(pattern) if crate::filter::matches("", &pattern).is_ok() => {
+                        rule = Some(FilterRule::Pattern(pattern))
+                    }
+                    Some(pattern) => {
+                        validator.error(&path, format!("invalid pattern: {}", pattern))
+                    }
+                    None => (),
+                },
+                "links" => match validator.boolean(&path, value) {
+                    Some(true) => rule = Some(FilterRule::Links),
+                    Some(false) => validator.error(&path, "can only be true"),
+                    None => (),
+                },
+                "action" => match validator.string(&path, value).as_deref() {
+                    Some("delete") => action = Some(ModAction::Delete),
+                    Some("timeout") => action = Some(ModAction::Timeout(0)),
+                    Some(other) => validator.error(
+                        &path,
+                        format!("must be \"delete\" or \"timeout\", not \"{}\"", other),
+                    ),
+                    None => (),
+                },
+                // Twitch's longest timeout is two weeks.
+                "seconds" => {
+                    if let Some(value) = validator.integer(&path, value, 1, 1_209_600) {
+                        seconds = value as u32;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+
+        match (rule, action) {
+            (Some(rule), Some(ModAction::Timeout(_))) => actions.push(FilterAction {
+                rule,
+                action: ModAction::Timeout(seconds),
+            }),
+            (Some(rule), Some(action)) => actions.push(FilterAction { rule, action }),
+            (None, _) => validator.error(&path, "needs a pattern or links = true"),
+            (_, None) => validator.error(&join(&path, "action"), "is required"),
+        }
+    }
+
+    actions
+}
+
+/// A list of Vim regexes, checked here so a typo is reported once rather
+/// than on every message.
+fn patterns(value: Object, validator: &mut Validator, path: &str) -> Option<Vec<String>> {
+    let patterns = validator.strings(path, value)?;
+
+    let invalid: Vec<_> = patterns
+        .iter()
+        .filter(|pattern| crate::filter::matches("", pattern).is_err())
+        .cloned()
+        .collect();
+
+    match invalid.is_empty() {
+        true => Some(patterns),
+        false => {
+            validator.error(path, format!("invalid patterns: {}", invalid.join(", ")));
+            None
+        }
+    }
+}
+
+impl FloodOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "enabled" => {
+                    if let Some(enabled) = validator.boolean(&path, value) {
+                        self.enabled = enabled;
+                    }
+                }
+                "per_user" => {
+                    if let Some(per_user) = validator.integer(&path, value, 1, 100) {
+                        self.per_user = per_user as usize;
+                    }
+                }
+                "global" => {
+                    if let Some(global) = validator.integer(&path, value, 1, 1000) {
+                        self.global = global as usize;
+                    }
+                }
+                // In seconds, like `calm`.
+                "slow" => {
+                    if let Some(seconds) = validator.integer(&path, value, 1, 3600) {
+                        self.slow = Duration::from_secs(seconds as u64);
+                    }
+                }
+                "calm" => {
+                    if let Some(seconds) = validator.integer(&path, value, 1, 3600) {
+                        self.calm = Duration::from_secs(seconds as u64);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl SoundOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "player" => match validator.strings(&path, value) {
+                    Some(player) if !player.is_empty() => self.player = Some(player),
+                    Some(_) => validator.error(&path, "must name a program"),
+                    None => (),
+                },
+                "volume" => {
+                    if let Some(volume) = validator.number(&path, value, 0.0, 1.0) {
+                        self.volume = volume;
+                    }
+                }
+                "command" | "raid" | "sub" => {
+                    let mut sound = Sound {
+                        file: String::new(),
+                        volume: 1.0,
+                    };
+
+                    // `raid = "horn.wav"` is short for `{ file = "horn.wav" }`.
+                    if value.kind() == ObjectKind::String {
+                        if let Some(file) = validator.string(&path, value) {
+                            sound.file = file;
+                        }
+                    } else if let Some(table) = validator.table(&path, value) {
+                        sound.apply(table, validator, &path);
+                    }
+
+                    match sound.file.is_empty() {
+                        true => validator.error(&path, "needs a file"),
+                        false => {
+                            self.events.insert(key, sound);
+                        }
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl Sound {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "file" => {
+                    if let Some(file) = validator.string(&path, value) {
+                        self.file = file;
+                    }
+                }
+                "volume" => {
+                    if let Some(volume) = validator.number(&path, value, 0.0, 1.0) {
+                        self.volume = volume;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl TryThisOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "duration" => {
+                    if let Some(seconds) = validator.number(&path, value, 5.0, 3600.0) {
+                        self.duration = Duration::from_secs_f64(seconds);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl BannerOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "step" => {
+                    if let Some(seconds) = validator.number(&path, value, 0.01, 1.0) {
+                        self.step = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "duration" => {
+                    if let Some(seconds) = validator.number(&path, value, 1.0, 60.0) {
+                        self.duration = Duration::from_secs_f64(seconds);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl CelebrationOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "enabled" => {
+                    if let Some(enabled) = validator.boolean(&path, value) {
+                        self.enabled = enabled;
+                    }
+                }
+                "banners" => {
+                    if let Some(banners) = validator.boolean(&path, value) {
+                        self.banners = banners;
+                    }
+                }
+                "duration" => {
+                    if let Some(seconds) = validator.number(&path, value, 1.0, 60.0) {
+                        self.duration = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "templates" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_templates(table, validator, &path);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+
+    fn apply_templates(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            if !["sub", "resub", "raid"].contains(&key.as_str()) {
+                validator.unknown(&path);
+                continue;
+            }
+
+            if let Some(template) = validator.string(&path, value) {
+                self.templates.insert(key, template);
+            }
+        }
+    }
+}
+
+impl RedemptionOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "enabled" => {
+                    if let Some(enabled) = validator.boolean(&path, value) {
+                        self.enabled = enabled;
+                    }
+                }
+                "actions" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.apply_actions(table, validator, &path);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+
+    fn apply_actions(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let reward = key.to_string_lossy().into_owned();
+            let path = join(path, &reward);
+
+            let action = match validator.string(&path, value) {
+                Some(action) => match action.as_str() {
+                    "colorscheme" => RedemptionAction::Colorscheme,
+                    "random_file" => RedemptionAction::RandomFile,
+                    command => match command.strip_prefix(':') {
+                        Some(command) if !command.trim().is_empty() => {
+                            RedemptionAction::Command(command.to_owned())
+                        }
+                        _ => {
+                            validator.error(
+                                &path,
+                                "must be colorscheme, random_file or an Ex command starting with :",
+                            );
+                            continue;
+                        }
+                    },
+                },
+                None => continue,
+            };
+
+            self.actions.insert(reward.to_lowercase(), action);
+        }
+    }
+}
+
+impl DisplayOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "mode" => match validator.string(&path, value).as_deref() {
+                    Some("log") => self.mode = DisplayMode::Log,
+                    Some("queue") => self.mode = DisplayMode::Queue,
+                    Some("annotate") => self.mode = DisplayMode::Annotate,
+                    Some(_) => validator.error(&path, "must be one of log, queue, annotate"),
+                    None => (),
+                },
+                "min_duration" => {
+                    if let Some(seconds) = validator.number(&path, value, 0.5, 60.0) {
+                        self.min_duration = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "max_depth" => {
+                    if let Some(depth) = validator.integer(&path, value, 1, 1000) {
+                        self.max_depth = depth as usize;
+                    }
+                }
+                "overflow" => match validator.string(&path, value).as_deref() {
+                    Some("drop_oldest") => self.overflow = Overflow::DropOldest,
+                    Some("drop_newest") => self.overflow = Overflow::DropNewest,
+                    Some(_) => validator.error(&path, "must be one of drop_oldest, drop_newest"),
+                    None => (),
+                },
+                "annotations" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.annotations.apply(table, validator, &path);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl AnnotationOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "highlight" => {
+                    if let Some(highlight) = validator.string(&path, value) {
+                        self.highlight = highlight;
+                    }
+                }
+                "duration" => {
+                    if let Some(seconds) = validator.number(&path, value, 1.0, 600.0) {
+                        self.duration = Duration::from_secs_f64(seconds);
+                    }
+                }
+                "max" => {
+                    if let Some(max) = validator.integer(&path, value, 1, 50) {
+                        self.max = max as usize;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl AccessibilityOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            let slot = match key.as_str() {
+                "enabled" => &mut self.enabled,
+                "echo" => &mut self.echo,
+                _ => {
+                    validator.unknown(&path);
+                    continue;
+                }
+            };
+
+            if let Some(value) = validator.boolean(&path, value) {
+                *slot = value;
+            }
+        }
+    }
+}
+
+impl MetricsOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "enabled" => {
+                    if let Some(enabled) = validator.boolean(&path, value) {
+                        self.enabled = enabled;
+                    }
+                }
+                "address" => {
+                    let Some(address) = validator.string(&path, value) else {
+                        continue;
+                    };
+
+                    match address.parse() {
+                        Ok(address) => self.address = address,
+                        Err(_) => validator.error(&path, "must be an address like 127.0.0.1:9184"),
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl LimitOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            let slot = match key.as_str() {
+                "history" => &mut self.history,
+                "errors" => &mut self.errors,
+                "stats" => &mut self.stats,
+                "pending" => &mut self.pending,
+                "buffer" => &mut self.buffer,
+                "viewers" => &mut self.viewers,
+                _ => {
+                    validator.unknown(&path);
+                    continue;
+                }
+            };
+
+            if let Some(limit) = validator.integer(&path, value, 10, 1_000_000) {
+                *slot = limit as usize;
+            }
+        }
+    }
+}
+
+impl AccountOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "rate_limit" => {
+                    if let Some(rate_limit) = validator.integer(&path, value, 1, 100) {
+                        self.rate_limit = rate_limit as u32;
+                    }
+                }
+                "token_env" => {
+                    if let Some(token_env) = validator.string(&path, value) {
+                        self.token_env = Some(token_env);
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+impl CooldownOptions {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "burst" => {
+                    if let Some(burst) = validator.integer(&path, value, 1, 100) {
+                        self.burst = burst as u32;
+                    }
+                }
+                "command" | "user" => {
+                    let Some(seconds) = validator.integer(&path, value, 0, 86_400) else {
+                        continue;
+                    };
+                    let seconds = Duration::from_secs(seconds as u64);
+
+                    match key.as_str() {
+                        "command" => self.command = seconds,
+                        _ => self.user = seconds,
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+/// Whether any of `badges` (as `set/version`) is in the `allowed` sets. An
+/// empty list allows everyone.
+pub fn badges_allowed(allowed: &[String], badges: &[String]) -> bool {
+    allowed.is_empty()
+        || badges.iter().any(|badge| {
+            let set = badge.split('/').next().unwrap_or_default();
+            allowed.iter().any(|allowed| allowed == set)
+        })
+}
+
+/// Account names end up in file names and keyring entries.
+pub fn valid_account(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Whether `color` is `#rrggbb`.
+pub fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn webhooks(list: Array, validator: &mut Validator, path: &str) -> Vec<WebhookOptions> {
+    let mut webhooks = Vec::new();
+
+    for (i, value) in list.into_iter().enumerate() {
+        // Numbered from 1 like the Lua list they come from.
+        let path = format!("{}[{}]", path, i + 1);
+
+        let Some(table) = validator.table(&path, value) else {
+            continue;
+        };
+
+        let mut url = None;
+        let mut events = None;
+
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(&path, &key);
+
+            match key.as_str() {
+                "url" => match validator.string(&path, value) {
+                    Some(value)
+                        if value.starts_with("http://") || value.starts_with("https://") =>
+                    {
+                        url = Some(value)
+                    }
+                    Some(_) => validator.error(&path, "must be an http(s) URL"),
+                    None => (),
+                },
+                "events" => events = event_types(validator, &path, value),
+                _ => validator.unknown(&path),
+            }
+        }
+
+        match (url, events) {
+            (Some(url), Some(events)) => webhooks.push(WebhookOptions { url, events }),
+            (None, _) => validator.error(&join(&path, "url"), "is required"),
+            (_, None) => validator.error(&join(&path, "events"), "is required"),
+        }
+    }
+
+    webhooks
+}
+
+fn announcements(list: Array, validator: &mut Validator, path: &str) -> Vec<Announcement> {
+    let mut announcements = Vec::new();
+
+    for (i, value) in list.into_iter().enumerate() {
+        let path = format!("{}[{}]", path, i + 1);
+
+        let Some(table) = validator.table(&path, value) else {
+            continue;
+        };
+
+        let mut text = None;
+        let mut every = None;
+        let mut chat = false;
+
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(&path, &key);
+
+            match key.as_str() {
+                "text" => match validator.string(&path, value) {
+                    Some(value) if !value.trim().is_empty() => text = Some(value),
+                    Some(_) => validator.error(&path, "must not be empty"),
+                    None => (),
+                },
+                // In minutes, anything shorter would be spam.
+                "every" => {
+                    if let Some(minutes) = validator.integer(&path, value, 1, 1440) {
+                        every = Some(Duration::from_secs(minutes as u64 * 60));
+                    }
+                }
+                "chat" => {
+                    if let Some(value) = validator.boolean(&path, value) {
+                        chat = value;
+                    }
+                }
+                _ => validator.unknown(&path),
+            }
+        }
+
+        match (text, every) {
+            (Some(text), Some(every)) => announcements.push(Announcement { text, every, chat }),
+            (None, _) => validator.error(&join(&path, "text"), "is required"),
+            (_, None) => validator.error(&join(&path, "every"), "is required"),
+        }
+    }
+
+    announcements
+}
+
+fn event_types(validator: &mut Validator, path: &str, value: Object) -> Option<Vec<String>> {
+    let types = validator.strings(path, value)?;
+
+    match types
+        .iter()
+        .find(|kind| !events::TYPES.contains(&kind.as_str()))
+    {
+        Some(unknown) => {
+            validator.error(
+                path,
+                format!(
+                    "contains unknown event \"{}\" (expected one of {})",
+                    unknown,
+                    events::TYPES.join(", ")
+                ),
+            );
+            None
+        }
+        None => Some(types),
+    }
+}
+
+impl ChannelOverrides {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            match key.as_str() {
+                "window" => {
+                    if let Some(table) = validator.table(&path, value) {
+                        self.window.apply(table, validator, &path);
+                    }
+                }
+                "commands" => {
+                    if let Some(commands) = commands(validator, &path, value) {
+                        self.commands = Some(commands);
+                    }
+                }
+                "output" => match validator.string(&path, value).as_deref() {
+                    Some("window") => self.output = Some(Output::Window),
+                    Some("buffer") => self.output = Some(Output::Buffer),
+                    Some(_) => validator.error(&path, "must be one of window, buffer"),
+                    None => (),
+                },
+                _ => validator.unknown(&path),
+            }
+        }
+    }
+}
+
+fn commands(validator: &mut Validator, path: &str, value: Object) -> Option<Vec<String>> {
+    let commands = validator.strings(path, value)?;
+
+    match commands
+        .iter()
+        .find(|command| !CHAT_COMMANDS.contains(&command.as_str()))
+    {
+        Some(unknown) => {
+            validator.error(
+                path,
+                format!(
+                    "contains unknown command \"{}\" (expected one of {})",
+                    unknown,
+                    CHAT_COMMANDS.join(", ")
+                ),
+            );
+            None
+        }
+        None => Some(commands),
+    }
+}
+
+/// Reports every setting that was left at its default in one warning.
+pub fn report(errors: &mut ErrorLog, diagnostics: &[Diagnostic]) {
+    if diagnostics.is_empty() {
+        return;
+    }
+
+    let mut message = tr("config.invalid");
+
+    for diagnostic in diagnostics {
+        message.push_str("\n  ");
+        message.push_str(&diagnostic.to_string());
+    }
+
+    errors.warn("Config", &message);
+}
+
+impl PathOverrides {
+    fn apply(&mut self, table: Dictionary, validator: &mut Validator, path: &str) {
+        for (key, value) in table {
+            let key = key.to_string_lossy().into_owned();
+            let path = join(path, &key);
+
+            let slot = match key.as_str() {
+                "data" => &mut self.data,
+                "cache" => &mut self.cache,
+                "state" => &mut self.state,
+                "log" => &mut self.log,
+                _ => {
+                    validator.unknown(&path);
+                    continue;
+                }
+            };
+
+            if let Some(dir) = validator.string(&path, value) {
+                let expanded = api::call_function::<_, String>("expand", (dir.as_str(),));
+                *slot = Some(expanded.unwrap_or(dir).into());
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use nvim_oxi::{conversion::FromObject, Array, Dictionary, Object, ObjectKind};
+
+/// A single problem found in the `setup()` table, e.g.
+/// "window.width must be a positive integer".
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.path, self.message)
+    }
+}
+
+/// Collects every problem instead of stopping at the first one. Each helper
+/// returns `None` for an invalid value so the caller keeps its default.
+#[derive(Default)]
+pub struct Validator {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    pub fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_owned(),
+            message: message.into(),
+        });
+    }
+
+    pub fn unknown(&mut self, path: &str) {
+        self.error(path, "is not a known option");
+    }
+
+    pub fn table(&mut self, path: &str, value: Object) -> Option<Dictionary> {
+        match value.kind() {
+            ObjectKind::Dictionary => Dictionary::from_object(value).ok(),
+            ObjectKind::Array if Array::from_object(value.clone()).ok()?.is_empty() => {
+                Some(Dictionary::new())
+            }
+            _ => {
+                self.error(path, "must be a table");
+                None
+            }
+        }
+    }
+
+    pub fn string(&mut self, path: &str, value: Object) -> Option<String> {
+        match String::from_object(value) {
+            Ok(string) if !string.is_empty() => Some(string),
+            _ => {
+                self.error(path, "must be a non-empty string");
+                None
+            }
+        }
+    }
+
+    pub fn list(&mut self, path: &str, value: Object) -> Option<Array> {
+        match value.kind() {
+            ObjectKind::Array => Array::from_object(value).ok(),
+            _ => {
+                self.error(path, "must be a list");
+                None
+            }
+        }
+    }
+
+    pub fn strings(&mut self, path: &str, value: Object) -> Option<Vec<String>> {
+        let strings = match value.kind() {
+            ObjectKind::Array => Array::from_object(value)
+                .ok()?
+                .into_iter()
+                .map(String::from_object)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .ok(),
+            _ => None,
+        };
+
+        if strings.is_none() {
+            self.error(path, "must be a list of strings");
+        }
+
+        strings
+    }
+
+    pub fn boolean(&mut self, path: &str, value: Object) -> Option<bool> {
+        match bool::from_object(value) {
+            Ok(boolean) => Some(boolean),
+            _ => {
+                self.error(path, "must be a boolean");
+                None
+            }
+        }
+    }
+
+    /// Accepts integers too, since Lua hands whole numbers over as such.
+    pub fn number(&mut self, path: &str, value: Object, min: f64, max: f64) -> Option<f64> {
+        let number = match value.kind() {
+            ObjectKind::Integer => i64::from_object(value).ok().map(|n| n as f64),
+            _ => f64::from_object(value).ok(),
+        };
+
+        match number {
+            Some(n) if (min..=max).contains(&n) => Some(n),
+            _ => {
+                self.error(
+                    path,
+                    format!("must be a number between {} and {}", min, max),
+                );
+                None
+            }
+        }
+    }
+
+    pub fn integer(&mut self, path: &str, value: Object, min: i64, max: i64) -> Option<i64> {
+        match i64::from_object(value) {
+            Ok(n) if (min..=max).contains(&n) => Some(n),
+            _ => {
+                self.error(
+                    path,
+                    format!("must be an integer between {} and {}", min, max),
+                );
+                None
+            }
+        }
+    }
+}
+
+pub fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
@@ -0,0 +1,88 @@
+//! `display.mode = "queue"`: `!nvim` messages are shown one at a time, each
+//! for at least `min_duration`, instead of scrolling past in the log.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use nvim_oxi::{api, Result};
+
+use crate::{
+    config::{DisplayOptions, Overflow, RenderMode, WindowOptions},
+    metrics,
+    ui::MessageView,
+    Plugin,
+};
+
+#[derive(Default)]
+pub struct DisplayQueue {
+    /// Each with the window options of the channel it came from.
+    pending: VecDeque<(MessageView, WindowOptions)>,
+    shown_at: Option<Instant>,
+}
+
+impl DisplayQueue {
+    pub fn push(&mut self, options: &DisplayOptions, view: MessageView, window: WindowOptions) {
+        if self.pending.len() >= options.max_depth {
+            metrics::MESSAGES_DROPPED.inc();
+
+            match options.overflow {
+                Overflow::DropOldest => {
+                    self.pending.pop_front();
+                }
+                Overflow::DropNewest => return,
+            }
+        }
+
+        self.pending.push_back((view, window));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// How long until [`tick`] shows the next message, unset with none waiting.
+pub fn next_tick(plugin: &Plugin) -> Option<Duration> {
+    if plugin.display.pending.is_empty() {
+        return None;
+    }
+
+    let shown = plugin.display.shown_at.map(|at| at.elapsed());
+    Some(
+        plugin
+            .config
+            .display
+            .min_duration
+            .saturating_sub(shown.unwrap_or(Duration::MAX)),
+    )
+}
+
+/// Shows the next message once the current one had its time, called right
+/// after a message is queued and again when [`next_tick`] says.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let display = &mut plugin.display;
+
+    if display
+        .shown_at
+        .is_some_and(|at| at.elapsed() < plugin.config.display.min_duration)
+    {
+        return Ok(());
+    }
+
+    let Some((view, window)) = display.pending.pop_front() else {
+        return Ok(());
+    };
+    display.shown_at = Some(Instant::now());
+
+    plugin.chat.set_options(window)?;
+    plugin.chat.replace_message(view)?;
+    metrics::MESSAGES_RENDERED.inc();
+
+    if plugin.render_mode == RenderMode::Immediate {
+        api::command("redraw")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! The JSON event schema shared by everything that records or forwards plugin
+//! activity.
+//!
+//! Every event is a single flat object. The `version` field is bumped whenever
+//! a field is removed or changes meaning; new fields may be added at any time.
+//!
+//! ```json
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "chat", "channel": "mikerimebot", "author": "user", "text": "!nvim hello", "badges": [] }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "message", "channel": "mikerimebot", "author": "user", "text": "hello" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "colorscheme", "channel": "mikerimebot", "author": "user", "badges": [], "name": "gruvbox" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "trythis", "channel": "mikerimebot", "author": "user", "badges": [], "name": "nord" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "highlight", "channel": "mikerimebot", "author": "user", "badges": ["moderator/1"], "group": "Comment", "fg": "#88c0d0" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "goto", "channel": "mikerimebot", "author": "user", "badges": ["vip/1"], "file": "src/lib.rs", "line": 42 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "vote", "channel": "mikerimebot", "author": "user", "badges": [], "choice": "tabs" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "keymap", "channel": "mikerimebot", "author": "user", "badges": ["vip/1"], "lhs": "<leader>x", "rhs": "<cmd>bdelete<cr>" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "banner", "channel": "mikerimebot", "author": "user", "badges": ["moderator/1"], "text": "follow!" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "focus", "channel": "mikerimebot", "author": "user", "badges": [], "number": 2 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "uptime", "channel": "mikerimebot", "author": "user", "badges": [] }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "help", "channel": "mikerimebot", "author": "user", "badges": [], "command": "hl" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "vote_ended", "title": "Tabs or spaces?", "choices": [{ "title": "tabs", "votes": 3 }, { "title": "spaces", "votes": 5 }], "winner": "spaces" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "connected" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "disconnected" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "reconnecting", "reason": "no keepalives", "attempt": 1, "retry_in": 1 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "subscription", "channel": "mikerimebot", "user": "viewer", "months": 3, "resub": true }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "raid", "channel": "mikerimebot", "from": "otherstreamer", "viewers": 42 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "milestone", "channel": "mikerimebot", "user": "viewer", "category": "watch-streak", "value": 5 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "gift_subs", "channel": "mikerimebot", "gifter": "viewer", "count": 5, "tier": "1000", "recipients": ["a", "b", "c", "d", "e"] }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "redemption", "channel": "mikerimebot", "user": "viewer", "reward": "Rotate colorscheme", "cost": 500, "input": "" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "summary", "text": "- ..." }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "poll", "id": "...", "title": "Tabs or spaces?", "choices": [{ "title": "Tabs", "votes": 3 }, { "title": "Spaces", "votes": 5 }], "status": "ACTIVE" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "prediction", "id": "...", "title": "Will it compile?", "outcomes": [{ "id": "...", "title": "Yes", "users": 4, "channel_points": 1200 }, { "id": "...", "title": "No", "users": 9, "channel_points": 5000 }], "status": "RESOLVED", "winning_outcome_id": "..." }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "raid_started", "to": "otherstreamer" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "raid_canceled" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "live_channels", "channels": ["otherstreamer"] }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "ad_schedule", "next_at": "2024-06-01T12:30:00Z", "duration": 90, "snoozes": 3 }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "ad_break", "length": 90, "automatic": false }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "marker", "position": 4980, "description": "that bug" }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "malformed", "channel": "mikerimebot", "author": "viewer", "badges": [], "command": "remind", "error": { "kind": "invalid", "name": "delay", "value": "soon" } }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "whisper", "user": "viewer", "text": "psst", "sent": false }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "replayed", "command": { "type": "colorscheme", "channel": "mikerimebot", "author": "user", "badges": [], "name": "gruvbox" } }
+//! { "version": 1, "timestamp": "2024-06-01T12:00:00Z", "type": "error", "severity": "error", "context": "Send", "message": "..." }
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::Command;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Every value of the `type` field.
+pub const TYPES: [&str; 44] = [
+    "chat",
+    "message",
+    "colorscheme",
+    "trythis",
+    "highlight",
+    "goto",
+    "vote",
+    "keymap",
+    "banner",
+    "option",
+    "font",
+    "snippet",
+    "remind",
+    "suggest",
+    "where",
+    "focus",
+    "uptime",
+    "help",
+    "malformed",
+    "vote_ended",
+    "connected",
+    "disconnected",
+    "reconnecting",
+    "subscription",
+    "raid",
+    "milestone",
+    "gift_subs",
+    "redemption",
+    "summary",
+    "poll",
+    "prediction",
+    "raid_started",
+    "raid_canceled",
+    "live_channels",
+    "ad_schedule",
+    "ad_break",
+    "marker",
+    "whisper",
+    "badges",
+    "blocked_users",
+    "viewers",
+    "stream",
+    "replayed",
+    "error",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub version: u32,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+impl Event {
+    pub fn new(command: Command) -> Self {
+        Event {
+            version: SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            command,
+        }
+    }
+}
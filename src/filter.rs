@@ -0,0 +1,272 @@
+//! What `!nvim` messages go through before they are shown: muted viewers,
+//! the `filter.blocklist`, links and `filter.max_length`. Muted viewers are
+//! kept in the plugin state, managed with `:TwitchMute` and `:TwitchUnmute`.
+//! `:TwitchMute sounds` mutes the sounds instead, `@sounds` a viewer.
+//! Every chat message is also checked against the `filter.actions`, which
+//! delete it or time its author out on Twitch.
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    auth,
+    config::{badges_allowed, FilterRule, ModAction},
+    i18n::{tr, tr_with},
+    logging,
+    twitch::{emotes::EmoteSpan, ChatMessage, Outgoing, Slash},
+    ui::float,
+    Plugin,
+};
+
+const STATE_KEY: &str = "muted";
+
+/// What a shortened message ends with.
+const ELLIPSIS: char = '…';
+
+/// Reads the muted viewers from the plugin state.
+pub fn read(plugin: &mut Plugin) {
+    plugin.muted = plugin.state.get(STATE_KEY).unwrap_or_default();
+}
+
+pub fn muted(plugin: &Plugin, user: &str) -> bool {
+    plugin.muted.contains(&user.to_lowercase())
+}
+
+/// Runs `:TwitchMute <user>`, `:TwitchUnmute <user>`, or `:TwitchMute` to
+/// list everyone muted.
+pub fn run(plugin: &mut Plugin, args: &[String], mute: bool) -> Result<()> {
+    let user = match args {
+        [] if mute => return show(plugin),
+        [sounds] if sounds == "sounds" => {
+            plugin.sounds.muted = mute;
+            let key = match mute {
+                true => "filter.sounds_muted",
+                false => "filter.sounds_unmuted",
+            };
+            api::notify(&tr(key), LogLevel::Info, &NotifyOpts::default())?;
+            return Ok(());
+        }
+        [user] => user.trim_start_matches('@').to_lowercase(),
+        _ => {
+            api::notify(&tr("filter.usage"), LogLevel::Error, &NotifyOpts::default())?;
+            return Ok(());
+        }
+    };
+
+    let muted = &mut plugin.muted;
+    let key = match mute {
+        true => {
+            muted.insert(user.clone());
+            "filter.muted"
+        }
+        false => {
+            muted.remove(&user);
+            "filter.unmuted"
+        }
+    };
+
+    plugin.state.set(STATE_KEY, &plugin.muted);
+
+    // Written right away like the access lists.
+    if let Err(e) = plugin.state.flush() {
+        plugin.errors.push("State", &e);
+    }
+
+    api::notify(
+        &tr_with(key, &[("user", &user)]),
+        LogLevel::Info,
+        &NotifyOpts::default(),
+    )?;
+
+    Ok(())
+}
+
+fn show(plugin: &Plugin) -> Result<()> {
+    let muted = &plugin.muted;
+
+    let line = match muted.is_empty() {
+        true => tr("filter.none"),
+        false => muted.iter().cloned().collect::<Vec<_>>().join(", "),
+    };
+
+    float::show(&tr("filter.title"), vec![line])
+}
+
+/// The message as it should be shown, with `emotes` moved along with the
+/// text, or `None` when it matches the blocklist.
+pub fn apply(
+    plugin: &Plugin,
+    text: String,
+    emotes: Vec<EmoteSpan>,
+) -> Result<Option<(String, Vec<EmoteSpan>)>> {
+    let options = &plugin.config.filter;
+
+    for pattern in &options.blocklist {
+        if matches(&text, pattern)? {
+            return Ok(None);
+        }
+    }
+
+    let (text, emotes) = match options.strip_links {
+        true => strip_links(&text, emotes),
+        false => (text, emotes),
+    };
+
+    Ok(Some(match options.max_length {
+        Some(max) => shorten(text, emotes, max),
+        None => (text, emotes),
+    }))
+}
+
+/// Takes the first of the `filter.actions` whose rule `message` matches, or
+/// only logs it with `filter.dry_run`. The broadcaster and moderators are
+/// left alone, as are channels other than the configured one.
+pub fn enforce(plugin: &Plugin, message: &ChatMessage) -> Result<()> {
+    let options = &plugin.config.filter;
+    let exempt = ["broadcaster", "moderator"].map(str::to_owned);
+
+    if options.actions.is_empty()
+        || message.channel != plugin.config.channel
+        || badges_allowed(&exempt, &message.badges)
+    {
+        return Ok(());
+    }
+
+    let mut matched = None;
+    for action in &options.actions {
+        let matches = match &action.rule {
+            FilterRule::Pattern(pattern) => matches(&message.text, pattern)?,
+            FilterRule::Links => message.text.split(' ').any(is_link),
+        };
+        if matches {
+            matched = Some(action);
+            break;
+        }
+    }
+    let Some(matched) = matched else {
+        return Ok(());
+    };
+
+    let rule = match &matched.rule {
+        FilterRule::Pattern(pattern) => format!("/{}/", pattern),
+        FilterRule::Links => "links".to_owned(),
+    };
+    let (slash, taken) = match matched.action {
+        // Injected messages have no id to delete.
+        ModAction::Delete if message.id.is_empty() => return Ok(()),
+        ModAction::Delete => (
+            Slash::Delete(message.id.clone()),
+            format!("deleting a message of {}", message.author),
+        ),
+        ModAction::Timeout(seconds) => (
+            Slash::Timeout {
+                user: message.author.to_lowercase(),
+                seconds,
+                reason: format!("filter: {}", rule),
+            },
+            format!("timing {} out for {}s", message.author, seconds),
+        ),
+    };
+
+    if options.dry_run {
+        logging::info(
+            "Filter",
+            format!("dry run, not {} for {}: {}", taken, rule, message.text),
+        );
+        return Ok(());
+    }
+
+    logging::info(
+        "Filter",
+        format!("{} for {}: {}", taken, rule, message.text),
+    );
+    let _ = plugin.outgoing.send(Outgoing::Slash {
+        account: auth::MAIN.to_owned(),
+        slash,
+    });
+
+    Ok(())
+}
+
+/// Whether the Vim regex `pattern` matches anywhere in `text`, ignoring
+/// case.
+pub fn matches(text: &str, pattern: &str) -> Result<bool> {
+    let index: i64 = api::call_function("match", (text, format!("\\c{}", pattern)))?;
+    Ok(index >= 0)
+}
+
+fn is_link(word: &str) -> bool {
+    let word = word.to_lowercase();
+    ["http://", "https://", "www."]
+        .iter()
+        .any(|prefix| word.starts_with(prefix))
+}
+
+/// Replaces every link with `[link]`. Emotes within a link go with it.
+fn strip_links(text: &str, emotes: Vec<EmoteSpan>) -> (String, Vec<EmoteSpan>) {
+    let placeholder = tr("filter.link");
+
+    let mut links = Vec::new();
+    let mut start = 0;
+    for word in text.split(' ') {
+        if is_link(word) {
+            links.push(start..start + word.len());
+        }
+        start += word.len() + 1;
+    }
+
+    if links.is_empty() {
+        return (text.to_owned(), emotes);
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut last = 0;
+    for link in &links {
+        stripped.push_str(&text[last..link.start]);
+        stripped.push_str(&placeholder);
+        last = link.end;
+    }
+    stripped.push_str(&text[last..]);
+
+    let emotes = emotes
+        .into_iter()
+        .filter(|emote| {
+            !links
+                .iter()
+                .any(|link| emote.start < link.end && link.start < emote.end)
+        })
+        .map(|emote| {
+            let before = links.iter().filter(|link| link.end <= emote.start);
+            let removed: usize = before.clone().map(|link| link.len()).sum();
+            let added = before.count() * placeholder.len();
+
+            EmoteSpan {
+                start: emote.start + added - removed,
+                end: emote.end + added - removed,
+                ..emote
+            }
+        })
+        .collect();
+
+    (stripped, emotes)
+}
+
+/// Cuts the text after `max` characters, dropping the emotes past the cut.
+fn shorten(text: String, emotes: Vec<EmoteSpan>, max: usize) -> (String, Vec<EmoteSpan>) {
+    let Some((cut, _)) = text.char_indices().nth(max) else {
+        return (text, emotes);
+    };
+
+    let kept = text[..cut].trim_end();
+    let emotes = emotes
+        .into_iter()
+        .filter(|emote| emote.end <= kept.len())
+        .collect();
+
+    let mut shortened = kept.to_owned();
+    shortened.push(ELLIPSIS);
+
+    (shortened, emotes)
+}
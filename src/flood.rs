@@ -0,0 +1,192 @@
+//! `flood.enabled = true`: when one viewer or the whole chat sends `!nvim`
+//! messages faster than `flood.per_user` or `flood.global` a second, slow
+//! mode starts and each viewer gets one message shown every `flood.slow`
+//! seconds, until chat stayed calm for `flood.calm`. `:TwitchSlowmode`
+//! turns it on and off by hand.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    i18n::{tr, tr_with},
+    ui::MessageView,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 2] = ["on", "off"];
+
+/// What floods are counted over.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Shown as the author of the slow mode notices.
+const NOTICE_AUTHOR: &str = "*";
+
+#[derive(Default)]
+pub struct Flood {
+    /// Who sent the messages of the last second, oldest first.
+    recent: VecDeque<(Instant, String)>,
+    slow: Option<Slow>,
+    /// When each viewer's last message was shown in slow mode.
+    shown: HashMap<String, Instant>,
+    last_flood: Option<Instant>,
+}
+
+struct Slow {
+    wait: Duration,
+    /// Started with `:TwitchSlowmode`, so only it ends it.
+    manual: bool,
+}
+
+/// Whether the `!nvim` message `author` just sent is shown.
+pub fn admit(plugin: &mut Plugin, author: &str) -> bool {
+    let now = Instant::now();
+    let author = author.to_lowercase();
+
+    if plugin.config.flood.enabled && flooding(plugin, now, &author) {
+        plugin.flood.last_flood = Some(now);
+
+        if plugin.flood.slow.is_none() {
+            let wait = plugin.config.flood.slow;
+            start(plugin, wait, false);
+        }
+    }
+
+    let flood = &mut plugin.flood;
+    let Some(slow) = &flood.slow else {
+        return true;
+    };
+
+    match flood.shown.get(&author) {
+        Some(shown) if now.duration_since(*shown) < slow.wait => false,
+        _ => {
+            flood.shown.insert(author, now);
+            true
+        }
+    }
+}
+
+/// Counts the message in and tells whether there are too many.
+fn flooding(plugin: &mut Plugin, now: Instant, author: &str) -> bool {
+    let recent = &mut plugin.flood.recent;
+
+    recent.push_back((now, author.to_owned()));
+    while recent
+        .front()
+        .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+    {
+        recent.pop_front();
+    }
+
+    let options = &plugin.config.flood;
+    let from_author = recent.iter().filter(|(_, sender)| sender == author).count();
+
+    from_author > options.per_user || recent.len() > options.global
+}
+
+/// Ends slow mode once chat calmed down, called every second or so.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let flood = &mut plugin.flood;
+    let now = Instant::now();
+
+    if let Some(slow) = &flood.slow {
+        // Viewers who may send again are forgotten.
+        flood
+            .shown
+            .retain(|_, shown| now.duration_since(*shown) < slow.wait);
+    }
+
+    let calm = flood
+        .last_flood
+        .is_none_or(|at| now.duration_since(at) >= plugin.config.flood.calm);
+
+    let ends = flood
+        .slow
+        .as_ref()
+        .is_some_and(|slow| !slow.manual && (calm || !plugin.config.flood.enabled));
+
+    match ends {
+        true => stop(plugin),
+        false => Ok(()),
+    }
+}
+
+/// Runs `:TwitchSlowmode on|off|<seconds>`, or says whether it is on
+/// without an argument.
+pub fn run(plugin: &mut Plugin, args: Option<&str>) -> Result<()> {
+    let wait = match args.map(str::trim).unwrap_or_default() {
+        "" => {
+            let message = match &plugin.flood.slow {
+                Some(slow) => tr_with("flood.on", &[("seconds", &slow.wait.as_secs())]),
+                None => tr("flood.off"),
+            };
+            api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+            return Ok(());
+        }
+        "on" => plugin.config.flood.slow,
+        "off" => return stop(plugin),
+        seconds => match seconds.parse::<u64>() {
+            Ok(seconds @ 1..=3600) => Duration::from_secs(seconds),
+            _ => {
+                api::notify(&tr("flood.usage"), LogLevel::Error, &NotifyOpts::default())?;
+                return Ok(());
+            }
+        },
+    };
+
+    start(plugin, wait, true);
+
+    Ok(())
+}
+
+fn start(plugin: &mut Plugin, wait: Duration, manual: bool) {
+    plugin.flood.slow = Some(Slow { wait, manual });
+    plugin.flood.shown.clear();
+
+    notice(
+        plugin,
+        tr_with("flood.started", &[("seconds", &wait.as_secs())]),
+    );
+}
+
+fn stop(plugin: &mut Plugin) -> Result<()> {
+    if plugin.flood.slow.take().is_none() {
+        return Ok(());
+    }
+    plugin.flood.shown.clear();
+
+    notice(plugin, tr("flood.ended"));
+
+    Ok(())
+}
+
+/// Puts `text` in the chat window between the messages, or in the plain
+/// buffer in accessibility mode.
+fn notice(plugin: &mut Plugin, text: String) {
+    let result = match plugin.config.accessibility.enabled {
+        true => plugin.plain.append(&text, plugin.config.accessibility.echo),
+        false => plugin.chat.show_message(MessageView {
+            id: String::new(),
+            author: NOTICE_AUTHOR.to_owned(),
+            color: None,
+            badges: Vec::new(),
+            text,
+            cheers: Vec::new(),
+            emotes: Vec::new(),
+            reply: None,
+            source: None,
+            received: Local::now(),
+        }),
+    };
+
+    if let Err(e) = result {
+        plugin.errors.push("Flood", &e);
+    }
+}
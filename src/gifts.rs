@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use nvim_oxi::Result;
+
+use crate::{
+    i18n::{tr, tr_with},
+    twitch::GiftTrain,
+    ui::float,
+};
+
+/// Only the most recent trains are kept for `:TwitchGifts`.
+const MAX_TRAINS: usize = 50;
+
+/// The gift trains of this session, oldest first.
+#[derive(Default)]
+pub struct Gifts {
+    trains: VecDeque<(DateTime<Local>, GiftTrain)>,
+}
+
+impl Gifts {
+    pub fn push(&mut self, train: GiftTrain) {
+        if self.trains.len() == MAX_TRAINS {
+            self.trains.pop_front();
+        }
+
+        self.trains.push_back((Local::now(), train));
+    }
+
+    /// Lists every train with its recipients.
+    pub fn show(&self) -> Result<()> {
+        let mut lines = Vec::new();
+
+        for (received, train) in &self.trains {
+            lines.push(format!("{}  {}", received.format("%H:%M"), summary(train)));
+            lines.extend(
+                train
+                    .recipients
+                    .iter()
+                    .map(|name| format!("       {}", name)),
+            );
+        }
+
+        if lines.is_empty() {
+            lines.push(tr("alerts.no_gifts"));
+        }
+
+        float::show(&tr("alerts.gifts_title"), lines)
+    }
+}
+
+/// "someone gifted 5 tier 1 subs"
+pub fn summary(train: &GiftTrain) -> String {
+    let tier = train
+        .tier
+        .parse::<u32>()
+        .map_or(1, |plan| (plan / 1000).max(1));
+
+    match &train.gifter {
+        Some(gifter) => tr_with(
+            "alerts.gift_subs",
+            &[("user", gifter), ("count", &train.count), ("tier", &tier)],
+        ),
+        None => tr_with(
+            "alerts.gift_subs_anonymous",
+            &[("count", &train.count), ("tier", &tier)],
+        ),
+    }
+}
@@ -0,0 +1,235 @@
+//! `:checkhealth nvim_plugin`. `lua/nvim_plugin/health.lua` reports the
+//! sections `health()` returns through `vim.health`, since the checks that
+//! need the plugin's state can only run here.
+
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use nvim_oxi::api;
+use serde::Serialize;
+
+use crate::{
+    auth,
+    i18n::{tr, tr_with},
+    twitch::{oauth, ConnectionStatus},
+    Plugin,
+};
+
+/// Where the IRC client connects to.
+const IRC_ADDRESS: &str = "irc.chat.twitch.tv:6697";
+
+/// How long each network check may take, which holds up `:checkhealth`.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+pub struct Section {
+    name: String,
+    items: Vec<Item>,
+}
+
+#[derive(Serialize)]
+struct Item {
+    /// The `vim.health` function reporting it: `ok`, `warn`, `error` or
+    /// `info`.
+    level: &'static str,
+    message: String,
+}
+
+impl Section {
+    fn new(name: String) -> Self {
+        Section {
+            name,
+            items: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, level: &'static str, message: String) {
+        self.items.push(Item { level, message });
+    }
+}
+
+/// How the connection thread is doing, from the `Connector`.
+pub struct Runtime {
+    /// Whether `setup()` started a connection yet.
+    pub started: bool,
+    /// Whether `:TwitchDisconnect` stopped it.
+    pub stopped: bool,
+    /// Whether its thread is still there.
+    pub alive: bool,
+}
+
+pub fn check(plugin: &Plugin, runtime: Runtime) -> Vec<Section> {
+    vec![
+        neovim(),
+        network(),
+        credentials(plugin),
+        connection(plugin),
+        threads(plugin, runtime),
+    ]
+}
+
+fn neovim() -> Section {
+    let mut section = Section::new(tr("health.neovim"));
+
+    let version = api::call_function::<_, String>("execute", ("version",))
+        .ok()
+        .and_then(|output| {
+            output
+                .lines()
+                .find(|line| !line.is_empty())
+                .map(str::to_owned)
+        })
+        .unwrap_or_default();
+
+    match api::call_function::<_, i64>("has", ("nvim-0.9",)) {
+        Ok(1) => section.push("ok", version),
+        _ => section.push("error", tr_with("health.too_old", &[("version", &version)])),
+    }
+
+    section
+}
+
+fn network() -> Section {
+    let mut section = Section::new(tr("health.network"));
+
+    let reached = IRC_ADDRESS
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())
+        .and_then(|mut addresses| addresses.next().ok_or_else(|| tr("health.unresolved")))
+        .and_then(|address| {
+            TcpStream::connect_timeout(&address, TIMEOUT).map_err(|e| e.to_string())
+        });
+
+    match reached {
+        Ok(_) => section.push(
+            "ok",
+            tr_with("health.reached", &[("address", &IRC_ADDRESS)]),
+        ),
+        Err(error) => section.push(
+            "error",
+            tr_with(
+                "health.unreachable",
+                &[("address", &IRC_ADDRESS), ("error", &error)],
+            ),
+        ),
+    }
+
+    section
+}
+
+fn credentials(plugin: &Plugin) -> Section {
+    let mut section = Section::new(tr("health.credentials"));
+    let (accounts, errors) = auth::load_all(&plugin.config.accounts, &plugin.paths);
+
+    for error in errors {
+        section.push("error", error);
+    }
+
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return section;
+    };
+
+    for account in plugin.config.accounts.keys() {
+        let Some(credentials) = accounts.get(account) else {
+            match account.as_str() {
+                auth::MAIN => section.push("warn", tr("health.anonymous")),
+                _ => section.push("warn", tr_with("health.no_token", &[("account", &account)])),
+            }
+            continue;
+        };
+
+        let validated = runtime.block_on(async {
+            tokio::time::timeout(TIMEOUT, oauth::validate(&credentials.token)).await
+        });
+
+        match validated {
+            Ok(Ok(Some(login))) => section.push(
+                "ok",
+                tr_with("health.valid", &[("account", &account), ("login", &login)]),
+            ),
+            Ok(Ok(None)) => {
+                section.push("error", tr_with("health.expired", &[("account", &account)]))
+            }
+            Ok(Err(error)) => section.push(
+                "warn",
+                tr_with(
+                    "health.unvalidated",
+                    &[("account", &account), ("error", &error)],
+                ),
+            ),
+            Err(_) => section.push(
+                "warn",
+                tr_with(
+                    "health.unvalidated",
+                    &[("account", &account), ("error", &tr("health.timed_out"))],
+                ),
+            ),
+        }
+    }
+
+    section
+}
+
+fn connection(plugin: &Plugin) -> Section {
+    let mut section = Section::new(tr("health.connection"));
+
+    let mut channels: Vec<_> = plugin.config.channels().into_iter().collect();
+    channels.sort();
+    let channels = channels
+        .iter()
+        .map(|channel| format!("#{}", channel))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match &plugin.status {
+        ConnectionStatus::Connected => {
+            section.push("ok", tr_with("health.joined", &[("channels", &channels)]))
+        }
+        ConnectionStatus::Connecting => section.push(
+            "info",
+            tr_with("health.joining", &[("channels", &channels)]),
+        ),
+        ConnectionStatus::Reconnecting {
+            reason, attempt, ..
+        } => section.push(
+            "warn",
+            tr_with(
+                "health.reconnecting",
+                &[("attempt", attempt), ("reason", reason)],
+            ),
+        ),
+        ConnectionStatus::Disconnected => section.push("warn", tr("health.disconnected")),
+    }
+
+    section
+}
+
+fn threads(plugin: &Plugin, runtime: Runtime) -> Section {
+    let mut section = Section::new(tr("health.runtime"));
+
+    match runtime {
+        Runtime { started: false, .. } => section.push("warn", tr("health.not_started")),
+        Runtime { stopped: true, .. } => section.push("info", tr("health.stopped")),
+        Runtime { alive: false, .. } => section.push("error", tr("health.thread_gone")),
+        _ => section.push("ok", tr("health.thread_running")),
+    }
+
+    section.push(
+        "info",
+        tr_with(
+            "health.pending",
+            &[
+                ("pending", &plugin.queue.pending()),
+                ("dropped", &plugin.queue.dropped()),
+                ("errors", &plugin.errors.len()),
+            ],
+        ),
+    );
+
+    section
+}
@@ -0,0 +1,182 @@
+//! `!help [command]` and `:TwitchCommands`: the chat commands of a channel
+//! as the registry has them, each with how it's used, who may use it and
+//! how often.
+
+use nvim_oxi::Result;
+
+use crate::{
+    config::CooldownOptions,
+    i18n::{tr, tr_with},
+    twitch::{args::schema_of, Outgoing},
+    ui::float,
+    Plugin,
+};
+
+/// How much of a reply the list of commands may take up, well under the
+/// 500 characters of a chat message.
+const MAX_LIST_LENGTH: usize = 350;
+
+/// A command as `!help` describes it.
+struct Entry {
+    name: String,
+    /// E.g. `!remind <delay> <text>`.
+    usage: String,
+    /// Badge sets allowed to use it, empty for everyone.
+    permission: Vec<String>,
+    cooldown: Option<CooldownOptions>,
+    /// The other names it goes by, with the prefix.
+    aliases: Vec<String>,
+}
+
+/// Every command chat can use in `channel`: the built-in ones first, then
+/// the text and Lua commands, by name.
+fn entries(plugin: &Plugin, channel: &str) -> Vec<Entry> {
+    let config = &plugin.config;
+    let prefix = &config.syntax.prefix;
+    let registry = &plugin.registry;
+
+    registry
+        .names(&config.for_channel(channel))
+        .into_iter()
+        .filter_map(|name| {
+            let policy = registry.policy(config, name)?;
+            let command = format!("{}{}", prefix, name);
+            let usage = match (name, schema_of(name)) {
+                ("nvim", _) => format!("{} <text>", command),
+                (_, Some(schema)) => schema.usage(&command),
+                (_, None) => command,
+            };
+
+            Some(Entry {
+                name: name.to_owned(),
+                usage,
+                permission: policy.permission.to_vec(),
+                cooldown: policy.cooldown,
+                aliases: config
+                    .syntax
+                    .aliases
+                    .iter()
+                    .filter(|(_, command)| *command == name)
+                    .map(|(alias, _)| format!("{}{}", prefix, alias))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+impl Entry {
+    /// E.g. `!hl <group> <fg> [bg] · broadcaster, moderator, vip · 3 at
+    /// once, then one every 2s and every 10s per viewer`.
+    fn describe(&self) -> String {
+        let who = match self.permission.is_empty() {
+            true => tr("help.everyone"),
+            false => self.permission.join(", "),
+        };
+        let cooldown = match &self.cooldown {
+            Some(cooldown) => cooldown_text(cooldown),
+            None => tr("help.no_cooldown"),
+        };
+
+        let mut text = format!("{} · {} · {}", self.usage, who, cooldown);
+        if !self.aliases.is_empty() {
+            text.push_str(&tr_with(
+                "help.aliases",
+                &[("aliases", &self.aliases.join(", "))],
+            ));
+        }
+
+        text
+    }
+}
+
+fn cooldown_text(cooldown: &CooldownOptions) -> String {
+    let key = match (cooldown.command.is_zero(), cooldown.user.is_zero()) {
+        (true, true) => return tr("help.no_cooldown"),
+        (false, true) => "help.cooldown",
+        (true, false) => "help.cooldown_user",
+        (false, false) => "help.cooldown_both",
+    };
+    let text = tr_with(
+        key,
+        &[
+            ("command", &cooldown.command.as_secs()),
+            ("user", &cooldown.user.as_secs()),
+        ],
+    );
+
+    match cooldown.burst > 1 {
+        true => tr_with(
+            "help.burst",
+            &[("burst", &cooldown.burst), ("cooldown", &text)],
+        ),
+        false => text,
+    }
+}
+
+/// Answers `author`'s `!help` in `channel` with the list of commands, or
+/// with how `command` is used.
+pub fn reply(plugin: &Plugin, channel: String, author: &str, command: Option<&str>) -> Result<()> {
+    let entries = entries(plugin, &channel);
+    let prefix = &plugin.config.syntax.prefix;
+
+    let text = match command {
+        Some(command) => {
+            // `!help !cs` works as well as `!help cs`.
+            let name = plugin
+                .config
+                .syntax
+                .command(command)
+                .or_else(|| {
+                    plugin
+                        .config
+                        .syntax
+                        .command(&format!("{}{}", prefix, command))
+                })
+                .unwrap_or_default();
+
+            match entries.iter().find(|entry| entry.name == name) {
+                Some(entry) => format!("@{} {}", author, entry.describe()),
+                None => tr_with("help.unknown", &[("user", &author), ("command", &command)]),
+            }
+        }
+        None => {
+            let mut list = String::new();
+
+            for (i, entry) in entries.iter().enumerate() {
+                let name = format!("{}{}", prefix, entry.name);
+
+                if list.len() + name.len() > MAX_LIST_LENGTH {
+                    list.push_str(&tr_with("help.more", &[("count", &(entries.len() - i))]));
+                    break;
+                }
+                if !list.is_empty() {
+                    list.push_str(", ");
+                }
+                list.push_str(&name);
+            }
+
+            tr_with(
+                "help.list",
+                &[
+                    ("user", &author),
+                    ("commands", &list),
+                    ("help", &format!("{}help", prefix)),
+                ],
+            )
+        }
+    };
+
+    let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+
+    Ok(())
+}
+
+/// Runs `:TwitchCommands`, the same listing in a float.
+pub fn show(plugin: &Plugin) -> Result<()> {
+    let lines = entries(plugin, &plugin.config.channel)
+        .iter()
+        .map(Entry::describe)
+        .collect();
+
+    float::show(&tr("help.title"), lines)
+}
@@ -0,0 +1,219 @@
+//! Highlight groups changed from chat with `!hl <group> <fg> [bg]`. Every
+//! change is kept along with what it replaced, so `:TwitchHighlights` can
+//! show who did what and `:TwitchHighlightUndo` can put it back.
+//! `:TwitchHighlights groups` lists the plugin's own groups instead.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    conversion::FromObject,
+    Dictionary, Object, Result,
+};
+
+use crate::{
+    i18n::{tr, tr_with},
+    ui::float,
+};
+
+/// Only the most recent changes can be undone.
+const MAX_CHANGES: usize = 100;
+
+const MAX_GROUP_LENGTH: usize = 200;
+
+struct Change {
+    timestamp: DateTime<Local>,
+    author: String,
+    group: String,
+    fg: String,
+    bg: Option<String>,
+    /// The group's definition before the change, as `nvim_get_hl()` gave it.
+    previous: Dictionary,
+}
+
+/// The changes of this session, oldest first.
+#[derive(Default)]
+pub struct HighlightLog {
+    changes: VecDeque<Change>,
+}
+
+impl HighlightLog {
+    /// Sets the colors of `group`, keeping its other attributes.
+    pub fn apply(&mut self, author: &str, group: &str, fg: &str, bg: Option<&str>) -> Result<()> {
+        let valid_group = !group.is_empty()
+            && group.len() <= MAX_GROUP_LENGTH
+            && group
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '@'));
+
+        if !valid_group {
+            return Err(invalid(tr_with(
+                "highlights.invalid_group",
+                &[("group", &group)],
+            )));
+        }
+
+        for color in [Some(fg), bg].into_iter().flatten() {
+            if api::get_color_by_name(color).is_err() {
+                return Err(invalid(tr_with(
+                    "highlights.invalid_color",
+                    &[("color", &color)],
+                )));
+            }
+        }
+
+        let previous = get_hl(group)?;
+
+        // A link would win over the colors, the group stands on its own now.
+        let mut definition: Dictionary = previous
+            .iter()
+            .filter(|(key, _)| key.to_string_lossy() != "link")
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        definition.insert("fg", fg);
+        if let Some(bg) = bg {
+            definition.insert("bg", bg);
+        }
+
+        set_hl(group, definition)?;
+
+        if self.changes.len() == MAX_CHANGES {
+            self.changes.pop_front();
+        }
+
+        self.changes.push_back(Change {
+            timestamp: Local::now(),
+            author: author.to_owned(),
+            group: group.to_owned(),
+            fg: fg.to_owned(),
+            bg: bg.map(str::to_owned),
+            previous,
+        });
+
+        Ok(())
+    }
+
+    /// Reverts the most recent change.
+    pub fn undo(&mut self) -> Result<()> {
+        let Some(change) = self.changes.pop_back() else {
+            api::notify(
+                &tr("highlights.nothing_to_undo"),
+                LogLevel::Info,
+                &NotifyOpts::default(),
+            )?;
+            return Ok(());
+        };
+
+        set_hl(&change.group, change.previous)?;
+
+        let message = tr_with(
+            "highlights.undone",
+            &[("group", &change.group), ("author", &change.author)],
+        );
+        api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+
+        Ok(())
+    }
+
+    /// Reverts the most recent change without telling, for `:TwitchUndo`.
+    pub fn revert_last(&mut self) -> Result<()> {
+        match self.changes.pop_back() {
+            Some(change) => set_hl(&change.group, change.previous),
+            None => Ok(()),
+        }
+    }
+
+    /// Lists every change with who made it.
+    pub fn show(&self) -> Result<()> {
+        let mut lines: Vec<String> = self
+            .changes
+            .iter()
+            .map(|change| {
+                format!(
+                    "{}  {}: {} {}{}",
+                    change.timestamp.format("%H:%M"),
+                    change.author,
+                    change.group,
+                    change.fg,
+                    change
+                        .bg
+                        .as_ref()
+                        .map(|bg| format!(" {}", bg))
+                        .unwrap_or_default()
+                )
+            })
+            .collect();
+
+        if lines.is_empty() {
+            lines.push(tr("highlights.none"));
+        }
+
+        float::show(&tr("highlights.title"), lines)
+    }
+}
+
+/// The plugin's own groups as they are defined now, whether by default, the
+/// colorscheme, `highlights` in the config, `:hi` or chat.
+pub fn show_groups() -> Result<()> {
+    let opts = Dictionary::from_iter([("link", true)]);
+    let groups: Dictionary = api::call_function("nvim_get_hl", (0, opts))?;
+
+    let mut lines: Vec<String> = groups
+        .iter()
+        .filter_map(|(name, definition)| {
+            let name = name.to_string_lossy();
+            // One for each author color, far too many to be of use here.
+            (name.starts_with("Twitch") && !name.starts_with("TwitchAuthor_"))
+                .then(|| format!("{}  {}", name, describe(definition.clone())))
+        })
+        .collect();
+    lines.sort();
+
+    float::show(&tr("highlights.groups_title"), lines)
+}
+
+/// Like `:hi` shows it, e.g. `fg=#e91916 bold` or `links to Comment`.
+fn describe(definition: Object) -> String {
+    let Ok(definition) = Dictionary::from_object(definition) else {
+        return String::new();
+    };
+
+    let mut parts = Vec::new();
+
+    for (key, value) in definition {
+        let key = key.to_string_lossy().into_owned();
+
+        if let Ok(link) = String::from_object(value.clone()) {
+            parts.push(tr_with("highlights.links_to", &[("group", &link)]));
+        } else if let Ok(true) = bool::from_object(value.clone()) {
+            parts.push(key);
+        } else if let Ok(number) = i64::from_object(value) {
+            match key.as_str() {
+                "fg" | "bg" | "sp" => parts.push(format!("{}=#{:06x}", key, number)),
+                _ => parts.push(format!("{}={}", key, number)),
+            }
+        }
+    }
+
+    match parts.is_empty() {
+        true => tr("highlights.cleared"),
+        false => parts.join(" "),
+    }
+}
+
+// Called through Vimscript so the definition round-trips as a dictionary,
+// whatever attributes it has.
+fn get_hl(group: &str) -> Result<Dictionary> {
+    let opts = Dictionary::from_iter([("name", Object::from(group)), ("link", true.into())]);
+    Ok(api::call_function("nvim_get_hl", (0, opts))?)
+}
+
+fn set_hl(group: &str, definition: Dictionary) -> Result<()> {
+    api::call_function::<_, Object>("nvim_set_hl", (0, group, definition))?;
+    Ok(())
+}
+
+fn invalid(message: String) -> nvim_oxi::Error {
+    api::Error::Other(message).into()
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::twitch::ChatMessage;
+
+/// Where the messages are kept between sessions.
+pub const STATE_KEY: &str = "history";
+
+/// How far back messages are kept.
+const RETENTION: Duration = Duration::hours(1);
+
+#[derive(Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub message: ChatMessage,
+}
+
+/// Recent chat messages, oldest first.
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    /// Caps memory use in very busy channels.
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        History {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+
+        while self.entries.len() > limit {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Every message kept, oldest first, as saved in the plugin state.
+    pub fn entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.entries
+    }
+
+    /// Puts back messages saved by an earlier session ahead of the current
+    /// ones, leaving out those that are too old by now.
+    pub fn restore(&mut self, saved: Vec<HistoryEntry>) {
+        let now = Local::now();
+
+        for entry in saved.into_iter().rev() {
+            if now - entry.timestamp < RETENTION {
+                self.entries.push_front(entry);
+            }
+        }
+
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn push(&mut self, message: ChatMessage) {
+        let now = Local::now();
+
+        while let Some(oldest) = self.entries.front() {
+            if self.entries.len() < self.limit && now - oldest.timestamp < RETENTION {
+                break;
+            }
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(HistoryEntry {
+            timestamp: now,
+            message,
+        });
+    }
+
+    /// The messages received during the last `minutes`.
+    pub fn since(&self, minutes: i64) -> impl Iterator<Item = &HistoryEntry> {
+        let cutoff = Local::now() - Duration::minutes(minutes);
+
+        self.entries
+            .iter()
+            .filter(move |entry| entry.timestamp >= cutoff)
+    }
+
+    /// The messages whose author or text contains `query`, ignoring case,
+    /// newest first. An empty query matches everything.
+    pub fn search(&self, query: &str) -> impl Iterator<Item = &HistoryEntry> {
+        let query = query.to_lowercase();
+
+        self.entries.iter().rev().filter(move |entry| {
+            entry.message.author.to_lowercase().contains(&query)
+                || entry.message.text.to_lowercase().contains(&query)
+        })
+    }
+
+    /// Everyone who chatted while the messages were kept, most recent first.
+    pub fn chatters(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+
+        self.entries
+            .iter()
+            .rev()
+            .map(|entry| entry.message.author.as_str())
+            .filter(|author| seen.insert(*author))
+            .collect()
+    }
+
+    /// The reply chain around the message `id`, from the oldest ancestor
+    /// still kept through every reply to any message of the chain.
+    pub fn thread(&self, id: &str) -> Vec<&HistoryEntry> {
+        let by_id = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.message.id.is_empty())
+            .map(|entry| (entry.message.id.as_str(), &entry.message))
+            .collect::<HashMap<_, _>>();
+
+        if !by_id.contains_key(id) {
+            return Vec::new();
+        }
+
+        let mut root = id;
+        let mut seen = HashSet::from([id]);
+
+        while let Some(parent) = by_id.get(root).and_then(|message| message.reply.as_ref()) {
+            if !seen.insert(&parent.id) {
+                break;
+            }
+            root = &parent.id;
+        }
+
+        // Replies always come after their parent, so one pass in order
+        // collects the whole tree.
+        let mut members = HashSet::from([root]);
+
+        self.entries
+            .iter()
+            .filter(|entry| {
+                let message = &entry.message;
+                let member = message.id == root
+                    || message
+                        .reply
+                        .as_ref()
+                        .is_some_and(|parent| members.contains(parent.id.as_str()));
+
+                if member {
+                    members.insert(&message.id);
+                }
+                member
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,244 @@
+//! Lua callbacks for plugin activity, registered with `on_message()`,
+//! `on_command_executed()`, `on_connect()`, `on_disconnect()` and
+//! `on_error()`. They are called alongside the autocommands, for plugins
+//! that would rather not match on patterns.
+//!
+//! Every Lua callback the plugin calls, these and the registered commands
+//! alike, runs through [`guarded`], so one that loops or hangs is stopped
+//! after `callback_timeout` instead of freezing the editor. What it asked of
+//! the connection thread is cancelled along with it.
+
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    collections::HashMap,
+    time::Duration,
+};
+
+use nvim_oxi::{
+    api, conversion::FromObject, serde::Serializer, Dictionary, Function, Object, Result,
+};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::{i18n::tr_with, Plugin};
+
+/// Calls a callback with a count hook that raises an error once it has run
+/// past its deadline, again and again should the callback catch it. It only
+/// sees interpreted code, and LuaJIT compiles nothing new while it is set,
+/// but it can't cut short a call into C like `vim.fn.system()`, nor a loop
+/// compiled while it ran unguarded before.
+const GUARD: &str = r#"(function()
+  local hrtime = (vim.uv or vim.loop).hrtime
+  local timeout = {}
+  return function(callback, data, seconds)
+    local deadline = hrtime() + seconds * 1e9
+    local hook, mask, count = debug.gethook()
+    debug.sethook(function()
+      if hrtime() > deadline then error(timeout) end
+    end, "", 1000)
+    local ok, result = pcall(callback, data)
+    debug.sethook(hook, mask, count)
+    if ok then return { ok = true, value = result } end
+    if result == timeout then return { ok = false, timeout = true } end
+    return { ok = false, error = tostring(result) }
+  end
+end)()"#;
+
+/// The context of the `on_error()` callbacks, whose failures aren't
+/// reported as errors again.
+pub const ON_ERROR: &str = "on_error";
+
+thread_local! {
+    static GUARD_FN: OnceCell<Function<(Object, Object, f64), Object>> = const { OnceCell::new() };
+    /// `callback_timeout`, the default until the config is loaded.
+    static TIMEOUT: Cell<Duration> = const { Cell::new(Duration::from_secs(1)) };
+    /// Failed callbacks waiting for [`report`].
+    static FAILURES: RefCell<Vec<Failure>> = const { RefCell::new(Vec::new()) };
+    /// The requests of the guarded callback running now.
+    static REQUESTS: RefCell<Option<Requests>> = const { RefCell::new(None) };
+}
+
+/// What a callback asked of the connection thread, cancelled all at once
+/// should it be stopped.
+#[derive(Default)]
+struct Requests {
+    cancel: CancellationToken,
+    made: usize,
+}
+
+/// A callback that raised an error or was stopped.
+pub struct Failure {
+    pub context: String,
+    pub message: String,
+    /// The channel, author and action of the chat command it was called
+    /// for, which goes into the audit log.
+    pub command: Option<(String, String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    /// Every chat message, with the `chat` event.
+    Message,
+    /// A chat command that went through, with an [`Executed`].
+    CommandExecuted,
+    Connect,
+    Disconnect,
+}
+
+impl Hook {
+    /// The function registering its callbacks.
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Message => "on_message",
+            Hook::CommandExecuted => "on_command_executed",
+            Hook::Connect => "on_connect",
+            Hook::Disconnect => "on_disconnect",
+        }
+    }
+}
+
+/// What `on_command_executed()` callbacks are called with.
+#[derive(Serialize)]
+pub struct Executed<'a> {
+    /// Without the prefix, e.g. `colorscheme`.
+    pub command: &'a str,
+    pub channel: &'a str,
+    pub author: &'a str,
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    callbacks: HashMap<Hook, Vec<Function<Object, ()>>>,
+}
+
+impl Hooks {
+    pub fn register(&mut self, hook: Hook, callback: Function<Object, ()>) {
+        self.callbacks.entry(hook).or_default().push(callback);
+    }
+
+    /// Calls every callback for `hook` with `data`.
+    pub fn fire(&self, hook: Hook, data: &impl Serialize) {
+        if let Some(callbacks) = self.callbacks.get(&hook) {
+            call(callbacks, data, hook.name());
+        }
+    }
+}
+
+/// Calls each of `callbacks`, registered with the function `context`, with
+/// `data`. Errors have their callbacks in the `ErrorLog`, which every other
+/// part of the plugin reports to.
+pub fn call(callbacks: &[Function<Object, ()>], data: &impl Serialize, context: &'static str) {
+    if callbacks.is_empty() {
+        return;
+    }
+    let Ok(data) = data.serialize(Serializer::new()) else {
+        return;
+    };
+
+    for callback in callbacks {
+        let callback = callback.clone();
+        let data = data.clone();
+
+        // Called once the plugin is no longer borrowed, like the registered
+        // commands.
+        nvim_oxi::schedule(move |_| -> Result<()> {
+            if let Err(message) = guarded(callback, data) {
+                fail(Failure {
+                    context: format!("Lua {}", context),
+                    message,
+                    command: None,
+                });
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Cancels a request along with the guarded callback making it, if one is
+/// running.
+pub fn cancellation() -> Option<CancellationToken> {
+    REQUESTS.with(|requests| {
+        let mut requests = requests.borrow_mut();
+        let requests = requests.as_mut()?;
+        requests.made += 1;
+        Some(requests.cancel.clone())
+    })
+}
+
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT.with(|cell| cell.set(timeout));
+}
+
+/// Calls `callback` with `data`, stopping it once it has run for
+/// `callback_timeout` and cancelling the requests it made. The error is the
+/// message it raised or that it was stopped.
+pub fn guarded(callback: impl Into<Object>, data: Object) -> std::result::Result<Object, String> {
+    let timeout = TIMEOUT.with(Cell::get);
+    let outer = REQUESTS.with(|requests| requests.replace(Some(Requests::default())));
+
+    let outcome = GUARD_FN.with(|guard| -> Result<Object> {
+        let guard = match guard.get() {
+            Some(guard) => guard.clone(),
+            None => {
+                let loaded = api::call_function("luaeval", (GUARD,))?;
+                guard.get_or_init(|| loaded).clone()
+            }
+        };
+        Ok(guard.call((callback.into(), data, timeout.as_secs_f64()))?)
+    });
+    let requests = REQUESTS
+        .with(|requests| requests.replace(outer))
+        .unwrap_or_default();
+    let outcome = outcome
+        .and_then(|outcome| Ok(Dictionary::from_object(outcome)?))
+        .map_err(|e| e.to_string())?;
+
+    let field = |key: &str| outcome.get(key).cloned().unwrap_or_default();
+    if bool::from_object(field("ok")).unwrap_or_default() {
+        return Ok(field("value"));
+    }
+
+    if !bool::from_object(field("timeout")).unwrap_or_default() {
+        return Err(String::from_object(field("error")).unwrap_or_default());
+    }
+
+    requests.cancel.cancel();
+
+    let stopped = tr_with("errors.timed_out", &[("seconds", &timeout.as_secs_f64())]);
+    Err(match requests.made {
+        0 => stopped,
+        made => format!(
+            "{}, {}",
+            stopped,
+            tr_with("errors.cancelled", &[("count", &made)])
+        ),
+    })
+}
+
+/// Keeps a failed callback for the next [`report`], the callbacks run once
+/// the plugin is no longer borrowed.
+pub fn fail(failure: Failure) {
+    FAILURES.with(|failures| failures.borrow_mut().push(failure));
+}
+
+/// Adds the callbacks that failed since the last time to `:TwitchErrors`,
+/// and those of chat commands to the audit log, called every second or so.
+pub fn report(plugin: &mut Plugin) {
+    let failures = FAILURES.with(|failures| std::mem::take(&mut *failures.borrow_mut()));
+
+    for failure in failures {
+        // Reporting it would call the failing callback again.
+        if failure.context.ends_with(ON_ERROR) {
+            api::err_writeln(&format!("{}: {}", failure.context, failure.message));
+            continue;
+        }
+
+        if let Some((channel, author, action)) = failure.command {
+            let result = Err(api::Error::Other(failure.message.clone()).into());
+            plugin
+                .audit
+                .record(&channel, &author, action, &result, None);
+        }
+        plugin.errors.push(&failure.context, &failure.message);
+    }
+}
@@ -0,0 +1,104 @@
+//! The message catalog for every string shown to the user.
+//!
+//! Each locale is a TOML file next to this module, compiled into the plugin.
+//! Values may contain `{name}` placeholders that are filled in by [`tr_with`].
+//! Keys missing from a translation fall back to English, so a catalog can be
+//! added before it is complete.
+
+use std::{cell::RefCell, collections::HashMap, fmt::Display, sync::OnceLock};
+
+use nvim_oxi::api;
+
+const FALLBACK: &str = "en";
+
+/// To add a translation, drop `<locale>.toml` in this directory and list it
+/// here.
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("en.toml")),
+    ("de", include_str!("de.toml")),
+];
+
+type Catalog = HashMap<String, String>;
+
+thread_local! {
+    static LOCALE: RefCell<&'static str> = const { RefCell::new(FALLBACK) };
+}
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static PARSED: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(locale, source)| {
+                let table = source
+                    .parse::<toml::Table>()
+                    .unwrap_or_else(|e| panic!("invalid {} catalog: {}", locale, e));
+
+                let mut catalog = Catalog::new();
+                flatten(&mut catalog, "", table);
+
+                (*locale, catalog)
+            })
+            .collect()
+    })
+}
+
+fn flatten(catalog: &mut Catalog, prefix: &str, table: toml::Table) {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key,
+            _ => format!("{}.{}", prefix, key),
+        };
+
+        match value {
+            toml::Value::Table(table) => flatten(catalog, &key, table),
+            toml::Value::String(text) => {
+                catalog.insert(key, text);
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn locales() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(locale, _)| *locale)
+}
+
+/// Uses `locale`, or Neovim's `v:lang` when unset (`de_DE.UTF-8` selects
+/// `de`). Unsupported languages fall back to English.
+pub fn set_locale(locale: Option<&str>) {
+    let lang = match locale {
+        Some(locale) => locale.to_owned(),
+        None => api::get_vvar::<String>("lang").unwrap_or_default(),
+    };
+
+    let language = lang.split(['_', '.', '-']).next().unwrap_or_default();
+
+    let locale = locales()
+        .find(|&locale| locale.eq_ignore_ascii_case(language))
+        .unwrap_or(FALLBACK);
+
+    LOCALE.with(|current| *current.borrow_mut() = locale);
+}
+
+pub fn tr(key: &str) -> String {
+    tr_with(key, &[])
+}
+
+pub fn tr_with(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let locale = LOCALE.with(|current| *current.borrow());
+    let catalogs = catalogs();
+
+    let mut text = catalogs[locale]
+        .get(key)
+        .or_else(|| catalogs[FALLBACK].get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_owned());
+
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+
+    text
+}
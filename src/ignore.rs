@@ -0,0 +1,170 @@
+//! Accounts chat doesn't hear from: the well-known bots, the `ignore.names`
+//! and `ignore.patterns`, whoever `:TwitchIgnore add` added and the accounts
+//! blocked on Twitch. Their messages stay in the history, but never run a
+//! command, enter a raffle or show up in the chat window.
+//! `:TwitchIgnore remove` makes an exception for any of them, both lists
+//! are kept in the plugin state.
+
+use std::collections::BTreeSet;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    filter,
+    i18n::{tr, tr_with},
+    ui::float,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 2] = ["add", "remove"];
+
+/// Bots that are in most channels, ignored unless `ignore.known_bots` is
+/// off.
+const KNOWN_BOTS: [&str; 10] = [
+    "nightbot",
+    "streamelements",
+    "streamlabs",
+    "moobot",
+    "fossabot",
+    "wizebot",
+    "sery_bot",
+    "soundalerts",
+    "commanderroot",
+    "pokemoncommunitygame",
+];
+
+const ADDED_KEY: &str = "ignore.added";
+const REMOVED_KEY: &str = "ignore.removed";
+
+#[derive(Default)]
+pub struct Ignored {
+    /// The accounts blocked on Twitch, as of the last time they were
+    /// fetched.
+    pub blocked: BTreeSet<String>,
+    /// Whoever `:TwitchIgnore add` added, as kept in the plugin state.
+    added: BTreeSet<String>,
+    /// The exceptions `:TwitchIgnore remove` made, kept the same way.
+    removed: BTreeSet<String>,
+}
+
+/// Reads both lists from the plugin state.
+pub fn read(plugin: &mut Plugin) {
+    let state = &plugin.state;
+    plugin.ignored.added = state.get(ADDED_KEY).unwrap_or_default();
+    plugin.ignored.removed = state.get(REMOVED_KEY).unwrap_or_default();
+}
+
+/// Whether `user` is ignored, for whichever reason.
+pub fn ignored(plugin: &Plugin, user: &str) -> bool {
+    let user = user.to_lowercase();
+    let ignored = &plugin.ignored;
+
+    !ignored.removed.contains(&user) && (ignored.added.contains(&user) || configured(plugin, &user))
+}
+
+/// Whether `user`, in lowercase, is ignored by the config or a block.
+fn configured(plugin: &Plugin, user: &str) -> bool {
+    let options = &plugin.config.ignore;
+
+    options.names.iter().any(|name| name == user)
+        || (options.known_bots && KNOWN_BOTS.contains(&user))
+        || (options.twitch_blocks && plugin.ignored.blocked.contains(user))
+        || options
+            .patterns
+            .iter()
+            .any(|pattern| filter::matches(user, pattern).unwrap_or_default())
+}
+
+/// Runs `:TwitchIgnore add|remove <user>`, or `:TwitchIgnore` to list who
+/// is ignored.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    let (add, user) = match args {
+        [] => return show(plugin),
+        [action, user] if action == "add" => (true, user),
+        [action, user] if action == "remove" => (false, user),
+        _ => {
+            api::notify(&tr("ignore.usage"), LogLevel::Error, &NotifyOpts::default())?;
+            return Ok(());
+        }
+    };
+    let user = user.trim_start_matches('@').to_lowercase();
+
+    let key = match add {
+        true => {
+            plugin.ignored.added.insert(user.clone());
+            plugin.ignored.removed.remove(&user);
+            "ignore.added"
+        }
+        false => {
+            plugin.ignored.added.remove(&user);
+            // Only an exception if something else ignores them.
+            if configured(plugin, &user) {
+                plugin.ignored.removed.insert(user.clone());
+            }
+            "ignore.removed"
+        }
+    };
+
+    plugin.state.set(ADDED_KEY, &plugin.ignored.added);
+    plugin.state.set(REMOVED_KEY, &plugin.ignored.removed);
+
+    // Written right away like the muted viewers.
+    if let Err(e) = plugin.state.flush() {
+        plugin.errors.push("State", &e);
+    }
+
+    api::notify(
+        &tr_with(key, &[("user", &user)]),
+        LogLevel::Info,
+        &NotifyOpts::default(),
+    )?;
+
+    Ok(())
+}
+
+fn show(plugin: &Plugin) -> Result<()> {
+    let options = &plugin.config.ignore;
+    let list = |names: Vec<String>| match names.is_empty() {
+        true => tr("ignore.none"),
+        false => names.join(", "),
+    };
+
+    let mut config: Vec<String> = options.names.clone();
+    config.extend(
+        options
+            .patterns
+            .iter()
+            .map(|pattern| format!("/{}/", pattern)),
+    );
+    if options.known_bots {
+        config.extend(KNOWN_BOTS.iter().map(|name| name.to_string()));
+    }
+    let blocked = match options.twitch_blocks {
+        true => plugin.ignored.blocked.iter().cloned().collect(),
+        false => Vec::new(),
+    };
+
+    let lines = vec![
+        tr_with("ignore.config", &[("names", &list(config))]),
+        tr_with(
+            "ignore.runtime",
+            &[(
+                "names",
+                &list(plugin.ignored.added.iter().cloned().collect()),
+            )],
+        ),
+        tr_with("ignore.blocked", &[("names", &list(blocked))]),
+        tr_with(
+            "ignore.exceptions",
+            &[(
+                "names",
+                &list(plugin.ignored.removed.iter().cloned().collect()),
+            )],
+        ),
+    ];
+
+    float::show(&tr("ignore.title"), lines)
+}
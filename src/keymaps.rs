@@ -0,0 +1,230 @@
+//! `!keymap <lhs> <rhs>`: chat proposes a normal mode mapping, which is only
+//! set once the streamer accepts it in a float with `y` (or turns it down
+//! with `n`). `:TwitchKeymaps` lists what was set this way and
+//! `:TwitchKeymaps revert` puts back whatever the keys did before.
+
+use std::collections::VecDeque;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Window},
+    Dictionary, Object, Result,
+};
+
+use crate::{
+    audit::Undo,
+    i18n::{tr, tr_with},
+    twitch::Outgoing,
+    ui::float,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 3] = ["accept", "reject", "revert"];
+
+/// Longer proposals are left out by the parser.
+pub const MAX_LHS: usize = 20;
+pub const MAX_RHS: usize = 120;
+
+/// Proposals waiting behind the one on display before more are dropped.
+const MAX_PENDING: usize = 10;
+
+struct Proposal {
+    channel: String,
+    author: String,
+    lhs: String,
+    rhs: String,
+}
+
+struct Applied {
+    lhs: String,
+    rhs: String,
+    author: String,
+    /// What `maparg()` returned before, empty if the keys weren't mapped.
+    previous: Dictionary,
+}
+
+#[derive(Default)]
+pub struct Keymaps {
+    /// The first one is the one asked about.
+    pending: VecDeque<Proposal>,
+    window: Option<Window>,
+    applied: Vec<Applied>,
+}
+
+/// Queues a `!keymap` and asks about it unless another one is open.
+pub fn propose(
+    plugin: &mut Plugin,
+    channel: String,
+    author: String,
+    lhs: String,
+    rhs: String,
+) -> Result<()> {
+    let keymaps = &mut plugin.keymaps;
+    if keymaps.pending.len() >= MAX_PENDING {
+        return Ok(());
+    }
+
+    keymaps.pending.push_back(Proposal {
+        channel,
+        author,
+        lhs,
+        rhs,
+    });
+
+    match float::showing(keymaps.window.as_ref()) {
+        true => Ok(()),
+        false => ask(plugin),
+    }
+}
+
+/// Runs `:TwitchKeymaps [accept|reject|revert]`, listing the mappings set
+/// from chat without one.
+pub fn run(plugin: &mut Plugin, args: Option<&str>) -> Result<()> {
+    match args.map(str::trim).unwrap_or_default() {
+        "" => show(plugin),
+        "accept" => decide(plugin, true),
+        "reject" => decide(plugin, false),
+        "revert" => revert(plugin),
+        _ => notify(&tr("keymaps.usage"), LogLevel::Error),
+    }
+}
+
+/// Opens the float asking about the first pending proposal.
+fn ask(plugin: &mut Plugin) -> Result<()> {
+    let Some(proposal) = plugin.keymaps.pending.front() else {
+        return Ok(());
+    };
+
+    let lines = vec![
+        tr_with("keymaps.proposed", &[("user", &proposal.author)]),
+        String::new(),
+        format!("  {}  →  {}", proposal.lhs, proposal.rhs),
+        String::new(),
+        tr("keymaps.hint"),
+    ];
+
+    let keys = [
+        (Mode::Normal, "y", "<cmd>TwitchKeymaps accept<cr>"),
+        (Mode::Normal, "n", "<cmd>TwitchKeymaps reject<cr>"),
+        (Mode::Normal, "q", "<cmd>TwitchKeymaps reject<cr>"),
+    ];
+    let (window, _) = float::confirm(
+        &tr("keymaps.title"),
+        lines,
+        plugin.config.window.focus,
+        &keys,
+    )?;
+    plugin.keymaps.window = Some(window);
+
+    Ok(())
+}
+
+fn decide(plugin: &mut Plugin, accepted: bool) -> Result<()> {
+    let Some(proposal) = plugin.keymaps.pending.pop_front() else {
+        return notify(&tr("keymaps.nothing_pending"), LogLevel::Info);
+    };
+
+    if let Some(window) = plugin.keymaps.window.take() {
+        if window.is_valid() {
+            window.close(true)?;
+        }
+    }
+
+    let key = match accepted {
+        true => {
+            let result = apply(plugin, &proposal);
+            let action = format!("keymap {} {}", proposal.lhs, proposal.rhs);
+            plugin.audit.record(
+                &proposal.channel,
+                &proposal.author,
+                action,
+                &result,
+                Some(Undo::Keymap),
+            );
+            result?;
+            "keymaps.accepted"
+        }
+        false => "keymaps.rejected",
+    };
+
+    let text = tr_with(key, &[("user", &proposal.author), ("lhs", &proposal.lhs)]);
+    let _ = plugin.outgoing.send(Outgoing::Reply {
+        channel: proposal.channel,
+        text,
+    });
+
+    ask(plugin)
+}
+
+fn apply(plugin: &mut Plugin, proposal: &Proposal) -> Result<()> {
+    let previous: Dictionary =
+        api::call_function("maparg", (proposal.lhs.as_str(), "n", false, true))?;
+
+    let desc = tr_with("keymaps.desc", &[("user", &proposal.author)]);
+    let opts = SetKeymapOpts::builder().noremap(true).desc(&desc).build();
+    api::set_keymap(Mode::Normal, &proposal.lhs, &proposal.rhs, &opts)?;
+
+    plugin.keymaps.applied.push(Applied {
+        lhs: proposal.lhs.clone(),
+        rhs: proposal.rhs.clone(),
+        author: proposal.author.clone(),
+        previous,
+    });
+
+    Ok(())
+}
+
+/// Removes every mapping set from chat, latest first so keys mapped twice
+/// end up as they were before either.
+fn revert(plugin: &mut Plugin) -> Result<()> {
+    let applied = std::mem::take(&mut plugin.keymaps.applied);
+    let count = applied.len();
+
+    for mapping in applied.into_iter().rev() {
+        restore(mapping)?;
+    }
+
+    notify(
+        &tr_with("keymaps.reverted", &[("count", &count)]),
+        LogLevel::Info,
+    )
+}
+
+/// Removes the latest mapping set from chat without telling, for
+/// `:TwitchUndo`.
+pub fn revert_last(plugin: &mut Plugin) -> Result<()> {
+    match plugin.keymaps.applied.pop() {
+        Some(mapping) => restore(mapping),
+        None => Ok(()),
+    }
+}
+
+/// Puts back whatever the keys of `mapping` did before.
+fn restore(mapping: Applied) -> Result<()> {
+    // Already gone if the user unmapped it.
+    let _ = api::del_keymap(Mode::Normal, &mapping.lhs);
+
+    if !mapping.previous.is_empty() {
+        api::call_function::<_, Object>("mapset", ("n", false, mapping.previous))?;
+    }
+
+    Ok(())
+}
+
+fn show(plugin: &Plugin) -> Result<()> {
+    let lines = match plugin.keymaps.applied.is_empty() {
+        true => vec![tr("keymaps.none")],
+        false => plugin
+            .keymaps
+            .applied
+            .iter()
+            .map(|mapping| format!("{}  →  {}  ({})", mapping.lhs, mapping.rhs, mapping.author))
+            .collect(),
+    };
+
+    float::show(&tr("keymaps.applied"), lines)
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
@@ -1,195 +1,2055 @@
-use std::{cell::RefCell, rc::Rc, thread};
+mod access;
+mod ads;
+mod approvals;
+mod audit;
+mod auth;
+mod backend;
+mod bench;
+mod commands;
+mod config;
+mod display;
+mod events;
+mod filter;
+mod flood;
+mod gifts;
+mod health;
+mod help;
+mod highlights;
+mod history;
+mod hooks;
+mod i18n;
+mod ignore;
+mod keymaps;
+mod llm;
+mod location;
+mod logging;
+mod metrics;
+mod moderation;
+mod options;
+mod paths;
+mod polls;
+mod raffle;
+mod raid;
+mod redemptions;
+mod replay;
+mod rpc;
+mod schedule;
+mod search;
+mod sentiment;
+mod simulate;
+mod snippets;
+mod sounds;
+mod spotlight;
+mod state;
+mod stats;
+mod statusline;
+mod suggest;
+mod summarize;
+#[cfg(feature = "integration")]
+mod tests;
+mod threads;
+mod transcript;
+mod trythis;
+mod twitch;
+mod ui;
+mod viewers;
+mod votes;
+mod webhooks;
+mod whispers;
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    rc::Rc,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
 
 use nvim_oxi::{
-    api::{self, opts::*, types::*, Buffer, Window},
-    libuv::AsyncHandle,
-    schedule, Result,
+    api::{self, opts::*, types::*},
+    libuv::{AsyncHandle, TimerHandle},
+    schedule, Dictionary, Function, Object, Result,
 };
-use tokio::sync::mpsc::{self, UnboundedSender};
-use twitch_irc::{
-    login::StaticLoginCredentials, message::ServerMessage, ClientConfig, SecureTCPTransport,
-    TwitchIRCClient,
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
 };
 
-#[tokio::main(flavor = "current_thread")]
-pub async fn connect(handle: AsyncHandle, sender: UnboundedSender<CommandPayload>) -> Result<()> {
-    let config = ClientConfig::default();
-    let (mut incoming_messages, client) =
-        TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(config);
+use ads::Ads;
+use approvals::Approvals;
+use audit::Audit;
+use auth::{Backend, Credentials};
+use commands::{
+    registry::CommandRegistry,
+    text,
+    throttle::{self, Throttle},
+    Command, CommandSender, Queue, SharedSyntax,
+};
+use config::{Config, Diagnostic, RenderMode};
+use display::DisplayQueue;
+use flood::Flood;
+use gifts::Gifts;
+use highlights::HighlightLog;
+use history::History;
+use hooks::Hooks;
+use i18n::{tr, tr_with};
+use ignore::Ignored;
+use keymaps::Keymaps;
+use paths::Paths;
+use polls::Polls;
+use raffle::Raffle;
+use raid::Raid;
+use redemptions::Redemptions;
+use replay::Replay;
+use schedule::Schedule;
+use sentiment::Sentiment;
+use simulate::Simulation;
+use sounds::Sounds;
+use state::State;
+use stats::{Dashboard, SessionStats};
+use suggest::Suggestions;
+use transcript::Transcript;
+use trythis::Trials;
+use twitch::{BadgeSets, ConnectionStatus, Outgoing, OutgoingSender, Slash};
+use ui::{Annotations, ChatWindow, ErrorLog, PinnedMessages, PlainLog, PluginError};
+use viewers::Viewers;
+use votes::Votes;
 
-    let join_handle = tokio::spawn(async move {
-        while let Some(message) = incoming_messages.recv().await {
-            match message {
-                ServerMessage::Privmsg(msg) => {
-                    let mut split = msg.message_text.trim().splitn(2, " ");
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often pinned messages expire and the ad countdown is updated.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long quitting waits for the connection thread to wind down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+const SHUTDOWN_POLL: Duration = Duration::from_millis(10);
 
-                    let command = split.next();
-                    let argument = split.next();
+struct Plugin {
+    config: Config,
+    paths: Paths,
+    state: State,
+    setup_opts: Object,
+    chat: ChatWindow,
+    pinned: PinnedMessages,
+    annotations: Annotations,
+    /// Where chat goes instead in accessibility mode.
+    plain: PlainLog,
+    /// The buffers of channels with `output = "buffer"`.
+    channel_logs: HashMap<String, PlainLog>,
+    errors: ErrorLog,
+    history: History,
+    gifts: Gifts,
+    raffle: Raffle,
+    votes: Votes,
+    transcript: Transcript,
+    simulation: Simulation,
+    replay: Replay,
+    redemptions: Redemptions,
+    hooks: Hooks,
+    keymaps: Keymaps,
+    trials: Trials,
+    ads: Ads,
+    raid: Raid,
+    flood: Flood,
+    schedule: Schedule,
+    suggestions: Suggestions,
+    sounds: Sounds,
+    branches: location::Branches,
+    ignored: Ignored,
+    access: access::Lists,
+    /// Viewers muted with `:TwitchMute`.
+    muted: BTreeSet<String>,
+    viewers: Viewers,
+    highlights: HighlightLog,
+    audit: Audit,
+    approvals: Approvals,
+    polls: Polls,
+    sentiment: Sentiment,
+    stats: SessionStats,
+    dashboard: Dashboard,
+    registry: CommandRegistry,
+    throttle: Throttle,
+    display: DisplayQueue,
+    /// When the timer of [`animate`] goes off, while one is set.
+    ticking: Option<Instant>,
+    badges: BadgeSets,
+    status: ConnectionStatus,
+    /// Starts out as configured and can be switched with `:TwitchRender`.
+    render_mode: RenderMode,
+    outgoing: OutgoingSender,
+    /// Commands on their way from the connection thread.
+    queue: Arc<Queue>,
+    /// `config.syntax` as the connection thread parses commands with it.
+    syntax: SharedSyntax,
+    /// Whether the last session was restored, which waits for `setup()` to
+    /// say where the state is. Until then nothing is saved over it.
+    restored: bool,
+}
 
-                    if let Some("!nvim") = command {
-                        if let Some(text) = argument {
-                            let name = msg.sender.name;
+impl Plugin {
+    fn new(config: Config, outgoing: UnboundedSender<Outgoing>) -> Result<Self> {
+        let paths = Paths::resolve(&config.paths);
+        let (state, error) = State::open(&paths.state);
 
-                            sender
-                                .send(CommandPayload {
-                                    command: Command::Message(name.to_owned(), text.to_owned()),
-                                })
-                                .unwrap();
+        logging::open(&paths.log);
+        logging::set_level(config.log_level);
 
-                            handle.send().unwrap();
-                        }
-                    }
+        let mut plugin = Plugin {
+            chat: ChatWindow::new(config.window.clone())?,
+            pinned: PinnedMessages::new(config.window.zindex)?,
+            annotations: Annotations::new(),
+            plain: PlainLog::new(config.limits.buffer),
+            channel_logs: HashMap::new(),
+            paths,
+            state,
+            setup_opts: Object::nil(),
+            errors: ErrorLog::new(config.limits.errors),
+            history: History::new(config.limits.history),
+            gifts: Gifts::default(),
+            raffle: Raffle::default(),
+            votes: Votes::default(),
+            transcript: Transcript::default(),
+            simulation: Simulation::default(),
+            replay: Replay::default(),
+            redemptions: Redemptions::default(),
+            hooks: Hooks::default(),
+            keymaps: Keymaps::default(),
+            trials: Trials::default(),
+            ads: Ads::default(),
+            raid: Raid::default(),
+            flood: Flood::default(),
+            schedule: Schedule::default(),
+            suggestions: Suggestions::default(),
+            sounds: Sounds::default(),
+            branches: location::Branches::default(),
+            ignored: Ignored::default(),
+            access: access::Lists::default(),
+            muted: BTreeSet::new(),
+            viewers: Viewers::new(config.limits.viewers),
+            highlights: HighlightLog::default(),
+            audit: Audit::default(),
+            approvals: Approvals::default(),
+            polls: Polls::default(),
+            sentiment: Sentiment::default(),
+            stats: SessionStats::new(config.limits.stats),
+            dashboard: Dashboard::default(),
+            registry: CommandRegistry::default(),
+            throttle: Throttle::default(),
+            display: DisplayQueue::default(),
+            ticking: None,
+            badges: BadgeSets::default(),
+            status: ConnectionStatus::Connecting,
+            render_mode: config.render,
+            queue: Arc::new(Queue::new(config.limits.pending)),
+            syntax: Arc::new(RwLock::new(config.syntax.clone())),
+            restored: false,
+            outgoing: OutgoingSender::new(outgoing),
+            config,
+        };
 
-                    if let Some("!colorscheme") = command {
-                        if let Some(colorscheme) = argument {
-                            sender
-                                .send(CommandPayload {
-                                    command: Command::ColorScheme(colorscheme.to_owned()),
-                                })
-                                .unwrap();
+        if let Some(e) = error {
+            plugin.errors.push("State", &e);
+        }
+        plugin.read_state();
 
-                            handle.send().unwrap();
-                        }
-                    }
-                }
-                _ => (),
+        Ok(plugin)
+    }
+
+    /// Stores the `setup()` table and rebuilds the config from it.
+    fn setup(&mut self, opts: Object) -> Vec<Diagnostic> {
+        self.setup_opts = opts;
+        let diagnostics = self.reload_config();
+
+        if !self.restored {
+            self.restored = true;
+            self.restore_state();
+        }
+
+        diagnostics
+    }
+
+    fn reload_config(&mut self) -> Vec<Diagnostic> {
+        let (config, diagnostics) = Config::load(&self.setup_opts);
+
+        i18n::set_locale(config.locale.as_deref());
+        hooks::set_timeout(config.callback_timeout);
+
+        if let Err(e) = self
+            .chat
+            .set_options(config.window.clone())
+            .and_then(|()| self.pinned.set_zindex(self.chat.zindex()))
+        {
+            self.errors.push("Config", &e);
+        }
+        if let Err(e) = ui::override_highlights(&config.highlights) {
+            self.errors.push("Config", &e);
+        }
+        if let Err(e) = ui::map_focus_key(
+            self.config.focus_key.as_deref(),
+            config.focus_key.as_deref(),
+        ) {
+            self.errors.push("Config", &e);
+        }
+        if config.limits != self.config.limits {
+            self.history.set_limit(config.limits.history);
+            self.errors.set_limit(config.limits.errors);
+            self.stats.set_limit(config.limits.stats);
+            self.queue.set_limit(config.limits.pending);
+            self.viewers.set_limit(config.limits.viewers);
+            self.plain.set_limit(config.limits.buffer);
+            for log in self.channel_logs.values_mut() {
+                log.set_limit(config.limits.buffer);
             }
         }
-    });
+        if config.syntax != self.config.syntax {
+            *self.syntax.write().unwrap() = config.syntax.clone();
+        }
 
-    client.join("mikerimebot".to_owned()).unwrap_or_else(|e| {
-        println!("{:?}", e);
-    });
+        // Only a changed setting overrides a switch made at runtime.
+        if config.render != self.config.render {
+            self.render_mode = config.render;
+        }
+        if config.log_level != self.config.log_level {
+            logging::set_level(config.log_level);
+        }
+        self.paths = Paths::resolve(&config.paths);
+        logging::open(&self.paths.log);
+        self.config = config;
 
-    join_handle.await.unwrap();
+        if self.state.dir() != Some(self.paths.state.as_path()) {
+            self.reopen_state();
+        }
 
-    Ok(())
-}
+        diagnostics
+    }
 
-struct Plugin {
-    buffer: Buffer,
-    window: Option<Window>,
-}
+    /// Lowers or raises the chat float and the pinned one along with it, see
+    /// `ChatWindow::set_lowered`.
+    fn set_floats_lowered(&mut self, lowered: bool) -> Result<()> {
+        self.chat.set_lowered(lowered)?;
+        self.pinned.set_zindex(self.chat.zindex())
+    }
+
+    fn tab_entered(&mut self) -> Result<()> {
+        self.chat.tab_entered()?;
+        self.pinned.tab_entered()
+    }
+
+    fn resize_windows(&mut self) -> Result<()> {
+        self.chat.resize()?;
+        self.pinned.resize()
+    }
+
+    /// Puts back the chat history, the cooldowns and the colorscheme chat
+    /// last switched to from the previous session.
+    fn restore_state(&mut self) {
+        if let Some(entries) = self.state.get(history::STATE_KEY) {
+            self.history.restore(entries);
+        }
+        if let Some(buckets) = self.state.get(throttle::STATE_KEY) {
+            self.throttle.restore(buckets);
+        }
+
+        let Some(name) = self.state.get::<String>(commands::COLORSCHEME_KEY) else {
+            return;
+        };
+        let result = commands::allowed_colorschemes(self).and_then(|allowed| {
+            match allowed.contains(&name) {
+                true => commands::apply_colorscheme(self, &name),
+                false => Ok(()),
+            }
+        });
+        if let Err(e) = result {
+            self.errors.push("State", &e);
+        }
+    }
+
+    /// Writes the state to disk along with what is only kept in memory
+    /// until then, the chat history and the cooldowns.
+    fn save_state(&mut self) -> std::result::Result<(), String> {
+        if self.restored {
+            self.state.set(history::STATE_KEY, self.history.entries());
+            self.state.set(throttle::STATE_KEY, self.throttle.save());
+        }
+
+        self.state.flush()
+    }
+
+    /// Moves the state to the current state directory, keeping what was
+    /// written so far in the old one.
+    fn reopen_state(&mut self) {
+        if let Err(e) = self.save_state() {
+            self.errors.push("State", &e);
+        }
+
+        let (state, error) = State::open(&self.paths.state);
+        self.state = state;
+
+        if let Some(e) = error {
+            self.errors.push("State", &e);
+        }
+        self.read_state();
+    }
+
+    /// Takes over what the state keeps that is looked at on every message,
+    /// rather than reading it from the state each time.
+    fn read_state(&mut self) {
+        self.registry.set_text(text::load(&self.state));
+        access::read(self);
+        filter::read(self);
+        ignore::read(self);
+    }
+
+    fn save_state_now(&mut self) -> Result<()> {
+        let (message, level) = match self.save_state() {
+            Ok(()) => (
+                tr_with("state.saved", &[("path", &self.paths.state.display())]),
+                LogLevel::Info,
+            ),
+            Err(e) => (
+                tr_with("state.save_failed", &[("error", &e)]),
+                LogLevel::Error,
+            ),
+        };
+
+        api::notify(&message, level, &NotifyOpts::default())?;
 
-impl Plugin {
-    fn err(&self, str: &str) -> Result<()> {
-        api::err_writeln(str);
         Ok(())
     }
 
-    fn colosrcheme(&self, colorscheme: String) -> Result<()> {
-        let mut command = String::from("colorscheme ");
-        command.push_str(colorscheme.as_str());
+    fn reset_state(&mut self) -> Result<()> {
+        let (message, level) = match self.state.reset() {
+            Ok(()) => {
+                self.read_state();
+                (tr("state.reset"), LogLevel::Info)
+            }
+            Err(e) => (
+                tr_with("state.reset_failed", &[("error", &e)]),
+                LogLevel::Error,
+            ),
+        };
 
-        api::command(command.as_str())?;
+        api::notify(&message, level, &NotifyOpts::default())?;
 
         Ok(())
     }
+}
+
+/// Spawns the connection thread once `setup()` has provided a configuration,
+/// and stops or starts it again for `:TwitchDisconnect` and friends.
+struct Connector {
+    sender: CommandSender,
+    /// Taken by the first connection, so what was sent before `setup()`
+    /// still goes out. Later ones get a channel of their own.
+    outgoing: Option<UnboundedReceiver<Outgoing>>,
+    /// Ends the running connection, closed once its thread is gone.
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    /// Whether `setup()` started it already.
+    started: bool,
+}
+
+impl Connector {
+    fn is_running(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(|shutdown| !shutdown.is_closed())
+    }
 
-    fn open_win(&mut self) -> Result<()> {
-        let opts = OptionOpts::builder()
-            .scope(api::opts::OptionScope::Global)
-            .build();
+    /// Returns whether this call started the connection, which it doesn't
+    /// while one is running.
+    fn start(&mut self, plugin: &mut Plugin) -> bool {
+        if self.is_running() {
+            return false;
+        }
+
+        let outgoing = self.outgoing.take().unwrap_or_else(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            plugin.outgoing = OutgoingSender::new(sender);
+            receiver
+        });
+        let (config, paths) = (plugin.config.clone(), plugin.paths.clone());
+        let syntax = Arc::clone(&plugin.syntax);
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let sender = self.sender.clone();
 
-        let cols = api::get_option_value::<u32>("columns", &opts)?;
-        let rows = api::get_option_value::<u32>("lines", &opts)?;
+        self.shutdown = Some(shutdown);
+        self.started = true;
+        plugin.status = ConnectionStatus::Connecting;
 
-        let width: u32 = 40;
-        let height: u32 = 10;
+        self.thread = Some(thread::spawn(move || {
+            // Loaded here rather than inside the runtime since the keychain
+            // backends block on their own executor.
+            let (accounts, errors) = auth::load_all(&config.accounts, &paths);
 
-        let x: f32 = ((cols / 2) - (width - 2) / 2) as f32;
-        let y: f32 = ((rows / 2) - (height - 2) / 2) as f32;
+            for message in errors {
+                sender.send(Command::Error(PluginError::error("Credentials", message)));
+            }
 
-        let config = WindowConfig::builder()
-            .relative(WindowRelativeTo::Editor)
-            .border(nvim_oxi::api::types::WindowBorder::Rounded)
-            .style(nvim_oxi::api::types::WindowStyle::Minimal)
-            .height(height)
-            .width(width)
-            .col(x)
-            .row(y)
-            .focusable(true)
-            .build();
+            let errors = sender.clone();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                twitch::connect(
+                    config,
+                    syntax,
+                    paths,
+                    accounts,
+                    sender,
+                    outgoing,
+                    shutdown_receiver,
+                )
+            }));
 
-        let window = nvim_oxi::api::open_win(&self.buffer, false, &config)?;
-        api::set_current_win(&window)?;
+            // Nothing else reconnects once the thread is gone, so this is
+            // the last chance to say why.
+            let message = match result {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e.to_string(),
+                Err(panic) => format!("the connection thread panicked: {}", panic_message(&*panic)),
+            };
 
-        self.window = Some(window);
+            errors.send(Command::Error(PluginError::error("Connection", message)));
+        }));
 
-        Ok(())
+        true
     }
 
-    fn show_msg(&mut self, author: &str, message: &str) -> Result<()> {
-        self.buffer.set_lines(0..10, false, [author, "", message])?;
+    /// Stops the connection as Neovim exits, waiting up to `timeout` for the
+    /// thread so sockets are closed rather than cut. A thread stuck past
+    /// that is left to the process exit.
+    fn shut_down(&mut self, timeout: Duration) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
 
-        if self.window.is_some() {
-            if let Some(win) = &self.window {
-                if !win.is_valid() {
-                    self.open_win()?;
-                }
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        let deadline = Instant::now() + timeout;
+
+        while !thread.is_finished() && Instant::now() < deadline {
+            thread::sleep(SHUTDOWN_POLL);
+        }
+
+        if thread.is_finished() {
+            let _ = thread.join();
+        }
+    }
+
+    /// How the connection thread is doing, for `:checkhealth`.
+    fn runtime(&self) -> health::Runtime {
+        health::Runtime {
+            started: self.started,
+            stopped: self.shutdown.is_none(),
+            alive: self
+                .thread
+                .as_ref()
+                .is_some_and(|thread| !thread.is_finished()),
+        }
+    }
+
+    /// Returns whether there was a connection to stop.
+    fn stop(&mut self, plugin: &mut Plugin) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        commands::dispatch(plugin, Command::Disconnected);
+
+        true
+    }
+}
+
+/// What a panic said, if it said anything.
+pub fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("no message")
+}
+
+/// `:TwitchConnect`, `:TwitchDisconnect` and `:TwitchRestart`.
+fn connection(plugin: &mut Plugin, connector: &mut Connector, action: &str) -> Result<()> {
+    let message = match action {
+        _ if !connector.started => tr("connection.not_set_up"),
+        "connect" if !connector.start(plugin) => tr("connection.running"),
+        "disconnect" if !connector.stop(plugin) => tr("connection.not_running"),
+        "restart" => {
+            connector.stop(plugin);
+            connector.start(plugin);
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+
+    api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+/// Prompts for the OAuth token so it never ends up in the command history.
+fn login(paths: &Paths, nick: &str, account: &str) -> Result<()> {
+    if !config::valid_account(account) {
+        let message = tr_with("credentials.invalid_account", &[("account", &account)]);
+        api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+        return Ok(());
+    }
+
+    let token: String = api::call_function("inputsecret", (tr("credentials.prompt"),))?;
+
+    if token.trim().is_empty() {
+        return Ok(());
+    }
+
+    let mut credentials = Credentials::new(nick, &token);
+
+    let refresh_token: String =
+        api::call_function("inputsecret", (tr("credentials.refresh_prompt"),))?;
+
+    if !refresh_token.trim().is_empty() {
+        credentials.refresh_token = Some(refresh_token.trim().to_owned());
+    }
+
+    let message = match auth::save(paths, account, &credentials) {
+        Ok(Backend::Keyring) => tr("credentials.stored_keyring"),
+        Ok(Backend::EncryptedFile) => tr("credentials.stored_file"),
+        Err(e) => tr_with("credentials.store_failed", &[("error", &e)]),
+    };
+
+    api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+fn logout(paths: &Paths, account: &str) -> Result<()> {
+    let (message, level) = match auth::clear(paths, account) {
+        Ok(()) => (tr("credentials.removed"), LogLevel::Info),
+        Err(e) => (
+            tr_with("credentials.remove_failed", &[("error", &e)]),
+            LogLevel::Error,
+        ),
+    };
+
+    api::notify(&message, level, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+fn export_stats(plugin: &Plugin, file: &str) -> Result<()> {
+    let path: String = api::call_function("expand", (file,))?;
+
+    let (message, level) = match plugin.stats.export_csv(Path::new(&path)) {
+        Ok(()) => (
+            tr_with("stats.exported", &[("path", &path)]),
+            LogLevel::Info,
+        ),
+        Err(e) => (
+            tr_with("stats.export_failed", &[("path", &path), ("error", &e)]),
+            LogLevel::Error,
+        ),
+    };
+
+    api::notify(&message, level, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+fn import_commands(plugin: &mut Plugin, file: &str) -> Result<()> {
+    let path: String = api::call_function("expand", (file,))?;
+
+    let (message, level) = match commands::import::read(Path::new(&path)) {
+        Ok(imported) => {
+            let count = imported.len();
+            let total = text::insert(plugin, imported);
+
+            // Written right away, an import is worth more than a counter.
+            if let Err(e) = plugin.state.flush() {
+                plugin.errors.push("State", &e);
             }
-        } else {
-            self.open_win()?;
+
+            (
+                tr_with(
+                    "commands.imported",
+                    &[("count", &count), ("total", &total), ("path", &path)],
+                ),
+                LogLevel::Info,
+            )
         }
+        Err(e) => (
+            tr_with("commands.import_failed", &[("path", &path), ("error", &e)]),
+            LogLevel::Error,
+        ),
+    };
 
-        Ok(())
+    api::notify(&message, level, &NotifyOpts::default())?;
+
+    Ok(())
+}
+
+/// Sends `text` to the channel as `account`, running it as a chat command
+/// if it starts with a `/`.
+fn send(plugin: &Plugin, account: &str, text: String) -> Result<()> {
+    if !plugin.config.accounts.contains_key(account) {
+        let known = plugin.config.accounts.keys().cloned().collect::<Vec<_>>();
+        let message = tr_with(
+            "send.unknown_account",
+            &[("account", &account), ("accounts", &known.join(", "))],
+        );
+        api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+        return Ok(());
     }
+
+    let account = account.to_owned();
+
+    let request = match Slash::parse(&text) {
+        None => Outgoing::Say { account, text },
+        Some(Ok(slash)) => Outgoing::Slash { account, slash },
+        Some(Err(message)) => {
+            api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+            return Ok(());
+        }
+    };
+
+    // The receiver lives as long as the connection thread, so a failed send
+    // only means the plugin is shutting down.
+    let _ = plugin.outgoing.send(request);
+
+    Ok(())
 }
 
-#[derive(Debug)]
-pub enum Command {
-    Message(String, String),
-    ColorScheme(String),
+/// The longest description Twitch accepts for a stream marker.
+const MARKER_DESCRIPTION_LIMIT: usize = 140;
+
+fn create_marker(plugin: &Plugin, description: Option<String>) -> Result<()> {
+    let description = description.unwrap_or_default();
+
+    if description.chars().count() > MARKER_DESCRIPTION_LIMIT {
+        let message = tr_with("marker.too_long", &[("limit", &MARKER_DESCRIPTION_LIMIT)]);
+        api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+        return Ok(());
+    }
+
+    let _ = plugin.outgoing.send(Outgoing::Marker { description });
+
+    Ok(())
 }
 
-#[derive(Debug)]
-pub struct CommandPayload {
-    command: Command,
+fn set_render_mode(plugin: &mut Plugin, name: Option<String>) -> Result<()> {
+    let mode = match name.as_deref().filter(|name| !name.is_empty()) {
+        None if plugin.render_mode == RenderMode::Batched => RenderMode::Immediate,
+        None => RenderMode::Batched,
+        Some(name) => match RenderMode::parse(name) {
+            Some(mode) => mode,
+            None => {
+                let message = tr_with("render.unknown", &[("mode", &name)]);
+                api::notify(&message, LogLevel::Error, &NotifyOpts::default())?;
+                return Ok(());
+            }
+        },
+    };
+
+    plugin.render_mode = mode;
+
+    let message = tr_with("render.switched", &[("mode", &mode.name())]);
+    api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+
+    Ok(())
 }
 
-#[nvim_oxi::plugin]
-pub fn nvim_plugin() -> Result<()> {
-    let (sender, mut receiver) = mpsc::unbounded_channel::<CommandPayload>();
+/// Completes `@name` from the recent chatters, most recent first.
+fn complete_mention(plugin: &Plugin, lead: &str) -> Vec<String> {
+    let Some(prefix) = lead.strip_prefix('@') else {
+        return Vec::new();
+    };
 
-    let buf = nvim_oxi::api::create_buf(false, true)?;
+    let prefix = prefix.to_lowercase();
 
-    let win: Option<Window> = None;
+    plugin
+        .history
+        .chatters()
+        .into_iter()
+        .filter(|name| name.to_lowercase().starts_with(&prefix))
+        .map(|name| format!("@{}", name))
+        .collect()
+}
 
-    let plugin: Rc<RefCell<Plugin>> = Rc::new(RefCell::new(Plugin {
-        buffer: buf,
-        window: win,
-    }));
+/// Reloads the config, from `opts` as the new `setup()` table if given, and
+/// reconnects if that changed something the running connection was started
+/// with. Everything else is swapped in without dropping chat.
+fn reload(plugin: &mut Plugin, connector: &mut Connector, opts: Option<Object>) -> Vec<Diagnostic> {
+    let previous = plugin.config.clone();
+    let diagnostics = match opts {
+        Some(opts) => plugin.setup(opts),
+        None => plugin.reload_config(),
+    };
 
-    let handle = AsyncHandle::new(move || {
-        let payload = receiver.blocking_recv().unwrap();
+    if connector.is_running() && previous.connection_changed(&plugin.config) {
+        connector.stop(plugin);
+        connector.start(plugin);
+
+        let message = tr("config.reconnecting");
+        if let Err(e) = api::notify(&message, LogLevel::Info, &NotifyOpts::default()) {
+            plugin.errors.push("Config", &e);
+        }
+    }
+
+    diagnostics
+}
+
+/// Sets a one-shot timer for the next step of the chat window fading out,
+/// see `window.dismiss_after`, or of the display queue, unless one goes off
+/// sooner. Nothing runs while neither is waiting for anything.
+fn animate(plugin: &Rc<RefCell<Plugin>>) -> Result<()> {
+    let (wait, due) = {
+        let mut this = plugin.borrow_mut();
+        let next = [this.chat.next_tick(), display::next_tick(&this)];
+        let Some(wait) = next.into_iter().flatten().min() else {
+            return Ok(());
+        };
 
-        let plugin_ref = Rc::clone(&plugin);
+        let due = Instant::now() + wait;
+        if this.ticking.is_some_and(|ticking| ticking <= due) {
+            return Ok(());
+        }
+        this.ticking = Some(due);
+        (wait, due)
+    };
 
+    let plugin = Rc::clone(plugin);
+    TimerHandle::once(wait, move || {
         schedule(move |_| {
-            let mut plugin = plugin_ref.borrow_mut();
-
-            match payload.command {
-                Command::Message(author, text) => {
-                    plugin
-                        .show_msg(author.as_str(), text.as_str())
-                        .unwrap_or_else(|_| {
-                            plugin.err("Plugin Error: Message").unwrap();
-                        });
-                }
-                Command::ColorScheme(colorscheme) => {
-                    plugin.colosrcheme(colorscheme).unwrap_or_else(|_| {
-                        plugin.err("Plugin Error: Colorscheme").unwrap();
-                    });
+            {
+                let mut this = plugin.borrow_mut();
+                // A sooner timer took over, and set the next one.
+                if this.ticking != Some(due) {
+                    return Ok(());
                 }
+                this.ticking = None;
+                this.chat.dismiss_tick()?;
+                display::tick(&mut this)?;
             }
+            animate(&plugin)
         });
+        Ok::<_, nvim_oxi::Error>(())
     })?;
 
-    thread::spawn(move || {
-        connect(handle, sender).unwrap_or_else(|e| {
-            println!("{:?}", e);
-        });
-    });
+    Ok(())
+}
+
+/// Polls the config file and reapplies the config whenever it changes.
+fn watch_config_files(
+    plugin: Rc<RefCell<Plugin>>,
+    connector: Rc<RefCell<Connector>>,
+) -> Result<()> {
+    let files = paths::config_files();
+    let mut last_modified: Vec<_> = files.iter().map(config::file::modified).collect();
+
+    TimerHandle::start(CONFIG_POLL_INTERVAL, CONFIG_POLL_INTERVAL, move |_| {
+        let modified: Vec<_> = files.iter().map(config::file::modified).collect();
+
+        if modified != last_modified {
+            last_modified = modified;
+
+            let plugin = Rc::clone(&plugin);
+            let connector = Rc::clone(&connector);
+
+            schedule(move |_| {
+                let mut plugin = plugin.borrow_mut();
+                let diagnostics = reload(&mut plugin, &mut connector.borrow_mut(), None);
+                config::report(&mut plugin.errors, &diagnostics);
+
+                Ok::<_, nvim_oxi::Error>(())
+            });
+        }
+    })?;
 
     Ok(())
 }
+
+#[nvim_oxi::plugin]
+pub fn nvim_plugin() -> Result<Dictionary> {
+    let (outgoing, outgoing_receiver) = mpsc::unbounded_channel::<Outgoing>();
+
+    let plugin = Rc::new(RefCell::new(Plugin::new(Config::default(), outgoing)?));
+
+    ui::define_highlights()?;
+
+    let tick_plugin = Rc::clone(&plugin);
+
+    TimerHandle::start(TICK_INTERVAL, TICK_INTERVAL, move |_| {
+        let plugin = Rc::clone(&tick_plugin);
+        schedule(move |_| {
+            let mut plugin = plugin.borrow_mut();
+            plugin.pinned.expire()?;
+            plugin.annotations.expire()?;
+            ads::tick(&mut plugin)?;
+            votes::tick(&mut plugin)?;
+            trythis::tick(&mut plugin)?;
+            stats::tick(&mut plugin)?;
+            flood::tick(&mut plugin)?;
+            schedule::tick(&mut plugin)?;
+            hooks::report(&mut plugin);
+            raid::tick(&mut plugin)
+        });
+    })?;
+
+    let errors_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchErrors",
+        move |_| errors_plugin.borrow_mut().errors.show(),
+        &CreateCommandOpts::builder()
+            .desc("Show all errors collected by the plugin")
+            .build(),
+    )?;
+
+    let login_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchLogin",
+        move |args: CommandArgs| {
+            let paths = login_plugin.borrow().paths.clone();
+            let nick = args.fargs.first().map_or("", String::as_str);
+            let account = args.fargs.get(1).map_or(auth::MAIN, String::as_str);
+            login(&paths, nick, account)
+        },
+        &CreateCommandOpts::builder()
+            .desc("Store the Twitch nick and OAuth token of an account")
+            .nargs(CommandNArgs::OneOrMore)
+            .build(),
+    )?;
+
+    let logout_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchLogout",
+        move |args: CommandArgs| {
+            let account = args.args.filter(|account| !account.is_empty());
+            logout(
+                &logout_plugin.borrow().paths,
+                account.as_deref().unwrap_or(auth::MAIN),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Wipe the stored Twitch credentials of an account")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .build(),
+    )?;
+
+    api::create_user_command(
+        "TwitchLog",
+        |args: CommandArgs| logging::run(args.args.as_deref()),
+        &CreateCommandOpts::builder()
+            .desc("Open the plugin log, or switch to another level")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                |(lead, _, _): (String, String, usize)| {
+                    logging::Level::NAMES
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let toggle_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchChatToggle",
+        move |_| toggle_plugin.borrow_mut().chat.toggle(),
+        &CreateCommandOpts::builder()
+            .desc("Show or hide the chat window or sidebar")
+            .build(),
+    )?;
+
+    let focus_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchChatFocus",
+        move |_| focus_plugin.borrow_mut().chat.toggle_focus(),
+        &CreateCommandOpts::builder()
+            .desc("Jump into the chat window, or back out of it")
+            .build(),
+    )?;
+
+    api::create_autocmd(
+        ["CursorMoved", "CursorMovedI", "TextChangedI", "InsertEnter"],
+        &CreateAutocmdOpts::builder()
+            .desc("Keep the nvim-twitch chat window from taking the focus while typing")
+            .callback(|_| {
+                ui::record_activity();
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build(),
+    )?;
+
+    let save_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchStateSave",
+        move |_| save_plugin.borrow_mut().save_state_now(),
+        &CreateCommandOpts::builder()
+            .desc("Write the plugin state to disk now rather than on exit")
+            .build(),
+    )?;
+
+    let reset_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchStateReset",
+        move |_| reset_plugin.borrow_mut().reset_state(),
+        &CreateCommandOpts::builder()
+            .desc("Forget all state the plugin has stored")
+            .build(),
+    )?;
+
+    for (events, lowered) in [
+        (["CompleteChanged", "CmdlineEnter"], true),
+        (["CompleteDone", "CmdlineLeave"], false),
+    ] {
+        let plugin = Rc::clone(&plugin);
+
+        api::create_autocmd(
+            events,
+            &CreateAutocmdOpts::builder()
+                .desc("Lower the nvim-twitch floats while other popups are open")
+                .callback(move |_| {
+                    let mut plugin = plugin.borrow_mut();
+
+                    if let Err(e) = plugin.set_floats_lowered(lowered) {
+                        plugin.errors.push("Window", &e);
+                    }
+
+                    Ok::<_, nvim_oxi::Error>(false)
+                })
+                .build(),
+        )?;
+    }
+
+    for (event, patterns) in [
+        ("VimResized", &["*"][..]),
+        ("OptionSet", &["cmdheight", "laststatus"]),
+    ] {
+        let plugin = Rc::clone(&plugin);
+
+        api::create_autocmd(
+            [event],
+            &CreateAutocmdOpts::builder()
+                .desc("Fit the nvim-twitch floats to the editor")
+                .patterns(patterns.iter().copied())
+                .callback(move |_| {
+                    let mut plugin = plugin.borrow_mut();
+
+                    if let Err(e) = plugin.resize_windows() {
+                        plugin.errors.push("Window", &e);
+                    }
+
+                    Ok::<_, nvim_oxi::Error>(false)
+                })
+                .build(),
+        )?;
+    }
+
+    let colorscheme_plugin = Rc::clone(&plugin);
+
+    api::create_autocmd(
+        ["ColorScheme"],
+        &CreateAutocmdOpts::builder()
+            .desc("Restore the nvim-twitch highlight groups after :colorscheme")
+            .callback(move |_| {
+                let mut plugin = colorscheme_plugin.borrow_mut();
+
+                let result = ui::define_highlights()
+                    .and_then(|()| ui::override_highlights(&plugin.config.highlights))
+                    .and_then(|()| plugin.chat.colors_changed());
+
+                if let Err(e) = result {
+                    plugin.errors.push("Window", &e);
+                }
+
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build(),
+    )?;
+
+    let tab_plugin = Rc::clone(&plugin);
+
+    api::create_autocmd(
+        ["TabEnter", "TabClosed"],
+        &CreateAutocmdOpts::builder()
+            .desc("Move or clean up the nvim-twitch floats when tabpages change")
+            .callback(move |args: AutocmdCallbackArgs| {
+                let mut plugin = tab_plugin.borrow_mut();
+
+                let result = match args.event.as_str() {
+                    "TabClosed" => {
+                        plugin.chat.tab_closed();
+                        Ok(())
+                    }
+                    _ => plugin.tab_entered(),
+                };
+
+                if let Err(e) = result {
+                    plugin.errors.push("Window", &e);
+                }
+
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build(),
+    )?;
+
+    let flush_plugin = Rc::clone(&plugin);
+
+    api::create_autocmd(
+        ["VimLeavePre"],
+        &CreateAutocmdOpts::builder()
+            .desc("Write the nvim-twitch state to disk")
+            .callback(move |_| {
+                let mut plugin = flush_plugin.borrow_mut();
+
+                if let Err(e) = plugin.save_state() {
+                    plugin.errors.push("State", &e);
+                }
+
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build(),
+    )?;
+
+    let mod_view_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchModView",
+        move |_| moderation::show(&mod_view_plugin.borrow()),
+        &CreateCommandOpts::builder()
+            .desc("List recent messages with their moderation scores")
+            .build(),
+    )?;
+
+    let history_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchHistory",
+        move |args: CommandArgs| search::show(&history_plugin, args.args.as_deref().unwrap_or("")),
+        &CreateCommandOpts::builder()
+            .desc("Search the chat of this session")
+            .nargs(CommandNArgs::Any)
+            .build(),
+    )?;
+
+    let thread_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchThread",
+        move |_| threads::show(&thread_plugin.borrow()),
+        &CreateCommandOpts::builder()
+            .desc("Show the reply chain of the message in the chat window")
+            .build(),
+    )?;
+
+    let gifts_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchGifts",
+        move |_| gifts_plugin.borrow().gifts.show(),
+        &CreateCommandOpts::builder()
+            .desc("List the gifted subs of this session with their recipients")
+            .build(),
+    )?;
+
+    let raffle_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchRaffle",
+        move |args: CommandArgs| raffle::run(&mut raffle_plugin.borrow_mut(), &args.fargs),
+        &CreateCommandOpts::builder()
+            .desc("Start a raffle for a chat keyword, draw its winner or cancel it")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                |(lead, line, _): (String, String, usize)| {
+                    // Only the subcommand is completed, the keyword is free.
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    raffle::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let ad_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchAd",
+        move |args: CommandArgs| ads::run(&mut ad_plugin.borrow_mut(), &args.fargs),
+        &CreateCommandOpts::builder()
+            .desc("Snooze the next ad break or run one of the given length in seconds")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    ads::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let access_plugin = Rc::clone(&plugin);
+    let access_complete_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchAccess",
+        move |args: CommandArgs| access::run(&mut access_plugin.borrow_mut(), &args.fargs),
+        &CreateCommandOpts::builder()
+            .desc("Allow a viewer every chat command, block them from all, or list both")
+            .nargs(CommandNArgs::Any)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+
+                    match arg {
+                        2 => access::SUBCOMMANDS
+                            .iter()
+                            .filter(|name| name.starts_with(&lead))
+                            .map(|name| name.to_string())
+                            .collect::<Vec<_>>(),
+                        3 => access_complete_plugin
+                            .borrow()
+                            .history
+                            .chatters()
+                            .into_iter()
+                            .filter(|name| name.to_lowercase().starts_with(&lead.to_lowercase()))
+                            .map(str::to_owned)
+                            .collect(),
+                        _ => Vec::new(),
+                    }
+                },
+            )))
+            .build(),
+    )?;
+
+    for (name, mute, desc) in [
+        (
+            "TwitchMute",
+            true,
+            "Hide a viewer's chat commands, or list the muted",
+        ),
+        (
+            "TwitchUnmute",
+            false,
+            "Let a muted viewer's chat commands through again",
+        ),
+    ] {
+        let mute_plugin = Rc::clone(&plugin);
+        let complete_plugin = Rc::clone(&plugin);
+
+        api::create_user_command(
+            name,
+            move |args: CommandArgs| filter::run(&mut mute_plugin.borrow_mut(), &args.fargs, mute),
+            &CreateCommandOpts::builder()
+                .desc(desc)
+                .nargs(CommandNArgs::Any)
+                .complete(CommandComplete::CustomList(Function::from_fn(
+                    move |(lead, _, _): (String, String, usize)| {
+                        complete_plugin
+                            .borrow()
+                            .history
+                            .chatters()
+                            .into_iter()
+                            .filter(|name| name.to_lowercase().starts_with(&lead.to_lowercase()))
+                            .map(str::to_owned)
+                            .collect::<Vec<_>>()
+                    },
+                )))
+                .build(),
+        )?;
+    }
+
+    let raid_plugin = Rc::clone(&plugin);
+    let raid_complete_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchRaid",
+        move |args: CommandArgs| raid::run(&mut raid_plugin.borrow_mut(), &args.fargs),
+        &CreateCommandOpts::builder()
+            .desc("Raid the given channel, or cancel the raid")
+            .nargs(CommandNArgs::One)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    raid_complete_plugin.borrow().raid.candidates(&lead)
+                },
+            )))
+            .build(),
+    )?;
+
+    let slowmode_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchSlowmode",
+        move |args: CommandArgs| {
+            flood::run(&mut slowmode_plugin.borrow_mut(), args.args.as_deref())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Show one message per viewer every few seconds: on, off or <seconds>")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    flood::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let poll_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchPollCreate",
+        move |args: CommandArgs| polls::create_poll(&mut poll_plugin.borrow_mut(), args.args),
+        &CreateCommandOpts::builder()
+            .desc("Start a poll: \"title\" choice choice... [seconds]")
+            .nargs(CommandNArgs::OneOrMore)
+            .build(),
+    )?;
+
+    let vote_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchPoll",
+        move |args: CommandArgs| votes::run(&mut vote_plugin.borrow_mut(), args.args),
+        &CreateCommandOpts::builder()
+            .desc("Start a chat vote: \"title\" option option... [seconds], or end/cancel it")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    votes::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let focus_vote_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchFocusVote",
+        move |args: CommandArgs| {
+            votes::run_focus(&mut focus_vote_plugin.borrow_mut(), args.args.as_deref())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Let chat vote on the buffer to switch to: [seconds], or end/cancel it")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    votes::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let keymap_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchKeymaps",
+        move |args: CommandArgs| {
+            keymaps::run(&mut keymap_plugin.borrow_mut(), args.args.as_deref())
+        },
+        &CreateCommandOpts::builder()
+            .desc(
+                "List the mappings set from chat, accept or reject a proposal, or revert them all",
+            )
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    keymaps::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let suggest_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchSuggestions",
+        move |args: CommandArgs| {
+            suggest::run(&mut suggest_plugin.borrow_mut(), args.args.as_deref())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Accept or reject the edit suggested from chat, or show it again")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    suggest::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let prediction_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchPrediction",
+        move |args: CommandArgs| polls::prediction(&mut prediction_plugin.borrow_mut(), args.args),
+        &CreateCommandOpts::builder()
+            .desc(
+                "Start a prediction: \"title\" outcome outcome... [seconds], or resolve/cancel it",
+            )
+            .nargs(CommandNArgs::OneOrMore)
+            .build(),
+    )?;
+
+    let results_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchPollResults",
+        move |_| {
+            let mut plugin = results_plugin.borrow_mut();
+            let zindex = plugin.chat.zindex();
+            plugin.polls.toggle(zindex)
+        },
+        &CreateCommandOpts::builder()
+            .desc("Show or hide the results of the latest poll or prediction")
+            .build(),
+    )?;
+
+    let highlights_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchHighlights",
+        move |args: CommandArgs| match args.args.as_deref().map(str::trim) {
+            Some("groups") => highlights::show_groups(),
+            _ => highlights_plugin.borrow().highlights.show(),
+        },
+        &CreateCommandOpts::builder()
+            .desc(
+                "List the highlight groups chat changed with !hl, or with groups the plugin's own",
+            )
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                |(lead, _, _): (String, String, usize)| {
+                    ["groups"]
+                        .into_iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let undo_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchHighlightUndo",
+        move |_| undo_plugin.borrow_mut().highlights.undo(),
+        &CreateCommandOpts::builder()
+            .desc("Revert the most recent !hl change")
+            .build(),
+    )?;
+
+    let audit_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchAudit",
+        move |_| audit::show(&audit_plugin.borrow()),
+        &CreateCommandOpts::builder()
+            .desc("List the changes chat made to the editor")
+            .build(),
+    )?;
+
+    let approvals_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchApprovals",
+        move |args: CommandArgs| {
+            approvals::run(
+                &mut approvals_plugin.borrow_mut(),
+                args.args.as_deref(),
+                (args.line1, args.line2),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Approve or reject the chat commands waiting for approval")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .range(CommandRange::CurrentLine)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    approvals::SUBCOMMANDS
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let undo_chat_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchUndo",
+        move |_| audit::undo(&mut undo_chat_plugin.borrow_mut()),
+        &CreateCommandOpts::builder()
+            .desc("Revert the latest change chat made to the editor")
+            .build(),
+    )?;
+
+    let stats_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchStats",
+        move |_| stats::show(&mut stats_plugin.borrow_mut()),
+        &CreateCommandOpts::builder()
+            .desc("Show a dashboard of this session's chat, commands and errors")
+            .build(),
+    )?;
+
+    let help_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchCommands",
+        move |_| help::show(&help_plugin.borrow()),
+        &CreateCommandOpts::builder()
+            .desc("List the chat commands with their usage, permissions and cooldowns")
+            .build(),
+    )?;
+
+    let export_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchStatsExport",
+        move |args: CommandArgs| {
+            export_stats(&export_plugin.borrow(), &args.args.unwrap_or_default())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Write the session statistics to a CSV file")
+            .nargs(CommandNArgs::One)
+            .complete(CommandComplete::File)
+            .build(),
+    )?;
+
+    let transcript_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchExport",
+        move |args: CommandArgs| {
+            transcript::run(&mut transcript_plugin.borrow_mut(), &args.fargs)
+        },
+        &CreateCommandOpts::builder()
+            .desc("Write this session's chat to a file as it comes in: <path> [--format md|json|jsonl], or stop")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    let names: Vec<String> = match arg {
+                        2 => {
+                            let mut names: Vec<String> =
+                                api::call_function("getcompletion", (lead.as_str(), "file"))
+                                    .unwrap_or_default();
+                            names.extend(transcript::SUBCOMMANDS.map(str::to_owned));
+                            names
+                        }
+                        3 => vec![String::from("--format")],
+                        4 => transcript::FORMATS.map(str::to_owned).to_vec(),
+                        _ => Vec::new(),
+                    };
+
+                    names
+                        .into_iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let import_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchImportCommands",
+        move |args: CommandArgs| {
+            import_commands(
+                &mut import_plugin.borrow_mut(),
+                &args.args.unwrap_or_default(),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Import text commands from a Nightbot or StreamElements export")
+            .nargs(CommandNArgs::One)
+            .complete(CommandComplete::File)
+            .build(),
+    )?;
+
+    let send_plugin = Rc::clone(&plugin);
+    let mention_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchSend",
+        move |args: CommandArgs| {
+            send(
+                &send_plugin.borrow(),
+                auth::MAIN,
+                args.args.unwrap_or_default(),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Send a message to the channel")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, _, _): (String, String, usize)| {
+                    complete_mention(&mention_plugin.borrow(), &lead)
+                },
+            )))
+            .build(),
+    )?;
+
+    let send_as_plugin = Rc::clone(&plugin);
+    let account_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchSendAs",
+        move |args: CommandArgs| {
+            let args = args.args.unwrap_or_default();
+            let (account, text) = args.split_once(' ').unwrap_or((&args, ""));
+            send(
+                &send_as_plugin.borrow(),
+                account,
+                text.trim_start().to_owned(),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Send a message to the channel as one of the configured accounts")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, cursor): (String, String, usize)| {
+                    let plugin = account_plugin.borrow();
+                    let before = line.get(..cursor).unwrap_or(&line);
+                    let words = before.split_whitespace().count();
+
+                    // The account is the first argument, after the command.
+                    if words == 1 || (words == 2 && !before.ends_with(' ')) {
+                        plugin
+                            .config
+                            .accounts
+                            .keys()
+                            .filter(|account| account.starts_with(&lead))
+                            .cloned()
+                            .collect()
+                    } else {
+                        complete_mention(&plugin, &lead)
+                    }
+                },
+            )))
+            .build(),
+    )?;
+
+    let render_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchRender",
+        move |args: CommandArgs| set_render_mode(&mut render_plugin.borrow_mut(), args.args),
+        &CreateCommandOpts::builder()
+            .desc("Switch between batched and immediate rendering, or toggle")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                |(lead, _, _): (String, String, usize)| {
+                    RenderMode::NAMES
+                        .iter()
+                        .filter(|name| name.starts_with(&lead))
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let marker_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchMarker",
+        move |args: CommandArgs| create_marker(&marker_plugin.borrow(), args.args),
+        &CreateCommandOpts::builder()
+            .desc("Mark this moment of the stream for the VOD, with an optional description")
+            .nargs(CommandNArgs::Any)
+            .build(),
+    )?;
+
+    let ignore_plugin = Rc::clone(&plugin);
+    let ignore_complete_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchIgnore",
+        move |args: CommandArgs| ignore::run(&mut ignore_plugin.borrow_mut(), &args.fargs),
+        &CreateCommandOpts::builder()
+            .desc("Ignore a viewer or bot, let one through again, or list who is ignored")
+            .nargs(CommandNArgs::Any)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    let names: Vec<String> = match arg {
+                        2 => ignore::SUBCOMMANDS
+                            .iter()
+                            .map(|name| name.to_string())
+                            .collect(),
+                        3 => ignore_complete_plugin
+                            .borrow()
+                            .history
+                            .chatters()
+                            .into_iter()
+                            .map(str::to_owned)
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+
+                    let lead = lead.to_lowercase();
+                    names
+                        .into_iter()
+                        .filter(|name| name.to_lowercase().starts_with(&lead))
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let whisper_plugin = Rc::clone(&plugin);
+    let whisper_complete_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchWhisper",
+        move |args: CommandArgs| whispers::run(&whisper_plugin.borrow(), args.args.as_deref()),
+        &CreateCommandOpts::builder()
+            .desc("Whisper to a viewer as the main account")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    // Only the viewer is completed, from who was in chat.
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    let lead = lead.to_lowercase();
+                    whisper_complete_plugin
+                        .borrow()
+                        .history
+                        .chatters()
+                        .into_iter()
+                        .filter(|name| name.to_lowercase().starts_with(&lead))
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                },
+            )))
+            .build(),
+    )?;
+
+    let heatmap_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchHeatmap",
+        move |_| heatmap_plugin.borrow().stats.show_heatmap(),
+        &CreateCommandOpts::builder()
+            .desc("Show chat activity per minute over the session")
+            .build(),
+    )?;
+
+    let summarize_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchSummarize",
+        move |args: CommandArgs| {
+            let minutes = args.args.and_then(|minutes| minutes.parse().ok());
+            summarize::run(&summarize_plugin.borrow(), minutes)
+        },
+        &CreateCommandOpts::builder()
+            .desc("Summarize the recent chat with an LLM")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .build(),
+    )?;
+
+    let bench_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchBench",
+        move |args: CommandArgs| {
+            let count = args.args.and_then(|count| count.parse().ok());
+            bench::run(&mut bench_plugin.borrow_mut(), count)
+        },
+        &CreateCommandOpts::builder()
+            .desc("Measure rendering throughput with a synthetic message burst")
+            .nargs(CommandNArgs::ZeroOrOne)
+            .build(),
+    )?;
+
+    let dispatch_plugin = Rc::clone(&plugin);
+    let queue = Arc::clone(&plugin.borrow().queue);
+    let dispatch_queue = Arc::clone(&queue);
+
+    // Left in the queue over `batch_window`, where it stays bounded and in
+    // priority order, then handled with one `schedule()` and one update of
+    // the chat buffer.
+    let flushing = Rc::new(Cell::new(false));
+
+    let handle = AsyncHandle::new(move || {
+        if flushing.replace(true) {
+            return Ok::<_, nvim_oxi::Error>(());
+        }
+
+        let (plugin_ref, queue, flushing) = (
+            Rc::clone(&dispatch_plugin),
+            Arc::clone(&dispatch_queue),
+            Rc::clone(&flushing),
+        );
+        let flush = move || {
+            schedule(move |_| {
+                // Whatever is sent from now on wakes up another flush.
+                flushing.set(false);
+                commands::dispatch_batch(&mut plugin_ref.borrow_mut(), queue.drain())?;
+                animate(&plugin_ref)
+            });
+        };
+
+        // Immediate rendering doesn't wait for the batch timer.
+        let window = match dispatch_plugin.try_borrow() {
+            Ok(plugin) if plugin.render_mode == RenderMode::Batched => plugin.config.batch_window,
+            _ => Duration::ZERO,
+        };
+        match window.is_zero() {
+            true => flush(),
+            false => {
+                TimerHandle::once(window, flush)?;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    let sender = CommandSender::new(handle, queue);
+
+    let connector = Rc::new(RefCell::new(Connector {
+        sender: sender.clone(),
+        outgoing: Some(outgoing_receiver),
+        shutdown: None,
+        thread: None,
+        started: false,
+    }));
+
+    let leave_connector = Rc::clone(&connector);
+
+    api::create_autocmd(
+        ["VimLeavePre"],
+        &CreateAutocmdOpts::builder()
+            .desc("Close the nvim-twitch connection")
+            .callback(move |_| {
+                leave_connector.borrow_mut().shut_down(SHUTDOWN_TIMEOUT);
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build(),
+    )?;
+
+    for (name, action, desc) in [
+        ("TwitchConnect", "connect", "Connect to Twitch chat again"),
+        (
+            "TwitchDisconnect",
+            "disconnect",
+            "Leave Twitch chat until :TwitchConnect",
+        ),
+        ("TwitchRestart", "restart", "Reconnect to Twitch chat"),
+    ] {
+        let plugin = Rc::clone(&plugin);
+        let connector = Rc::clone(&connector);
+
+        api::create_user_command(
+            name,
+            move |_| {
+                connection(
+                    &mut plugin.borrow_mut(),
+                    &mut connector.borrow_mut(),
+                    action,
+                )
+            },
+            &CreateCommandOpts::builder().desc(desc).build(),
+        )?;
+    }
+
+    let reload_plugin = Rc::clone(&plugin);
+    let reload_connector = Rc::clone(&connector);
+
+    api::create_user_command(
+        "TwitchReloadConfig",
+        move |_| {
+            let mut plugin = reload_plugin.borrow_mut();
+            let diagnostics = reload(&mut plugin, &mut reload_connector.borrow_mut(), None);
+
+            if diagnostics.is_empty() {
+                api::notify(
+                    &tr("config.reloaded"),
+                    LogLevel::Info,
+                    &NotifyOpts::default(),
+                )?;
+            }
+            config::report(&mut plugin.errors, &diagnostics);
+
+            Ok::<_, nvim_oxi::Error>(())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Reload the setup() options and the config files")
+            .build(),
+    )?;
+
+    let simulate_plugin = Rc::clone(&plugin);
+    let simulate_sender = sender.clone();
+
+    api::create_user_command(
+        "TwitchSimulate",
+        move |args: CommandArgs| {
+            simulate::run(
+                &mut simulate_plugin.borrow_mut(),
+                &simulate_sender,
+                args.args.as_deref(),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Play a chat script as if it came from Twitch: <file> [speed], or stop")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    let mut names: Vec<String> =
+                        api::call_function("getcompletion", (lead.as_str(), "file"))
+                            .unwrap_or_default();
+                    names.extend(
+                        simulate::SUBCOMMANDS
+                            .iter()
+                            .filter(|name| name.starts_with(&lead))
+                            .map(|name| name.to_string()),
+                    );
+                    names
+                },
+            )))
+            .build(),
+    )?;
+
+    let record_plugin = Rc::clone(&plugin);
+
+    api::create_user_command(
+        "TwitchRecord",
+        move |args: CommandArgs| {
+            replay::record(&mut record_plugin.borrow_mut(), args.args.as_deref())
+        },
+        &CreateCommandOpts::builder()
+            .desc("Record what chat commands do to the editor: <path>, or stop")
+            .nargs(CommandNArgs::One)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    let mut names: Vec<String> =
+                        api::call_function("getcompletion", (lead.as_str(), "file"))
+                            .unwrap_or_default();
+                    names.extend(
+                        replay::SUBCOMMANDS
+                            .iter()
+                            .filter(|name| name.starts_with(&lead))
+                            .map(|name| name.to_string()),
+                    );
+                    names
+                },
+            )))
+            .build(),
+    )?;
+
+    let replay_plugin = Rc::clone(&plugin);
+    let replay_sender = sender.clone();
+
+    api::create_user_command(
+        "TwitchReplay",
+        move |args: CommandArgs| {
+            replay::run(
+                &mut replay_plugin.borrow_mut(),
+                &replay_sender,
+                args.args.as_deref(),
+            )
+        },
+        &CreateCommandOpts::builder()
+            .desc("Run the chat commands of a :TwitchRecord again: <file> [speed], or stop")
+            .nargs(CommandNArgs::OneOrMore)
+            .complete(CommandComplete::CustomList(Function::from_fn(
+                move |(lead, line, _): (String, String, usize)| {
+                    let arg = line.split_whitespace().count() + usize::from(lead.is_empty());
+                    if arg != 2 {
+                        return Vec::new();
+                    }
+
+                    let mut names: Vec<String> =
+                        api::call_function("getcompletion", (lead.as_str(), "file"))
+                            .unwrap_or_default();
+                    names.extend(
+                        replay::SUBCOMMANDS
+                            .iter()
+                            .filter(|name| name.starts_with(&lead))
+                            .map(|name| name.to_string()),
+                    );
+                    names
+                },
+            )))
+            .build(),
+    )?;
+
+    rpc::register(&plugin, sender, connector)
+}
@@ -0,0 +1,81 @@
+//! A minimal client for OpenAI-compatible chat completion endpoints, used
+//! from the connection thread.
+
+use std::{env, time::Duration};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::LlmOptions;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct Response {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: String,
+}
+
+/// An endpoint with the client for it, built once and reused for every
+/// request. It belongs to the connection's runtime, which its pooled
+/// connections are tied to.
+#[derive(Clone)]
+pub struct Llm {
+    client: reqwest::Client,
+    options: LlmOptions,
+}
+
+impl Llm {
+    pub fn new(options: LlmOptions) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Llm { client, options })
+    }
+
+    /// Sends `input` with the `system` instructions and returns the reply.
+    pub async fn complete(&self, system: &str, input: &str) -> Result<String, String> {
+        let body = json!({
+            "model": self.options.model,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": input },
+            ],
+        });
+
+        let mut request = self.client.post(&self.options.endpoint).json(&body);
+
+        // Local servers usually don't need a key, so a missing variable is
+        // only an error if the endpoint rejects the request.
+        if let Ok(key) = env::var(&self.options.api_key_env) {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json::<Response>()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or_else(|| "the response contained no choices".to_owned())
+    }
+}
@@ -0,0 +1,240 @@
+//! `!where`: tells chat which file the streamer is in, at which line and on
+//! which git branch, without changing anything in the editor.
+//!
+//! The branch comes from gitsigns or fugitive when either is installed.
+//! Otherwise git is asked in the background and the answer waits for it,
+//! so a slow repository never holds up the editor.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use nvim_oxi::{
+    api::{self, opts::*},
+    Array, Dictionary, Function, Object, Result,
+};
+
+use crate::{
+    i18n::tr_with,
+    twitch::{Outgoing, OutgoingSender},
+    Plugin,
+};
+
+/// How long a branch git named is taken as still checked out.
+const BRANCH_TTL: Duration = Duration::from_secs(30);
+
+/// The branches git named, by directory, shared with the lookups still
+/// running.
+#[derive(Default, Clone)]
+pub struct Branches(Rc<RefCell<HashMap<PathBuf, Looked>>>);
+
+/// When git was asked, and the branch it named.
+type Looked = (Instant, Option<String>);
+
+impl Branches {
+    /// What git named for `dir` within the last [`BRANCH_TTL`], `None` if
+    /// it has to be asked again.
+    fn get(&self, dir: &Path) -> Option<Option<String>> {
+        self.0
+            .borrow()
+            .get(dir)
+            .filter(|(at, _)| at.elapsed() < BRANCH_TTL)
+            .map(|(_, branch)| branch.clone())
+    }
+
+    fn insert(&self, dir: PathBuf, branch: Option<String>) {
+        let mut branches = self.0.borrow_mut();
+        branches.retain(|_, (at, _)| at.elapsed() < BRANCH_TTL);
+        branches.insert(dir, (Instant::now(), branch));
+    }
+}
+
+/// Answers `author`'s `!where` in `channel`, right away unless git has to
+/// be asked for the branch first.
+pub fn reply(plugin: &Plugin, channel: String, author: &str) -> Result<()> {
+    let Some(location) = describe()? else {
+        let text = tr_with("where.no_file", &[("user", &author)]);
+        let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+        return Ok(());
+    };
+
+    let answer = Answer {
+        outgoing: plugin.outgoing.clone(),
+        channel,
+        author: author.to_owned(),
+        location,
+    };
+
+    if let Some(branch) = editor_branch()? {
+        answer.send(branch);
+        return Ok(());
+    }
+
+    let Some(dir) = answer.location.dir.clone() else {
+        answer.send(None);
+        return Ok(());
+    };
+
+    match plugin.branches.get(&dir) {
+        Some(branch) => answer.send(branch),
+        None => ask_git(plugin.branches.clone(), dir, answer),
+    }
+
+    Ok(())
+}
+
+/// A `!where` waiting for its branch.
+struct Answer {
+    outgoing: OutgoingSender,
+    channel: String,
+    author: String,
+    location: Location,
+}
+
+impl Answer {
+    fn send(self, branch: Option<String>) {
+        let location = self.location;
+        let key = match branch {
+            Some(_) => "where.with_branch",
+            None => "where.file",
+        };
+        let text = tr_with(
+            key,
+            &[
+                ("user", &self.author),
+                ("file", &location.file),
+                ("line", &location.line),
+                ("column", &location.column),
+                ("filetype", &location.filetype),
+                ("branch", &branch.unwrap_or_default()),
+            ],
+        );
+
+        let _ = self.outgoing.send(Outgoing::Reply {
+            channel: self.channel,
+            text,
+        });
+    }
+}
+
+struct Location {
+    /// Relative to the working directory, or only the name of a file
+    /// outside it, so no home directory ends up in chat.
+    file: String,
+    line: usize,
+    /// 1-based, in bytes like the `col()` Neovim shows.
+    column: usize,
+    /// `text` for a buffer without one.
+    filetype: String,
+    /// The directory the file is in.
+    dir: Option<PathBuf>,
+}
+
+/// Where the cursor is, unless the current buffer isn't a file.
+fn describe() -> Result<Option<Location>> {
+    let buffer = api::get_current_buf();
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    let buftype: String = api::get_option_value("buftype", &opts)?;
+    let name = buffer.get_name()?;
+
+    if !buftype.is_empty() || name.as_os_str().is_empty() {
+        return Ok(None);
+    }
+
+    let file = shown_name(&name)?;
+    let (line, column) = api::get_current_win().get_cursor()?;
+    let filetype: String = api::get_option_value("filetype", &opts)?;
+    let filetype = match filetype.is_empty() {
+        true => String::from("text"),
+        false => filetype,
+    };
+
+    Ok(Some(Location {
+        file,
+        line,
+        column: column + 1,
+        filetype,
+        dir: name.parent().map(Path::to_path_buf),
+    }))
+}
+
+/// The buffer name `name` relative to the working directory, or only the
+/// file's name outside it.
+pub fn shown_name(name: &Path) -> Result<String> {
+    let relative: String = api::call_function("fnamemodify", (name.to_string_lossy(), ":."))?;
+
+    Ok(
+        match Path::new(&relative).is_absolute() || relative.starts_with("..") {
+            true => name
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or(relative),
+            false => relative,
+        },
+    )
+}
+
+/// The branch gitsigns or fugitive know the current buffer is on, `None`
+/// if neither is there to ask.
+fn editor_branch() -> Result<Option<Option<String>>> {
+    let head = match api::get_current_buf().get_var::<String>("gitsigns_head") {
+        Ok(head) => head,
+        Err(_) => match api::call_function::<_, bool>("exists", ("*FugitiveHead",))? {
+            true => api::call_function("FugitiveHead", Array::new())?,
+            false => return Ok(None),
+        },
+    };
+
+    Ok(Some(checked_out(&head)))
+}
+
+/// Asks git in the background for the branch checked out in the repository
+/// `dir` is in, then sends `answer`. Outside a repository or on a detached
+/// `HEAD` it goes without one.
+fn ask_git(branches: Branches, dir: PathBuf, answer: Answer) {
+    let answer = Rc::new(RefCell::new(Some(answer)));
+
+    let (looked_up, cache, waiting) = (dir.clone(), branches.clone(), Rc::clone(&answer));
+    let on_stdout: Function<(i64, Vec<String>, String), ()> =
+        Function::from_fn_once(move |(_, lines, _): (i64, Vec<String>, String)| {
+            let branch = lines.first().and_then(|line| checked_out(line));
+            cache.insert(looked_up, branch.clone());
+            if let Some(answer) = waiting.take() {
+                answer.send(branch);
+            }
+            Ok::<_, nvim_oxi::Error>(())
+        });
+
+    let command = Array::from_iter([
+        "git".to_owned(),
+        "-C".to_owned(),
+        dir.to_string_lossy().into_owned(),
+        "symbolic-ref".to_owned(),
+        "--short".to_owned(),
+        "-q".to_owned(),
+        "HEAD".to_owned(),
+    ]);
+    let opts = Dictionary::from_iter([
+        ("stdout_buffered", Object::from(true)),
+        ("on_stdout", Object::from(on_stdout)),
+    ]);
+
+    // Without git there is no branch to show, and no point asking again
+    // right away.
+    let started = api::call_function::<_, i64>("jobstart", (command, opts));
+    if !matches!(started, Ok(job) if job > 0) {
+        branches.insert(dir, None);
+        if let Some(answer) = answer.take() {
+            answer.send(None);
+        }
+    }
+}
+
+fn checked_out(head: &str) -> Option<String> {
+    let head = head.trim();
+    (!head.is_empty() && head != "HEAD").then(|| head.to_owned())
+}
@@ -0,0 +1,228 @@
+//! The plugin's own log, `nvim-twitch.log` in the log directory, viewable
+//! with `:TwitchLog`. Anything printed to stdout would end up in the middle
+//! of the TUI, so this is where both threads write what they are doing.
+//!
+//! Once the file grows past [`MAX_SIZE`] it is moved to `nvim-twitch.log.1`
+//! and so on, keeping [`KEEP`] old files.
+
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::Local;
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::i18n::{tr, tr_with};
+
+const FILE_NAME: &str = "nvim-twitch.log";
+
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Old files kept next to the current one.
+const KEEP: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub const NAMES: [&'static str; 4] = ["error", "warn", "info", "debug"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Level::NAMES[self as usize]
+    }
+
+    fn from_index(index: u8) -> Self {
+        match index {
+            0 => Level::Error,
+            1 => Level::Warn,
+            2 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+struct Logger {
+    /// Empty until [`open`] says where the log goes.
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    path: PathBuf::new(),
+    file: None,
+    size: 0,
+});
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Writes the log to `dir` from now on. Must be called on the main thread
+/// first, the connection thread only writes.
+pub fn open(dir: &Path) {
+    let mut logger = LOGGER.lock().unwrap();
+    let path = dir.join(FILE_NAME);
+
+    if logger.path != path {
+        logger.path = path;
+        logger.file = None;
+    }
+}
+
+pub fn level() -> Level {
+    Level::from_index(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn error(context: &str, message: impl Display) {
+    write(Level::Error, context, message);
+}
+
+pub fn warn(context: &str, message: impl Display) {
+    write(Level::Warn, context, message);
+}
+
+pub fn info(context: &str, message: impl Display) {
+    write(Level::Info, context, message);
+}
+
+pub fn debug(context: &str, message: impl Display) {
+    write(Level::Debug, context, message);
+}
+
+/// Appends a line to the log if `level` is enabled. Failing to write is
+/// ignored, the log is what failures would be reported to.
+pub fn write(level: Level, context: &str, message: impl Display) {
+    if level > self::level() {
+        return;
+    }
+
+    let mut logger = LOGGER.lock().unwrap();
+    if logger.path.as_os_str().is_empty() {
+        return;
+    }
+
+    let line = format!(
+        "{} {:<5} [{}] {}\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        level.name().to_uppercase(),
+        context,
+        message
+    );
+
+    if logger.size + line.len() as u64 > MAX_SIZE {
+        logger.rotate();
+    }
+
+    if logger.file.is_none() {
+        logger.reopen();
+    }
+    if let Some(file) = &mut logger.file {
+        if file.write_all(line.as_bytes()).is_ok() {
+            logger.size += line.len() as u64;
+        }
+    }
+}
+
+impl Logger {
+    fn reopen(&mut self) {
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .ok();
+        self.size = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+    }
+
+    fn rotate(&mut self) {
+        self.file = None;
+
+        let numbered = |n: usize| self.path.with_extension(format!("log.{}", n));
+        for n in (1..KEEP).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        let _ = fs::rename(&self.path, numbered(1));
+    }
+}
+
+/// Runs `:TwitchLog [level]`, opening the log in a scratch buffer or
+/// switching to another level.
+pub fn run(args: Option<&str>) -> Result<()> {
+    let (message, level) = match args.map(str::trim).unwrap_or_default() {
+        "" => return show(),
+        name => match Level::parse(name) {
+            Some(level) => {
+                set_level(level);
+                (tr_with("log.level", &[("level", &name)]), LogLevel::Info)
+            }
+            None => (tr("log.usage"), LogLevel::Error),
+        },
+    };
+
+    api::notify(&message, level, &NotifyOpts::default())?;
+    Ok(())
+}
+
+fn show() -> Result<()> {
+    let path = LOGGER.lock().unwrap().path.clone();
+    let data = fs::read_to_string(&path).unwrap_or_default();
+
+    let mut buffer = api::create_buf(false, true)?;
+    buffer.set_lines(.., false, data.lines())?;
+
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    api::set_option_value("modifiable", false, &opts)?;
+    api::set_option_value("filetype", "twitchlog", &opts)?;
+
+    api::command("split")?;
+    api::get_current_win().set_buf(&buffer)?;
+    api::command("normal! G")?;
+
+    // Syntax belongs to the current buffer, so it is set once it is shown.
+    for (level, group) in [
+        ("ERROR", "TwitchLogError"),
+        ("WARN", "TwitchLogWarn"),
+        ("INFO", "TwitchLogInfo"),
+        ("DEBUG", "TwitchLogDebug"),
+    ] {
+        api::command(&format!(
+            r"syntax match {} /\v^\S+ \S+ \zs{}>/",
+            group, level
+        ))?;
+    }
+    api::command(r"syntax match TwitchTimestamp /\v^\S+ \S+/")?;
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! Counters exported in the Prometheus text format on `/metrics`.
+//!
+//! They are bumped from both the Neovim and the connection thread, so they
+//! live in a static rather than in `Plugin`.
+
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Counter {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+pub static MESSAGES_RECEIVED: Counter = Counter::new(
+    "nvim_twitch_messages_received_total",
+    "Chat messages received from Twitch.",
+);
+pub static MESSAGES_RENDERED: Counter = Counter::new(
+    "nvim_twitch_messages_rendered_total",
+    "Messages shown in the chat window.",
+);
+pub static MESSAGES_DROPPED: Counter = Counter::new(
+    "nvim_twitch_messages_dropped_total",
+    "Messages not shown because the command is disabled, the message was hidden or the display queue was full.",
+);
+pub static RECONNECTS: Counter = Counter::new(
+    "nvim_twitch_reconnects_total",
+    "Times the connection was restarted.",
+);
+pub static ERRORS: Counter = Counter::new("nvim_twitch_errors_total", "Errors reported.");
+
+const COUNTERS: [&Counter; 5] = [
+    &MESSAGES_RECEIVED,
+    &MESSAGES_RENDERED,
+    &MESSAGES_DROPPED,
+    &RECONNECTS,
+    &ERRORS,
+];
+
+fn render() -> String {
+    let mut text = String::new();
+
+    for counter in COUNTERS {
+        let _ = writeln!(text, "# HELP {} {}", counter.name, counter.help);
+        let _ = writeln!(text, "# TYPE {} counter", counter.name);
+        let _ = writeln!(
+            text,
+            "{} {}",
+            counter.name,
+            counter.value.load(Ordering::Relaxed)
+        );
+    }
+
+    text
+}
+
+/// Serves `/metrics` until the runtime shuts down. This is only meant for a
+/// Prometheus scraper, so anything beyond the request line is ignored.
+pub async fn serve(address: SocketAddr) -> Result<(), String> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("could not listen on {}: {}", address, e))?;
+
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        tokio::spawn(respond(stream));
+    }
+}
+
+async fn respond(mut stream: TcpStream) {
+    let mut request = [0; 1024];
+
+    let Ok(read) = stream.read(&mut request).await else {
+        return;
+    };
+
+    let request = String::from_utf8_lossy(&request[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let response = if path == "/metrics" {
+        let body = render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+    };
+
+    let _ = stream.write_all(response.as_bytes()).await;
+}
@@ -0,0 +1,116 @@
+//! Opt-in toxicity scoring for incoming messages. Scores only ever hide a
+//! message from the chat window, nothing is done to the viewer.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use nvim_oxi::Result;
+
+use crate::{
+    commands::{Command, CommandSender},
+    config::Config,
+    i18n::{tr, tr_with},
+    llm::Llm,
+    ui::{float, PluginError},
+    Plugin,
+};
+
+const PROMPT: &str = "You are a moderation assistant for a Twitch chat. Rate how \
+    toxic, harassing or hateful the message is on a scale from 0 to 1, where 0 \
+    is harmless and 1 is clearly abusive. Reply with the number only.";
+
+/// How many messages the mod view lists.
+const MOD_VIEW_MESSAGES: usize = 50;
+
+/// Scores messages on the connection thread before they are sent on.
+#[derive(Clone)]
+pub struct Moderator {
+    llm: Llm,
+    threshold: f64,
+    /// Set after a failed request so a broken endpoint is reported once
+    /// rather than for every message.
+    failing: Arc<AtomicBool>,
+}
+
+impl Moderator {
+    pub fn new(config: &Config) -> std::result::Result<Self, String> {
+        Ok(Moderator {
+            llm: Llm::new(config.llm.clone())?,
+            threshold: config.moderation.threshold,
+            failing: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Whether a message scored `risk` is hidden, and its chat command
+    /// ignored.
+    pub fn hides(&self, risk: Option<f64>) -> bool {
+        risk.is_some_and(|risk| risk >= self.threshold)
+    }
+
+    /// Returns `None` when the message couldn't be scored, it is then shown
+    /// as usual.
+    pub async fn score(&self, text: &str, sender: &CommandSender) -> Option<f64> {
+        let result = self
+            .llm
+            .complete(PROMPT, text)
+            .await
+            .and_then(|reply| parse_score(&reply));
+
+        match result {
+            Ok(score) => {
+                self.failing.store(false, Ordering::Relaxed);
+                Some(score)
+            }
+            Err(message) => {
+                if !self.failing.swap(true, Ordering::Relaxed) {
+                    sender.send(Command::Error(PluginError::error("Moderation", message)));
+                }
+                None
+            }
+        }
+    }
+}
+
+fn parse_score(reply: &str) -> std::result::Result<f64, String> {
+    reply
+        .trim()
+        .parse::<f64>()
+        .map(|score| score.clamp(0.0, 1.0))
+        .map_err(|_| format!("expected a score, got {:?}", reply))
+}
+
+/// Lists the latest messages with their scores, including hidden ones.
+pub fn show(plugin: &Plugin) -> Result<()> {
+    let threshold = plugin.config.moderation.threshold;
+
+    let entries = plugin.history.since(60).collect::<Vec<_>>();
+
+    let lines = entries
+        .iter()
+        .rev()
+        .take(MOD_VIEW_MESSAGES)
+        .rev()
+        .map(|entry| {
+            let message = &entry.message;
+
+            let score = match message.risk {
+                Some(risk) if risk >= threshold => {
+                    tr_with("moderation.hidden", &[("score", &format!("{:.2}", risk))])
+                }
+                Some(risk) => format!("{:.2}", risk),
+                None => "-".to_owned(),
+            };
+
+            format!(
+                "{:>8}  {}: {}",
+                score,
+                message.author,
+                message.display_text()
+            )
+        })
+        .collect();
+
+    float::show(&tr("moderation.title"), lines)
+}
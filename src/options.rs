@@ -0,0 +1,148 @@
+//! `!opt <name> <value>` and `!font <font> [size]`: chat sets one of a few
+//! editor options that can't do any harm, each value parsed and checked by
+//! what the option takes before it is set.
+
+use nvim_oxi::{
+    api::{self, opts::*},
+    Object, Result,
+};
+
+use crate::{audit::Undo, commands, i18n::tr_with, Plugin};
+
+/// What an option takes.
+enum Kind {
+    Boolean,
+    Integer(i64, i64),
+    OneOf(&'static [&'static str]),
+    Font,
+}
+
+/// The options chat may set, nothing outside of them.
+const ALLOWED: [(&str, Kind); 14] = [
+    ("background", Kind::OneOf(&["dark", "light"])),
+    ("guifont", Kind::Font),
+    ("number", Kind::Boolean),
+    ("relativenumber", Kind::Boolean),
+    ("cursorline", Kind::Boolean),
+    ("cursorcolumn", Kind::Boolean),
+    ("wrap", Kind::Boolean),
+    ("list", Kind::Boolean),
+    ("spell", Kind::Boolean),
+    ("signcolumn", Kind::OneOf(&["auto", "yes", "no", "number"])),
+    ("scrolloff", Kind::Integer(0, 999)),
+    ("conceallevel", Kind::Integer(0, 3)),
+    ("laststatus", Kind::Integer(0, 3)),
+    ("linespace", Kind::Integer(0, 20)),
+];
+
+const MAX_FONT: usize = 100;
+/// Sizes given to `!font` as a separate word, in points.
+const FONT_SIZES: std::ops::RangeInclusive<u32> = 6..=40;
+
+/// Sets option `name` to `value`, if chat may.
+pub fn set(
+    plugin: &mut Plugin,
+    channel: String,
+    author: &str,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    let (name, value) = (name.to_lowercase(), value.trim());
+
+    let (result, undo) = match parse(&name, value).and_then(|value| apply(&name, value)) {
+        Ok(previous) => (
+            Ok(()),
+            Some(Undo::Option {
+                name: name.clone(),
+                previous,
+            }),
+        ),
+        Err(e) => (Err(e), None),
+    };
+    let action = format!("opt {} {}", name, value);
+    plugin.audit.record(&channel, author, action, &result, undo);
+    result?;
+
+    let text = tr_with(
+        "confirm.option",
+        &[("name", &name), ("value", &value), ("user", &author)],
+    );
+    commands::confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+/// Sets `guifont` from `!font`, where a trailing number is the size.
+pub fn font(plugin: &mut Plugin, channel: String, author: &str, font: &str) -> Result<()> {
+    let font = font.trim();
+
+    let font = match font.rsplit_once(' ') {
+        Some((family, size)) if !font.contains(':') => match size.parse::<u32>() {
+            Ok(size) if FONT_SIZES.contains(&size) => format!("{}:h{}", family.trim(), size),
+            Ok(_) => return Err(invalid(tr_with("options.invalid_size", &[("size", &size)]))),
+            Err(_) => font.to_owned(),
+        },
+        _ => font.to_owned(),
+    };
+
+    set(plugin, channel, author, "guifont", &font)
+}
+
+/// The value `name` would be set to, as the option takes it.
+fn parse(name: &str, value: &str) -> Result<Object> {
+    let Some((_, kind)) = ALLOWED.iter().find(|(allowed, _)| *allowed == name) else {
+        let names: Vec<_> = ALLOWED.iter().map(|(name, _)| *name).collect();
+        return Err(invalid(tr_with(
+            "options.not_allowed",
+            &[("name", &name), ("allowed", &names.join(", "))],
+        )));
+    };
+
+    let wrong = || {
+        invalid(tr_with(
+            "options.invalid_value",
+            &[("name", &name), ("value", &value)],
+        ))
+    };
+
+    let value = match kind {
+        Kind::Boolean => match value.to_lowercase().as_str() {
+            "on" | "true" | "yes" | "1" => Object::from(true),
+            "off" | "false" | "no" | "0" => Object::from(false),
+            _ => return Err(wrong()),
+        },
+        Kind::Integer(min, max) => match value.parse::<i64>() {
+            Ok(number) if (*min..=*max).contains(&number) => Object::from(number),
+            _ => return Err(wrong()),
+        },
+        Kind::OneOf(choices) if choices.contains(&value) => Object::from(value),
+        Kind::Font
+            if !value.is_empty()
+                && value.chars().count() <= MAX_FONT
+                && !value.chars().any(char::is_control) =>
+        {
+            Object::from(value)
+        }
+        Kind::OneOf(_) | Kind::Font => return Err(wrong()),
+    };
+
+    Ok(value)
+}
+
+/// Sets `name` the way `:set` would, returning what it was before.
+fn apply(name: &str, value: Object) -> Result<Object> {
+    let opts = OptionOpts::default();
+    let previous = api::get_option_value::<Object>(name, &opts)?;
+    api::set_option_value(name, value, &opts)?;
+    Ok(previous)
+}
+
+/// Puts back what `name` was before chat set it, for `:TwitchUndo`.
+pub fn restore(name: &str, previous: Object) -> Result<()> {
+    api::set_option_value(name, previous, &OptionOpts::default())?;
+    Ok(())
+}
+
+fn invalid(message: String) -> nvim_oxi::Error {
+    api::Error::Other(message).into()
+}
@@ -0,0 +1,83 @@
+//! Where the plugin keeps its files on disk.
+//!
+//! Each directory is `stdpath(kind)/nvim-twitch`, so it follows the XDG
+//! variables and `NVIM_APPNAME` like the rest of Neovim, and can be
+//! overridden from the config.
+
+use std::{env, path::PathBuf};
+
+use nvim_oxi::api;
+use serde::Serialize;
+
+use crate::auth;
+
+const DIR_NAME: &str = "nvim-twitch";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathOverrides {
+    pub data: Option<PathBuf>,
+    pub cache: Option<PathBuf>,
+    pub state: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Paths {
+    pub data: PathBuf,
+    pub cache: PathBuf,
+    pub state: PathBuf,
+    pub log: PathBuf,
+}
+
+impl Paths {
+    /// Must be called on the main thread since it asks Neovim for `stdpath()`.
+    pub fn resolve(overrides: &PathOverrides) -> Paths {
+        Paths {
+            data: dir(&overrides.data, "data", "XDG_DATA_HOME", ".local/share"),
+            cache: dir(&overrides.cache, "cache", "XDG_CACHE_HOME", ".cache"),
+            state: dir(&overrides.state, "state", "XDG_STATE_HOME", ".local/state"),
+            log: dir(&overrides.log, "log", "XDG_STATE_HOME", ".local/state"),
+        }
+    }
+
+    pub fn credentials_file(&self, account: &str) -> PathBuf {
+        match account {
+            auth::MAIN => self.data.join("credentials.enc"),
+            account => self.data.join(format!("credentials-{}.enc", account)),
+        }
+    }
+}
+
+/// The config files in the order they are read, so the one next to the
+/// Neovim config wins. They can't be moved by the config itself:
+/// `~/.config/nvim-twitch/config.toml` only follows `$XDG_CONFIG_HOME` and
+/// `stdpath("config")/twitch.toml` also `NVIM_APPNAME`. Must be called on
+/// the main thread like [`Paths::resolve`].
+pub fn config_files() -> Vec<PathBuf> {
+    let own = xdg("XDG_CONFIG_HOME", ".config").map(|base| base.join(DIR_NAME).join("config.toml"));
+    let nvim = api::call_function::<_, String>("stdpath", ("config",))
+        .ok()
+        .map(|base| PathBuf::from(base).join("twitch.toml"));
+
+    own.into_iter().chain(nvim).collect()
+}
+
+fn dir(overridden: &Option<PathBuf>, kind: &str, var: &str, fallback: &str) -> PathBuf {
+    if let Some(path) = overridden {
+        return path.clone();
+    }
+
+    let base = api::call_function::<_, String>("stdpath", (kind,))
+        .ok()
+        .map(PathBuf::from)
+        .or_else(|| xdg(var, fallback).map(|base| base.join("nvim")))
+        .unwrap_or_else(env::temp_dir);
+
+    base.join(DIR_NAME)
+}
+
+fn xdg(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+}
@@ -0,0 +1,332 @@
+//! `:TwitchPollCreate` and `:TwitchPrediction`, with their results shown in
+//! a float that follows the votes.
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    i18n::{tr, tr_with},
+    twitch::{
+        polls::{Poll, PollAction, Prediction},
+        Outgoing,
+    },
+    ui::ResultsWindow,
+    Plugin,
+};
+
+const POLL_TITLE_LIMIT: usize = 60;
+const POLL_CHOICES: (usize, usize) = (2, 5);
+const POLL_SECONDS: (u32, u32) = (15, 1800);
+
+const PREDICTION_TITLE_LIMIT: usize = 45;
+const PREDICTION_OUTCOMES: (usize, usize) = (2, 10);
+const PREDICTION_SECONDS: (u32, u32) = (30, 1800);
+
+/// How long voting lasts unless a duration is given.
+const DEFAULT_SECONDS: u32 = 120;
+
+const OPTION_LIMIT: usize = 25;
+
+const BAR_WIDTH: usize = 10;
+
+#[derive(Default)]
+pub struct Polls {
+    poll: Option<Poll>,
+    /// The latest prediction, which `resolve` and `cancel` apply to.
+    prediction: Option<Prediction>,
+    results: ResultsWindow,
+}
+
+impl Polls {
+    /// Shows `poll` unless it is shown already, as one just created comes
+    /// again when EventSub tells it began. Returns whether it was.
+    pub fn show_poll(&mut self, poll: Poll, zindex: u32) -> Result<bool> {
+        if self.poll.as_ref() == Some(&poll) {
+            return Ok(false);
+        }
+
+        let total: u64 = poll.choices.iter().map(|choice| choice.votes).sum();
+
+        let mut lines: Vec<String> = poll
+            .choices
+            .iter()
+            .map(|choice| {
+                format!(
+                    "{} {:>3}% {} ({})",
+                    bar(choice.votes, total),
+                    percent(choice.votes, total),
+                    choice.title,
+                    choice.votes
+                )
+            })
+            .collect();
+        lines.push(status(&poll.status));
+
+        let title = poll.title.clone();
+        self.poll = Some(poll);
+
+        self.results.show(&title, lines, zindex).map(|()| true)
+    }
+
+    /// Like [`Polls::show_poll`], for predictions.
+    pub fn show_prediction(&mut self, prediction: Prediction, zindex: u32) -> Result<bool> {
+        if self.prediction.as_ref() == Some(&prediction) {
+            return Ok(false);
+        }
+
+        let total: u64 = prediction
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.channel_points)
+            .sum();
+
+        let mut lines: Vec<String> = prediction
+            .outcomes
+            .iter()
+            .enumerate()
+            .map(|(i, outcome)| {
+                let won = prediction.winning_outcome_id.as_ref() == Some(&outcome.id);
+
+                format!(
+                    "{}. {} {:>3}% {} ({}){}",
+                    i + 1,
+                    bar(outcome.channel_points, total),
+                    percent(outcome.channel_points, total),
+                    outcome.title,
+                    tr_with(
+                        "polls.predictors",
+                        &[
+                            ("users", &outcome.users),
+                            ("points", &outcome.channel_points),
+                        ],
+                    ),
+                    if won { " ✔" } else { "" }
+                )
+            })
+            .collect();
+        lines.push(status(&prediction.status));
+
+        let title = prediction.title.clone();
+        self.prediction = Some(prediction);
+
+        self.results.show(&title, lines, zindex).map(|()| true)
+    }
+
+    pub fn toggle(&mut self, zindex: u32) -> Result<()> {
+        self.results.toggle(zindex)
+    }
+}
+
+/// Runs `:TwitchPollCreate "title" choice choice... [seconds]`.
+pub fn create_poll(plugin: &mut Plugin, args: Option<String>) -> Result<()> {
+    let Some((title, choices, seconds)) = parse(
+        args.as_deref().unwrap_or_default(),
+        POLL_TITLE_LIMIT,
+        POLL_CHOICES,
+        POLL_SECONDS,
+    ) else {
+        return usage(
+            "polls.poll_usage",
+            POLL_TITLE_LIMIT,
+            POLL_CHOICES,
+            POLL_SECONDS,
+        );
+    };
+
+    send(
+        plugin,
+        PollAction::CreatePoll {
+            title,
+            choices,
+            seconds,
+        },
+    )
+}
+
+/// Runs `:TwitchPrediction "title" outcome outcome... [seconds]`,
+/// `:TwitchPrediction resolve <number>` or `:TwitchPrediction cancel`.
+pub fn prediction(plugin: &mut Plugin, args: Option<String>) -> Result<()> {
+    let args = args.unwrap_or_default();
+    let words: Vec<&str> = args.split_whitespace().collect();
+
+    let action = match words[..] {
+        ["resolve", number] => {
+            let Some(prediction) = &plugin.polls.prediction else {
+                return notify(&tr("polls.no_prediction"), LogLevel::Error);
+            };
+
+            let outcome = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| prediction.outcomes.get(number.checked_sub(1)?));
+
+            match outcome {
+                Some(outcome) => PollAction::ResolvePrediction {
+                    id: prediction.id.clone(),
+                    outcome_id: outcome.id.clone(),
+                },
+                None => {
+                    let message = tr_with(
+                        "polls.unknown_outcome",
+                        &[("count", &prediction.outcomes.len())],
+                    );
+                    return notify(&message, LogLevel::Error);
+                }
+            }
+        }
+        ["cancel"] => match &plugin.polls.prediction {
+            Some(prediction) => PollAction::CancelPrediction {
+                id: prediction.id.clone(),
+            },
+            None => return notify(&tr("polls.no_prediction"), LogLevel::Error),
+        },
+        _ => match parse(
+            &args,
+            PREDICTION_TITLE_LIMIT,
+            PREDICTION_OUTCOMES,
+            PREDICTION_SECONDS,
+        ) {
+            Some((title, outcomes, seconds)) => PollAction::CreatePrediction {
+                title,
+                outcomes,
+                seconds,
+            },
+            None => {
+                return usage(
+                    "polls.prediction_usage",
+                    PREDICTION_TITLE_LIMIT,
+                    PREDICTION_OUTCOMES,
+                    PREDICTION_SECONDS,
+                )
+            }
+        },
+    };
+
+    send(plugin, action)
+}
+
+fn send(plugin: &Plugin, action: PollAction) -> Result<()> {
+    let _ = plugin.outgoing.send(Outgoing::Poll(action));
+    Ok(())
+}
+
+/// Splits `"title" option option... [seconds]`, checking each against
+/// Twitch's limits.
+pub fn parse(
+    args: &str,
+    title_limit: usize,
+    (min, max): (usize, usize),
+    (shortest, longest): (u32, u32),
+) -> Option<(String, Vec<String>, u32)> {
+    let mut words = split_quoted(args);
+
+    let seconds = match words.last().and_then(|last| last.parse::<u32>().ok()) {
+        Some(seconds) => {
+            words.pop();
+            seconds
+        }
+        None => DEFAULT_SECONDS,
+    };
+
+    if words.is_empty() {
+        return None;
+    }
+
+    let title = words.remove(0);
+
+    let valid = !title.is_empty()
+        && title.chars().count() <= title_limit
+        && (min..=max).contains(&words.len())
+        && words
+            .iter()
+            .all(|option| option.chars().count() <= OPTION_LIMIT)
+        && (shortest..=longest).contains(&seconds);
+
+    valid.then_some((title, words, seconds))
+}
+
+/// Splits on whitespace, keeping what is inside double quotes together.
+fn split_quoted(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+
+    if started {
+        words.push(word);
+    }
+
+    words
+}
+
+fn bar(count: u64, total: u64) -> String {
+    let filled = match total {
+        0 => 0,
+        total => (count * BAR_WIDTH as u64 / total) as usize,
+    };
+
+    format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+}
+
+fn percent(count: u64, total: u64) -> u64 {
+    match total {
+        0 => 0,
+        total => count * 100 / total,
+    }
+}
+
+fn status(status: &str) -> String {
+    match status {
+        "ACTIVE" => tr("polls.active"),
+        "LOCKED" => tr("polls.locked"),
+        "RESOLVED" => tr("polls.resolved"),
+        "CANCELED" | "TERMINATED" | "ARCHIVED" => tr("polls.canceled"),
+        _ => tr("polls.ended"),
+    }
+}
+
+pub fn usage(
+    key: &str,
+    title_limit: usize,
+    (min, max): (usize, usize),
+    (shortest, longest): (u32, u32),
+) -> Result<()> {
+    let message = tr_with(
+        key,
+        &[
+            ("title", &title_limit),
+            ("min", &min),
+            ("max", &max),
+            ("length", &OPTION_LIMIT),
+            ("shortest", &shortest),
+            ("longest", &longest),
+        ],
+    );
+    notify(&message, LogLevel::Error)
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
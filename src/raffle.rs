@@ -0,0 +1,157 @@
+//! Giveaways run with `:TwitchRaffle`: viewers enter by typing a keyword and
+//! a weighted random winner is drawn from them.
+
+use std::collections::BTreeMap;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use rand::Rng;
+
+use crate::{
+    commands,
+    i18n::{tr, tr_with},
+    twitch::{ChatMessage, Outgoing},
+    ui::StatusFloat,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 3] = ["start", "draw", "cancel"];
+
+pub struct Raffle {
+    /// Set while entries are open.
+    keyword: Option<String>,
+    /// Tickets held by each entrant.
+    entrants: BTreeMap<String, u32>,
+    counter: StatusFloat,
+}
+
+impl Default for Raffle {
+    fn default() -> Self {
+        Raffle {
+            keyword: None,
+            entrants: BTreeMap::new(),
+            counter: StatusFloat::new(0),
+        }
+    }
+}
+
+/// Runs `:TwitchRaffle start <keyword>`, `draw` or `cancel`.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    match args {
+        [command, keyword] if command == "start" => start(plugin, keyword),
+        [command] if command == "draw" => draw(plugin),
+        [command] if command == "cancel" => {
+            plugin.raffle.keyword = None;
+            plugin.raffle.entrants.clear();
+            plugin.raffle.counter.close()
+        }
+        _ => notify(&tr("raffle.usage"), LogLevel::Error),
+    }
+}
+
+/// Enters the author if the message is the keyword of an open raffle.
+pub fn enter(plugin: &mut Plugin, message: &ChatMessage) -> Result<()> {
+    let Some(keyword) = &plugin.raffle.keyword else {
+        return Ok(());
+    };
+
+    let own_channel = message.channel.is_empty() || message.channel == plugin.config.channel;
+
+    if !own_channel || !message.text.trim().eq_ignore_ascii_case(keyword) {
+        return Ok(());
+    }
+
+    if plugin.raffle.entrants.contains_key(&message.author) {
+        return Ok(());
+    }
+
+    let subscribed = message.badges.iter().any(|badge| {
+        let set = badge.split('/').next().unwrap_or_default();
+        set == "subscriber" || set == "founder"
+    });
+    let tickets = match subscribed {
+        true => plugin.config.raffle.sub_luck,
+        false => 1,
+    };
+
+    plugin
+        .raffle
+        .entrants
+        .insert(message.author.clone(), tickets);
+
+    show_count(plugin)
+}
+
+fn start(plugin: &mut Plugin, keyword: &str) -> Result<()> {
+    plugin.raffle.keyword = Some(keyword.to_owned());
+    plugin.raffle.entrants.clear();
+
+    say(plugin, tr_with("raffle.started", &[("keyword", &keyword)]));
+    show_count(plugin)
+}
+
+fn draw(plugin: &mut Plugin) -> Result<()> {
+    let total: u32 = plugin.raffle.entrants.values().sum();
+
+    if total == 0 {
+        return notify(&tr("raffle.no_entrants"), LogLevel::Warn);
+    }
+
+    // Entries close with the first draw, later ones pick from who is left.
+    plugin.raffle.keyword = None;
+    plugin.raffle.counter.close()?;
+
+    let mut ticket = rand::thread_rng().gen_range(0..total);
+    let winner = plugin
+        .raffle
+        .entrants
+        .iter()
+        .find(|(_, &tickets)| {
+            let won = ticket < tickets;
+            ticket = ticket.saturating_sub(tickets);
+            won
+        })
+        .map(|(name, _)| name.clone())
+        .expect("a ticket below the total is held by someone");
+
+    plugin.raffle.entrants.remove(&winner);
+
+    let text = tr_with("raffle.winner", &[("user", &winner)]);
+    say(plugin, text.clone());
+    commands::announce(plugin, &format!("🎟 {}", text))
+}
+
+fn show_count(plugin: &mut Plugin) -> Result<()> {
+    if plugin.config.accessibility.enabled {
+        return Ok(());
+    }
+
+    let Some(keyword) = &plugin.raffle.keyword else {
+        return Ok(());
+    };
+
+    let text = tr_with(
+        "raffle.count",
+        &[
+            ("keyword", keyword),
+            ("count", &plugin.raffle.entrants.len()),
+        ],
+    );
+    let zindex = plugin.chat.zindex();
+
+    plugin.raffle.counter.show(&format!("🎟 {}", text), zindex)
+}
+
+fn say(plugin: &Plugin, text: String) {
+    let _ = plugin.outgoing.send(Outgoing::Reply {
+        channel: plugin.config.channel.clone(),
+        text,
+    });
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
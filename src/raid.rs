@@ -0,0 +1,110 @@
+//! `:TwitchRaid` to raid out of the channel, with a countdown until the raid
+//! goes ahead.
+
+use std::time::Instant;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    commands,
+    i18n::{tr, tr_with},
+    twitch::{
+        raids::{RaidAction, COUNTDOWN},
+        Outgoing,
+    },
+    ui::StatusFloat,
+    Plugin,
+};
+
+pub struct Raid {
+    /// The raided channel and when the raid was started.
+    target: Option<(String, Instant)>,
+    /// Followed channels that are live, offered by completion.
+    pub live: Vec<String>,
+    countdown: StatusFloat,
+}
+
+impl Default for Raid {
+    fn default() -> Self {
+        Raid {
+            target: None,
+            live: Vec::new(),
+            // Above the ad break countdown.
+            countdown: StatusFloat::new(2),
+        }
+    }
+}
+
+impl Raid {
+    /// Completion candidates for `:TwitchRaid`.
+    pub fn candidates(&self, lead: &str) -> Vec<String> {
+        std::iter::once("cancel")
+            .chain(self.live.iter().map(String::as_str))
+            .filter(|name| name.starts_with(lead))
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+/// Runs `:TwitchRaid <channel>` or `:TwitchRaid cancel`.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    let action = match args {
+        [command] if command == "cancel" => RaidAction::Cancel,
+        [channel] => RaidAction::Start(channel.trim_start_matches('#').to_lowercase()),
+        _ => {
+            api::notify(&tr("raid.usage"), LogLevel::Error, &NotifyOpts::default())?;
+            return Ok(());
+        }
+    };
+
+    let _ = plugin.outgoing.send(Outgoing::Raid(action));
+
+    Ok(())
+}
+
+pub fn started(plugin: &mut Plugin, to: &str) -> Result<()> {
+    plugin.raid.target = Some((to.to_owned(), Instant::now()));
+    commands::announce(plugin, &tr_with("raid.started", &[("to", &to)]))?;
+    tick(plugin)
+}
+
+pub fn canceled(plugin: &mut Plugin) -> Result<()> {
+    plugin.raid.target = None;
+    plugin.raid.countdown.close()?;
+    commands::announce(plugin, &tr("raid.canceled"))
+}
+
+/// Updates the countdown, called every second or so.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let Some((to, started)) = &plugin.raid.target else {
+        return Ok(());
+    };
+
+    let Some(left) = COUNTDOWN.checked_sub(started.elapsed()) else {
+        plugin.raid.target = None;
+        return plugin.raid.countdown.close();
+    };
+
+    // Accessibility mode has no countdown, the start was announced.
+    if plugin.config.accessibility.enabled {
+        return Ok(());
+    }
+
+    let seconds = left.as_secs();
+    let text = format!(
+        "🚀 {}",
+        tr_with(
+            "raid.countdown",
+            &[
+                ("to", to),
+                ("left", &format!("{}:{:02}", seconds / 60, seconds % 60)),
+            ],
+        )
+    );
+    let zindex = plugin.chat.zindex();
+
+    plugin.raid.countdown.show(&text, zindex)
+}
@@ -0,0 +1,141 @@
+//! Channel point redemptions, each running the action `redemptions.actions`
+//! maps its reward to, or a Lua callback set with `on_redemption()`. Rewards
+//! are matched by title, ignoring case.
+
+use std::{collections::HashMap, path::Path};
+
+use nvim_oxi::{api, serde::Serializer, Array, Function, Object, Result};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+
+use crate::{
+    audit::Undo,
+    commands,
+    config::RedemptionAction,
+    events::Event,
+    hooks::{self, Failure},
+    spotlight, Plugin,
+};
+
+#[derive(Default)]
+pub struct Redemptions {
+    /// Keyed by reward title in lowercase, over any configured action.
+    callbacks: HashMap<String, Function<Object, ()>>,
+}
+
+impl Redemptions {
+    pub fn register(&mut self, reward: &str, callback: Function<Object, ()>) {
+        self.callbacks.insert(reward.to_lowercase(), callback);
+    }
+}
+
+/// What redeeming `reward` would change in the editor, unset if a callback
+/// takes it or nothing is mapped to it.
+pub fn action(plugin: &Plugin, reward: &str) -> Option<String> {
+    let reward = reward.to_lowercase();
+
+    if plugin.redemptions.callbacks.contains_key(&reward) {
+        return None;
+    }
+
+    let action = match plugin.config.redemptions.actions.get(&reward)? {
+        RedemptionAction::Colorscheme => "next colorscheme",
+        RedemptionAction::RandomFile => "random file",
+        RedemptionAction::Command(command) => command,
+    };
+
+    Some(format!("reward {}: {}", reward, action))
+}
+
+/// Runs whatever `reward` is mapped to. Rewards without a mapping only fire
+/// the autocommand.
+pub fn run(
+    plugin: &mut Plugin,
+    channel: &str,
+    user: &str,
+    reward: &str,
+    event: &Event,
+) -> Result<()> {
+    let reward = reward.to_lowercase();
+
+    if let Some(callback) = plugin.redemptions.callbacks.get(&reward).cloned() {
+        let data = event.serialize(Serializer::new());
+
+        // Called once the plugin is no longer borrowed, like the registered
+        // commands.
+        let (channel, user) = (channel.to_owned(), user.to_owned());
+        nvim_oxi::schedule(move |_| -> Result<()> {
+            if let Err(message) = hooks::guarded(callback, data?) {
+                hooks::fail(Failure {
+                    context: "Redemption".to_owned(),
+                    message,
+                    command: Some((channel, user, format!("reward {}", reward))),
+                });
+            }
+            Ok(())
+        });
+
+        return Ok(());
+    }
+
+    let (result, undo) = match plugin.config.redemptions.actions.get(&reward).cloned() {
+        Some(RedemptionAction::Colorscheme) => {
+            let previous = api::get_var::<String>("colors_name").ok();
+            (rotate_colorscheme(plugin), Undo::Colorscheme(previous))
+        }
+        Some(RedemptionAction::RandomFile) => {
+            let undo = Undo::jump()?;
+            (random_file(), undo)
+        }
+        // Whatever an Ex command did can't be told apart to revert it.
+        Some(RedemptionAction::Command(command)) => {
+            let result = api::command(&command).map_err(Into::into);
+            let action = format!("reward {}: {}", reward, command);
+            plugin.audit.record(channel, user, action, &result, None);
+            return result;
+        }
+        None => return Ok(()),
+    };
+
+    let action = format!("reward {}", reward);
+    plugin
+        .audit
+        .record(channel, user, action, &result, Some(undo));
+
+    result
+}
+
+/// Switches to the allowed colorscheme after the current one.
+fn rotate_colorscheme(plugin: &mut Plugin) -> Result<()> {
+    let allowed = commands::allowed_colorschemes(plugin)?;
+    let current = api::get_var::<String>("colors_name").ok();
+
+    let next = match current.and_then(|current| allowed.iter().position(|name| *name == current)) {
+        Some(index) => allowed.get((index + 1) % allowed.len()),
+        None => allowed.first(),
+    };
+    let Some(next) = next.cloned() else {
+        return Ok(());
+    };
+
+    commands::apply_colorscheme(plugin, &next)
+}
+
+/// Opens a random file under the working directory, leaving out the same
+/// files `!goto` does.
+fn random_file() -> Result<()> {
+    let cwd: String = api::call_function("getcwd", Array::new())?;
+    let cwd = Path::new(&cwd);
+
+    // Relative to the working directory, honouring 'wildignore'.
+    let files: Vec<String> = api::call_function("glob", ("**/*", false, true))?;
+    let files: Vec<_> = files
+        .iter()
+        .filter_map(|file| spotlight::resolve(cwd, file))
+        .collect();
+
+    match files.choose(&mut rand::thread_rng()) {
+        Some(path) => spotlight::open(path),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,253 @@
+//! `:TwitchRecord <path>` and `:TwitchReplay <file> [speed]`: what chat did
+//! to the editor, written down as it went through and played back later,
+//! for highlight clips or to find out which command broke something.
+//!
+//! A recording is one event per line, in the schema of `src/events.rs`,
+//! with only the chat commands that ran. Commands that just answer in
+//! chat, like `!where`, and `!nvim` messages are left out. The JSON of a
+//! `:TwitchExport` replays as well, from the commands that went through.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use serde_json::Value;
+
+use crate::{
+    commands::{Command, CommandSender},
+    events::Event,
+    i18n::{tr, tr_with},
+    simulate::{self, SPEEDS},
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 1] = ["stop"];
+
+/// Commands that change nothing in the editor.
+const NOT_RECORDED: [&str; 4] = ["nvim", "where", "uptime", "help"];
+
+struct Recording {
+    file: BufWriter<File>,
+    path: PathBuf,
+    count: usize,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    recording: Option<Recording>,
+    /// Set to stop the one playing.
+    stop: Option<Arc<AtomicBool>>,
+}
+
+/// Runs `:TwitchRecord <path>` or `:TwitchRecord stop`.
+pub fn record(plugin: &mut Plugin, args: Option<&str>) -> Result<()> {
+    let words: Vec<&str> = args.unwrap_or_default().split_whitespace().collect();
+
+    let file = match words.as_slice() {
+        ["stop"] => {
+            return match plugin.replay.recording.take() {
+                Some(recording) => notify(
+                    &tr_with(
+                        "replay.recorded",
+                        &[
+                            ("count", &recording.count),
+                            ("path", &recording.path.display()),
+                        ],
+                    ),
+                    LogLevel::Info,
+                ),
+                None => notify(&tr("replay.not_recording"), LogLevel::Error),
+            }
+        }
+        [file] => *file,
+        _ => return notify(&tr("replay.record_usage"), LogLevel::Error),
+    };
+
+    let path = PathBuf::from(api::call_function::<_, String>("expand", (file,))?);
+
+    // Whatever was being recorded is done with.
+    plugin.replay.recording = None;
+
+    match File::create(&path) {
+        Ok(file) => {
+            let text = tr_with("replay.recording", &[("path", &path.display())]);
+            plugin.replay.recording = Some(Recording {
+                file: BufWriter::new(file),
+                path,
+                count: 0,
+            });
+            notify(&text, LogLevel::Info)
+        }
+        Err(e) => notify(
+            &tr_with("replay.failed", &[("path", &path.display()), ("error", &e)]),
+            LogLevel::Error,
+        ),
+    }
+}
+
+/// Adds a chat command that went through, if a recording is running. Like
+/// an export, it stops at the first error.
+pub fn command(plugin: &mut Plugin, event: &Event) {
+    let Some(recording) = &mut plugin.replay.recording else {
+        return;
+    };
+    if !replayable(&event.command) {
+        return;
+    }
+
+    let written = serde_json::to_writer(&mut recording.file, event)
+        .map_err(std::io::Error::from)
+        .and_then(|()| writeln!(recording.file))
+        .and_then(|()| recording.file.flush());
+
+    match written {
+        Ok(()) => recording.count += 1,
+        Err(e) => {
+            let message = tr_with(
+                "replay.failed",
+                &[("path", &recording.path.display()), ("error", &e)],
+            );
+            plugin.replay.recording = None;
+            plugin.errors.push("Record", &message);
+        }
+    }
+}
+
+/// Whether `command` came from chat and changed something in the editor.
+fn replayable(command: &Command) -> bool {
+    !matches!(command, Command::Malformed { .. })
+        && command
+            .chat_origin()
+            .is_some_and(|(_, name, ..)| !NOT_RECORDED.contains(&name))
+}
+
+/// Runs `:TwitchReplay <file> [speed]` or `:TwitchReplay stop`.
+pub fn run(plugin: &mut Plugin, sender: &CommandSender, args: Option<&str>) -> Result<()> {
+    let words: Vec<&str> = args.unwrap_or_default().split_whitespace().collect();
+
+    let (file, speed) = match words.as_slice() {
+        ["stop"] => {
+            return match plugin.replay.stop.take() {
+                Some(stop) => {
+                    stop.store(true, Ordering::Relaxed);
+                    notify(&tr("replay.stopped"), LogLevel::Info)
+                }
+                None => notify(&tr("replay.none"), LogLevel::Error),
+            }
+        }
+        [file] => (*file, Some(1.0)),
+        [file, speed] => (*file, speed.parse().ok()),
+        _ => ("", None),
+    };
+    let Some(speed) = speed.filter(|speed| (SPEEDS.0..=SPEEDS.1).contains(speed)) else {
+        let text = tr_with(
+            "replay.usage",
+            &[("slowest", &SPEEDS.0), ("fastest", &SPEEDS.1)],
+        );
+        return notify(&text, LogLevel::Error);
+    };
+
+    let path: String = api::call_function("expand", (file,))?;
+    let commands = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| simulate::read(&text))
+        .and_then(parse);
+
+    match commands {
+        Ok(commands) => {
+            let text = tr_with(
+                "replay.started",
+                &[("count", &commands.len()), ("path", &path)],
+            );
+            start(plugin, sender, commands, speed);
+            notify(&text, LogLevel::Info)
+        }
+        Err(e) => notify(
+            &tr_with("replay.invalid", &[("path", &path), ("error", &e)]),
+            LogLevel::Error,
+        ),
+    }
+}
+
+/// The commands of a recording, each with how long after the first it
+/// ran. Export lines take the event of the commands that went through, and
+/// anything else that isn't a chat command is left out.
+fn parse(values: Vec<Value>) -> std::result::Result<Vec<(Duration, Command)>, String> {
+    let mut commands = Vec::new();
+    let mut first: Option<DateTime<Utc>> = None;
+
+    for (i, mut value) in values.into_iter().enumerate() {
+        match value.get("kind").and_then(Value::as_str) {
+            Some("command") if value.get("error").is_none() => {
+                value = value["event"].take();
+            }
+            Some(_) => continue,
+            None => (),
+        }
+
+        let event: Event =
+            serde_json::from_value(value).map_err(|e| format!("event {}: {}", i + 1, e))?;
+        if !replayable(&event.command) {
+            continue;
+        }
+
+        let first = *first.get_or_insert(event.timestamp);
+        let after = (event.timestamp - first).to_std().unwrap_or_default();
+        commands.push((after, event.command));
+    }
+
+    Ok(commands)
+}
+
+/// Hands `commands` over on a thread of their own, `speed` times as fast,
+/// after stopping the replay that was playing.
+fn start(
+    plugin: &mut Plugin,
+    sender: &CommandSender,
+    commands: Vec<(Duration, Command)>,
+    speed: f64,
+) {
+    if let Some(previous) = plugin.replay.stop.take() {
+        previous.store(true, Ordering::Relaxed);
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    plugin.replay.stop = Some(Arc::clone(&stop));
+
+    let sender = sender.clone();
+
+    thread::spawn(move || {
+        let mut elapsed = Duration::ZERO;
+
+        for (after, command) in commands {
+            let after = after.div_f64(speed);
+            thread::sleep(after.saturating_sub(elapsed));
+            elapsed = elapsed.max(after);
+
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            sender.send(Command::Replayed {
+                command: Box::new(command),
+            });
+        }
+    });
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
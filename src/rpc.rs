@@ -0,0 +1,367 @@
+//! Functions exposed to Lua and, through thin Vimscript wrappers, to RPC
+//! clients such as `nvim --server ... --remote-expr 'TwitchStatus()'`.
+
+use std::{cell::RefCell, rc::Rc};
+
+use nvim_oxi::{
+    api,
+    conversion::FromObject,
+    serde::{Deserializer, Serializer},
+    Dictionary, Function, Object, Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth,
+    commands::{message_count_key, Command, CommandSender},
+    config, health,
+    hooks::Hook,
+    i18n::tr,
+    search, simulate,
+    statusline::Statusline,
+    twitch::{parse_command, ChatMessage, ConnectionStatus},
+    Connector, Plugin,
+};
+
+const VIMSCRIPT_WRAPPERS: &str = r#"
+function! TwitchStatus() abort
+  return json_encode(luaeval('require("nvim_plugin").status()'))
+endfunction
+
+function! TwitchSend(text) abort
+  call luaeval('require("nvim_plugin").send(_A)', a:text)
+  return ''
+endfunction
+
+function! TwitchStatusline() abort
+  return luaeval('require("nvim_plugin").statusline()')
+endfunction
+
+function! TwitchStats() abort
+  return json_encode(luaeval('require("nvim_plugin").stats()'))
+endfunction
+
+function! TwitchToggle() abort
+  call luaeval('require("nvim_plugin").toggle()')
+  return ''
+endfunction
+"#;
+
+#[derive(Serialize)]
+struct Status<'a> {
+    channel: &'a str,
+    messages: u64,
+    sentiment: Option<f64>,
+    #[serde(flatten)]
+    connection: &'a ConnectionStatus,
+    memory: Memory,
+}
+
+/// Entries held in each capped buffer, see `limits` in the config.
+#[derive(Serialize)]
+struct Memory {
+    history: usize,
+    errors: usize,
+    stats: usize,
+    pending: usize,
+    viewers: usize,
+    /// How many didn't fit in `pending` since Neovim started.
+    dropped: u64,
+    /// Messages waiting their turn with `display.mode = "queue"`.
+    queued: usize,
+}
+
+/// Defines the Vimscript wrappers and returns the Lua module table.
+pub fn register(
+    plugin: &Rc<RefCell<Plugin>>,
+    sender: CommandSender,
+    connector: Rc<RefCell<Connector>>,
+) -> Result<Dictionary> {
+    api::exec(VIMSCRIPT_WRAPPERS, false)?;
+    api::exec(search::PICKERS, false)?;
+
+    let setup = {
+        let plugin = Rc::clone(plugin);
+        let connector = Rc::clone(&connector);
+
+        Function::<Object, ()>::from_fn_mut(move |opts: Object| -> Result<()> {
+            // Later calls only reload the config, and reconnect if they
+            // changed the connection.
+            let diagnostics = crate::reload(
+                &mut plugin.borrow_mut(),
+                &mut connector.borrow_mut(),
+                Some(opts),
+            );
+            config::report(&mut plugin.borrow_mut().errors, &diagnostics);
+
+            if !connector.borrow().started {
+                connector.borrow_mut().start(&mut plugin.borrow_mut());
+                crate::watch_config_files(Rc::clone(&plugin), Rc::clone(&connector))?;
+            }
+
+            Ok(())
+        })
+    };
+
+    let status = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(), Object>::from_fn(move |()| -> Result<Object> {
+            let plugin = plugin.borrow();
+
+            let status = Status {
+                channel: &plugin.config.channel,
+                messages: plugin
+                    .state
+                    .get(&message_count_key(&plugin.config.channel))
+                    .unwrap_or(0),
+                sentiment: plugin.sentiment.mood(),
+                connection: &plugin.status,
+                memory: Memory {
+                    history: plugin.history.len(),
+                    errors: plugin.errors.len(),
+                    stats: plugin.stats.len(),
+                    pending: plugin.queue.pending(),
+                    viewers: plugin.viewers.len(),
+                    dropped: plugin.queue.dropped(),
+                    queued: plugin.display.len(),
+                },
+            };
+
+            Ok(status.serialize(Serializer::new())?)
+        })
+    };
+
+    let paths = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(), Object>::from_fn(move |()| -> Result<Object> {
+            Ok(plugin.borrow().paths.serialize(Serializer::new())?)
+        })
+    };
+
+    let statusline = {
+        let plugin = Rc::clone(plugin);
+
+        // `{ table = true }` returns the parts rather than the text.
+        Function::<Option<Dictionary>, Object>::from_fn(move |opts: Option<Dictionary>| {
+            let plugin = plugin.borrow();
+            let statusline = Statusline::of(&plugin);
+
+            let table = opts
+                .and_then(|opts| opts.get("table").cloned())
+                .and_then(|table| bool::from_object(table).ok())
+                .unwrap_or(false);
+
+            match table {
+                true => Ok::<_, nvim_oxi::Error>(statusline.serialize(Serializer::new())?),
+                false => Ok(Object::from(statusline.text())),
+            }
+        })
+    };
+
+    // Like `:TwitchConnect` and `:TwitchDisconnect`, returning whether the
+    // connection was started or stopped rather than saying so.
+    let connection = |connect: bool| {
+        let plugin = Rc::clone(plugin);
+        let connector = Rc::clone(&connector);
+
+        Function::<(), bool>::from_fn(move |()| -> Result<bool> {
+            let mut connector = connector.borrow_mut();
+
+            if !connector.started {
+                return Err(api::Error::Other(tr("connection.not_set_up")).into());
+            }
+
+            let mut plugin = plugin.borrow_mut();
+            Ok(match connect {
+                true => connector.start(&mut plugin),
+                false => connector.stop(&mut plugin),
+            })
+        })
+    };
+    let (connect, disconnect) = (connection(true), connection(false));
+
+    // Used by `lua/nvim_plugin/health.lua` for `:checkhealth nvim_plugin`.
+    let health = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(), Object>::from_fn(move |()| -> Result<Object> {
+            let runtime = connector.borrow().runtime();
+            let sections = health::check(&plugin.borrow(), runtime);
+            Ok(sections.serialize(Serializer::new())?)
+        })
+    };
+
+    let stats = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(), Object>::from_fn(move |()| -> Result<Object> {
+            Ok(plugin
+                .borrow()
+                .stats
+                .snapshot()
+                .serialize(Serializer::new())?)
+        })
+    };
+
+    let send = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<String, ()>::from_fn(move |text: String| {
+            crate::send(&plugin.borrow(), auth::MAIN, text)
+        })
+    };
+
+    let toggle = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(), ()>::from_fn(move |()| plugin.borrow_mut().chat.toggle())
+    };
+
+    let register_command = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(String, Dictionary), ()>::from_fn(
+            move |(name, opts): (String, Dictionary)| -> Result<()> {
+                plugin
+                    .borrow_mut()
+                    .registry
+                    .register(&name, opts)
+                    .map_err(|message| api::Error::Other(message).into())
+            },
+        )
+    };
+
+    let unregister_command = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<String, bool>::from_fn(move |name: String| {
+            plugin.borrow_mut().registry.unregister(&name)
+        })
+    };
+
+    let on_vote_result = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<Function<Object, ()>, ()>::from_fn(move |callback| {
+            plugin.borrow_mut().votes.on_result = Some(callback);
+        })
+    };
+
+    let on_redemption = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<(String, Function<Object, ()>), ()>::from_fn(move |(reward, callback)| {
+            plugin.borrow_mut().redemptions.register(&reward, callback);
+        })
+    };
+
+    let hook = |hook: Hook| {
+        let plugin = Rc::clone(plugin);
+
+        Function::<Function<Object, ()>, ()>::from_fn(move |callback| {
+            plugin.borrow_mut().hooks.register(hook, callback);
+        })
+    };
+
+    let history = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<Option<String>, Object>::from_fn(move |query| -> Result<Object> {
+            let plugin = plugin.borrow();
+            let found = search::find(&plugin, query.as_deref().unwrap_or(""));
+            Ok(found.serialize(Serializer::new())?)
+        })
+    };
+
+    let jump = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<String, ()>::from_fn(move |id: String| -> Result<()> {
+            search::jump(&mut plugin.borrow_mut(), &id)
+        })
+    };
+
+    let on_error = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<Function<Object, ()>, ()>::from_fn(move |callback| {
+            plugin.borrow_mut().errors.on_error(callback);
+        })
+    };
+
+    let viewer = {
+        let plugin = Rc::clone(plugin);
+
+        Function::<String, Object>::from_fn(move |name: String| -> Result<Object> {
+            Ok(plugin
+                .borrow()
+                .viewers
+                .get(&name)
+                .serialize(Serializer::new())?)
+        })
+    };
+
+    let simulate = {
+        let plugin = Rc::clone(plugin);
+        let sender = sender.clone();
+
+        Function::<Object, ()>::from_fn(move |messages: Object| -> Result<()> {
+            let values = Vec::<serde_json::Value>::deserialize(Deserializer::new(messages))?;
+            simulate::from_lua(&mut plugin.borrow_mut(), &sender, values)
+                .map_err(|e| api::Error::Other(e).into())
+        })
+    };
+
+    // Injected messages take the same route through the channel as live chat
+    // so they are dispatched exactly like a real `PRIVMSG` would be.
+    let plugin = Rc::clone(plugin);
+    let inject = Function::<Object, ()>::from_fn(move |message: Object| -> Result<()> {
+        let mut message = ChatMessage::deserialize(Deserializer::new(message))?;
+
+        if message.channel.is_empty() {
+            message.channel = plugin.borrow().config.channel.clone();
+        }
+
+        let command = parse_command(&message, &plugin.borrow().config.syntax);
+
+        sender.send(Command::Chat(message));
+
+        if let Some(command) = command {
+            sender.send(command);
+        }
+
+        Ok(())
+    });
+
+    Ok(Dictionary::from_iter([
+        ("setup", Object::from(setup)),
+        ("connect", Object::from(connect)),
+        ("disconnect", Object::from(disconnect)),
+        ("status", Object::from(status)),
+        ("paths", Object::from(paths)),
+        ("statusline", Object::from(statusline)),
+        ("stats", Object::from(stats)),
+        ("health", Object::from(health)),
+        ("send", Object::from(send)),
+        ("toggle", Object::from(toggle)),
+        ("register_command", Object::from(register_command)),
+        ("unregister_command", Object::from(unregister_command)),
+        ("on_vote_result", Object::from(on_vote_result)),
+        ("on_redemption", Object::from(on_redemption)),
+        ("on_message", Object::from(hook(Hook::Message))),
+        (
+            "on_command_executed",
+            Object::from(hook(Hook::CommandExecuted)),
+        ),
+        ("on_connect", Object::from(hook(Hook::Connect))),
+        ("on_disconnect", Object::from(hook(Hook::Disconnect))),
+        ("on_error", Object::from(on_error)),
+        ("history", Object::from(history)),
+        ("jump", Object::from(jump)),
+        ("inject", Object::from(inject)),
+        ("simulate", Object::from(simulate)),
+        ("viewer", Object::from(viewer)),
+    ]))
+}
@@ -0,0 +1,136 @@
+//! The `announcements` shown every so often, and said in chat as well when
+//! they have `chat = true`, and the reminders chat sets with
+//! `!remind 10m stretch`, each popping up as a notification once. Both are
+//! checked on the plugin's tick timer.
+
+use std::time::{Duration, Instant};
+
+use chrono::{Local, TimeDelta};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{commands, config::Announcement, i18n::tr_with, twitch::Outgoing, Plugin};
+
+/// How far ahead a reminder can be set.
+const MAX_DELAY: Duration = Duration::from_secs(4 * 60 * 60);
+const MAX_PER_USER: usize = 3;
+const MAX_REMINDERS: usize = 50;
+
+#[derive(Default)]
+pub struct Schedule {
+    /// What `due` was set up for, to start over when the config changes.
+    announcements: Vec<Announcement>,
+    /// When each announcement is shown next, in the same order.
+    due: Vec<Instant>,
+    pub reminders: Vec<Reminder>,
+}
+
+pub struct Reminder {
+    at: Instant,
+    author: String,
+    text: String,
+}
+
+/// Sets a reminder from `!remind`, `seconds` from now.
+pub fn remind(
+    plugin: &mut Plugin,
+    channel: String,
+    author: &str,
+    seconds: u64,
+    text: String,
+) -> Result<()> {
+    let delay = Duration::from_secs(seconds);
+    let waiting = plugin
+        .schedule
+        .reminders
+        .iter()
+        .filter(|reminder| reminder.author.eq_ignore_ascii_case(author))
+        .count();
+
+    let refused = if delay > MAX_DELAY {
+        Some(tr_with(
+            "schedule.too_late",
+            &[("hours", &(MAX_DELAY.as_secs() / 3600))],
+        ))
+    } else if waiting >= MAX_PER_USER {
+        Some(tr_with(
+            "schedule.too_many",
+            &[("user", &author), ("count", &waiting)],
+        ))
+    } else if plugin.schedule.reminders.len() >= MAX_REMINDERS {
+        Some(tr_with("schedule.full", &[("count", &MAX_REMINDERS)]))
+    } else {
+        None
+    };
+
+    let result = match refused {
+        Some(message) => Err(api::Error::Other(message).into()),
+        None => Ok(()),
+    };
+    let action = format!("remind {}s {}", seconds, text);
+    plugin.audit.record(&channel, author, action, &result, None);
+    result?;
+
+    plugin.schedule.reminders.push(Reminder {
+        at: Instant::now() + delay,
+        author: author.to_owned(),
+        text,
+    });
+
+    let time = Local::now() + TimeDelta::seconds(seconds as i64);
+    let text = tr_with(
+        "confirm.remind",
+        &[("user", &author), ("time", &time.format("%H:%M"))],
+    );
+    commands::confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+/// Shows the announcements and reminders that are due, called every second
+/// or so.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let now = Instant::now();
+    let schedule = &mut plugin.schedule;
+
+    if schedule.announcements != plugin.config.announcements {
+        schedule.announcements = plugin.config.announcements.clone();
+        schedule.due = schedule
+            .announcements
+            .iter()
+            .map(|announcement| now + announcement.every)
+            .collect();
+    }
+
+    let mut shown = Vec::new();
+    for (announcement, due) in schedule.announcements.iter().zip(&mut schedule.due) {
+        if *due <= now {
+            *due = now + announcement.every;
+            shown.push(announcement.clone());
+        }
+    }
+
+    let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut schedule.reminders)
+        .into_iter()
+        .partition(|reminder| reminder.at <= now);
+    schedule.reminders = waiting;
+
+    for announcement in shown {
+        if announcement.chat {
+            let _ = plugin.outgoing.send(Outgoing::Reply {
+                channel: plugin.config.channel.clone(),
+                text: announcement.text.clone(),
+            });
+        }
+        commands::announce(plugin, &announcement.text)?;
+    }
+
+    for Reminder { author, text, .. } in due {
+        let message = tr_with("schedule.reminder", &[("user", &author), ("text", &text)]);
+        api::notify(&message, LogLevel::Info, &NotifyOpts::default())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! `:TwitchHistory`: searching the chat kept in the session history, with
+//! Telescope and fzf-lua pickers over the same messages.
+
+use std::{cell::RefCell, rc::Rc};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use serde::Serialize;
+
+use crate::{
+    i18n::{tr, tr_with},
+    ui::float,
+    Plugin,
+};
+
+/// `:Telescope twitch history` once the extension is loaded, and
+/// `require("nvim_plugin.fzf").history()`. Both take the messages from
+/// `history()`, jump with `<CR>` and copy the text with `<C-y>`.
+pub const PICKERS: &str = r#"
+lua << EOF
+local function line(entry)
+  return entry.time .. "  " .. entry.author .. ": " .. entry.text
+end
+
+package.preload["telescope._extensions.twitch"] = function()
+  return require("telescope").register_extension({
+    exports = {
+      history = function(opts)
+        opts = opts or {}
+        local twitch = require("nvim_plugin")
+        local actions = require("telescope.actions")
+        local state = require("telescope.actions.state")
+
+        require("telescope.pickers").new(opts, {
+          prompt_title = "Twitch chat",
+          finder = require("telescope.finders").new_table({
+            results = twitch.history(),
+            entry_maker = function(entry)
+              return { value = entry, display = line(entry), ordinal = entry.author .. " " .. entry.text }
+            end,
+          }),
+          sorter = require("telescope.config").values.generic_sorter(opts),
+          attach_mappings = function(prompt, map)
+            actions.select_default:replace(function()
+              actions.close(prompt)
+              twitch.jump(state.get_selected_entry().value.id)
+            end)
+            map({ "i", "n" }, "<C-y>", function()
+              actions.close(prompt)
+              vim.fn.setreg(vim.v.register, state.get_selected_entry().value.text)
+            end)
+            return true
+          end,
+        }):find()
+      end,
+    },
+  })
+end
+
+package.preload["nvim_plugin.fzf"] = function()
+  return {
+    history = function()
+      local twitch = require("nvim_plugin")
+      local entries, lines = twitch.history(), {}
+      for i, entry in ipairs(entries) do
+        lines[i] = i .. "\t" .. line(entry)
+      end
+
+      local function selected(choice)
+        return entries[tonumber(choice[1]:match("^%d+"))]
+      end
+
+      require("fzf-lua").fzf_exec(lines, {
+        prompt = "Twitch chat> ",
+        fzf_opts = { ["--delimiter"] = "\t", ["--with-nth"] = "2.." },
+        actions = {
+          ["default"] = function(choice) twitch.jump(selected(choice).id) end,
+          ["ctrl-y"] = function(choice) vim.fn.setreg(vim.v.register, selected(choice).text) end,
+        },
+      })
+    end,
+  }
+end
+EOF
+"#;
+
+/// A message as `history()` returns it.
+#[derive(Serialize)]
+pub struct Found<'a> {
+    /// Empty for injected messages, which can't be jumped to.
+    pub id: &'a str,
+    pub time: String,
+    pub channel: &'a str,
+    pub author: &'a str,
+    pub text: &'a str,
+}
+
+/// The messages `query` finds, newest first.
+pub fn find<'a>(plugin: &'a Plugin, query: &str) -> Vec<Found<'a>> {
+    plugin
+        .history
+        .search(query)
+        .map(|entry| Found {
+            id: &entry.message.id,
+            time: entry.timestamp.format("%H:%M:%S").to_string(),
+            channel: &entry.message.channel,
+            author: &entry.message.author,
+            text: &entry.message.text,
+        })
+        .collect()
+}
+
+/// Runs `:TwitchHistory [query]`, listing what it finds in a float where
+/// `<CR>` jumps to a message and `y` copies its text.
+pub fn show(plugin: &Rc<RefCell<Plugin>>, query: &str) -> Result<()> {
+    let (lines, found): (Vec<_>, Vec<_>) = find(&plugin.borrow(), query)
+        .into_iter()
+        .map(|found| {
+            let line = format!("{}  {}: {}", found.time, found.author, found.text);
+            (line, (found.id.to_owned(), found.text.to_owned()))
+        })
+        .unzip();
+
+    if lines.is_empty() {
+        api::notify(&tr("history.none"), LogLevel::Info, &NotifyOpts::default())?;
+        return Ok(());
+    }
+
+    let title = tr_with("history.title", &[("count", &lines.len())]);
+    let mut buffer = float::open(&title, lines)?;
+    let found = Rc::new(found);
+
+    let selected = |found: &[(String, String)]| {
+        let (line, _) = api::get_current_win().get_cursor().ok()?;
+        found.get(line.checked_sub(1)?).cloned()
+    };
+
+    let plugin = Rc::clone(plugin);
+    let jump_found = Rc::clone(&found);
+    let opts = SetKeymapOpts::builder()
+        .nowait(true)
+        .silent(true)
+        .callback(move |()| -> Result<()> {
+            let Some((id, _)) = selected(&jump_found) else {
+                return Ok(());
+            };
+
+            api::get_current_win().close(true)?;
+            jump(&mut plugin.borrow_mut(), &id)
+        })
+        .build();
+    buffer.set_keymap(Mode::Normal, "<CR>", "", &opts)?;
+
+    let opts = SetKeymapOpts::builder()
+        .nowait(true)
+        .silent(true)
+        .callback(move |()| -> Result<()> {
+            let Some((_, text)) = selected(&found) else {
+                return Ok(());
+            };
+
+            let register = api::get_vvar::<String>("register")?;
+            api::call_function::<_, i64>("setreg", (register, text))?;
+            Ok(())
+        })
+        .build();
+    buffer.set_keymap(Mode::Normal, "y", "", &opts)?;
+
+    Ok(())
+}
+
+/// Focuses the chat window on the message `id`, if it is still there.
+/// Only `!nvim` messages are shown in it, the rest can't be jumped to.
+pub fn jump(plugin: &mut Plugin, id: &str) -> Result<()> {
+    if !plugin.chat.jump(id)? {
+        api::notify(
+            &tr("history.not_shown"),
+            LogLevel::Info,
+            &NotifyOpts::default(),
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! A rough "chat mood" estimate from a small word and emote lexicon. It is
+//! meant as a fun indicator, not as analysis.
+
+/// Weight of each new message in the moving average, so roughly the last
+/// twenty messages shape the mood.
+const SMOOTHING: f64 = 0.1;
+
+const LEXICON: &[(&str, f64)] = &[
+    // Emotes
+    ("pogchamp", 1.0),
+    ("pog", 1.0),
+    ("poggers", 1.0),
+    ("kreygasm", 0.8),
+    ("seemsgood", 0.8),
+    ("<3", 0.8),
+    ("lul", 0.6),
+    ("kekw", 0.6),
+    ("lol", 0.5),
+    ("gg", 0.7),
+    ("notlikethis", -0.7),
+    ("biblethump", -0.6),
+    ("residentsleeper", -0.8),
+    ("wutface", -0.6),
+    ("sadge", -0.7),
+    ("f", -0.3),
+    // Words
+    ("love", 0.9),
+    ("great", 0.8),
+    ("awesome", 0.9),
+    ("amazing", 0.9),
+    ("nice", 0.6),
+    ("cool", 0.5),
+    ("thanks", 0.6),
+    ("fun", 0.6),
+    ("hype", 0.8),
+    ("wow", 0.5),
+    ("good", 0.5),
+    ("bad", -0.6),
+    ("boring", -0.8),
+    ("hate", -0.9),
+    ("awful", -0.9),
+    ("terrible", -0.9),
+    ("worst", -0.9),
+    ("sad", -0.6),
+    ("lag", -0.4),
+    ("cringe", -0.6),
+    ("ugh", -0.5),
+];
+
+/// A moving average of message scores between -1 and 1.
+#[derive(Default)]
+pub struct Sentiment {
+    mood: f64,
+    scored: usize,
+}
+
+impl Sentiment {
+    /// Messages without any known word leave the mood untouched.
+    pub fn push(&mut self, text: &str) {
+        if let Some(score) = score(text) {
+            self.mood += SMOOTHING * (score - self.mood);
+            self.scored += 1;
+        }
+    }
+
+    pub fn mood(&self) -> Option<f64> {
+        (self.scored > 0).then_some(self.mood)
+    }
+
+    /// An emoji followed by the score, e.g. `😄 +0.42`, for the statusline.
+    pub fn indicator(&self) -> String {
+        let Some(mood) = self.mood() else {
+            return "😐".to_owned();
+        };
+
+        let emoji = match mood {
+            m if m >= 0.5 => "😄",
+            m if m >= 0.15 => "🙂",
+            m if m > -0.15 => "😐",
+            m if m > -0.5 => "🙁",
+            _ => "😠",
+        };
+
+        format!("{} {:+.2}", emoji, mood)
+    }
+}
+
+/// The average weight of the known words in `text`.
+fn score(text: &str) -> Option<f64> {
+    let weights = text
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| c.is_ascii_punctuation() && c != '<')
+                .to_lowercase()
+        })
+        .filter_map(|word| {
+            LEXICON
+                .iter()
+                .find(|(entry, _)| *entry == word)
+                .map(|(_, weight)| *weight)
+        })
+        .collect::<Vec<_>>();
+
+    (!weights.is_empty()).then(|| weights.iter().sum::<f64>() / weights.len() as f64)
+}
@@ -0,0 +1,322 @@
+//! `:TwitchSimulate <file> [speed]` and `simulate(messages)` from Lua: play
+//! recorded or scripted chat through a [`Relay`] of its own, which hands it
+//! over exactly like a live connection would. Handy for working on the
+//! plugin, recording demos and testing without a network.
+//!
+//! A script is a JSON list, or one JSON object per line. Each is a message
+//! as `inject()` takes it, or an event with a `type`: `subscription`,
+//! `raid`, `gift` or `milestone`. `delay` is how many seconds to wait before
+//! it, one by default; with a `timestamp` instead the recorded gaps are
+//! kept, so a `:TwitchExport` JSON file plays back as it happened.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, FixedOffset};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    backend::{ChatEvent, Relay},
+    commands::CommandSender,
+    i18n::{tr, tr_with},
+    twitch::{ChatMessage, GIFT_QUIET},
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 1] = ["stop"];
+
+/// Between events with neither a `delay` nor a `timestamp`.
+const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+pub const SPEEDS: (f64, f64) = (0.1, 100.0);
+
+#[derive(Default)]
+pub struct Simulation {
+    /// Set to stop the one playing.
+    stop: Option<Arc<AtomicBool>>,
+}
+
+/// The scripted form of the [`ChatEvent`]s, with the channel filled in
+/// before it is read.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Scripted {
+    /// Boxed like in the [`ChatEvent`].
+    Message(Box<ChatMessage>),
+    Subscription {
+        channel: String,
+        user: String,
+        #[serde(default = "one")]
+        months: u64,
+        #[serde(default)]
+        resub: bool,
+    },
+    Raid {
+        channel: String,
+        from: String,
+        #[serde(default = "one")]
+        viewers: u64,
+    },
+    Gift {
+        channel: String,
+        #[serde(default)]
+        gifter: Option<String>,
+        #[serde(default = "tier_one")]
+        tier: String,
+        recipient: String,
+    },
+    Milestone {
+        channel: String,
+        user: String,
+        category: String,
+        value: u64,
+    },
+}
+
+fn one() -> u64 {
+    1
+}
+
+fn tier_one() -> String {
+    "1000".to_owned()
+}
+
+impl From<Scripted> for ChatEvent {
+    fn from(scripted: Scripted) -> Self {
+        match scripted {
+            Scripted::Message(message) => ChatEvent::Message(message),
+            Scripted::Subscription {
+                channel,
+                user,
+                months,
+                resub,
+            } => ChatEvent::Subscription {
+                channel,
+                user,
+                months,
+                resub,
+            },
+            Scripted::Raid {
+                channel,
+                from,
+                viewers,
+            } => ChatEvent::Raid {
+                channel,
+                from,
+                viewers,
+            },
+            Scripted::Gift {
+                channel,
+                gifter,
+                tier,
+                recipient,
+            } => ChatEvent::Gift {
+                channel,
+                gifter,
+                tier,
+                recipient,
+            },
+            Scripted::Milestone {
+                channel,
+                user,
+                category,
+                value,
+            } => ChatEvent::Milestone {
+                channel,
+                user,
+                category,
+                value,
+            },
+        }
+    }
+}
+
+/// Runs `:TwitchSimulate <file> [speed]` or `:TwitchSimulate stop`.
+pub fn run(plugin: &mut Plugin, sender: &CommandSender, args: Option<&str>) -> Result<()> {
+    let words: Vec<&str> = args.unwrap_or_default().split_whitespace().collect();
+
+    let (file, speed) = match words.as_slice() {
+        ["stop"] => {
+            return match plugin.simulation.stop.take() {
+                Some(stop) => {
+                    stop.store(true, Ordering::Relaxed);
+                    notify(&tr("simulate.stopped"), LogLevel::Info)
+                }
+                None => notify(&tr("simulate.none"), LogLevel::Error),
+            }
+        }
+        [file] => (*file, Some(1.0)),
+        [file, speed] => (*file, speed.parse().ok()),
+        _ => ("", None),
+    };
+    let Some(speed) = speed.filter(|speed| (SPEEDS.0..=SPEEDS.1).contains(speed)) else {
+        let text = tr_with(
+            "simulate.usage",
+            &[("slowest", &SPEEDS.0), ("fastest", &SPEEDS.1)],
+        );
+        return notify(&text, LogLevel::Error);
+    };
+
+    let path: String = api::call_function("expand", (file,))?;
+    let events = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| read(&text))
+        .and_then(|values| parse(values, &plugin.config.channel));
+
+    match events {
+        Ok(events) => {
+            let text = tr_with(
+                "simulate.started",
+                &[("count", &events.len()), ("path", &path)],
+            );
+            start(plugin, sender, events, speed);
+            notify(&text, LogLevel::Info)
+        }
+        Err(e) => notify(
+            &tr_with("simulate.invalid", &[("path", &path), ("error", &e)]),
+            LogLevel::Error,
+        ),
+    }
+}
+
+/// `simulate(messages)` from Lua, a list of what a script line holds.
+pub fn from_lua(
+    plugin: &mut Plugin,
+    sender: &CommandSender,
+    values: Vec<Value>,
+) -> std::result::Result<(), String> {
+    let events = parse(values, &plugin.config.channel)?;
+    start(plugin, sender, events, 1.0);
+
+    Ok(())
+}
+
+/// A JSON list or JSON Lines, blank lines left out.
+pub fn read(text: &str) -> std::result::Result<Vec<Value>, String> {
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    }
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// The events of a script, each with how long to wait before it. The
+/// `command` lines of a `:TwitchExport` are left out, the messages bring
+/// them back.
+fn parse(
+    values: Vec<Value>,
+    channel: &str,
+) -> std::result::Result<Vec<(Duration, ChatEvent)>, String> {
+    let mut events = Vec::new();
+    let mut previous: Option<DateTime<FixedOffset>> = None;
+
+    for (i, mut value) in values.into_iter().enumerate() {
+        let Value::Object(fields) = &mut value else {
+            return Err(format!("event {}: expected an object", i + 1));
+        };
+
+        if fields.remove("kind").as_ref().and_then(Value::as_str) == Some("command") {
+            continue;
+        }
+
+        let timestamp = fields
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok());
+        let delay = match (fields.get("delay"), timestamp, previous) {
+            (Some(delay), ..) => delay
+                .as_f64()
+                .and_then(|delay| Duration::try_from_secs_f64(delay).ok())
+                .ok_or_else(|| format!("event {}: delay must be seconds", i + 1))?,
+            (None, Some(at), Some(before)) => (at - before).to_std().unwrap_or_default(),
+            (None, None, _) if !events.is_empty() => DEFAULT_DELAY,
+            (None, ..) => Duration::ZERO,
+        };
+        previous = timestamp.or(previous);
+
+        fields
+            .entry("type")
+            .or_insert_with(|| Value::from("message"));
+        if fields
+            .get("channel")
+            .and_then(Value::as_str)
+            .is_none_or(str::is_empty)
+        {
+            fields.insert("channel".to_owned(), Value::from(channel));
+        }
+
+        let scripted =
+            Scripted::deserialize(value).map_err(|e| format!("event {}: {}", i + 1, e))?;
+        events.push((delay, ChatEvent::from(scripted)));
+    }
+
+    Ok(events)
+}
+
+/// Plays `events` on a thread of their own, `speed` times as fast, after
+/// stopping the simulation that was playing.
+fn start(
+    plugin: &mut Plugin,
+    sender: &CommandSender,
+    events: Vec<(Duration, ChatEvent)>,
+    speed: f64,
+) {
+    if let Some(previous) = plugin.simulation.stop.take() {
+        previous.store(true, Ordering::Relaxed);
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    plugin.simulation.stop = Some(Arc::clone(&stop));
+
+    let config = plugin.config.clone();
+    let syntax = Arc::clone(&plugin.syntax);
+    let sender = sender.clone();
+
+    thread::spawn(move || {
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+        else {
+            return;
+        };
+
+        runtime.block_on(async {
+            let relay = Relay::new(&config, syntax, sender);
+            let gifts = events
+                .iter()
+                .any(|(_, event)| matches!(event, ChatEvent::Gift { .. }));
+
+            for (delay, event) in events {
+                tokio::time::sleep(delay.div_f64(speed)).await;
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                relay.forward(event);
+            }
+
+            // Gift trains go out once no more gifts came for a while.
+            if gifts {
+                tokio::time::sleep(GIFT_QUIET).await;
+            }
+        });
+    });
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! `!snippet <name>`: chat inserts one of the `snippets` from the config at
+//! the cursor, with `{user}`, `{channel}`, `{date}` and `{time}` filled in.
+//! Nothing but the streamer's own templates can be inserted.
+
+use chrono::Local;
+use nvim_oxi::{
+    api::{self, types::*},
+    Result,
+};
+
+use crate::{commands, i18n::tr_with, Plugin};
+
+/// Inserts the snippet `name` after the cursor, as `p` would.
+pub fn insert(plugin: &mut Plugin, channel: String, author: &str, name: &str) -> Result<()> {
+    let result = match plugin.config.snippets.get(name) {
+        Some(template) => {
+            let text = expand(template, &channel, author);
+            api::put(text.split('\n'), RegisterType::Charwise, true, true).map_err(Into::into)
+        }
+        None => {
+            let names: Vec<_> = plugin.config.snippets.keys().map(String::as_str).collect();
+            Err(api::Error::Other(tr_with(
+                "snippets.unknown",
+                &[("name", &name), ("snippets", &names.join(", "))],
+            ))
+            .into())
+        }
+    };
+
+    let action = format!("snippet {}", name);
+    plugin.audit.record(&channel, author, action, &result, None);
+    result?;
+
+    let text = tr_with("confirm.snippet", &[("name", &name), ("user", &author)]);
+    commands::confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+fn expand(template: &str, channel: &str, author: &str) -> String {
+    let now = Local::now();
+
+    template
+        .replace("{user}", author)
+        .replace("{channel}", channel.trim_start_matches('#'))
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H:%M").to_string())
+}
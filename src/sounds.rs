@@ -0,0 +1,170 @@
+//! `sounds`: a sound played on raids, subs and chat commands, by an
+//! external player so nothing is decoded in the editor. Built only with
+//! `--features sounds`; `:TwitchMute sounds` silences them for the session.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use nvim_oxi::Result;
+
+use crate::{config::Sound, Plugin};
+
+/// Tried in order when `sounds.player` isn't set, with how each takes the
+/// volume from 0 to 1.
+#[cfg(feature = "sounds")]
+const PLAYERS: [(&str, VolumeArgs); 4] = [
+    ("paplay", |volume| {
+        vec![format!("--volume={}", (volume * 65536.0).round())]
+    }),
+    ("afplay", |volume| vec!["-v".to_owned(), volume.to_string()]),
+    ("mpv", |volume| {
+        vec![
+            "--no-video".to_owned(),
+            "--really-quiet".to_owned(),
+            format!("--volume={}", percent(volume)),
+        ]
+    }),
+    ("ffplay", |volume| {
+        vec![
+            "-nodisp".to_owned(),
+            "-autoexit".to_owned(),
+            "-loglevel".to_owned(),
+            "quiet".to_owned(),
+            "-volume".to_owned(),
+            percent(volume).to_string(),
+        ]
+    }),
+];
+
+/// The arguments giving a player the volume, from 0 to 1.
+#[cfg(feature = "sounds")]
+type VolumeArgs = fn(f64) -> Vec<String>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SoundEvent {
+    Command,
+    Raid,
+    Sub,
+}
+
+impl SoundEvent {
+    /// Its key in `sounds`.
+    fn name(self) -> &'static str {
+        match self {
+            SoundEvent::Command => "command",
+            SoundEvent::Raid => "raid",
+            SoundEvent::Sub => "sub",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Sounds {
+    pub muted: bool,
+    /// By event, set while its player runs.
+    playing: HashMap<&'static str, Arc<AtomicBool>>,
+}
+
+/// Plays the sound set for `event`, if there is one, sounds aren't muted
+/// and the last one for the event is done, so a chat spamming commands
+/// can't start a player for each. It plays on while the editor goes on.
+pub fn play(plugin: &mut Plugin, event: SoundEvent) {
+    if plugin.sounds.muted {
+        return;
+    }
+    let Some(sound) = plugin.config.sounds.events.get(event.name()) else {
+        return;
+    };
+
+    let playing = plugin.sounds.playing.entry(event.name()).or_default();
+    if playing.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let volume = plugin.config.sounds.volume * sound.volume;
+    let player = plugin.config.sounds.player.as_deref();
+    if let Err(e) = spawn(player, sound, volume, Arc::clone(playing)) {
+        playing.store(false, Ordering::Release);
+        plugin.errors.push("Sounds", &e);
+    }
+}
+
+#[cfg(feature = "sounds")]
+fn spawn(
+    player: Option<&[String]>,
+    sound: &Sound,
+    volume: f64,
+    playing: Arc<AtomicBool>,
+) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    use nvim_oxi::api;
+
+    let file: String = api::call_function("expand", (sound.file.as_str(),))?;
+
+    let (program, args) = match player {
+        Some([program, args @ ..]) => {
+            let args = args
+                .iter()
+                .map(|arg| {
+                    arg.replace("{file}", &file)
+                        .replace("{volume}", &percent(volume).to_string())
+                })
+                .collect();
+            (program.clone(), args)
+        }
+        _ => {
+            let Some((program, args)) = PLAYERS.iter().find(|(program, _)| {
+                api::call_function::<_, i64>("executable", (*program,))
+                    .is_ok_and(|found| found == 1)
+            }) else {
+                return Err(api::Error::Other(
+                    "no sound player found, set sounds.player".to_owned(),
+                )
+                .into());
+            };
+
+            let mut args = args(volume);
+            args.push(file);
+            (program.to_string(), args)
+        }
+    };
+
+    let mut child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| api::Error::Other(format!("can't run {}: {}", program, e)))?;
+
+    // Waited for elsewhere, so the player doesn't linger as a zombie.
+    std::thread::spawn(move || {
+        let _ = child.wait();
+        playing.store(false, Ordering::Release);
+    });
+
+    Ok(())
+}
+
+/// Nothing is configured without the feature, the config says so.
+#[cfg(not(feature = "sounds"))]
+fn spawn(
+    _player: Option<&[String]>,
+    _sound: &Sound,
+    _volume: f64,
+    playing: Arc<AtomicBool>,
+) -> Result<()> {
+    playing.store(false, Ordering::Release);
+    Ok(())
+}
+
+#[cfg(feature = "sounds")]
+fn percent(volume: f64) -> u32 {
+    (volume * 100.0).round() as u32
+}
@@ -0,0 +1,112 @@
+//! `!goto <file>:<line>`: lets chat point the streamer at a line of the
+//! project, which is opened and briefly highlighted.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::CmdInfos},
+    libuv::TimerHandle,
+    schedule, Array, Result,
+};
+
+use crate::{audit::Undo, commands, i18n::tr_with, twitch::Outgoing, Plugin};
+
+/// How long the line stays highlighted.
+const DURATION: Duration = Duration::from_secs(3);
+
+/// Opens `file` at `line` if it is a file inside the working directory,
+/// otherwise tells chat there is no such file.
+pub fn goto(
+    plugin: &mut Plugin,
+    channel: String,
+    author: &str,
+    file: &str,
+    line: usize,
+) -> Result<()> {
+    let cwd: String = api::call_function("getcwd", Array::new())?;
+
+    let Some(path) = resolve(Path::new(&cwd), file) else {
+        let text = tr_with("goto.unknown", &[("user", &author), ("file", &file)]);
+        let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+        return Ok(());
+    };
+
+    let undo = Undo::jump()?;
+    let result = open(&path);
+    plugin.audit.record(
+        &channel,
+        author,
+        format!("goto {} {}", file, line),
+        &result,
+        Some(undo),
+    );
+    result?;
+
+    let mut buffer = api::get_current_buf();
+    let line = line.clamp(1, buffer.line_count()?.max(1));
+
+    api::get_current_win().set_cursor(line, 0)?;
+    api::command("normal! zz")?;
+
+    let namespace = api::create_namespace("nvim_twitch_spotlight");
+    buffer.clear_namespace(namespace, ..)?;
+
+    let opts = SetExtmarkOpts::builder()
+        .line_hl_group("TwitchSpotlight")
+        .build();
+    buffer.set_extmark(namespace, line - 1, 0, &opts)?;
+
+    TimerHandle::once(DURATION, move || {
+        let mut buffer = buffer.clone();
+        schedule(move |_| match buffer.is_valid() {
+            true => buffer.clear_namespace(namespace, ..),
+            false => Ok(()),
+        });
+        Ok::<_, nvim_oxi::Error>(())
+    })?;
+
+    let text = tr_with(
+        "confirm.goto",
+        &[("file", &file), ("line", &line), ("user", &author)],
+    );
+    commands::confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+/// Opens `path` in the current window.
+pub fn open(path: &Path) -> Result<()> {
+    // Structured, so the path is only ever the argument.
+    let infos = CmdInfos::builder()
+        .cmd("edit")
+        .args([path.to_string_lossy()])
+        .build();
+    api::cmd(&infos, &CmdOpts::default())?;
+
+    Ok(())
+}
+
+/// The file `file` names relative to `cwd`. Anything outside it, hidden
+/// like `.env` or not a regular file is left out, since whatever is opened
+/// is shown on stream.
+pub fn resolve(cwd: &Path, file: &str) -> Option<PathBuf> {
+    let relative = Path::new(file);
+
+    let plain = relative.components().all(|component| match component {
+        Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+        Component::CurDir => true,
+        _ => false,
+    });
+    if !plain {
+        return None;
+    }
+
+    let cwd = cwd.canonicalize().ok()?;
+    let path = cwd.join(relative).canonicalize().ok()?;
+
+    // Symlinks may still lead elsewhere.
+    (path.starts_with(&cwd) && path.is_file()).then_some(path)
+}
@@ -0,0 +1,142 @@
+//! Plugin state that survives restarts, kept as JSON in
+//! `stdpath("state")/nvim-twitch/state.json`.
+//!
+//! Values are read once at startup and written back when Neovim exits or the
+//! state is reset. Each layout change bumps [`VERSION`] and appends a step to
+//! [`MIGRATIONS`], so older files are upgraded in place on load.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const FILE_NAME: &str = "state.json";
+
+const VERSION: u64 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` file to version `n + 1`.
+const MIGRATIONS: [fn(Value) -> Value; VERSION as usize] = [v0_to_v1];
+
+#[derive(Default, Serialize, Deserialize)]
+struct StateFile {
+    version: u64,
+    values: BTreeMap<String, Value>,
+}
+
+pub struct State {
+    path: PathBuf,
+    file: StateFile,
+    dirty: bool,
+}
+
+impl State {
+    /// Loads the state from `dir`. A file that can't be read is moved aside
+    /// and the state starts out empty, the error is still returned so it can
+    /// be reported.
+    pub fn open(dir: &Path) -> (State, Option<String>) {
+        let path = dir.join(FILE_NAME);
+
+        let (file, error) = match load(&path) {
+            Ok(file) => (file, None),
+            Err(e) => {
+                let _ = fs::rename(&path, path.with_extension("json.bak"));
+                (StateFile::default(), Some(e))
+            }
+        };
+
+        let state = State {
+            path,
+            file,
+            dirty: false,
+        };
+
+        (state, error)
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.path.parent()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.file.values.get(key)?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    pub fn set<T: Serialize>(&mut self, key: &str, value: T) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.file.values.insert(key.to_owned(), value);
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the state to disk if anything changed since the last flush.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        self.file.version = VERSION;
+
+        let data = serde_json::to_vec_pretty(&self.file).map_err(|e| e.to_string())?;
+
+        // Written next to the real file and renamed so a crash never leaves
+        // a half-written state behind.
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.path).map_err(|e| e.to_string())?;
+
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    /// Forgets every stored value and removes the file.
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.file = StateFile::default();
+        self.dirty = false;
+
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<StateFile, String> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(StateFile::default()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut value: Value = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version > VERSION {
+        return Err(format!(
+            "{} was written by a newer version of the plugin (state version {})",
+            path.display(),
+            version
+        ));
+    }
+
+    for migrate in &MIGRATIONS[version as usize..] {
+        value = migrate(value);
+    }
+
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// A file without a version, e.g. written by hand, is a bare object of values.
+fn v0_to_v1(value: Value) -> Value {
+    json!({ "version": 1, "values": value })
+}
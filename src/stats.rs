@@ -0,0 +1,420 @@
+//! Counts gathered over the current session for `:TwitchStats`, a
+//! dashboard that keeps itself up to date while it is open.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+    time::Instant,
+};
+
+use chrono::{DateTime, Duration, Local};
+use nvim_oxi::{
+    api::{self, opts::*, Buffer, Window},
+    Result,
+};
+use serde::Serialize;
+
+use crate::{
+    i18n::{tr, tr_with},
+    metrics,
+    twitch::{cheer, ChatMessage},
+    ui::float,
+    Plugin,
+};
+
+/// How often the open dashboard is redrawn.
+const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How many entries each chart shows.
+const TOP: usize = 10;
+
+const BAR_WIDTH: usize = 20;
+
+/// Minutes per heatmap row, so each row covers an hour of the stream.
+const HEATMAP_ROW: usize = 60;
+
+/// Activity levels from an idle minute to the busiest one.
+const LEVELS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Words too common to say anything about the chat.
+const STOPWORDS: &[&str] = &[
+    "the", "and", "you", "that", "this", "for", "are", "was", "but", "not", "with", "have", "its",
+    "it's", "just", "what", "your", "can", "all", "get", "out", "i'm", "how", "like", "they",
+    "him", "her", "she", "his", "from", "there", "then", "than", "when", "who", "why", "has",
+    "had", "did", "does",
+];
+
+#[derive(Serialize)]
+pub struct SessionStats {
+    started: DateTime<Local>,
+    /// Messages received in each minute since `started`.
+    per_minute: Vec<u64>,
+    /// Messages per chatter.
+    chatters: HashMap<String, u64>,
+    /// Executed chat commands by name, e.g. `colorscheme`.
+    commands: HashMap<String, u64>,
+    /// Subscriptions and raids.
+    alerts: HashMap<String, u64>,
+    emotes: HashMap<String, u64>,
+    words: HashMap<String, u64>,
+    /// Distinct chatters, emotes and words kept each.
+    #[serde(skip)]
+    limit: usize,
+}
+
+/// What `stats()` returns: the counts along with the totals the dashboard
+/// shows.
+#[derive(Serialize)]
+pub struct Snapshot<'a> {
+    #[serde(flatten)]
+    stats: &'a SessionStats,
+    totals: Totals,
+}
+
+#[derive(Serialize)]
+struct Totals {
+    messages: u64,
+    rendered: u64,
+    dropped: u64,
+    commands: u64,
+    errors: u64,
+    reconnects: u64,
+}
+
+/// The open `:TwitchStats` float.
+#[derive(Default)]
+pub struct Dashboard {
+    window: Option<(Window, Buffer)>,
+    refreshed: Option<Instant>,
+}
+
+impl SessionStats {
+    pub fn new(limit: usize) -> Self {
+        SessionStats {
+            started: Local::now(),
+            per_minute: Vec::new(),
+            chatters: HashMap::new(),
+            commands: HashMap::new(),
+            alerts: HashMap::new(),
+            emotes: HashMap::new(),
+            words: HashMap::new(),
+            limit,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+
+        for counts in [&mut self.chatters, &mut self.emotes, &mut self.words] {
+            while counts.len() > limit {
+                evict(counts);
+            }
+        }
+    }
+
+    /// Distinct keys across all counts.
+    pub fn len(&self) -> usize {
+        self.chatters.len()
+            + self.commands.len()
+            + self.alerts.len()
+            + self.emotes.len()
+            + self.words.len()
+    }
+
+    pub fn record(&mut self, message: &ChatMessage) {
+        let minute = (Local::now() - self.started).num_minutes().max(0) as usize;
+
+        if self.per_minute.len() <= minute {
+            self.per_minute.resize(minute + 1, 0);
+        }
+        self.per_minute[minute] += 1;
+
+        bump(&mut self.chatters, &message.author, self.limit);
+
+        for emote in &message.emotes {
+            bump(&mut self.emotes, emote, self.limit);
+        }
+
+        let emotes: HashSet<&str> = message.emotes.iter().map(String::as_str).collect();
+
+        let cheers = match message.bits {
+            Some(_) => cheer::find(&message.text),
+            None => Vec::new(),
+        };
+        let cheermotes: HashSet<&str> = cheers
+            .iter()
+            .map(|cheer| &message.text[cheer.start..cheer.end])
+            .collect();
+
+        for word in message.text.split_whitespace() {
+            if emotes.contains(word) || cheermotes.contains(word) || word.starts_with('!') {
+                continue;
+            }
+
+            let word = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase();
+
+            if word.chars().count() >= 3 && !STOPWORDS.contains(&word.as_str()) {
+                bump(&mut self.words, &word, self.limit);
+            }
+        }
+    }
+
+    pub fn record_command(&mut self, name: &str) {
+        *self.commands.entry(name.to_owned()).or_default() += 1;
+    }
+
+    pub fn record_alert(&mut self, kind: &str) {
+        *self.alerts.entry(kind.to_owned()).or_default() += 1;
+    }
+
+    /// Writes every count as a `kind,key,count` row, which keeps the file
+    /// easy to pivot in a spreadsheet or load with pandas.
+    pub fn export_csv(&self, path: &Path) -> std::result::Result<(), String> {
+        let mut csv = String::from("kind,key,count\n");
+
+        for (minute, count) in self.per_minute.iter().enumerate() {
+            let time = self.started + Duration::minutes(minute as i64);
+            row(
+                &mut csv,
+                "minute",
+                &time.format("%Y-%m-%dT%H:%M").to_string(),
+                *count,
+            );
+        }
+
+        for (kind, counts) in [
+            ("user", &self.chatters),
+            ("command", &self.commands),
+            ("alert", &self.alerts),
+            ("emote", &self.emotes),
+            ("word", &self.words),
+        ] {
+            let sorted: BTreeMap<_, _> = counts.iter().collect();
+
+            for (key, count) in sorted {
+                row(&mut csv, kind, key, *count);
+            }
+        }
+
+        fs::write(path, csv).map_err(|e| e.to_string())
+    }
+
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot {
+            stats: self,
+            totals: self.totals(),
+        }
+    }
+
+    fn totals(&self) -> Totals {
+        Totals {
+            messages: self.per_minute.iter().sum(),
+            rendered: metrics::MESSAGES_RENDERED.get(),
+            dropped: metrics::MESSAGES_DROPPED.get(),
+            commands: self.commands.values().sum(),
+            errors: metrics::ERRORS.get(),
+            reconnects: metrics::RECONNECTS.get(),
+        }
+    }
+
+    fn dashboard(&self) -> Vec<String> {
+        let totals = self.totals();
+        let minutes = (Local::now() - self.started).num_minutes();
+
+        let mut lines = vec![
+            tr_with(
+                "stats.since",
+                &[
+                    ("time", &self.started.format("%H:%M")),
+                    ("minutes", &minutes),
+                ],
+            ),
+            tr_with(
+                "stats.messages",
+                &[
+                    ("count", &totals.messages),
+                    ("rendered", &totals.rendered),
+                    ("dropped", &totals.dropped),
+                ],
+            ),
+            tr_with("stats.commands_executed", &[("count", &totals.commands)]),
+            tr_with(
+                "stats.errors",
+                &[
+                    ("count", &totals.errors),
+                    ("reconnects", &totals.reconnects),
+                ],
+            ),
+        ];
+
+        for (title, counts) in [
+            ("stats.chatters", &self.chatters),
+            ("stats.commands", &self.commands),
+            ("stats.emotes", &self.emotes),
+            ("stats.words", &self.words),
+        ] {
+            lines.push(String::new());
+            lines.push(tr(title));
+            lines.extend(chart(counts));
+        }
+
+        lines
+    }
+
+    /// One row per hour of the session, one character per minute, darker
+    /// for busier minutes, so spikes stand out when looking for VOD moments.
+    pub fn show_heatmap(&self) -> Result<()> {
+        let max = self.per_minute.iter().copied().max().unwrap_or(0);
+
+        if max == 0 {
+            return float::show(&tr("stats.heatmap_title"), vec![tr("stats.none")]);
+        }
+
+        let mut lines: Vec<String> = self
+            .per_minute
+            .chunks(HEATMAP_ROW)
+            .enumerate()
+            .map(|(row, counts)| {
+                let start = self.started + Duration::minutes((row * HEATMAP_ROW) as i64);
+
+                let cells: String = counts
+                    .iter()
+                    .map(|&n| {
+                        let level = (n * (LEVELS.len() as u64 - 1)).div_ceil(max) as usize;
+                        LEVELS[level]
+                    })
+                    .collect();
+
+                format!("{} │{}│", start.format("%H:%M"), cells)
+            })
+            .collect();
+
+        lines.push(String::new());
+        lines.push(tr_with("stats.heatmap_peak", &[("count", &max)]));
+
+        float::show(&tr("stats.heatmap_title"), lines)
+    }
+}
+
+/// Runs `:TwitchStats`, opening the dashboard.
+pub fn show(plugin: &mut Plugin) -> Result<()> {
+    let buffer = float::open(&tr("stats.title"), plugin.stats.dashboard())?;
+    plugin.dashboard.window = Some((api::get_current_win(), buffer));
+    plugin.dashboard.refreshed = Some(Instant::now());
+
+    Ok(())
+}
+
+/// Redraws the dashboard while it is open, called every second or so.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let Some((window, buffer)) = &mut plugin.dashboard.window else {
+        return Ok(());
+    };
+    if !window.is_valid() || !buffer.is_valid() {
+        plugin.dashboard.window = None;
+        return Ok(());
+    }
+    if plugin
+        .dashboard
+        .refreshed
+        .is_some_and(|refreshed| refreshed.elapsed() < REFRESH_INTERVAL)
+    {
+        return Ok(());
+    }
+
+    let lines = plugin.stats.dashboard();
+    buffer.set_lines(.., false, lines.iter().map(String::as_str))?;
+
+    let opts = OptionOpts::builder().scope(OptionScope::Global).build();
+    let rows = api::get_option_value::<u32>("lines", &opts)?;
+    window.set_height((lines.len() as u32).min(rows.saturating_sub(4)).max(1))?;
+
+    plugin.dashboard.refreshed = Some(Instant::now());
+
+    Ok(())
+}
+
+fn row(csv: &mut String, kind: &str, key: &str, count: u64) {
+    csv.push_str(kind);
+    csv.push(',');
+
+    if key.contains([',', '"', '\n', '\r']) {
+        csv.push('"');
+        csv.push_str(&key.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(key);
+    }
+
+    csv.push(',');
+    csv.push_str(&count.to_string());
+    csv.push('\n');
+}
+
+fn top(counts: &HashMap<String, u64>) -> Vec<(&str, u64)> {
+    let mut top: Vec<_> = counts.iter().map(|(key, &n)| (key.as_str(), n)).collect();
+
+    // Ties are broken alphabetically so the chart doesn't reshuffle.
+    top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    top.truncate(TOP);
+
+    top
+}
+
+fn chart(counts: &HashMap<String, u64>) -> Vec<String> {
+    let top = top(counts);
+
+    if top.is_empty() {
+        return vec![format!("  {}", tr("stats.none"))];
+    }
+
+    let max = top[0].1;
+    let label_width = top
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    top.iter()
+        .map(|&(key, n)| {
+            let bar = ((n * BAR_WIDTH as u64).div_ceil(max)) as usize;
+
+            format!(
+                "  {:<width$} {} {}",
+                key,
+                "█".repeat(bar),
+                n,
+                width = label_width
+            )
+        })
+        .collect()
+}
+
+/// Counts `key`, making room by dropping the rarest key once `counts` holds
+/// `limit` of them. Those would never make a top list anyway.
+fn bump(counts: &mut HashMap<String, u64>, key: &str, limit: usize) {
+    if let Some(count) = counts.get_mut(key) {
+        *count += 1;
+        return;
+    }
+
+    if counts.len() >= limit {
+        evict(counts);
+    }
+
+    counts.insert(key.to_owned(), 1);
+}
+
+fn evict(counts: &mut HashMap<String, u64>) {
+    let rarest = counts
+        .iter()
+        .min_by_key(|(_, count)| **count)
+        .map(|(key, _)| key.clone());
+
+    if let Some(key) = rarest {
+        counts.remove(&key);
+    }
+}
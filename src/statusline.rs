@@ -0,0 +1,61 @@
+//! The statusline component behind `statusline()` and `TwitchStatusline()`,
+//! for lualine, heirline or a plain `statusline`.
+
+use serde::Serialize;
+
+use crate::{i18n::tr_with, twitch::ConnectionStatus, Plugin};
+
+#[derive(Serialize)]
+pub struct Statusline<'a> {
+    #[serde(flatten)]
+    pub connection: &'a ConnectionStatus,
+    pub channel: &'a str,
+    /// Chat messages received during the last minute.
+    pub per_minute: usize,
+    /// Messages from the connection Neovim hasn't handled yet.
+    pub pending: usize,
+    /// Messages waiting their turn with `display.mode = "queue"`.
+    pub queued: usize,
+    /// The chat mood, e.g. `🙂 +0.31`.
+    pub mood: String,
+}
+
+impl<'a> Statusline<'a> {
+    pub fn of(plugin: &'a Plugin) -> Self {
+        Statusline {
+            connection: &plugin.status,
+            channel: &plugin.config.channel,
+            per_minute: plugin.history.since(1).count(),
+            pending: plugin.queue.pending(),
+            queued: plugin.display.len(),
+            mood: plugin.sentiment.indicator(),
+        }
+    }
+
+    /// E.g. `● #channel 12/min 🙂 +0.31`, with the messages still waiting
+    /// before the mood when there are any.
+    pub fn text(&self) -> String {
+        let icon = match self.connection {
+            ConnectionStatus::Connected => "●",
+            ConnectionStatus::Connecting | ConnectionStatus::Reconnecting { .. } => "◌",
+            ConnectionStatus::Disconnected => "○",
+        };
+
+        let mut text = format!(
+            "{} #{} {}",
+            icon,
+            self.channel,
+            tr_with("statusline.rate", &[("count", &self.per_minute)])
+        );
+
+        let waiting = self.pending + self.queued;
+        if waiting > 0 {
+            text.push(' ');
+            text.push_str(&tr_with("statusline.waiting", &[("count", &waiting)]));
+        }
+
+        text.push(' ');
+        text.push_str(&self.mood);
+        text
+    }
+}
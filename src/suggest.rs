@@ -0,0 +1,322 @@
+//! `!suggest <line>[-<line>] <replacement>`: chat proposes replacing lines
+//! of the buffer on screen, with `\n` between the new lines. The diff waits
+//! in a float until the streamer applies it with `y`, as one change that
+//! `u` takes back, or turns it down with `n`.
+
+use std::collections::VecDeque;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+use crate::{
+    audit::Undo,
+    i18n::{tr, tr_with},
+    twitch::Outgoing,
+    ui::float,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 2] = ["accept", "reject"];
+
+/// Lines a suggestion may replace or bring in, the float has to fit them.
+pub const MAX_LINES: usize = 20;
+
+/// Suggestions waiting behind the one on display before more are dropped.
+const MAX_PENDING: usize = 10;
+
+struct Suggestion {
+    channel: String,
+    author: String,
+    buffer: Buffer,
+    /// The buffer's name relative to the working directory.
+    file: String,
+    /// The first replaced line, 0-based.
+    start: usize,
+    /// The lines as they were when it was suggested.
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct Suggestions {
+    /// The first one is the one asked about.
+    pending: VecDeque<Suggestion>,
+    window: Option<Window>,
+}
+
+/// Queues a `!suggest` for lines `start` to `end`, 1-based, of the current
+/// buffer and asks about it unless another one is open.
+pub fn propose(
+    plugin: &mut Plugin,
+    channel: String,
+    author: String,
+    (start, end): (usize, usize),
+    lines: Vec<String>,
+) -> Result<()> {
+    if plugin.suggestions.pending.len() >= MAX_PENDING {
+        return Ok(());
+    }
+
+    let buffer = api::get_current_buf();
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    let buftype: String = api::get_option_value("buftype", &opts)?;
+    let modifiable: bool = api::get_option_value("modifiable", &opts)?;
+
+    // Only files, not the chat log, a terminal or a float like this one.
+    if !buftype.is_empty() || !modifiable {
+        return Err(invalid(tr_with("suggest.no_file", &[("user", &author)])));
+    }
+
+    let file: String = api::call_function("expand", ("%:.",))?;
+    let count = buffer.line_count()?;
+    if end > count {
+        return Err(invalid(tr_with(
+            "suggest.out_of_range",
+            &[("user", &author), ("file", &file), ("lines", &count)],
+        )));
+    }
+
+    let before: Vec<String> = buffer
+        .get_lines(start - 1..end, true)?
+        .map(|line| line.to_string_lossy().into_owned())
+        .collect();
+
+    // Chat can't type leading spaces, lines without them keep those of
+    // the first line they replace.
+    let indent: String = before[0]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let after: Vec<String> = lines
+        .into_iter()
+        .map(|line| match line.starts_with(char::is_whitespace) {
+            true => line,
+            false if line.is_empty() => line,
+            false => format!("{}{}", indent, line),
+        })
+        .collect();
+
+    if after == before {
+        return Err(invalid(tr_with("suggest.unchanged", &[("user", &author)])));
+    }
+
+    plugin.suggestions.pending.push_back(Suggestion {
+        channel,
+        author,
+        buffer,
+        file,
+        start: start - 1,
+        before,
+        after,
+    });
+
+    match float::showing(plugin.suggestions.window.as_ref()) {
+        true => Ok(()),
+        false => ask(plugin),
+    }
+}
+
+/// Runs `:TwitchSuggestions [accept|reject]`, showing the waiting
+/// suggestion again without an argument.
+pub fn run(plugin: &mut Plugin, args: Option<&str>) -> Result<()> {
+    match args.map(str::trim).unwrap_or_default() {
+        "" if plugin.suggestions.pending.is_empty() => {
+            notify(&tr("suggest.nothing_pending"), LogLevel::Info)
+        }
+        "" => ask(plugin),
+        "accept" => decide(plugin, true),
+        "reject" => decide(plugin, false),
+        _ => notify(&tr("suggest.usage"), LogLevel::Error),
+    }
+}
+
+/// Opens the float with the diff of the first pending suggestion.
+fn ask(plugin: &mut Plugin) -> Result<()> {
+    let Some(suggestion) = plugin.suggestions.pending.front() else {
+        return Ok(());
+    };
+
+    let mut lines = vec![
+        tr_with(
+            "suggest.proposed",
+            &[
+                ("user", &suggestion.author),
+                ("file", &suggestion.file),
+                ("line", &(suggestion.start + 1)),
+            ],
+        ),
+        String::new(),
+    ];
+    let first = lines.len();
+    let diff = diff(&suggestion.before, &suggestion.after);
+    lines.extend(diff.iter().map(|(kind, line)| format!("{} {}", kind, line)));
+    lines.push(String::new());
+    lines.push(tr("suggest.hint"));
+
+    let keys = [
+        (Mode::Normal, "y", "<cmd>TwitchSuggestions accept<cr>"),
+        (Mode::Normal, "n", "<cmd>TwitchSuggestions reject<cr>"),
+        (Mode::Normal, "q", "<cmd>TwitchSuggestions reject<cr>"),
+    ];
+    let (window, mut buffer) = float::confirm(
+        &tr("suggest.title"),
+        lines,
+        plugin.config.window.focus,
+        &keys,
+    )?;
+    plugin.suggestions.window = Some(window);
+
+    let namespace = api::create_namespace("nvim_twitch_suggest");
+    for (i, (kind, line)) in diff.iter().enumerate() {
+        let group = match kind {
+            '-' => "DiffDelete",
+            '+' => "DiffAdd",
+            _ => continue,
+        };
+        let opts = SetExtmarkOpts::builder()
+            .end_col(line.len() + 2)
+            .hl_group(group)
+            .build();
+        buffer.set_extmark(namespace, first + i, 0, &opts)?;
+    }
+
+    Ok(())
+}
+
+fn decide(plugin: &mut Plugin, accepted: bool) -> Result<()> {
+    let Some(suggestion) = plugin.suggestions.pending.pop_front() else {
+        return notify(&tr("suggest.nothing_pending"), LogLevel::Info);
+    };
+
+    if let Some(window) = plugin.suggestions.window.take() {
+        if window.is_valid() {
+            window.close(true)?;
+        }
+    }
+
+    let key = match accepted {
+        true => {
+            let result = apply(&suggestion);
+            let action = format!(
+                "suggest {}:{} {}",
+                suggestion.file,
+                suggestion.start + 1,
+                suggestion.after.join("\\n")
+            );
+            let undo = Undo::Edit {
+                buffer: suggestion.buffer.clone(),
+                start: suggestion.start,
+                lines: suggestion.before.clone(),
+                count: suggestion.after.len(),
+            };
+            plugin.audit.record(
+                &suggestion.channel,
+                &suggestion.author,
+                action,
+                &result,
+                Some(undo),
+            );
+            result?;
+            "suggest.accepted"
+        }
+        false => "suggest.rejected",
+    };
+
+    let text = tr_with(
+        key,
+        &[
+            ("user", &suggestion.author),
+            ("file", &suggestion.file),
+            ("line", &(suggestion.start + 1)),
+        ],
+    );
+    let _ = plugin.outgoing.send(Outgoing::Reply {
+        channel: suggestion.channel,
+        text,
+    });
+
+    ask(plugin)
+}
+
+/// Replaces the lines in one go, so a single `u` takes it back, unless
+/// they changed since the suggestion was made.
+fn apply(suggestion: &Suggestion) -> Result<()> {
+    let mut buffer = suggestion.buffer.clone();
+    let range = suggestion.start..suggestion.start + suggestion.before.len();
+
+    let current: Option<Vec<String>> = match buffer.is_valid() {
+        true => buffer.get_lines(range.clone(), false).ok().map(|lines| {
+            lines
+                .map(|line| line.to_string_lossy().into_owned())
+                .collect()
+        }),
+        false => None,
+    };
+    if current.as_ref() != Some(&suggestion.before) {
+        return Err(invalid(tr_with(
+            "suggest.changed",
+            &[("user", &suggestion.author), ("file", &suggestion.file)],
+        )));
+    }
+
+    buffer.set_lines(range, true, suggestion.after.iter().map(String::as_str))?;
+
+    Ok(())
+}
+
+/// Puts back the lines an accepted suggestion replaced, for `:TwitchUndo`.
+pub fn restore(mut buffer: Buffer, start: usize, lines: Vec<String>, count: usize) -> Result<()> {
+    // Left be if the buffer is gone or lost the lines by now.
+    if !buffer.is_valid() || start + count > buffer.line_count()? {
+        return Ok(());
+    }
+
+    buffer.set_lines(start..start + count, true, lines.iter().map(String::as_str))?;
+    Ok(())
+}
+
+/// The lines of a unified diff from `before` to `after`, each marked with
+/// `' '`, `'-'` or `'+'`, from their longest common subsequence.
+fn diff<'a>(before: &'a [String], after: &'a [String]) -> Vec<(char, &'a str)> {
+    let (n, m) = (before.len(), after.len());
+
+    // `common[i][j]` is the length of the longest common subsequence of
+    // `before[i..]` and `after[j..]`.
+    let mut common = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = match before[i] == after[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && before[i] == after[j] {
+            lines.push((' ', before[i].as_str()));
+            (i, j) = (i + 1, j + 1);
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', before[i].as_str()));
+            i += 1;
+        } else {
+            lines.push(('+', after[j].as_str()));
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+fn invalid(message: String) -> nvim_oxi::Error {
+    api::Error::Other(message).into()
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
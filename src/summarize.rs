@@ -0,0 +1,65 @@
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{
+    i18n::tr,
+    twitch::{ConnectionStatus, Outgoing},
+    Plugin,
+};
+
+const DEFAULT_MINUTES: i64 = 10;
+
+pub const PROMPT: &str = "You summarize Twitch chat for a streamer who stepped away. \
+    Reply with at most eight short bullet points starting with \"- \", covering \
+    the main topics, questions aimed at the streamer and anything that needs \
+    attention. Do not invent messages.";
+
+/// Sends the chat of the last `minutes` to the LLM, the summary comes back
+/// as a [`Command::Summary`](crate::commands::Command::Summary).
+pub fn run(plugin: &Plugin, minutes: Option<i64>) -> Result<()> {
+    let minutes = minutes.unwrap_or(DEFAULT_MINUTES);
+
+    let transcript = plugin
+        .history
+        .since(minutes)
+        .map(|entry| {
+            format!(
+                "[{}] {}: {}",
+                entry.timestamp.format("%H:%M"),
+                entry.message.author,
+                entry.message.display_text()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if transcript.is_empty() {
+        api::notify(&tr("summary.empty"), LogLevel::Info, &NotifyOpts::default())?;
+        return Ok(());
+    }
+
+    // The connection asks the LLM, without one the summary would never come.
+    let sent = matches!(plugin.status, ConnectionStatus::Connected)
+        && plugin
+            .outgoing
+            .send(Outgoing::Summarize { transcript })
+            .is_ok();
+    if !sent {
+        api::notify(
+            &tr("summary.disconnected"),
+            LogLevel::Error,
+            &NotifyOpts::default(),
+        )?;
+        return Ok(());
+    }
+
+    api::notify(
+        &tr("summary.pending"),
+        LogLevel::Info,
+        &NotifyOpts::default(),
+    )?;
+
+    Ok(())
+}
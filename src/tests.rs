@@ -0,0 +1,2132 @@
+//! Integration tests run inside a headless Neovim.
+//!
+//! Build the library first and then run them with
+//! `cargo build --features integration && cargo test --features integration`.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::Rc,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::ExtmarkPosition},
+    conversion::FromObject,
+    libuv::AsyncHandle,
+    serde::Deserializer,
+    Dictionary, Function, Object,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    approvals, audit, auth,
+    backend::{Admission, ChatBackend, ChatEvent, LocalBackend, Relay},
+    commands::{self, Command, CommandSender, Queue, Syntax},
+    config::{
+        ChannelOverrides, Config, DisplayMode, FilterAction, FilterRule, Focus, Layout, ModAction,
+        Output, RedemptionAction, WindowOverrides,
+    },
+    events::Event,
+    filter, flood,
+    hooks::{self, Failure},
+    ignore, keymaps, replay, schedule, search, simulate, stats, suggest, transcript, trythis,
+    twitch::{
+        args::{schema_of, ArgError},
+        emotes::EmoteSpan,
+        parse_command, serve, ChatMessage, ConnectionStatus, MockChat, Outgoing, OutgoingSender,
+        Slash,
+    },
+    votes, Plugin,
+};
+
+fn plugin() -> Plugin {
+    let mut config = Config::default();
+    // Keeps the chat log lines the same whenever the tests run.
+    config.window.timestamps = false;
+    plugin_with(config)
+}
+
+fn plugin_with(config: Config) -> Plugin {
+    let (outgoing, _) = mpsc::unbounded_channel();
+    Plugin::new(config, outgoing).unwrap()
+}
+
+fn feed(plugin: &mut Plugin, chat: MockChat) {
+    for command in chat.commands() {
+        commands::dispatch(plugin, command);
+    }
+}
+
+/// The whole way live chat takes: `chat` is read as the backend would be,
+/// through a [`Relay`] into the plugin's queue, which is then dispatched
+/// like on a wakeup. Unlike [`feed`], the chat lines themselves go through
+/// too. Hands back `chat` for what was sent to it.
+fn receive(plugin: &mut Plugin, mut chat: MockChat) -> MockChat {
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&plugin.queue));
+    let relay = Relay::new(&plugin.config, Arc::clone(&plugin.syntax), sender);
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap()
+        .block_on(async {
+            while let Some(event) = chat.next_event().await {
+                relay.forward(event);
+            }
+        });
+
+    let payloads = plugin.queue.drain();
+    commands::dispatch_batch(plugin, payloads).unwrap();
+
+    chat
+}
+
+fn lines(buffer: &api::Buffer) -> Vec<String> {
+    buffer
+        .get_lines(.., false)
+        .unwrap()
+        .map(|line| line.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[nvim_oxi::test]
+fn focus_goes_back_to_where_it_was() {
+    let mut config = Config::default();
+    config.window.focus = Focus::Never;
+    let mut plugin = plugin_with(config);
+
+    let mut editor = api::get_current_win();
+    api::get_current_buf()
+        .set_lines(.., true, ["one", "two", "three"])
+        .unwrap();
+    editor.set_cursor(3, 2).unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!nvim hello"),
+    );
+    assert_eq!(api::get_current_win(), editor);
+
+    plugin.chat.toggle_focus().unwrap();
+    assert_ne!(api::get_current_win(), editor);
+    api::command("normal! gg").unwrap();
+
+    plugin.chat.toggle_focus().unwrap();
+    assert_eq!(api::get_current_win(), editor);
+    assert_eq!(editor.get_cursor().unwrap(), (3, 2));
+}
+
+#[nvim_oxi::test]
+fn message_opens_chat_window() {
+    let mut plugin = plugin();
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!nvim hello"),
+    );
+
+    let window = api::get_current_win();
+    assert!(window.get_config().unwrap().relative.is_some());
+
+    let buffer = window.get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["viewer", "hello"]);
+}
+
+#[nvim_oxi::test]
+fn sidebar_is_a_split_with_the_chat_filetype() {
+    let mut config = Config::default();
+    config.window.timestamps = false;
+    config.window.layout = Layout::Sidebar;
+    let mut plugin = plugin_with(config);
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!nvim hello"),
+    );
+
+    let window = api::get_current_win();
+    assert!(window.get_config().unwrap().relative.is_none());
+    assert_eq!(window.get_width().unwrap(), plugin.config.window.width);
+
+    let opts = OptionOpts::builder().window(window.clone()).build();
+    assert!(!api::get_option_value::<bool>("number", &opts).unwrap());
+
+    let buffer = window.get_buf().unwrap();
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    let filetype: String = api::get_option_value("filetype", &opts).unwrap();
+    assert_eq!(filetype, "twitchchat");
+    assert_eq!(lines(&buffer), ["viewer", "hello"]);
+}
+
+#[nvim_oxi::test]
+fn annotations_are_capped_at_max() {
+    let mut config = Config::default();
+    config.display.mode = DisplayMode::Annotate;
+    config.display.annotations.max = 2;
+    let mut plugin = plugin_with(config);
+
+    let chat = MockChat::default()
+        .say("first", "!nvim one")
+        .say("second", "!nvim two")
+        .say("third", "!nvim three");
+
+    feed(&mut plugin, chat);
+
+    // No window was opened for them.
+    assert!(api::get_current_win()
+        .get_config()
+        .unwrap()
+        .relative
+        .is_none());
+
+    let namespace = api::create_namespace("nvim_twitch_annotations");
+    let extmarks = api::get_current_buf()
+        .get_extmarks(
+            namespace,
+            ExtmarkPosition::ByTuple((0, 0)),
+            ExtmarkPosition::ByTuple((0, 0)),
+            &GetExtmarksOpts::default(),
+        )
+        .unwrap();
+    assert_eq!(extmarks.count(), 2);
+}
+
+#[nvim_oxi::test]
+fn authors_are_shown_with_badges_and_color() {
+    let mut plugin = plugin();
+    let channel = plugin.config.channel.clone();
+    crate::ui::define_highlights().unwrap();
+
+    commands::dispatch(
+        &mut plugin,
+        Command::Message {
+            id: String::new(),
+            channel,
+            author: "viewer".to_owned(),
+            color: Some("#FF4500".to_owned()),
+            badges: vec!["moderator/1".to_owned()],
+            text: "hello".to_owned(),
+            emotes: Vec::new(),
+            bits: None,
+            reply: None,
+            source: None,
+            risk: None,
+        },
+    );
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["[MOD] viewer", "hello"]);
+
+    let exists: i64 = api::call_function("hlexists", ("TwitchAuthor_ff4500",)).unwrap();
+    assert_eq!(exists, 1);
+}
+
+#[nvim_oxi::test]
+fn chatters_without_a_color_get_one_from_the_palette() {
+    crate::ui::define_highlights().unwrap();
+    let palette = ["#112233".to_owned(), "#445566".to_owned()];
+    let group = |author: &str, color: Option<&str>, palette: &[String]| {
+        crate::ui::author_highlight(author, color, palette).unwrap()
+    };
+
+    assert_eq!(group("Viewer", None, &palette), "TwitchAuthor_445566");
+    assert_eq!(
+        group("viewer", Some("red"), &palette),
+        "TwitchAuthor_445566"
+    );
+    assert_eq!(
+        group("viewer", Some("#ABCDEF"), &palette),
+        "TwitchAuthor_abcdef"
+    );
+    assert_eq!(group("viewer", None, &[]), "TwitchAuthor");
+
+    // Past the limit, colors never seen before come from the palette.
+    for i in 0..300 {
+        group("viewer", Some(&format!("#00{:04x}", i)), &palette);
+    }
+    assert_eq!(
+        group("viewer", Some("#fedcba"), &palette),
+        "TwitchAuthor_445566"
+    );
+    assert_eq!(
+        group("viewer", Some("#ABCDEF"), &palette),
+        "TwitchAuthor_abcdef"
+    );
+}
+
+#[nvim_oxi::test]
+fn named_groups_keep_what_the_user_set() {
+    let mut plugin = plugin();
+    crate::ui::define_highlights().unwrap();
+
+    api::command("highlight TwitchMessage guifg=#123456").unwrap();
+    // As after `:colorscheme`.
+    crate::ui::define_highlights().unwrap();
+
+    let message: Dictionary = api::call_function(
+        "nvim_get_hl",
+        (0, Dictionary::from_iter([("name", "TwitchMessage")])),
+    )
+    .unwrap();
+    assert_eq!(
+        message.get("fg").cloned().map(i64::from_object),
+        Some(Ok(0x123456))
+    );
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!nvim hello"),
+    );
+
+    let opts = OptionOpts::builder().window(api::get_current_win()).build();
+    let winhighlight: String = api::get_option_value("winhighlight", &opts).unwrap();
+    assert_eq!(winhighlight, "FloatBorder:TwitchBorder");
+}
+
+#[nvim_oxi::test]
+fn emotes_are_shown_as_placeholders() {
+    let mut plugin = plugin();
+    let channel = plugin.config.channel.clone();
+
+    let emote = EmoteSpan {
+        id: "25".to_owned(),
+        code: "Kappa".to_owned(),
+        start: 6,
+        end: 11,
+    };
+
+    commands::dispatch(
+        &mut plugin,
+        Command::Message {
+            id: String::new(),
+            channel,
+            author: "viewer".to_owned(),
+            color: None,
+            badges: Vec::new(),
+            text: "hello Kappa".to_owned(),
+            emotes: vec![emote],
+            bits: None,
+            reply: None,
+            source: None,
+            risk: None,
+        },
+    );
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["viewer", "hello :Kappa:"]);
+}
+
+#[nvim_oxi::test]
+fn messages_are_filtered() {
+    let mut config = Config::default();
+    config.window.timestamps = false;
+    config.filter.blocklist = vec!["spoiler".to_owned()];
+    config.filter.max_length = Some(12);
+    let mut plugin = plugin_with(config);
+
+    let chat = MockChat::default()
+        .say("first", "!nvim the SPOILER is")
+        .say("second", "!nvim see https://example.com")
+        .say("third", "!nvim a rather long message");
+
+    feed(&mut plugin, chat);
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(
+        lines(&buffer),
+        ["second", "see [link]", "", "third", "a rather lon…"]
+    );
+}
+
+#[nvim_oxi::test]
+fn redemptions_run_their_action() {
+    let mut config = Config::default();
+    config.redemptions.actions.insert(
+        "hydrate".to_owned(),
+        RedemptionAction::Command("let g:hydrated = 1".to_owned()),
+    );
+    let mut plugin = plugin_with(config);
+
+    let redemption = |reward: &str| Command::Redemption {
+        channel: "mikerimebot".to_owned(),
+        user: "viewer".to_owned(),
+        reward: reward.to_owned(),
+        cost: 100,
+        input: String::new(),
+    };
+
+    commands::dispatch(&mut plugin, redemption("Unmapped"));
+    assert!(api::get_var::<i64>("hydrated").is_err());
+
+    commands::dispatch(&mut plugin, redemption("Hydrate"));
+    assert_eq!(api::get_var::<i64>("hydrated").unwrap(), 1);
+}
+
+#[nvim_oxi::test]
+fn keymaps_from_chat_are_reverted() {
+    let mut plugin = plugin();
+    let rhs = |lhs: &str| -> String { api::call_function("maparg", (lhs, "n")).unwrap() };
+
+    api::set_keymap(
+        api::types::Mode::Normal,
+        "<F7>",
+        ":echo<cr>",
+        &Default::default(),
+    )
+    .unwrap();
+
+    commands::dispatch(
+        &mut plugin,
+        Command::Keymap {
+            channel: "mikerimebot".to_owned(),
+            author: "viewer".to_owned(),
+            badges: vec!["vip/1".to_owned()],
+            lhs: "<F7>".to_owned(),
+            rhs: ":bnext<cr>".to_owned(),
+        },
+    );
+    // Nothing is set before the streamer accepts.
+    assert_eq!(rhs("<F7>"), ":echo<CR>");
+
+    keymaps::run(&mut plugin, Some("accept")).unwrap();
+    assert_eq!(rhs("<F7>"), ":bnext<CR>");
+
+    keymaps::run(&mut plugin, Some("revert")).unwrap();
+    assert_eq!(rhs("<F7>"), ":echo<CR>");
+}
+
+#[nvim_oxi::test]
+fn proposals_leave_the_cursor_alone_unless_focus_allows() {
+    let mut plugin = plugin();
+    plugin.config.window.focus = Focus::Never;
+    let editor = api::get_current_win();
+
+    commands::dispatch(
+        &mut plugin,
+        Command::Keymap {
+            channel: "mikerimebot".to_owned(),
+            author: "viewer".to_owned(),
+            badges: vec!["vip/1".to_owned()],
+            lhs: "<F8>".to_owned(),
+            rhs: ":bnext<cr>".to_owned(),
+        },
+    );
+    assert_eq!(api::get_current_win(), editor);
+    assert_eq!(api::list_wins().count(), 2);
+
+    keymaps::run(&mut plugin, Some("reject")).unwrap();
+    assert_eq!(api::list_wins().count(), 1);
+}
+
+#[nvim_oxi::test]
+fn suggested_edits_are_applied_once_accepted() {
+    let mut plugin = plugin();
+    let mut buffer = api::get_current_buf();
+    buffer
+        .set_lines(.., true, ["fn main() {", "    old();", "}"])
+        .unwrap();
+
+    commands::dispatch(
+        &mut plugin,
+        Command::Suggest {
+            channel: "mikerimebot".to_owned(),
+            author: "viewer".to_owned(),
+            badges: vec!["vip/1".to_owned()],
+            start: 2,
+            end: 2,
+            lines: vec!["new();".to_owned(), "more();".to_owned()],
+        },
+    );
+
+    let float = api::get_current_win().get_buf().unwrap();
+    assert_eq!(&lines(&float)[2..4], ["-     old();", "+     new();"]);
+    // Nothing changes before the streamer accepts.
+    assert_eq!(lines(&buffer), ["fn main() {", "    old();", "}"]);
+
+    suggest::run(&mut plugin, Some("accept")).unwrap();
+    assert_eq!(
+        lines(&buffer),
+        ["fn main() {", "    new();", "    more();", "}"]
+    );
+
+    audit::undo(&mut plugin).unwrap();
+    assert_eq!(lines(&buffer), ["fn main() {", "    old();", "}"]);
+}
+
+#[nvim_oxi::test]
+fn history_is_searched_newest_first() {
+    let mut plugin = plugin();
+
+    let chat = MockChat::default()
+        .say("first", "hello there")
+        .say("second", "unrelated")
+        .say("Hello_Fan", "hi");
+
+    feed(&mut plugin, chat);
+
+    let found = search::find(&plugin, "HELLO");
+    assert_eq!(
+        found.iter().map(|found| found.author).collect::<Vec<_>>(),
+        ["Hello_Fan", "first"]
+    );
+}
+
+#[nvim_oxi::test]
+fn history_is_restored_by_the_next_session() {
+    let mut plugin = plugin();
+    plugin.restored = true;
+
+    feed(&mut plugin, MockChat::default().say("viewer", "hello"));
+    plugin.save_state().unwrap();
+
+    let mut next = self::plugin();
+    next.restore_state();
+    assert_eq!(next.history.search("hello").count(), 1);
+
+    next.state.reset().unwrap();
+}
+
+#[nvim_oxi::test]
+fn transcripts_keep_being_written() {
+    let mut plugin = plugin();
+    plugin.config.cooldowns.remove("where");
+    let path = std::env::temp_dir().join("nvim twitch transcript.md");
+    let args = |words: &[&str]| {
+        words
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // `feed()` only passes on the commands, these are the lines themselves.
+    let say = |plugin: &mut Plugin, author: &str, text: &str| {
+        let message =
+            serde_json::json!({ "channel": plugin.config.channel, "author": author, "text": text });
+        commands::dispatch(
+            plugin,
+            Command::Chat(serde_json::from_value(message).unwrap()),
+        );
+    };
+
+    say(&mut plugin, "first", "before");
+    transcript::run(&mut plugin, &args(&[&path.to_string_lossy()])).unwrap();
+    say(&mut plugin, "second", "after");
+    feed(&mut plugin, MockChat::default().say("third", "!where"));
+    transcript::run(&mut plugin, &args(&["stop"])).unwrap();
+    say(&mut plugin, "fourth", "too late");
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = written
+        .lines()
+        .filter(|line| line.starts_with("- "))
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("**first**: before"));
+    assert!(lines[1].ends_with("**second**: after"));
+    assert!(lines[2].ends_with("**third** ran `!where` ✓"));
+
+    let path = std::env::temp_dir().join("nvim-twitch-transcript.txt");
+    transcript::run(
+        &mut plugin,
+        &args(&[&path.to_string_lossy(), "--format", "json"]),
+    )
+    .unwrap();
+    transcript::run(&mut plugin, &args(&["stop"])).unwrap();
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&written).unwrap();
+    assert_eq!(entries[0]["kind"], "message");
+    assert_eq!(entries[0]["author"], "first");
+
+    transcript::run(
+        &mut plugin,
+        &args(&[&path.to_string_lossy(), "--format=jsonl"]),
+    )
+    .unwrap();
+    transcript::run(&mut plugin, &args(&["stop"])).unwrap();
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    let first: serde_json::Value = serde_json::from_str(written.lines().next().unwrap()).unwrap();
+    assert_eq!(first["author"], "first");
+}
+
+#[nvim_oxi::test]
+fn messages_are_appended_to_the_log() {
+    let mut plugin = plugin();
+
+    let chat = MockChat::default()
+        .say("first", "!nvim one")
+        .say("second", "just chatting")
+        .say("third", "!nvim three");
+
+    feed(&mut plugin, chat);
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["first", "one", "", "third", "three"]);
+}
+
+#[nvim_oxi::test]
+fn long_messages_wrap_under_the_author() {
+    let mut config = Config::default();
+    config.window.timestamps = false;
+    config.window.width = 20;
+    let mut plugin = plugin_with(config);
+
+    let text = "the quick brown fox jumps over the lazy dog";
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", &format!("!nvim {}", text))
+            .say("viewer", "!nvim 日本語のテキストです"),
+    );
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(
+        lines(&buffer),
+        [
+            "viewer",
+            "the quick brown fox",
+            "  jumps over the",
+            "  lazy dog",
+            "",
+            "viewer",
+            // Two columns each.
+            "日本語のテキストです",
+        ]
+    );
+
+    let mut config = plugin.config.clone();
+    config.window.width = 10;
+    config.window.max_height = 2;
+    plugin.chat.set_options(config.window).unwrap();
+
+    assert_eq!(
+        lines(&buffer),
+        [
+            "viewer",
+            "the quick",
+            "  brown…",
+            "",
+            "viewer",
+            "日本語のテ",
+            "  キスト…",
+        ]
+    );
+}
+
+#[nvim_oxi::test]
+fn floods_bring_on_slow_mode() {
+    let mut config = Config::default();
+    config.window.timestamps = false;
+    // Room for the notice on one line.
+    config.window.width = 60;
+    config.flood.enabled = true;
+    config.flood.per_user = 2;
+    let mut plugin = plugin_with(config);
+
+    let chat = (0..5).fold(MockChat::default(), |chat, i| {
+        chat.say("viewer", &format!("!nvim {}", i))
+    });
+    feed(&mut plugin, chat);
+
+    let started = crate::i18n::tr_with("flood.started", &[("seconds", &10)]);
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(
+        lines(&buffer),
+        ["viewer", "0", "", "viewer", "1", "", "*", &started, "", "viewer", "2"]
+    );
+
+    flood::run(&mut plugin, Some("off")).unwrap();
+    feed(&mut plugin, MockChat::default().say("viewer", "!nvim 5"));
+
+    assert_eq!(lines(&buffer).last().unwrap(), "5");
+}
+
+#[nvim_oxi::test]
+fn sounds_are_muted_apart_from_viewers() {
+    let mut plugin = plugin();
+
+    filter::run(&mut plugin, &["sounds".to_owned()], true).unwrap();
+    assert!(plugin.sounds.muted);
+    assert!(!filter::muted(&plugin, "sounds"));
+
+    filter::run(&mut plugin, &["sounds".to_owned()], false).unwrap();
+    filter::run(&mut plugin, &["@sounds".to_owned()], true).unwrap();
+    assert!(!plugin.sounds.muted);
+    assert!(filter::muted(&plugin, "sounds"));
+}
+
+#[nvim_oxi::test]
+fn bots_are_ignored_until_let_through() {
+    let mut config = Config::default();
+    config.ignore.patterns = vec!["bot$".to_owned()];
+    let mut plugin = plugin_with(config);
+
+    assert!(ignore::ignored(&plugin, "Nightbot"));
+    assert!(ignore::ignored(&plugin, "my_chat_bot"));
+    assert!(!ignore::ignored(&plugin, "viewer"));
+
+    ignore::run(&mut plugin, &["add".to_owned(), "@Viewer".to_owned()]).unwrap();
+    ignore::run(&mut plugin, &["remove".to_owned(), "nightbot".to_owned()]).unwrap();
+    assert!(ignore::ignored(&plugin, "viewer"));
+    assert!(!ignore::ignored(&plugin, "nightbot"));
+
+    // Taking back an add leaves no exception behind.
+    ignore::run(&mut plugin, &["remove".to_owned(), "viewer".to_owned()]).unwrap();
+    ignore::run(&mut plugin, &["add".to_owned(), "nightbot".to_owned()]).unwrap();
+    assert!(!ignore::ignored(&plugin, "viewer"));
+    assert!(ignore::ignored(&plugin, "nightbot"));
+}
+
+#[nvim_oxi::test]
+fn reminders_go_off_once() {
+    let mut plugin = plugin();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!remind 1s stretch")
+            .say("other", "!remind 5h later"),
+    );
+    assert_eq!(plugin.schedule.reminders.len(), 1);
+
+    std::thread::sleep(Duration::from_millis(1100));
+    schedule::tick(&mut plugin).unwrap();
+
+    assert!(plugin.schedule.reminders.is_empty());
+}
+
+#[nvim_oxi::test]
+fn code_blocks_are_highlighted_on_lines_of_their_own() {
+    let mut plugin = plugin();
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!nvim try ```lua print(1)``` here"),
+    );
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["viewer", "try", "  print(1)", "  here"]);
+
+    // Lua's parser comes with Neovim.
+    let groups: Vec<String> = buffer
+        .get_extmarks(
+            api::create_namespace("nvim_twitch_chat"),
+            ExtmarkPosition::ByTuple((2, 0)),
+            ExtmarkPosition::ByTuple((3, 0)),
+            &GetExtmarksOpts::builder().details(true).build(),
+        )
+        .unwrap()
+        .filter_map(|(_, _, _, infos)| infos?.hl_group)
+        .collect();
+    assert!(groups.iter().any(|group| group.ends_with(".lua")));
+}
+
+#[nvim_oxi::test]
+fn batched_messages_are_added_together() {
+    let mut plugin = plugin();
+
+    plugin.chat.begin_batch();
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("first", "!nvim one")
+            .say("second", "!nvim two"),
+    );
+
+    // Nothing is drawn until the batch ends.
+    assert!(api::get_current_win()
+        .get_config()
+        .unwrap()
+        .relative
+        .is_none());
+
+    plugin.chat.end_batch().unwrap();
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["first", "one", "", "second", "two"]);
+}
+
+#[nvim_oxi::test]
+fn chat_log_is_trimmed_to_max_lines() {
+    let mut config = Config::default();
+    config.window.timestamps = false;
+    config.window.max_lines = 10;
+    let mut plugin = plugin_with(config);
+
+    let chat = (0..10).fold(MockChat::default(), |chat, i| {
+        chat.say("viewer", &format!("!nvim {}", i))
+    });
+
+    feed(&mut plugin, chat);
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    let lines = lines(&buffer);
+    assert!(lines.len() <= 10);
+    assert_eq!(lines.last().unwrap(), "9");
+}
+
+#[nvim_oxi::test]
+fn one_wakeup_drains_a_burst() {
+    let queue = Arc::new(Queue::new(10_000));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+
+    let chat = (0..500).fold(MockChat::default(), |chat, i| {
+        chat.say("viewer", &format!("!nvim {}", i))
+    });
+
+    // Every message is sent as chat and as the command in it.
+    for command in chat.commands() {
+        sender.send(command);
+    }
+    assert_eq!(queue.pending(), 1000);
+
+    let payloads = queue.drain();
+    assert_eq!(payloads.len(), 1000);
+    assert_eq!(queue.pending(), 0);
+    assert!(queue.drain().is_empty());
+
+    // In the order they were sent.
+    assert!(matches!(
+        &payloads.last().unwrap().command,
+        Command::Message { text, .. } if text == "499"
+    ));
+}
+
+#[nvim_oxi::test]
+fn backend_events_are_relayed_as_commands() {
+    let queue = Arc::new(Queue::new(10_000));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+
+    let syntax = Arc::new(RwLock::new(Syntax::default()));
+    let relay = Relay::new(&Config::default(), syntax, sender);
+    let mut chat = MockChat::default()
+        .say("viewer", "!nvim hello")
+        .say("viewer", "just chatting");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        while let Some(event) = chat.next_event().await {
+            relay.forward(event);
+        }
+
+        chat.send(auth::MAIN, "channel".to_owned(), "hi".to_owned(), false)
+            .await
+            .unwrap();
+    });
+
+    let commands: Vec<_> = queue
+        .drain()
+        .into_iter()
+        .map(|payload| payload.command)
+        .collect();
+    assert!(matches!(
+        &commands[..],
+        [
+            Command::Chat(_),
+            Command::Message { text, .. },
+            Command::Chat(_),
+        ] if text == "hello"
+    ));
+    assert_eq!(chat.sent(), [("channel".to_owned(), "hi".to_owned())]);
+}
+
+#[nvim_oxi::test]
+fn irc_lines_reach_the_chat_window_and_the_editor() {
+    let mut plugin = plugin();
+    plugin.config.cooldowns.remove("hl");
+
+    receive(
+        &mut plugin,
+        MockChat::default()
+            .privmsg("viewer", &[], "!nvim hello")
+            .privmsg("viewer", &[], "!hl Comment #ff0000")
+            .privmsg("helper", &["moderator/1"], "!hl Comment #88c0d0"),
+    );
+
+    let buffer = api::get_current_win().get_buf().unwrap();
+    assert_eq!(lines(&buffer), ["viewer", "hello"]);
+    // The lines themselves were kept as well.
+    assert_eq!(plugin.history.len(), 3);
+
+    // Only the moderator may change highlights.
+    let comment: Dictionary = api::call_function(
+        "nvim_get_hl",
+        (0, Dictionary::from_iter([("name", "Comment")])),
+    )
+    .unwrap();
+    assert_eq!(
+        comment.get("fg").cloned().map(i64::from_object),
+        Some(Ok(0x88c0d0))
+    );
+}
+
+#[nvim_oxi::test]
+fn usernotices_are_read_as_events() {
+    let mut chat = MockChat::default()
+        .irc(
+            "@badge-info=;badges=;color=;display-name=Friend;emotes=;flags=;id=1;login=friend;\
+             mod=0;msg-id=raid;msg-param-displayName=Friend;msg-param-login=friend;\
+             msg-param-profileImageURL=https://example.com/friend.png;\
+             msg-param-viewerCount=42;room-id=1;subscriber=0;system-msg=42\\sraiders;\
+             tmi-sent-ts=1717243200000;user-id=3;user-type= :tmi.twitch.tv USERNOTICE #mikerimebot",
+        )
+        .irc(":tmi.twitch.tv PONG tmi.twitch.tv");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        assert!(matches!(
+            chat.next_event().await,
+            Some(ChatEvent::Raid { from, viewers: 42, .. }) if from == "Friend"
+        ));
+        // Nothing stands for a pong.
+        assert!(chat.next_event().await.is_none());
+    });
+}
+
+#[nvim_oxi::test]
+fn a_full_scoring_queue_keeps_chat_in_order() {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    // As many as wait to be scored, and then some.
+    const MESSAGES: usize = 200 + 10;
+
+    let queue = Arc::new(Queue::new(10_000));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+
+    let mut config = Config::default();
+    config.moderation.enabled = true;
+    config.moderation.threshold = 0.5;
+    config.llm.endpoint = "http://127.0.0.1:39126/".to_owned();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let commands = runtime.block_on(async {
+        // Rates everything as toxic, though not before the whole flood is in.
+        let endpoint = tokio::net::TcpListener::bind(("127.0.0.1", 39_126))
+            .await
+            .unwrap();
+        let flooded = tokio::time::Instant::now() + Duration::from_millis(200);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = endpoint.accept().await {
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut length = 0;
+                    let mut header = String::new();
+                    while stream.read_line(&mut header).await.unwrap() > 2 {
+                        if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                        header.clear();
+                    }
+                    stream.read_exact(&mut vec![0; length]).await.unwrap();
+
+                    tokio::time::sleep_until(flooded).await;
+                    let body = r#"{"choices": [{"message": {"content": "0.9"}}]}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        let syntax = Arc::new(RwLock::new(Syntax::default()));
+        let relay = Relay::new(&config, syntax, sender);
+        let mut chat = (0..MESSAGES).fold(MockChat::default(), |chat, i| {
+            chat.say("viewer", &i.to_string())
+        });
+        while let Some(event) = chat.next_event().await {
+            relay.forward(event);
+        }
+
+        let mut commands = Vec::new();
+        for _ in 0..500 {
+            commands.extend(queue.drain().into_iter().map(|payload| payload.command));
+            if commands.len() >= MESSAGES {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        commands
+    });
+
+    let said: Vec<_> = commands
+        .iter()
+        .map(|command| match command {
+            Command::Chat(message) => (message.text.parse::<usize>().unwrap(), message.risk),
+            _ => panic!("expected only chat"),
+        })
+        .collect();
+    // In the order they were said, the ones too far behind unscored rather
+    // than ahead of those still waiting.
+    let expected: Vec<_> = (0..MESSAGES)
+        .map(|i| (i, (i < 200).then_some(0.9)))
+        .collect();
+    assert_eq!(said, expected);
+}
+
+#[nvim_oxi::test]
+fn backends_that_end_or_panic_are_restarted() {
+    let queue = Arc::new(Queue::new(10_000));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+
+    let config = Config::default();
+    let syntax = Arc::new(RwLock::new(Syntax::default()));
+    let (_outgoing, receiver) = mpsc::unbounded_channel();
+    let outgoing = Arc::new(tokio::sync::Mutex::new(receiver));
+    let mut chats = vec![
+        MockChat::default().say("viewer", "after the restart"),
+        MockChat::default()
+            .say("viewer", "before the crash")
+            .crashing(),
+    ];
+    let connect = || {
+        let chat = chats.pop().ok_or_else(|| "no more chats".to_owned());
+        async { chat }
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let serving = serve(connect, &config, &syntax, &sender, outgoing);
+        // The first restart waits a second at most, the second one longer.
+        let _ = tokio::time::timeout(Duration::from_millis(1_500), serving).await;
+    });
+
+    let commands: Vec<_> = queue
+        .drain()
+        .into_iter()
+        .map(|payload| payload.command)
+        .collect();
+    assert!(matches!(
+        &commands[..],
+        [
+            Command::Chat(before),
+            Command::Reconnecting { reason: crashed, attempt: 1, .. },
+            Command::Chat(after),
+            Command::Reconnecting { reason: ended, attempt: 2, .. },
+        ] if before.text == "before the crash"
+            && crashed.contains("the mock chat crashed")
+            && after.text == "after the restart"
+            && ended == "connection closed"
+    ));
+}
+
+#[nvim_oxi::test]
+fn simulated_chat_goes_through_the_relay() {
+    let queue = Arc::new(Queue::new(10_000));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+    let mut plugin = plugin();
+
+    let script = serde_json::json!([
+        { "author": "viewer", "text": "!nvim hello" },
+        { "kind": "command", "command": "!nvim", "timestamp": "2024-06-01T12:00:00Z" },
+        { "type": "raid", "from": "friend", "viewers": 5, "delay": 0.01 },
+    ]);
+    let values = serde_json::from_value(script).unwrap();
+    simulate::from_lua(&mut plugin, &sender, values).unwrap();
+
+    let mut commands = Vec::new();
+    for _ in 0..100 {
+        commands.extend(queue.drain().into_iter().map(|payload| payload.command));
+        if commands.len() >= 3 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(matches!(
+        &commands[..],
+        [
+            Command::Chat(_),
+            Command::Message { text, .. },
+            Command::Raid { channel, from, viewers: 5 },
+        ] if text == "hello" && from == "friend" && *channel == plugin.config.channel
+    ));
+}
+
+#[nvim_oxi::test]
+fn local_clients_chat_over_plain_tcp() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let admission = Admission {
+            token: "secret".to_owned(),
+            origins: Vec::new(),
+        };
+        let mut backend = LocalBackend::bind(39_123, "home".to_owned(), admission)
+            .await
+            .unwrap();
+        assert!(matches!(
+            backend.next_event().await,
+            Some(ChatEvent::Joined)
+        ));
+
+        let mut client = tokio::net::TcpStream::connect(("127.0.0.1", 39_123))
+            .await
+            .unwrap();
+        client
+            .write_all(
+                b"secret\n{\"author\": \"viewer\", \"text\": \"!nvim hi\", \"badges\": [\"broadcaster/1\"]}\nplain text\n",
+            )
+            .await
+            .unwrap();
+
+        let Some(ChatEvent::Message(first)) = backend.next_event().await else {
+            panic!("expected a message");
+        };
+        assert_eq!(
+            (
+                first.channel.as_str(),
+                first.author.as_str(),
+                first.text.as_str()
+            ),
+            ("home", "viewer", "!nvim hi")
+        );
+        // Clients can't claim badges.
+        assert!(first.badges.is_empty());
+        let Some(ChatEvent::Message(second)) = backend.next_event().await else {
+            panic!("expected a message");
+        };
+        assert_eq!(
+            (second.author.as_str(), second.text.as_str()),
+            ("local", "plain text")
+        );
+
+        backend
+            .send(auth::MAIN, "home".to_owned(), "hello".to_owned(), false)
+            .await
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(&mut client)
+            .read_line(&mut line)
+            .await
+            .unwrap();
+        let sent: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(sent["text"], "hello");
+        assert_eq!(sent["channel"], "home");
+    });
+}
+
+#[nvim_oxi::test]
+fn local_clients_are_dropped_for_overlong_lines() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // One byte over the limit, and no more, so the client isn't reset over
+    // bytes left unread.
+    let overlong = vec![b'x'; 64 * 1024 + 1];
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let admission = Admission {
+            token: "secret".to_owned(),
+            origins: Vec::new(),
+        };
+        let mut backend = LocalBackend::bind(39_125, "home".to_owned(), admission)
+            .await
+            .unwrap();
+        assert!(matches!(
+            backend.next_event().await,
+            Some(ChatEvent::Joined)
+        ));
+
+        // Before the token as well as after it.
+        for before in [&b""[..], b"secret\n"] {
+            let mut client = tokio::net::TcpStream::connect(("127.0.0.1", 39_125))
+                .await
+                .unwrap();
+            client.write_all(before).await.unwrap();
+            client.write_all(&overlong).await.unwrap();
+
+            let mut reply = String::new();
+            tokio::select! {
+                _ = backend.next_event() => panic!("expected no message"),
+                read = client.read_to_string(&mut reply) => read.unwrap(),
+            };
+            let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+            assert_eq!(reply["error"], "lines can be 65536 bytes at most");
+        }
+    });
+}
+
+#[nvim_oxi::test]
+fn local_websockets_need_the_token_and_no_foreign_origin() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let admission = Admission {
+            token: "secret".to_owned(),
+            origins: vec!["http://localhost:8080".to_owned()],
+        };
+        let mut backend = LocalBackend::bind(39_124, "home".to_owned(), admission)
+            .await
+            .unwrap();
+        assert!(matches!(
+            backend.next_event().await,
+            Some(ChatEvent::Joined)
+        ));
+
+        // The backend only accepts while it is read.
+        let connect = |url: &str, origin: Option<&str>| {
+            let mut request = url.into_client_request().unwrap();
+            if let Some(origin) = origin {
+                request
+                    .headers_mut()
+                    .insert("origin", origin.parse().unwrap());
+            }
+            tokio::spawn(tokio_tungstenite::connect_async(request))
+        };
+        let refused = [
+            connect("ws://127.0.0.1:39124/", None),
+            connect("ws://127.0.0.1:39124/?token=wrong", None),
+            connect(
+                "ws://127.0.0.1:39124/?token=secret",
+                Some("https://evil.example"),
+            ),
+        ];
+        let allowed = connect(
+            "ws://127.0.0.1:39124/?token=secret",
+            Some("http://localhost:8080"),
+        );
+
+        let handshakes = async {
+            let mut results = Vec::new();
+            for attempt in refused {
+                results.push(attempt.await.unwrap().is_err());
+            }
+            (results, allowed.await.unwrap())
+        };
+        let (refused, allowed) = tokio::select! {
+            done = handshakes => done,
+            event = backend.next_event() => panic!("unexpected {:?}", event),
+        };
+        assert_eq!(refused, [true, true, true]);
+
+        let mut socket = allowed.unwrap().0;
+        socket
+            .send(Message::text(r#"{"author": "viewer", "text": "!nvim hi"}"#))
+            .await
+            .unwrap();
+
+        let Some(ChatEvent::Message(message)) = backend.next_event().await else {
+            panic!("expected a message");
+        };
+        assert_eq!(message.text, "!nvim hi");
+    });
+}
+
+#[nvim_oxi::test]
+fn chat_beyond_the_pending_limit_is_dropped() {
+    let queue = Arc::new(Queue::new(10));
+    let handle = AsyncHandle::new(|| {}).unwrap();
+    let sender = CommandSender::new(handle, Arc::clone(&queue));
+
+    let chat = serde_json::json!({ "author": "viewer", "text": "hi" });
+    for _ in 0..20 {
+        sender.send(Command::Chat(serde_json::from_value(chat.clone()).unwrap()));
+    }
+    sender.send(Command::Connected);
+    let streamer = serde_json::json!({
+        "author": "streamer",
+        "badges": ["broadcaster/1"],
+        "text": "!colorscheme gruvbox",
+    });
+    let streamer: ChatMessage = serde_json::from_value(streamer).unwrap();
+    sender.send(parse_command(&streamer, &Syntax::default()).unwrap());
+
+    // Only chat is dropped, the status change and the streamer's command
+    // take the place of the oldest and go first.
+    let payloads = queue.drain();
+    assert_eq!(payloads.len(), 10);
+    assert_eq!(queue.dropped(), 12);
+    assert!(matches!(payloads[0].command, Command::Connected));
+    assert!(matches!(payloads[1].command, Command::ColorScheme { .. }));
+    assert!(payloads[2..]
+        .iter()
+        .all(|payload| matches!(payload.command, Command::Chat(_))));
+}
+
+#[nvim_oxi::test]
+fn plain_chat_is_ignored() {
+    let mut plugin = plugin();
+    let before = api::list_wins().count();
+
+    feed(&mut plugin, MockChat::default().say("viewer", "hello"));
+
+    assert_eq!(api::list_wins().count(), before);
+}
+
+#[nvim_oxi::test]
+fn colorscheme_command_applies_scheme() {
+    let mut plugin = plugin();
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!colorscheme blue"),
+    );
+
+    let name = api::get_var::<String>("colors_name").unwrap();
+    assert_eq!(name, "blue");
+}
+
+#[nvim_oxi::test]
+fn recorded_commands_replay_without_their_cooldowns() {
+    let mut plugin = plugin();
+    plugin.config.cooldowns.remove("where");
+    let path = std::env::temp_dir().join("nvim-twitch-recording.jsonl");
+
+    replay::record(&mut plugin, Some(&path.to_string_lossy())).unwrap();
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!colorscheme blue")
+            .say("viewer", "!where")
+            .say("viewer", "!colorscheme nope"),
+    );
+    replay::record(&mut plugin, Some("stop")).unwrap();
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    let events: Vec<Event> = written
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        &events[0].command,
+        Command::ColorScheme { name, .. } if name == "blue"
+    ));
+
+    // `!colorscheme` is still cooling down from the first one.
+    api::command("colorscheme desert").unwrap();
+    let command = Box::new(events[0].command.clone());
+    commands::dispatch(&mut plugin, Command::Replayed { command });
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "blue");
+}
+
+#[nvim_oxi::test]
+fn chat_changes_are_undone_latest_first() {
+    let mut plugin = plugin();
+    plugin.config.cooldowns.remove("colorscheme");
+    api::command("colorscheme desert").unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!colorscheme blue")
+            .say("other", "!colorscheme evening"),
+    );
+
+    audit::undo(&mut plugin).unwrap();
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "blue");
+
+    audit::undo(&mut plugin).unwrap();
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "desert");
+}
+
+#[nvim_oxi::test]
+fn snippets_are_inserted_at_the_cursor() {
+    let mut plugin = plugin();
+    plugin.config.permissions.remove("snippet");
+    plugin.config.cooldowns.remove("snippet");
+    plugin
+        .config
+        .snippets
+        .insert("credit".to_owned(), "// by {user}\n// ok".to_owned());
+
+    let buffer = api::get_current_buf();
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!snippet nothing")
+            .say("viewer", "!snippet CREDIT"),
+    );
+
+    assert_eq!(lines(&buffer), ["// by viewer", "// ok"]);
+}
+
+#[nvim_oxi::test]
+fn arguments_are_quoted_and_checked() {
+    let commands = MockChat::default()
+        .say("viewer", r#"!goto "notes/todo list.md:3""#)
+        .say("viewer", "!colorscheme gruvbox --bg=LIGHT")
+        .say("viewer", "!remind 5m don't forget")
+        .say("viewer", "!remind soon stretch")
+        .say("viewer", "!hl Normal red blue green")
+        .say("viewer", r#"!snippet "credit"#)
+        .say("viewer", "!colorscheme gruvbox --contrast=hard")
+        .commands();
+
+    let errors: Vec<ArgError> = commands
+        .iter()
+        .filter_map(|command| match command {
+            Command::Malformed { error, .. } => Some(error.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            ArgError::invalid("delay", "soon"),
+            ArgError::TooMany,
+            ArgError::UnclosedQuote,
+            ArgError::UnknownFlag {
+                flag: "--contrast".to_owned()
+            },
+        ]
+    );
+
+    assert!(matches!(
+        &commands[0],
+        Command::Goto { file, line: 3, .. } if file == "notes/todo list.md"
+    ));
+    assert!(matches!(
+        &commands[1],
+        Command::ColorScheme { name, background: Some(background), .. }
+            if name == "gruvbox" && background == "light"
+    ));
+    assert!(matches!(
+        &commands[2],
+        Command::Remind { seconds: 300, text, .. } if text == "don't forget"
+    ));
+    assert_eq!(
+        schema_of("hl").unwrap().usage("!hl"),
+        "!hl <group> <fg> [bg]"
+    );
+}
+
+#[nvim_oxi::test]
+fn where_answers_with_the_file_on_screen() {
+    let (outgoing, mut sent) = mpsc::unbounded_channel();
+    let mut plugin = Plugin::new(Config::default(), outgoing).unwrap();
+    plugin.config.cooldowns.remove("where");
+
+    feed(&mut plugin, MockChat::default().say("viewer", "!where"));
+    api::command("edit notes.md").unwrap();
+    feed(&mut plugin, MockChat::default().say("viewer", "!where"));
+
+    let replies: Vec<String> = std::iter::from_fn(|| sent.try_recv().ok())
+        .filter_map(|outgoing| match outgoing {
+            Outgoing::Reply { text, .. } => Some(text),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0], "@viewer no file is open right now");
+    // The branch depends on where the tests run.
+    assert!(replies[1].starts_with("@viewer notes.md:1:1 (markdown)"));
+}
+
+#[nvim_oxi::test]
+fn help_lists_the_commands_and_tells_how_one_is_used() {
+    let (outgoing, mut sent) = mpsc::unbounded_channel();
+    let mut config = Config::default();
+    config.cooldowns.remove("help");
+    config
+        .syntax
+        .aliases
+        .insert("cs".to_owned(), "colorscheme".to_owned());
+    let mut plugin = Plugin::new(config, outgoing).unwrap();
+
+    feed(&mut plugin, MockChat::default().say("viewer", "!help"));
+    feed(&mut plugin, MockChat::default().say("viewer", "!help hl"));
+    feed(&mut plugin, MockChat::default().say("viewer", "!help !cs"));
+    feed(&mut plugin, MockChat::default().say("viewer", "!help nope"));
+
+    let replies: Vec<String> = std::iter::from_fn(|| sent.try_recv().ok())
+        .filter_map(|outgoing| match outgoing {
+            Outgoing::Reply { text, .. } => Some(text),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(replies.len(), 4);
+    assert!(replies[0].starts_with("@viewer Commands: !nvim, !colorscheme, "));
+    assert!(replies[0].ends_with("!help <command> tells more"));
+    assert_eq!(
+        replies[1],
+        "@viewer !hl <group> <fg> [bg] · broadcaster, moderator, vip · 3 at once, then one every 2s and every 10s per viewer"
+    );
+    assert!(replies[2].starts_with("@viewer !colorscheme"));
+    assert!(replies[2].ends_with(" · also !cs"));
+    assert_eq!(replies[3], "@viewer there is no nope command");
+}
+
+#[nvim_oxi::test]
+fn uptime_and_followage_come_from_what_helix_told() {
+    let (outgoing, mut sent) = mpsc::unbounded_channel();
+    let mut plugin = Plugin::new(Config::default(), outgoing).unwrap();
+    plugin.config.cooldowns.remove("uptime");
+    let mut replies = || -> Vec<String> {
+        std::iter::from_fn(|| sent.try_recv().ok())
+            .filter_map(|outgoing| match outgoing {
+                Outgoing::Reply { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+    };
+
+    feed(&mut plugin, MockChat::default().say("viewer", "!uptime"));
+    assert_eq!(replies(), ["@viewer the stream hasn't been checked yet"]);
+
+    let started_at = chrono::Utc::now() - chrono::Duration::minutes(134);
+    let followed_at = chrono::Utc::now() - chrono::Duration::days(40);
+    commands::dispatch(
+        &mut plugin,
+        serde_json::from_value(serde_json::json!({
+            "type": "stream",
+            "stream": { "started_at": started_at, "title": "coding", "game_name": "Software and Game Development", "viewer_count": 12 },
+        }))
+        .unwrap(),
+    );
+    commands::dispatch(
+        &mut plugin,
+        serde_json::from_value(serde_json::json!({
+            "type": "viewers",
+            "viewers": [{ "login": "viewer", "display_name": "Viewer", "followed_at": followed_at, "tier": "1000" }],
+        }))
+        .unwrap(),
+    );
+    feed(&mut plugin, MockChat::default().say("viewer", "!uptime"));
+    assert_eq!(replies(), ["@viewer live for 2h 14m"]);
+
+    commands::text::insert(
+        &mut plugin,
+        BTreeMap::from([(
+            "followage".to_owned(),
+            commands::text::TextCommand {
+                response: "@{user}: {followage}".to_owned(),
+                cooldown_secs: 0,
+            },
+        )]),
+    );
+    for author in ["Viewer", "lurker"] {
+        let message = serde_json::json!({
+            "channel": plugin.config.channel,
+            "author": author,
+            "text": "!followage",
+        });
+        commands::dispatch(
+            &mut plugin,
+            Command::Chat(serde_json::from_value(message).unwrap()),
+        );
+    }
+    assert_eq!(replies(), ["@Viewer: 40 days", "@lurker: not following"]);
+    assert_eq!(
+        plugin
+            .viewers
+            .get("@VIEWER")
+            .and_then(|viewer| viewer.tier.as_deref()),
+        Some("1000")
+    );
+}
+
+#[nvim_oxi::test]
+fn text_commands_cool_down_like_the_built_in_ones() {
+    let (outgoing, mut sent) = mpsc::unbounded_channel();
+    let mut plugin = Plugin::new(Config::default(), outgoing).unwrap();
+    plugin.config.notify_cooldowns = true;
+
+    commands::text::insert(
+        &mut plugin,
+        BTreeMap::from([(
+            "discord".to_owned(),
+            commands::text::TextCommand {
+                response: "discord.gg/nvim".to_owned(),
+                cooldown_secs: 30,
+            },
+        )]),
+    );
+    for _ in 0..3 {
+        let message = serde_json::json!({
+            "channel": plugin.config.channel,
+            "author": "viewer",
+            "text": "!discord",
+        });
+        commands::dispatch(
+            &mut plugin,
+            Command::Chat(serde_json::from_value(message).unwrap()),
+        );
+    }
+
+    let replies: Vec<String> = std::iter::from_fn(|| sent.try_recv().ok())
+        .filter_map(|outgoing| match outgoing {
+            Outgoing::Reply { text, .. } => Some(text),
+            _ => None,
+        })
+        .collect();
+    // Told once, however often it's tried.
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0], "discord.gg/nvim");
+    assert!(replies[1].starts_with("@viewer !discord is cooling down"));
+}
+
+#[nvim_oxi::test]
+fn filter_actions_moderate_on_twitch() {
+    let mut config = Config::default();
+    config.filter.actions = vec![
+        FilterAction {
+            rule: FilterRule::Pattern("buy followers".to_owned()),
+            action: ModAction::Timeout(60),
+        },
+        FilterAction {
+            rule: FilterRule::Links,
+            action: ModAction::Delete,
+        },
+    ];
+    let (outgoing, mut sent) = mpsc::unbounded_channel();
+    let mut plugin = Plugin::new(config, outgoing).unwrap();
+
+    let say = |plugin: &mut Plugin, author: &str, badges: &[&str], text: &str| {
+        let message = serde_json::json!({
+            "id": "abc",
+            "channel": plugin.config.channel,
+            "author": author,
+            "badges": badges,
+            "text": text,
+        });
+        commands::dispatch(
+            plugin,
+            Command::Chat(serde_json::from_value(message).unwrap()),
+        );
+    };
+
+    say(
+        &mut plugin,
+        "Spammer",
+        &[],
+        "Buy followers at www.example.com",
+    );
+    say(&mut plugin, "linker", &[], "see https://example.com");
+    say(
+        &mut plugin,
+        "mod",
+        &["moderator/1"],
+        "see https://example.com",
+    );
+    say(&mut plugin, "viewer", &[], "hello");
+
+    plugin.config.filter.dry_run = true;
+    say(&mut plugin, "linker", &[], "see https://example.com");
+
+    let slashes: Vec<Slash> = std::iter::from_fn(|| sent.try_recv().ok())
+        .filter_map(|outgoing| match outgoing {
+            Outgoing::Slash { slash, .. } => Some(slash),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        slashes,
+        [
+            Slash::Timeout {
+                user: "spammer".to_owned(),
+                seconds: 60,
+                reason: "filter: /buy followers/".to_owned(),
+            },
+            Slash::Delete("abc".to_owned()),
+        ]
+    );
+}
+
+#[nvim_oxi::test]
+fn only_allowed_options_are_set_from_chat() {
+    let mut plugin = plugin();
+    plugin.config.cooldowns.remove("opt");
+    plugin.config.permissions.remove("opt");
+    let global = OptionOpts::builder().scope(OptionScope::Global).build();
+    api::set_option_value("scrolloff", 0, &global).unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!opt scrolloff 8")
+            .say("viewer", "!opt shell /bin/evil")
+            .say("viewer", "!opt conceallevel 9"),
+    );
+    assert_eq!(
+        api::get_option_value::<i64>("scrolloff", &OptionOpts::default()).unwrap(),
+        8
+    );
+    assert_ne!(
+        api::get_option_value::<String>("shell", &OptionOpts::default()).unwrap(),
+        "/bin/evil"
+    );
+
+    audit::undo(&mut plugin).unwrap();
+    assert_eq!(
+        api::get_option_value::<i64>("scrolloff", &OptionOpts::default()).unwrap(),
+        0
+    );
+}
+
+#[nvim_oxi::test]
+fn the_dashboard_counts_commands() {
+    let mut plugin = plugin();
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!nvim hello")
+            .say("other", "!nvim again"),
+    );
+
+    stats::show(&mut plugin).unwrap();
+
+    let lines = lines(&api::get_current_buf());
+    assert!(lines.contains(&"Commands: 2 run".to_owned()));
+    assert!(lines
+        .iter()
+        .any(|line| line.trim_start().starts_with("nvim")));
+}
+
+#[nvim_oxi::test]
+fn held_commands_run_once_approved() {
+    let mut plugin = plugin();
+    plugin.config.approve = true;
+    plugin.config.cooldowns.remove("colorscheme");
+    api::command("colorscheme desert").unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!colorscheme blue")
+            .say("other", "!colorscheme evening"),
+    );
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "desert");
+
+    // The float has the cursor, its first line is the oldest command.
+    approvals::run(&mut plugin, Some("approve"), (1, 1)).unwrap();
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "blue");
+
+    approvals::run(&mut plugin, Some("reject_all"), (1, 1)).unwrap();
+    approvals::run(&mut plugin, Some("approve_all"), (1, 1)).unwrap();
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "blue");
+}
+
+#[nvim_oxi::test]
+fn tried_colorschemes_unwind_to_the_first() {
+    let mut plugin = plugin();
+    plugin.config.trythis.duration = Duration::ZERO;
+    plugin.config.cooldowns.remove("trythis");
+    api::command("colorscheme desert").unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!trythis blue")
+            .say("other", "!trythis evening"),
+    );
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "evening");
+
+    trythis::tick(&mut plugin).unwrap();
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "desert");
+}
+
+#[nvim_oxi::test]
+fn aliases_and_prefix_are_matched_in_any_case() {
+    let mut plugin = plugin();
+    let syntax = Syntax {
+        prefix: "?".to_owned(),
+        aliases: BTreeMap::from([("cs".to_owned(), "colorscheme".to_owned())]),
+    };
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .written_in(syntax)
+            .say("viewer", "?CS desert")
+            .say("viewer", "!colorscheme blue"),
+    );
+
+    let name = api::get_var::<String>("colors_name").unwrap();
+    assert_eq!(name, "desert");
+}
+
+#[nvim_oxi::test]
+fn chat_commands_cannot_chain_ex_commands() {
+    // Without limits, so every payload gets as far as the editor.
+    let mut config = Config::default();
+    config.cooldowns.clear();
+    config.permissions.clear();
+    let mut plugin = plugin_with(config);
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!colorscheme blue"),
+    );
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!colorscheme blue | let g:injected = 1")
+            .say("viewer", "!colorscheme desert\nlet g:injected = 1")
+            .say("viewer", "!hl Comment red|let g:injected=1"),
+    );
+
+    assert!(api::get_var::<i64>("injected").is_err());
+    assert_eq!(api::get_var::<String>("colors_name").unwrap(), "blue");
+}
+
+#[nvim_oxi::test]
+fn goto_stays_inside_the_working_directory() {
+    let mut config = Config::default();
+    config.cooldowns.clear();
+    config.permissions.clear();
+    let mut plugin = plugin_with(config);
+
+    let project = std::env::temp_dir().join("nvim-twitch-goto");
+    std::fs::create_dir_all(project.join("src")).unwrap();
+    let lines = (1..=20)
+        .map(|i| format!("line {}\n", i))
+        .collect::<String>();
+    std::fs::write(project.join("src/main.rs"), lines).unwrap();
+    std::fs::write(project.join(".env"), "SECRET=1\n").unwrap();
+    std::env::set_current_dir(&project).unwrap();
+    api::command(&format!("cd {}", project.display())).unwrap();
+
+    feed(
+        &mut plugin,
+        MockChat::default()
+            .say("viewer", "!goto ../../etc/passwd:1")
+            .say("viewer", "!goto .env:1")
+            .say("viewer", "!goto /etc/passwd"),
+    );
+    assert!(api::get_current_buf()
+        .get_name()
+        .unwrap()
+        .as_os_str()
+        .is_empty());
+
+    feed(
+        &mut plugin,
+        MockChat::default().say("viewer", "!goto src/main.rs:12"),
+    );
+
+    let buffer = api::get_current_buf();
+    assert!(buffer.get_name().unwrap().ends_with("src/main.rs"));
+    assert_eq!(api::get_current_win().get_cursor().unwrap(), (12, 0));
+}
+
+#[nvim_oxi::test]
+fn connection_events_fire_user_autocmds() {
+    let mut plugin = plugin();
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    {
+        let received = Rc::clone(&received);
+
+        let opts = CreateAutocmdOpts::builder()
+            .patterns(["TwitchConnected", "TwitchReconnecting"])
+            .callback(move |args: api::types::AutocmdCallbackArgs| {
+                let event = Event::deserialize(Deserializer::new(args.data)).unwrap();
+                received.borrow_mut().push(event.command);
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build();
+
+        api::create_autocmd(["User"], &opts).unwrap();
+    }
+
+    commands::dispatch(&mut plugin, Command::Connected);
+    commands::dispatch(
+        &mut plugin,
+        Command::Reconnecting {
+            reason: "no keepalives".to_owned(),
+            attempt: 1,
+            retry_in: 1,
+        },
+    );
+
+    let received = received.borrow();
+    assert!(matches!(received[0], Command::Connected));
+    assert!(matches!(
+        &received[1],
+        Command::Reconnecting { reason, .. } if reason == "no keepalives"
+    ));
+    assert!(matches!(
+        plugin.status,
+        ConnectionStatus::Reconnecting { .. }
+    ));
+}
+
+#[nvim_oxi::test]
+fn chat_votes_are_tallied_once_per_viewer() {
+    let mut plugin = plugin();
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    {
+        let received = Rc::clone(&received);
+
+        let opts = CreateAutocmdOpts::builder()
+            .patterns(["TwitchVoteEnded"])
+            .callback(move |args: api::types::AutocmdCallbackArgs| {
+                let event = Event::deserialize(Deserializer::new(args.data)).unwrap();
+                received.borrow_mut().push(event.command);
+                Ok::<_, nvim_oxi::Error>(false)
+            })
+            .build();
+
+        api::create_autocmd(["User"], &opts).unwrap();
+    }
+
+    votes::run(
+        &mut plugin,
+        Some("\"Tabs or spaces?\" tabs spaces".to_owned()),
+    )
+    .unwrap();
+
+    let chat = MockChat::default()
+        .say("first", "!vote tabs")
+        .say("second", "!vote 2")
+        .say("first", "!vote SPACES")
+        .say("third", "!vote emacs");
+
+    feed(&mut plugin, chat);
+    votes::run(&mut plugin, Some("end".to_owned())).unwrap();
+
+    let received = received.borrow();
+    let Command::VoteEnded {
+        choices, winner, ..
+    } = &received[0]
+    else {
+        panic!("expected a vote result, got {:?}", received[0]);
+    };
+    assert_eq!(winner.as_deref(), Some("spaces"));
+    assert_eq!(
+        choices
+            .iter()
+            .map(|choice| choice.votes)
+            .collect::<Vec<_>>(),
+        [0, 2]
+    );
+}
+
+#[nvim_oxi::test]
+fn chat_votes_switch_to_the_winning_buffer() {
+    let mut plugin = plugin();
+
+    api::command("edit first.rs | edit second.rs | edit third.rs").unwrap();
+    votes::run_focus(&mut plugin, None).unwrap();
+
+    let chat = MockChat::default()
+        .say("first", "!focus 1")
+        .say("second", "!focus 1")
+        .say("third", "!vote 3")
+        .say("fourth", "!focus 9");
+
+    feed(&mut plugin, chat);
+    votes::run_focus(&mut plugin, Some("end")).unwrap();
+
+    let name = api::get_current_buf().get_name().unwrap();
+    assert_eq!(name.file_name().unwrap(), "first.rs");
+
+    // Gone with `!focus` from `commands`.
+    plugin.config.commands.retain(|name| name != "focus");
+    votes::run_focus(&mut plugin, None).unwrap();
+    feed(&mut plugin, MockChat::default().say("first", "!focus 2"));
+    votes::run_focus(&mut plugin, Some("end")).unwrap();
+
+    let name = api::get_current_buf().get_name().unwrap();
+    assert_eq!(name.file_name().unwrap(), "first.rs");
+}
+
+#[nvim_oxi::test]
+fn channel_overrides_are_layered_over_the_config() {
+    let mut config = Config::default();
+    config.channels.insert(
+        "other".to_owned(),
+        ChannelOverrides {
+            window: WindowOverrides {
+                width: Some(60),
+                ..WindowOverrides::default()
+            },
+            commands: Some(vec!["nvim".to_owned()]),
+            ..ChannelOverrides::default()
+        },
+    );
+    let mut plugin = plugin_with(config);
+
+    api::command("colorscheme default").unwrap();
+
+    let chat = MockChat::default()
+        .in_channel("other")
+        .say("viewer", "!colorscheme blue")
+        .say("viewer", "!nvim hello");
+
+    feed(&mut plugin, chat);
+
+    let name = api::get_var::<String>("colors_name").unwrap();
+    assert_eq!(name, "default");
+
+    let window = api::get_current_win();
+    assert_eq!(window.get_width().unwrap(), 60);
+    assert_eq!(window.get_height().unwrap(), 10);
+}
+
+#[nvim_oxi::test]
+fn channels_can_be_routed_to_a_buffer() {
+    let mut config = Config::default();
+    config.channels.insert(
+        "other".to_owned(),
+        ChannelOverrides {
+            output: Some(Output::Buffer),
+            ..ChannelOverrides::default()
+        },
+    );
+    let mut plugin = plugin_with(config);
+
+    let chat = MockChat::default()
+        .in_channel("other")
+        .say("viewer", "!nvim hello");
+
+    feed(&mut plugin, chat);
+
+    // Nothing is drawn in the chat window.
+    assert!(api::get_current_win()
+        .get_config()
+        .unwrap()
+        .relative
+        .is_none());
+
+    let buffer = api::list_bufs()
+        .find(|buffer| {
+            buffer
+                .get_name()
+                .is_ok_and(|name| name.to_string_lossy().ends_with("twitch-chat://other"))
+        })
+        .unwrap();
+    assert_eq!(lines(&buffer), ["viewer: hello"]);
+}
+
+#[nvim_oxi::test]
+fn chat_buffers_keep_the_last_lines() {
+    let mut config = Config::default();
+    config.limits.buffer = 10;
+    config.channels.insert(
+        "other".to_owned(),
+        ChannelOverrides {
+            output: Some(Output::Buffer),
+            ..ChannelOverrides::default()
+        },
+    );
+    let mut plugin = plugin_with(config);
+
+    let mut chat = MockChat::default().in_channel("other");
+    for i in 1..=15 {
+        chat = chat.say("viewer", &format!("!nvim {}", i));
+    }
+    feed(&mut plugin, chat);
+
+    let buffer = api::list_bufs()
+        .find(|buffer| {
+            buffer
+                .get_name()
+                .is_ok_and(|name| name.to_string_lossy().ends_with("twitch-chat://other"))
+        })
+        .unwrap();
+    let expected: Vec<String> = (6..=15).map(|i| format!("viewer: {}", i)).collect();
+    assert_eq!(lines(&buffer), expected);
+}
+
+#[nvim_oxi::test]
+fn looping_callbacks_are_stopped() {
+    let mut plugin = plugin();
+    hooks::set_timeout(Duration::from_millis(50));
+
+    let answer: Function<Object, Object> =
+        api::call_function("luaeval", ("function(n) return n + 1 end",)).unwrap();
+    let reply = hooks::guarded(answer, Object::from(41)).unwrap();
+    assert_eq!(i64::from_object(reply).unwrap(), 42);
+
+    let looping: Function<Object, Object> =
+        api::call_function("luaeval", ("function() while true do end end",)).unwrap();
+    let message = hooks::guarded(looping, Object::nil()).unwrap_err();
+    assert!(message.contains("0.05s"));
+
+    hooks::fail(Failure {
+        context: "Lua command !dice".to_owned(),
+        message,
+        command: Some(("chan".to_owned(), "viewer".to_owned(), "dice".to_owned())),
+    });
+    hooks::report(&mut plugin);
+    assert_eq!(plugin.errors.len(), 1);
+}
+
+#[nvim_oxi::test]
+fn stopped_callbacks_cancel_their_requests() {
+    hooks::set_timeout(Duration::from_millis(50));
+    let (sender, mut sent) = mpsc::unbounded_channel();
+    let outgoing = OutgoingSender::new(sender);
+    let webhook = || Outgoing::Webhook {
+        url: "http://127.0.0.1:9/".to_owned(),
+        body: "{}".to_owned(),
+    };
+
+    let making = outgoing.clone();
+    let request: Function<(), ()> = Function::from_fn(move |()| -> nvim_oxi::Result<()> {
+        let _ = making.send(webhook());
+        Ok(())
+    });
+    let looping: Function<Object, Object> = api::call_function(
+        "luaeval",
+        ("function(request) request() while true do end end",),
+    )
+    .unwrap();
+    let message = hooks::guarded(looping, Object::from(request)).unwrap_err();
+    assert!(message.ends_with(": 1"));
+
+    let Ok(Outgoing::Cancellable { request, cancel }) = sent.try_recv() else {
+        panic!("expected a cancellable request");
+    };
+    assert!(matches!(*request, Outgoing::Webhook { .. }));
+    assert!(cancel.is_cancelled());
+
+    // Outside of a callback nothing can cancel it.
+    outgoing.send(webhook()).unwrap();
+    assert!(matches!(sent.try_recv(), Ok(Outgoing::Webhook { .. })));
+}
+
+#[nvim_oxi::test]
+fn only_connection_settings_need_a_reconnect() {
+    let config = Config::default();
+
+    let mut reloaded = config.clone();
+    reloaded.window.width = 80;
+    reloaded.cooldowns.clear();
+    assert!(!config.connection_changed(&reloaded));
+
+    reloaded
+        .channels
+        .insert("other".to_owned(), ChannelOverrides::default());
+    assert!(config.connection_changed(&reloaded));
+}
@@ -0,0 +1,40 @@
+//! The reply chain of the message shown in the chat window.
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+
+use crate::{i18n::tr, ui::float, Plugin};
+
+/// Opens a float with the thread the shown message belongs to, oldest first.
+pub fn show(plugin: &Plugin) -> Result<()> {
+    let entries = plugin.history.thread(plugin.chat.shown_id());
+
+    if entries.len() < 2 && entries.iter().all(|entry| entry.message.reply.is_none()) {
+        api::notify(&tr("thread.none"), LogLevel::Info, &NotifyOpts::default())?;
+        return Ok(());
+    }
+
+    let mut lines = Vec::with_capacity(entries.len() + 1);
+
+    // The start of the thread may already have aged out of the history, its
+    // reply still carries a copy.
+    if let Some(parent) = entries
+        .first()
+        .and_then(|entry| entry.message.reply.as_ref())
+    {
+        lines.push(format!("{:>5}  {}: {}", "", parent.author, parent.text));
+    }
+
+    lines.extend(entries.iter().map(|entry| {
+        format!(
+            "{}  {}: {}",
+            entry.timestamp.format("%H:%M"),
+            entry.message.author,
+            entry.message.display_text()
+        )
+    }));
+
+    float::show(&tr("thread.title"), lines)
+}
@@ -0,0 +1,319 @@
+//! `:TwitchExport <path> [--format md|json|jsonl]`: writes the chat of this
+//! session to a file, for notes on the VOD. The kept history goes first,
+//! then every message and chat command is added as it comes in until
+//! `:TwitchExport stop`, so nothing piles up in memory on long streams.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Result,
+};
+use serde::Serialize;
+
+use crate::{
+    approvals,
+    events::Event,
+    i18n::{tr, tr_with},
+    twitch::ChatMessage,
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 1] = ["stop"];
+pub const FORMATS: [&str; 3] = ["md", "json", "jsonl"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// A list with a line per message, readable as is.
+    Markdown,
+    /// One JSON array, closed once the export stops.
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl Format {
+    /// `.json` files are a JSON array, `.jsonl` files JSON Lines and
+    /// anything else Markdown.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            Some("jsonl") => Format::JsonLines,
+            _ => Format::Markdown,
+        }
+    }
+}
+
+struct Writer {
+    file: BufWriter<File>,
+    path: PathBuf,
+    format: Format,
+    /// How many entries went in so far, the ones after the first are
+    /// preceded by a comma in a JSON array.
+    entries: usize,
+    closed: bool,
+}
+
+#[derive(Default)]
+pub struct Transcript {
+    writer: Option<Writer>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry<'a> {
+    Message {
+        timestamp: DateTime<Local>,
+        channel: &'a str,
+        author: &'a str,
+        text: &'a str,
+    },
+    Command {
+        timestamp: DateTime<Local>,
+        author: &'a str,
+        command: &'a str,
+        event: &'a Event,
+        /// Why it failed, unset when it went through.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Runs `:TwitchExport <path> [--format md|json|jsonl]` or
+/// `:TwitchExport stop`, with a path that may have escaped spaces in it.
+pub fn run(plugin: &mut Plugin, args: &[String]) -> Result<()> {
+    let words: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let (file, format) = match words.as_slice() {
+        ["stop"] => return stop(plugin),
+        [file] => (*file, None),
+        [file, "--format", format] => (*file, Some(*format)),
+        [file, flag] if flag.starts_with("--format=") => {
+            (*file, Some(flag.trim_start_matches("--format=")))
+        }
+        _ => return notify(&usage(), LogLevel::Error),
+    };
+
+    let path = PathBuf::from(api::call_function::<_, String>("expand", (file,))?);
+    let format = match format {
+        None => Format::of(&path),
+        Some("md") => Format::Markdown,
+        Some("json") => Format::Json,
+        Some("jsonl") => Format::JsonLines,
+        Some(_) => return notify(&usage(), LogLevel::Error),
+    };
+
+    // Whatever was being written is done with.
+    plugin.transcript.writer = None;
+
+    let file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => return failed(&path, &e.to_string()),
+    };
+    let mut writer = Writer {
+        file: BufWriter::new(file),
+        path,
+        format,
+        entries: 0,
+        closed: false,
+    };
+
+    let written = writer.header(&plugin.config.channel).and_then(|()| {
+        plugin
+            .history
+            .entries()
+            .iter()
+            .try_for_each(|entry| writer.write(&message_entry(entry.timestamp, &entry.message)))
+    });
+    if let Err(e) = written.and_then(|()| writer.file.flush()) {
+        return failed(&writer.path, &e.to_string());
+    }
+
+    let text = tr_with(
+        "transcript.started",
+        &[
+            ("path", &writer.path.display()),
+            ("messages", &plugin.history.len()),
+        ],
+    );
+    plugin.transcript.writer = Some(writer);
+
+    notify(&text, LogLevel::Info)
+}
+
+fn stop(plugin: &mut Plugin) -> Result<()> {
+    let Some(mut writer) = plugin.transcript.writer.take() else {
+        return notify(&tr("transcript.none"), LogLevel::Error);
+    };
+
+    match writer.close() {
+        Ok(()) => notify(
+            &tr_with("transcript.stopped", &[("path", &writer.path.display())]),
+            LogLevel::Info,
+        ),
+        Err(e) => failed(&writer.path, &e.to_string()),
+    }
+}
+
+/// Adds a chat message as it comes in.
+pub fn message(plugin: &mut Plugin, message: &ChatMessage) {
+    append(plugin, &message_entry(Local::now(), message));
+}
+
+/// Adds a chat command once it ran, with its arguments for those that can
+/// wait for approval and only as `!name` otherwise.
+pub fn command(plugin: &mut Plugin, author: &str, name: &str, event: &Event, result: &Result<()>) {
+    if plugin.transcript.writer.is_none() {
+        return;
+    }
+
+    let action = approvals::action(plugin, &event.command)
+        .map(|(_, _, action)| action)
+        .unwrap_or_else(|| name.to_owned());
+    let command = format!("{}{}", plugin.config.syntax.prefix, action);
+    let entry = Entry::Command {
+        timestamp: event.timestamp.with_timezone(&Local),
+        author,
+        command: &command,
+        event,
+        error: result.as_ref().err().map(ToString::to_string),
+    };
+
+    append(plugin, &entry);
+}
+
+fn message_entry(timestamp: DateTime<Local>, message: &ChatMessage) -> Entry<'_> {
+    Entry::Message {
+        timestamp,
+        channel: &message.channel,
+        author: &message.author,
+        text: &message.text,
+    }
+}
+
+/// Writes `entry` if an export is running, which stops at the first error
+/// rather than failing on every message after it.
+fn append(plugin: &mut Plugin, entry: &Entry) {
+    let Some(writer) = &mut plugin.transcript.writer else {
+        return;
+    };
+
+    let written = writer.write(entry).and_then(|()| writer.file.flush());
+    if let Err(e) = written {
+        let message = tr_with(
+            "transcript.failed",
+            &[("path", &writer.path.display()), ("error", &e)],
+        );
+        plugin.transcript.writer = None;
+        plugin.errors.push("Export", &message);
+    }
+}
+
+impl Writer {
+    fn header(&mut self, channel: &str) -> std::io::Result<()> {
+        match self.format {
+            Format::Markdown => writeln!(
+                self.file,
+                "# #{}, {}\n",
+                channel,
+                Local::now().format("%Y-%m-%d %H:%M")
+            ),
+            Format::Json => write!(self.file, "["),
+            Format::JsonLines => Ok(()),
+        }
+    }
+
+    fn write(&mut self, entry: &Entry) -> std::io::Result<()> {
+        self.entries += 1;
+
+        match self.format {
+            Format::Json => {
+                let comma = if self.entries > 1 { "," } else { "" };
+                writeln!(self.file, "{}", comma)?;
+                serde_json::to_writer(&mut self.file, entry).map_err(std::io::Error::from)
+            }
+            Format::JsonLines => {
+                serde_json::to_writer(&mut self.file, entry)?;
+                writeln!(self.file)
+            }
+            Format::Markdown => match entry {
+                Entry::Message {
+                    timestamp,
+                    author,
+                    text,
+                    ..
+                } => writeln!(
+                    self.file,
+                    "- `{}` **{}**: {}",
+                    timestamp.format("%H:%M:%S"),
+                    author,
+                    text
+                ),
+                Entry::Command {
+                    timestamp,
+                    author,
+                    command,
+                    error,
+                    ..
+                } => {
+                    let result = match error {
+                        Some(error) => format!("✗ {}", error),
+                        None => String::from("✓"),
+                    };
+                    writeln!(
+                        self.file,
+                        "- `{}` **{}** ran `{}` {}",
+                        timestamp.format("%H:%M:%S"),
+                        author,
+                        command,
+                        result
+                    )
+                }
+            },
+        }
+    }
+}
+
+impl Writer {
+    /// Ends the JSON array, once.
+    fn close(&mut self) -> std::io::Result<()> {
+        if std::mem::replace(&mut self.closed, true) || self.format != Format::Json {
+            return Ok(());
+        }
+
+        writeln!(self.file, "\n]")?;
+        self.file.flush()
+    }
+}
+
+/// An export replaced by another is closed as well, an array without its
+/// `]` is no JSON at all.
+impl Drop for Writer {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn usage() -> String {
+    tr_with("transcript.usage", &[("formats", &FORMATS.join("|"))])
+}
+
+fn failed(path: &Path, error: &str) -> Result<()> {
+    notify(
+        &tr_with(
+            "transcript.failed",
+            &[("path", &path.display()), ("error", &error)],
+        ),
+        LogLevel::Error,
+    )
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! `!trythis <colorscheme>`: a colorscheme that is only tried for a while
+//! before the previous one comes back. Trying another one meanwhile stacks
+//! on top, and the stack unwinds as the trials run out, so everything ends
+//! up on the colorscheme from before the first.
+
+use std::time::Instant;
+
+use nvim_oxi::{api, Result};
+
+use crate::{audit::Undo, commands, i18n::tr_with, Plugin};
+
+/// Trials stacked beyond this are turned down until some run out.
+const MAX_DEPTH: usize = 10;
+
+struct Trial {
+    /// What was on before, unset if no colorscheme was loaded.
+    previous: Option<String>,
+    ends: Instant,
+}
+
+#[derive(Default)]
+pub struct Trials {
+    /// The latest trial last.
+    stack: Vec<Trial>,
+}
+
+impl Trials {
+    /// Drops every trial, for when chat switches colorschemes for good.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+/// Switches to `name` for `trythis.duration`, if chat may use it.
+pub fn try_colorscheme(
+    plugin: &mut Plugin,
+    channel: String,
+    author: &str,
+    name: &str,
+) -> Result<()> {
+    if plugin.trials.stack.len() >= MAX_DEPTH
+        || !commands::colorscheme_allowed(plugin, &channel, author, name)?
+    {
+        return Ok(());
+    }
+
+    let previous = api::get_var::<String>("colors_name").ok();
+    let result = commands::switch_colorscheme(name);
+    plugin.audit.record(
+        &channel,
+        author,
+        format!("trythis {}", name),
+        &result,
+        Some(Undo::Colorscheme(previous.clone())),
+    );
+    result?;
+
+    let duration = plugin.config.trythis.duration;
+    plugin.trials.stack.push(Trial {
+        previous,
+        ends: Instant::now() + duration,
+    });
+
+    let text = tr_with(
+        "confirm.trythis",
+        &[
+            ("name", &name),
+            ("user", &author),
+            ("seconds", &duration.as_secs()),
+        ],
+    );
+    commands::confirm(plugin, channel, text);
+
+    Ok(())
+}
+
+/// Unwinds the trials that ran out. One still running on top keeps the
+/// ones below it, which then end together with it.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    let now = Instant::now();
+    let mut restore = None;
+
+    while let Some(trial) = plugin.trials.stack.last() {
+        if trial.ends > now {
+            break;
+        }
+        restore = plugin.trials.stack.pop().map(|trial| trial.previous);
+    }
+
+    match restore {
+        Some(Some(previous)) => commands::switch_colorscheme(&previous),
+        Some(None) => commands::switch_colorscheme("default"),
+        None => Ok(()),
+    }
+}
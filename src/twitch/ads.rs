@@ -0,0 +1,107 @@
+//! The ad schedule of the primary channel. EventSub tells when a break
+//! begins and when the stream goes live, Helix what is scheduled next, so
+//! it is only fetched when one of them changes it, and looked at again
+//! shortly before the next break in case it was snoozed elsewhere.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::helix::Helix;
+
+/// How long before a scheduled break it is fetched again, the first one
+/// ahead of the countdown.
+const RECHECK_BEFORE: [Duration; 2] = [Duration::from_secs(6 * 60), Duration::from_secs(30)];
+
+/// After a break, until Twitch has scheduled the next one.
+pub const AFTER_BREAK: Duration = Duration::from_secs(30);
+
+/// Until a schedule that couldn't be fetched is tried again.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The longest ad break `:TwitchAd run` can start.
+pub const MAX_LENGTH: u32 = 180;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdSchedule {
+    /// Unset while the channel is offline or has nothing scheduled.
+    pub next_at: Option<DateTime<Utc>>,
+    /// Seconds the next break lasts.
+    pub duration: u32,
+    /// Snoozes left.
+    pub snoozes: u32,
+}
+
+/// What EventSub tells that changes the schedule.
+#[derive(Debug)]
+pub enum Notice {
+    Online,
+    /// A break of `length` seconds began.
+    Began {
+        length: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum AdAction {
+    /// Pushes the next scheduled break back by five minutes.
+    Snooze,
+    /// Starts a break of this many seconds right away.
+    Run(u32),
+}
+
+pub async fn schedule(helix: &Helix, broadcaster_id: &str) -> Result<AdSchedule, String> {
+    let schedule = helix.ad_schedule(broadcaster_id).await?;
+
+    Ok(AdSchedule {
+        next_at: timestamp(&schedule.next_ad_at),
+        duration: schedule.duration,
+        snoozes: schedule.snooze_count,
+    })
+}
+
+/// How long until `schedule` should be fetched again, if before its next
+/// break.
+pub fn recheck_in(schedule: &AdSchedule) -> Option<Duration> {
+    let left = (schedule.next_at? - Utc::now()).to_std().ok()?;
+
+    RECHECK_BEFORE
+        .iter()
+        .find_map(|before| left.checked_sub(*before))
+}
+
+/// Snoozes the next break and returns the schedule after it.
+pub async fn snooze(helix: &Helix, broadcaster_id: &str) -> Result<AdSchedule, String> {
+    let snoozed = helix.snooze_ad(broadcaster_id).await?;
+    let mut schedule = schedule(helix, broadcaster_id).await?;
+
+    // The schedule may not reflect the snooze yet.
+    schedule.next_at = timestamp(&snoozed.next_ad_at).or(schedule.next_at);
+    schedule.snoozes = snoozed.snooze_count;
+
+    Ok(schedule)
+}
+
+/// Helix documents RFC 3339 timestamps but has been seen sending Unix
+/// seconds, as a string or a number. Empty means unset.
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    let seconds = match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) if text.is_empty() => return None,
+        Value::String(text) => match text.parse::<i64>() {
+            Ok(seconds) => Some(seconds),
+            Err(_) => {
+                return DateTime::parse_from_rfc3339(text)
+                    .ok()
+                    .map(|at| at.with_timezone(&Utc))
+            }
+        },
+        _ => None,
+    };
+
+    seconds
+        .filter(|&seconds| seconds > 0)
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+}
@@ -0,0 +1,384 @@
+//! The arguments of chat commands. Each command has a [`Schema`] naming
+//! its arguments and what they hold, which splits them into words, with
+//! `"double"` or `'single'` quotes keeping spaces together, and `--name=value`
+//! flags. Whatever doesn't fit comes back as an [`ArgError`] for the viewer.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{tr, tr_with};
+
+/// What an argument holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// A single word, quoted if it has spaces.
+    Word,
+    /// Everything from there on as it was typed, quotes and all.
+    Text,
+    /// `90s`, `10m` or `2h`, a bare number being minutes.
+    Delay,
+    /// `12` or `12-14`, 1-based.
+    Lines,
+    /// A whole number from 1.
+    Number,
+}
+
+#[derive(Debug)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub optional: bool,
+}
+
+/// `--name=value`, with `value` one of `values`.
+#[derive(Debug)]
+pub struct Flag {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+#[derive(Debug)]
+pub struct Schema {
+    pub args: &'static [Arg],
+    pub flags: &'static [Flag],
+}
+
+const fn required(name: &'static str, kind: Kind) -> Arg {
+    Arg {
+        name,
+        kind,
+        optional: false,
+    }
+}
+
+const fn optional(name: &'static str, kind: Kind) -> Arg {
+    Arg {
+        name,
+        kind,
+        optional: true,
+    }
+}
+
+const fn schema(args: &'static [Arg]) -> Schema {
+    Schema { args, flags: &[] }
+}
+
+/// The commands with arguments. `!nvim` keeps its text exactly as typed and
+/// `!where` and `!uptime` ignore anything after them, so none is here.
+const SCHEMAS: [(&str, Schema); 14] = [
+    (
+        "colorscheme",
+        Schema {
+            args: &[required("name", Kind::Word)],
+            flags: &[Flag {
+                name: "bg",
+                values: &["dark", "light"],
+            }],
+        },
+    ),
+    ("trythis", schema(&[required("name", Kind::Word)])),
+    ("goto", schema(&[required("file:line", Kind::Word)])),
+    ("vote", schema(&[required("choice", Kind::Text)])),
+    (
+        "keymap",
+        schema(&[required("lhs", Kind::Word), required("rhs", Kind::Text)]),
+    ),
+    (
+        "suggest",
+        schema(&[
+            required("lines", Kind::Lines),
+            required("replacement", Kind::Text),
+        ]),
+    ),
+    ("banner", schema(&[required("text", Kind::Text)])),
+    (
+        "opt",
+        schema(&[required("name", Kind::Word), required("value", Kind::Text)]),
+    ),
+    ("snippet", schema(&[required("name", Kind::Word)])),
+    (
+        "remind",
+        schema(&[required("delay", Kind::Delay), required("text", Kind::Text)]),
+    ),
+    ("font", schema(&[required("font", Kind::Text)])),
+    ("focus", schema(&[required("buffer", Kind::Number)])),
+    ("help", schema(&[optional("command", Kind::Word)])),
+    (
+        "hl",
+        schema(&[
+            required("group", Kind::Word),
+            required("fg", Kind::Word),
+            optional("bg", Kind::Word),
+        ]),
+    ),
+];
+
+/// The schema of the chat command `name`, unset for those without
+/// arguments to check.
+pub fn schema_of(name: &str) -> Option<&'static Schema> {
+    SCHEMAS
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, schema)| schema)
+}
+
+/// Why a command's arguments didn't parse. Translated only once it reaches
+/// the editor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ArgError {
+    Missing { name: String },
+    Invalid { name: String, value: String },
+    TooMany,
+    UnclosedQuote,
+    UnknownFlag { flag: String },
+}
+
+impl ArgError {
+    pub fn invalid(name: &str, value: impl Into<String>) -> Self {
+        ArgError::Invalid {
+            name: name.to_owned(),
+            value: value.into(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ArgError::Missing { name } => tr_with("args.missing", &[("name", name)]),
+            ArgError::Invalid { name, value } => {
+                tr_with("args.invalid", &[("name", name), ("value", value)])
+            }
+            ArgError::TooMany => tr("args.too_many"),
+            ArgError::UnclosedQuote => tr("args.unclosed_quote"),
+            ArgError::UnknownFlag { flag } => tr_with("args.unknown_flag", &[("flag", flag)]),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Text(String),
+    Seconds(u64),
+    Lines(usize, usize),
+    Number(usize),
+}
+
+/// Arguments that matched their [`Schema`]. The getters fall back to
+/// nothing for optional arguments that were left out.
+#[derive(Debug)]
+pub struct Args {
+    values: Vec<Value>,
+    flags: BTreeMap<&'static str, String>,
+}
+
+impl Args {
+    pub fn text(&mut self, index: usize) -> String {
+        self.optional(index).unwrap_or_default()
+    }
+
+    pub fn optional(&mut self, index: usize) -> Option<String> {
+        match self.values.get_mut(index) {
+            Some(Value::Text(text)) => Some(std::mem::take(text)),
+            _ => None,
+        }
+    }
+
+    pub fn seconds(&self, index: usize) -> u64 {
+        match self.values.get(index) {
+            Some(Value::Seconds(seconds)) => *seconds,
+            _ => 0,
+        }
+    }
+
+    pub fn lines(&self, index: usize) -> (usize, usize) {
+        match self.values.get(index) {
+            Some(Value::Lines(start, end)) => (*start, *end),
+            _ => (0, 0),
+        }
+    }
+
+    pub fn number(&self, index: usize) -> usize {
+        match self.values.get(index) {
+            Some(Value::Number(number)) => *number,
+            _ => 0,
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).map(String::as_str)
+    }
+}
+
+impl Schema {
+    pub fn parse(&self, text: &str) -> Result<Args, ArgError> {
+        let mut args = Args {
+            values: Vec::new(),
+            flags: BTreeMap::new(),
+        };
+        let mut pos = 0;
+
+        loop {
+            pos += text[pos..].len() - text[pos..].trim_start().len();
+            if pos == text.len() {
+                break;
+            }
+
+            // Left as typed, it could well have an apostrophe in it.
+            if let Some(arg) = self.args.get(args.values.len()) {
+                if arg.kind == Kind::Text {
+                    args.values
+                        .push(Value::Text(text[pos..].trim_end().to_owned()));
+                    break;
+                }
+            }
+
+            let (word, quoted) = word(text, &mut pos)?;
+
+            if let Some(flag) = word.strip_prefix("--").filter(|_| !quoted) {
+                if !self.flags.is_empty() {
+                    let (name, value) = self.flag(flag)?;
+                    args.flags.insert(name, value);
+                    continue;
+                }
+            }
+
+            let Some(arg) = self.args.get(args.values.len()) else {
+                return Err(ArgError::TooMany);
+            };
+            args.values.push(arg.value(word)?);
+        }
+
+        match self.args[args.values.len()..]
+            .iter()
+            .find(|arg| !arg.optional)
+        {
+            Some(arg) => Err(ArgError::Missing {
+                name: arg.name.to_owned(),
+            }),
+            None => Ok(args),
+        }
+    }
+
+    fn flag(&self, flag: &str) -> Result<(&'static str, String), ArgError> {
+        let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+        let Some(known) = self.flags.iter().find(|known| known.name == name) else {
+            return Err(ArgError::UnknownFlag {
+                flag: format!("--{}", name),
+            });
+        };
+
+        let value = value.to_lowercase();
+        match known.values.contains(&value.as_str()) {
+            true => Ok((known.name, value)),
+            false if value.is_empty() => Err(ArgError::Missing {
+                name: format!("--{}", name),
+            }),
+            false => Err(ArgError::invalid(&format!("--{}", name), value)),
+        }
+    }
+
+    /// How the command is used, e.g. `!colorscheme <name> [--bg=dark|light]`.
+    pub fn usage(&self, command: &str) -> String {
+        let mut usage = command.to_owned();
+
+        for arg in self.args {
+            match arg.optional {
+                true => usage.push_str(&format!(" [{}]", arg.name)),
+                false => usage.push_str(&format!(" <{}>", arg.name)),
+            }
+        }
+        for flag in self.flags {
+            usage.push_str(&format!(" [--{}={}]", flag.name, flag.values.join("|")));
+        }
+
+        usage
+    }
+}
+
+impl Arg {
+    fn value(&self, word: String) -> Result<Value, ArgError> {
+        match self.kind {
+            Kind::Word | Kind::Text => Ok(Value::Text(word)),
+            Kind::Delay => delay_seconds(&word)
+                .map(Value::Seconds)
+                .ok_or_else(|| ArgError::invalid(self.name, word)),
+            Kind::Lines => lines(&word)
+                .map(|(start, end)| Value::Lines(start, end))
+                .ok_or_else(|| ArgError::invalid(self.name, word)),
+            Kind::Number => match word.parse() {
+                Ok(number) if number > 0 => Ok(Value::Number(number)),
+                _ => Err(ArgError::invalid(self.name, word)),
+            },
+        }
+    }
+}
+
+/// The word starting at `pos`, moving `pos` past it, and whether it starts
+/// with a quote, so `"--bg"` isn't a flag. Inside quotes a backslash keeps
+/// the next quote or backslash.
+fn word(text: &str, pos: &mut usize) -> Result<(String, bool), ArgError> {
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut quote = None;
+    let mut chars = text[*pos..].char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                *pos += offset;
+                return Ok((word, quoted));
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                quoted |= offset == 0;
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(open), '\\') => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, next)) if next == open || next == '\\' => word.push(next),
+                    _ => {
+                        word.push('\\');
+                        continue;
+                    }
+                }
+            }
+            (_, c) => word.push(c),
+        }
+        chars.next();
+    }
+
+    if quote.is_some() {
+        return Err(ArgError::UnclosedQuote);
+    }
+
+    *pos = text.len();
+    Ok((word, quoted))
+}
+
+/// `90s`, `10m` or `2h` in seconds, a bare number being minutes.
+fn delay_seconds(delay: &str) -> Option<u64> {
+    let delay = delay.to_lowercase();
+    let (number, unit) = match delay.strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, &delay[number.len()..]),
+        None => (delay.as_str(), "m"),
+    };
+
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number.checked_mul(60)?,
+        _ => number.checked_mul(3600)?,
+    };
+
+    (seconds > 0).then_some(seconds)
+}
+
+/// `12` or `12-14` as the first and last line.
+fn lines(lines: &str) -> Option<(usize, usize)> {
+    match lines.split_once('-') {
+        Some((start, end)) => Some((start.parse().ok()?, end.parse().ok()?)),
+        None => Some((lines.parse().ok()?, lines.parse().ok()?)),
+    }
+}
@@ -0,0 +1,277 @@
+//! The Twitch IRC connection as a [`ChatBackend`]: one client per account,
+//! of which only the main one reads chat.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time,
+};
+use twitch_irc::{
+    login::StaticLoginCredentials,
+    message::{ServerMessage, UserNoticeEvent},
+    ClientConfig, SecureTCPTransport, TwitchIRCClient,
+};
+
+use super::{
+    parse::ChatMessage,
+    ratelimit::RateLimit,
+    shared::{self, SharedChat},
+};
+use crate::{
+    auth::{self, Credentials},
+    backend::{ChatBackend, ChatEvent},
+    config::SharedChatFilter,
+    metrics,
+};
+
+type Client = TwitchIRCClient<SecureTCPTransport, StaticLoginCredentials>;
+
+pub struct TwitchBackend<'a> {
+    incoming: UnboundedReceiver<ServerMessage>,
+    clients: HashMap<String, Client>,
+    /// Kept by the caller across reconnects, Twitch doesn't reset its count
+    /// either.
+    limits: &'a mut HashMap<String, RateLimit>,
+    shared: SharedChat,
+    filter: SharedChatFilter,
+    /// Messages from other channels of a shared session, once their channel
+    /// was looked up.
+    resolved: (UnboundedSender<ChatMessage>, UnboundedReceiver<ChatMessage>),
+    last_seen: Instant,
+}
+
+impl<'a> TwitchBackend<'a> {
+    /// Connects every account and joins `channels` as the main one, along
+    /// with why joining failed if it did.
+    pub fn connect(
+        accounts: &HashMap<String, Credentials>,
+        channels: impl IntoIterator<Item = String>,
+        limits: &'a mut HashMap<String, RateLimit>,
+        shared: SharedChat,
+        filter: SharedChatFilter,
+    ) -> (Self, Option<String>) {
+        let (incoming, client) = Client::new(client_config(accounts.get(auth::MAIN)));
+
+        // The other accounts only send, whatever they receive is dropped.
+        let mut clients: HashMap<String, Client> = accounts
+            .iter()
+            .filter(|(name, _)| *name != auth::MAIN)
+            .map(|(name, credentials)| {
+                let (mut incoming, client) = Client::new(client_config(Some(credentials)));
+                tokio::spawn(async move { while incoming.recv().await.is_some() {} });
+                (name.clone(), client)
+            })
+            .collect();
+        clients.insert(auth::MAIN.to_owned(), client.clone());
+
+        let backend = TwitchBackend {
+            incoming,
+            clients,
+            limits,
+            shared,
+            filter,
+            resolved: mpsc::unbounded_channel(),
+            last_seen: Instant::now(),
+        };
+
+        let error = client
+            .set_wanted_channels(channels.into_iter().collect())
+            .err()
+            .map(|e| e.to_string());
+
+        (backend, error)
+    }
+
+    /// How long Twitch has been quiet, pings included.
+    pub fn silent_for(&self) -> Duration {
+        self.last_seen.elapsed()
+    }
+
+    pub async fn ping(&self, timeout: Duration) {
+        if let Some(client) = self.clients.get(auth::MAIN) {
+            let _ = time::timeout(timeout, client.ping()).await;
+        }
+    }
+
+    /// The event `message` stands for, if any. Messages from other channels
+    /// of a shared session come back through `resolved` instead.
+    fn normalize(&self, message: ServerMessage) -> Option<ChatEvent> {
+        let msg = match message {
+            ServerMessage::Privmsg(msg) => msg,
+            message => return event(message),
+        };
+        metrics::MESSAGES_RECEIVED.inc();
+
+        let Some(room) = shared::source_room(&msg) else {
+            return Some(ChatEvent::Message(Box::new(ChatMessage::from(msg))));
+        };
+
+        if self.filter == SharedChatFilter::Own {
+            metrics::MESSAGES_DROPPED.inc();
+            return None;
+        }
+
+        // Looked up on a task of its own so one slow request doesn't hold up
+        // the rest of the chat.
+        let mut message = ChatMessage::from(msg);
+        let shared = self.shared.clone();
+        let resolved = self.resolved.0.clone();
+
+        tokio::spawn(async move {
+            message.source = Some(shared.channel(&room).await);
+            let _ = resolved.send(message);
+        });
+        None
+    }
+}
+
+/// The event `message` stands for, if any, with chat taken as it is: a
+/// message from another channel of a shared session keeps no `source`.
+pub fn event(message: ServerMessage) -> Option<ChatEvent> {
+    let event = match message {
+        ServerMessage::Join(_) => ChatEvent::Joined,
+        ServerMessage::Privmsg(msg) => ChatEvent::Message(Box::new(ChatMessage::from(msg))),
+        ServerMessage::UserNotice(notice) => match notice.event {
+            UserNoticeEvent::SubOrResub {
+                is_resub,
+                cumulative_months,
+                ..
+            } => ChatEvent::Subscription {
+                channel: notice.channel_login,
+                user: notice.sender.name,
+                months: cumulative_months,
+                resub: is_resub,
+            },
+            UserNoticeEvent::Raid { viewer_count, .. } => ChatEvent::Raid {
+                channel: notice.channel_login,
+                from: notice.sender.name,
+                viewers: viewer_count,
+            },
+            UserNoticeEvent::SubGift {
+                is_sender_anonymous,
+                recipient,
+                sub_plan,
+                ..
+            } => ChatEvent::Gift {
+                channel: notice.channel_login,
+                gifter: (!is_sender_anonymous).then_some(notice.sender.name),
+                tier: sub_plan,
+                recipient: recipient.name,
+            },
+            UserNoticeEvent::SubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+                ..
+            } => ChatEvent::MysteryGift {
+                channel: notice.channel_login,
+                gifter: Some(notice.sender.name),
+                count: mass_gift_count,
+                tier: sub_plan,
+            },
+            UserNoticeEvent::AnonSubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+            } => ChatEvent::MysteryGift {
+                channel: notice.channel_login,
+                gifter: None,
+                count: mass_gift_count,
+                tier: sub_plan,
+            },
+            UserNoticeEvent::Unknown if notice.event_id == "viewermilestone" => {
+                let tag = |name: &str| notice.source.tags.0.get(name).cloned().flatten();
+
+                let (Some(category), Some(value)) = (
+                    tag("msg-param-category"),
+                    tag("msg-param-value").and_then(|value| value.parse().ok()),
+                ) else {
+                    return None;
+                };
+
+                ChatEvent::Milestone {
+                    channel: notice.channel_login,
+                    user: notice.sender.name,
+                    category,
+                    value,
+                }
+            }
+            _ => return None,
+        },
+        ServerMessage::Whisper(whisper) => ChatEvent::Whisper {
+            from: whisper.sender.login,
+            text: whisper.message_text,
+        },
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+impl ChatBackend for TwitchBackend<'_> {
+    async fn next_event(&mut self) -> Option<ChatEvent> {
+        loop {
+            tokio::select! {
+                message = self.incoming.recv() => {
+                    self.last_seen = Instant::now();
+
+                    if let Some(event) = self.normalize(message?) {
+                        return Some(event);
+                    }
+                }
+                Some(message) = self.resolved.1.recv() => {
+                    return Some(ChatEvent::Message(Box::new(message)));
+                }
+            }
+        }
+    }
+
+    /// Unless that would go over the account's rate limit.
+    async fn send(
+        &mut self,
+        account: &str,
+        channel: String,
+        text: String,
+        action: bool,
+    ) -> Result<(), String> {
+        let Some(client) = self.clients.get(account) else {
+            return Err(format!(
+                "no credentials for account {0}, run :TwitchLogin <nick> {0}",
+                account
+            ));
+        };
+
+        if let Some(limit) = self.limits.get_mut(account) {
+            if let Err(wait) = limit.acquire() {
+                return Err(format!(
+                    "{} is at its rate limit, try again in {}s",
+                    account,
+                    wait.as_secs() + 1
+                ));
+            }
+        }
+
+        let result = match action {
+            true => client.me(channel, text).await,
+            false => client.say(channel, text).await,
+        };
+
+        result.map_err(|e| e.to_string())
+    }
+
+    fn can_send_as(&self, account: &str) -> bool {
+        self.clients.contains_key(account)
+    }
+}
+
+fn client_config(credentials: Option<&Credentials>) -> ClientConfig<StaticLoginCredentials> {
+    match credentials {
+        Some(credentials) => ClientConfig::new_simple(StaticLoginCredentials::new(
+            credentials.login.clone(),
+            Some(credentials.token.clone()),
+        )),
+        None => ClientConfig::default(),
+    }
+}
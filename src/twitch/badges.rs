@@ -0,0 +1,134 @@
+//! Badge titles from Helix, cached in `stdpath("cache")` so they show up
+//! right away on the next start and Helix is asked at most once a day.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::helix::Helix;
+
+const CACHE_FILE: &str = "badges.json";
+
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BadgeSets {
+    fetched_at: Option<DateTime<Utc>>,
+    /// Titles keyed by `set/version`.
+    global: HashMap<String, String>,
+    /// Channel-specific titles (subscriber tiers, bit tiers, ...) keyed by
+    /// channel login, these take precedence over the global ones.
+    channels: HashMap<String, HashMap<String, String>>,
+}
+
+impl BadgeSets {
+    /// The titles of the `badges` the renderer should show, in the order they
+    /// were given. Until Helix was asked, the common ones get a short label
+    /// and the others are left out.
+    pub fn titles(&self, channel: &str, badges: &[String]) -> Vec<Badge> {
+        let channel = self.channels.get(channel);
+
+        badges
+            .iter()
+            .filter_map(|badge| {
+                // Injected messages may leave out the version.
+                let key = match badge.contains('/') {
+                    true => badge.clone(),
+                    false => format!("{}/1", badge),
+                };
+
+                let set = key.split('/').next().unwrap_or_default();
+                let title = channel
+                    .and_then(|titles| titles.get(&key))
+                    .or_else(|| self.global.get(&key))
+                    .cloned()
+                    .or_else(|| short_title(set).map(str::to_owned))?;
+
+                Some(Badge {
+                    set: set.to_owned(),
+                    title,
+                })
+            })
+            .collect()
+    }
+
+    fn is_fresh(&self, channels: &[String]) -> bool {
+        let recent = self.fetched_at.is_some_and(|fetched_at| {
+            (Utc::now() - fetched_at).to_std().unwrap_or_default() < REFRESH_INTERVAL
+        });
+
+        recent
+            && channels
+                .iter()
+                .all(|channel| self.channels.contains_key(channel))
+    }
+}
+
+/// A badge as shown ahead of the author.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    /// E.g. `moderator`, which picks the highlight group.
+    pub set: String,
+    pub title: String,
+}
+
+fn short_title(set: &str) -> Option<&'static str> {
+    match set {
+        "broadcaster" => Some("HOST"),
+        "moderator" => Some("MOD"),
+        "vip" => Some("VIP"),
+        "subscriber" | "founder" => Some("SUB"),
+        _ => None,
+    }
+}
+
+pub fn cache_path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(CACHE_FILE)
+}
+
+pub fn load_cache(path: &Path) -> Option<BadgeSets> {
+    let data = fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn save_cache(path: &Path, badges: &BadgeSets) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let data = serde_json::to_vec(badges).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())
+}
+
+/// Returns the cached badges while they are fresh, otherwise fetches and
+/// caches them again.
+pub async fn refresh(helix: &Helix, path: &Path, channels: &[String]) -> Result<BadgeSets, String> {
+    if let Some(cached) = load_cache(path) {
+        if cached.is_fresh(channels) {
+            return Ok(cached);
+        }
+    }
+
+    let mut badges = BadgeSets {
+        fetched_at: Some(Utc::now()),
+        global: helix.global_badges().await?,
+        channels: HashMap::new(),
+    };
+
+    for channel in channels {
+        let id = helix.user_id(channel).await?;
+        badges
+            .channels
+            .insert(channel.clone(), helix.channel_badges(&id).await?);
+    }
+
+    save_cache(path, &badges)?;
+
+    Ok(badges)
+}
@@ -0,0 +1,111 @@
+//! Cheermotes, the `Cheer100`-style words that carry the bits of a cheer.
+//! They only count as such in messages that actually contain bits.
+
+/// The prefixes of the global cheermotes. Partner cheermotes aren't listed,
+/// they are left as plain text.
+const PREFIXES: &[&str] = &[
+    "cheer",
+    "doodlecheer",
+    "biblethump",
+    "cheerwhal",
+    "corgo",
+    "uni",
+    "showlove",
+    "party",
+    "seemsgood",
+    "pride",
+    "kappa",
+    "frankerz",
+    "heyguys",
+    "dansgame",
+    "elegiggle",
+    "trihard",
+    "kreygasm",
+    "4head",
+    "swiftrage",
+    "notlikethis",
+    "failfish",
+    "vohiyo",
+    "pjsalt",
+    "mrdestructoid",
+    "bday",
+    "ripcheer",
+    "shamrock",
+    "pogchamp",
+];
+
+/// Tiers by their minimum amount, lowest first.
+pub const TIERS: [u64; 5] = [1, 100, 1000, 5000, 10000];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheer {
+    /// Byte range of the cheermote in the message text.
+    pub start: usize,
+    pub end: usize,
+    pub bits: u64,
+}
+
+impl Cheer {
+    pub fn tier(&self) -> u64 {
+        TIERS
+            .iter()
+            .rev()
+            .copied()
+            .find(|&tier| self.bits >= tier)
+            .unwrap_or(TIERS[0])
+    }
+
+    /// E.g. `TwitchCheer1000`, see [`crate::ui::define_highlights`].
+    pub fn highlight(&self) -> String {
+        format!("TwitchCheer{}", self.tier())
+    }
+}
+
+pub fn find(text: &str) -> Vec<Cheer> {
+    let mut cheers = Vec::new();
+    let mut offset = 0;
+
+    for word in text.split(' ') {
+        if let Some(bits) = bits(word) {
+            cheers.push(Cheer {
+                start: offset,
+                end: offset + word.len(),
+                bits,
+            });
+        }
+
+        offset += word.len() + 1;
+    }
+
+    cheers
+}
+
+fn bits(word: &str) -> Option<u64> {
+    let digits = word.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, amount) = word.split_at(digits);
+
+    if !PREFIXES
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(prefix))
+    {
+        return None;
+    }
+
+    amount.parse().ok().filter(|&bits| bits > 0)
+}
+
+/// The text with every cheermote replaced by `[N bits]`, for places that
+/// can't highlight them such as the mod view and summaries.
+pub fn annotate(text: &str) -> String {
+    let mut annotated = String::new();
+    let mut rest = 0;
+
+    for cheer in find(text) {
+        annotated.push_str(&text[rest..cheer.start]);
+        annotated.push_str(&format!("[{} bits]", cheer.bits));
+        rest = cheer.end;
+    }
+
+    annotated.push_str(&text[rest..]);
+    annotated
+}
@@ -0,0 +1,1126 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use serde::Serialize;
+use tokio::{
+    sync::{
+        mpsc::{self, error::SendError, UnboundedReceiver, UnboundedSender},
+        oneshot, Mutex,
+    },
+    time,
+};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    ads::{self, AdAction},
+    backend::TwitchBackend,
+    badges, eventsub,
+    helix::Helix,
+    metadata::Lookups,
+    oauth,
+    polls::{self, PollAction},
+    raids::{self, RaidAction},
+    ratelimit::RateLimit,
+    shared::SharedChat,
+    slash::Slash,
+};
+use crate::{
+    auth::{self, Credentials},
+    backend::{supervised, Admission, ChatBackend, LocalBackend, Relay},
+    commands::{Command, CommandSender, SharedSyntax},
+    config::{BackendKind, Config},
+    hooks,
+    llm::Llm,
+    metrics,
+    paths::Paths,
+    summarize,
+    ui::PluginError,
+    webhooks,
+};
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(150);
+/// The first reconnect waits this long, doubling with every failure up to
+/// `MAX_RESTART_DELAY`.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+/// A connection that stayed up this long resets the backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(2 * 60);
+/// How often the accounts blocked on Twitch are fetched again.
+const BLOCKS_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    /// Stopped with `:TwitchDisconnect`.
+    Disconnected,
+    Reconnecting {
+        reason: String,
+        attempt: u32,
+        retry_in: u64,
+    },
+}
+
+/// Requests sent from the Neovim thread to the Twitch connection.
+#[derive(Debug)]
+pub enum Outgoing {
+    /// Sends `text` to the configured channel as `account`.
+    Say { account: String, text: String },
+    /// A chat command from `:TwitchSend` or `:TwitchSendAs`.
+    Slash { account: String, slash: Slash },
+    /// Answers in the channel a message came from, as the bot account.
+    Reply { channel: String, text: String },
+    /// POSTs a JSON `body` to a webhook.
+    Webhook { url: String, body: String },
+    /// Asks the LLM for a summary of the transcript.
+    Summarize { transcript: String },
+    /// Creates a stream marker on the primary channel as the main account.
+    Marker { description: String },
+    /// Whispers `text` to `to` as the main account.
+    Whisper { to: String, text: String },
+    /// Snoozes or starts an ad break on the primary channel.
+    Ad(AdAction),
+    /// Creates or ends a poll or prediction on the primary channel.
+    Poll(PollAction),
+    /// Starts or cancels a raid out of the primary channel.
+    Raid(RaidAction),
+    /// Any of the others made by a Lua callback, dropped or cut short if
+    /// the callback is stopped for running too long.
+    Cancellable {
+        request: Box<Outgoing>,
+        cancel: CancellationToken,
+    },
+}
+
+impl Outgoing {
+    /// The request, and what cancels it, which never fires for one that
+    /// isn't [`Outgoing::Cancellable`].
+    fn cancellable(self) -> (Outgoing, CancellationToken) {
+        match self {
+            Outgoing::Cancellable { request, cancel } => (*request, cancel),
+            request => (request, CancellationToken::new()),
+        }
+    }
+}
+
+/// Where the editor sends its requests. The ones made while a Lua callback
+/// runs go as [`Outgoing::Cancellable`], see `hooks::guarded`.
+#[derive(Clone)]
+pub struct OutgoingSender(UnboundedSender<Outgoing>);
+
+impl OutgoingSender {
+    pub fn new(sender: UnboundedSender<Outgoing>) -> Self {
+        OutgoingSender(sender)
+    }
+
+    pub fn send(&self, request: Outgoing) -> Result<(), SendError<Outgoing>> {
+        let request = match hooks::cancellation() {
+            Some(cancel) => Outgoing::Cancellable {
+                request: Box::new(request),
+                cancel,
+            },
+            None => request,
+        };
+
+        self.0.send(request)
+    }
+}
+
+/// What the editor asks for, handed from one connection to the next and
+/// left over by one that panicked.
+pub type SharedOutgoing = Arc<Mutex<UnboundedReceiver<Outgoing>>>;
+
+/// Runs the connection until `shutdown` fires. Returning drops the runtime,
+/// which cancels every task spawned on it along with the IRC client.
+#[tokio::main(flavor = "current_thread")]
+pub async fn connect(
+    config: Config,
+    syntax: SharedSyntax,
+    paths: Paths,
+    accounts: HashMap<String, Credentials>,
+    sender: CommandSender,
+    outgoing: UnboundedReceiver<Outgoing>,
+    shutdown: oneshot::Receiver<()>,
+) -> nvim_oxi::Result<()> {
+    tokio::select! {
+        _ = run(config, syntax, paths, accounts, sender, outgoing) => (),
+        _ = shutdown => (),
+    }
+
+    Ok(())
+}
+
+async fn run(
+    config: Config,
+    syntax: SharedSyntax,
+    paths: Paths,
+    mut accounts: HashMap<String, Credentials>,
+    sender: CommandSender,
+    outgoing: UnboundedReceiver<Outgoing>,
+) {
+    let outgoing = Arc::new(Mutex::new(outgoing));
+
+    if config.backend == BackendKind::WebSocket {
+        serve_locally(&config, &syntax, &sender, outgoing).await;
+        return;
+    }
+
+    refresh_credentials(&config, &paths, &mut accounts, &sender).await;
+
+    tokio::spawn(refresh_badges(
+        config.clone(),
+        paths.clone(),
+        accounts.get(auth::MAIN).cloned(),
+        sender.clone(),
+    ));
+
+    let helix = match (&config.client_id, accounts.get(auth::MAIN)) {
+        (Some(client_id), Some(credentials)) => Helix::new(client_id, credentials).ok(),
+        _ => None,
+    };
+
+    let primary = match (&helix, accounts.get(auth::MAIN)) {
+        (Some(helix), Some(credentials)) => Some(Primary::new(
+            helix.clone(),
+            config.channel.clone(),
+            credentials,
+        )),
+        _ => None,
+    };
+
+    // EventSub tells the ads watcher when to look at the schedule.
+    let (notices, noticed) = mpsc::unbounded_channel();
+    if let Some(primary) = primary.clone() {
+        tokio::spawn(watch_ads(primary, noticed, sender.clone()));
+    }
+    match (helix.clone(), config.redemptions.enabled) {
+        (Some(helix), redemptions) => {
+            tokio::spawn(eventsub::watch(
+                helix,
+                config.channel.clone(),
+                redemptions,
+                sender.clone(),
+                notices,
+            ));
+        }
+        (None, true) => sender.send(Command::Error(PluginError::error(
+            "EventSub",
+            "redemptions need a client_id and :TwitchLogin",
+        ))),
+        (None, false) => (),
+    }
+    if let Some(primary) = primary.clone() {
+        tokio::spawn(watch_followed(primary.clone(), sender.clone()));
+
+        if config.ignore.twitch_blocks {
+            tokio::spawn(watch_blocks(primary, sender.clone()));
+        }
+    }
+
+    let keep =
+        || Kept {
+            relay: Relay::new(&config, Arc::clone(&syntax), sender.clone()).looking_up(
+                Lookups::start(helix.clone(), config.channel.clone(), &sender),
+            ),
+            primary: primary.clone(),
+            shared: SharedChat::new(helix.clone()),
+            limits: config
+                .accounts
+                .iter()
+                .map(|(name, options)| (name.clone(), RateLimit::new(options.rate_limit)))
+                .collect(),
+        };
+    let mut kept = keep();
+
+    if let Some(address) = config.metrics.address() {
+        let sender = sender.clone();
+
+        tokio::spawn(async move {
+            if let Err(message) = metrics::serve(address).await {
+                sender.send(Command::Error(PluginError::error("Metrics", message)));
+            }
+        });
+    }
+
+    let mut attempt = 0;
+
+    loop {
+        let started = Instant::now();
+        let connection = watch_twitch(
+            config.clone(),
+            kept,
+            accounts.clone(),
+            sender.clone(),
+            Arc::clone(&outgoing),
+        );
+        let reason = match supervised(connection).await {
+            Ok((reason, back)) => {
+                kept = back;
+                reason
+            }
+            // What it kept went down with it.
+            Err(reason) => {
+                kept = keep();
+                reason
+            }
+        };
+
+        metrics::RECONNECTS.inc();
+
+        if started.elapsed() >= STABLE_AFTER {
+            attempt = 0;
+        }
+        attempt += 1;
+
+        let delay = backoff(attempt);
+        sender.send(Command::Reconnecting {
+            reason,
+            attempt,
+            retry_in: delay.as_secs(),
+        });
+
+        time::sleep(delay).await;
+
+        // An expired token is a common reason to end up here.
+        refresh_credentials(&config, &paths, &mut accounts, &sender).await;
+        if let (Some(helix), Some(credentials)) = (&helix, accounts.get(auth::MAIN)) {
+            helix.update(credentials);
+        }
+    }
+}
+
+/// Doubles the delay with every attempt and adds up to a quarter on top, so
+/// many clients dropped at once don't all come back in the same second.
+pub fn backoff(attempt: u32) -> Duration {
+    let delay = RESTART_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_RESTART_DELAY);
+    let jitter = rand::thread_rng().gen_range(0.0..=0.25);
+
+    delay.mul_f64(1.0 + jitter)
+}
+
+/// Runs the `websocket` backend in place of Twitch. Nothing goes to Twitch
+/// this way, not even with credentials, so Helix is left out altogether.
+async fn serve_locally(
+    config: &Config,
+    syntax: &SharedSyntax,
+    sender: &CommandSender,
+    outgoing: SharedOutgoing,
+) {
+    let connect = || {
+        let admission = Admission {
+            token: config.backend_token.clone(),
+            origins: config.backend_origins.clone(),
+        };
+        LocalBackend::bind(config.port, config.channel.clone(), admission)
+    };
+
+    serve(connect, config, syntax, sender, outgoing).await
+}
+
+/// Reads each backend `connect` makes until it ends, then makes another
+/// after the backoff. Each runs on a task of its own, see [`supervised`].
+pub async fn serve<B, F>(
+    mut connect: impl FnMut() -> F,
+    config: &Config,
+    syntax: &SharedSyntax,
+    sender: &CommandSender,
+    outgoing: SharedOutgoing,
+) where
+    B: ChatBackend + Send + 'static,
+    F: Future<Output = Result<B, String>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        let reason = match connect().await {
+            Ok(backend) => {
+                let connection = watch_backend(
+                    backend,
+                    config.clone(),
+                    Arc::clone(syntax),
+                    sender.clone(),
+                    Arc::clone(&outgoing),
+                );
+                supervised(connection).await.unwrap_or_else(|reason| reason)
+            }
+            Err(reason) => reason,
+        };
+
+        attempt += 1;
+
+        let delay = backoff(attempt);
+        sender.send(Command::Reconnecting {
+            reason,
+            attempt,
+            retry_in: delay.as_secs(),
+        });
+
+        time::sleep(delay).await;
+    }
+}
+
+/// Relays a backend other than Twitch until it ends, which only chat goes
+/// through, and returns the reason.
+async fn watch_backend(
+    mut backend: impl ChatBackend,
+    config: Config,
+    syntax: SharedSyntax,
+    sender: CommandSender,
+    outgoing: SharedOutgoing,
+) -> String {
+    let (config, sender) = (&config, &sender);
+    let mut outgoing = outgoing.lock().await;
+    let relay = Relay::new(config, syntax, sender.clone());
+
+    loop {
+        tokio::select! {
+            event = backend.next_event() => {
+                match event {
+                    Some(event) => relay.forward(event),
+                    None => return "connection closed".to_owned(),
+                }
+            }
+            Some(request) = outgoing.recv() => {
+                let (request, cancel) = request.cancellable();
+                let channel = config.channel.clone();
+
+                let handled = async {
+                    match request {
+                        Outgoing::Say { account, text } => {
+                            backend.send(&account, channel, text, false).await
+                        }
+                        Outgoing::Reply { channel, text } => {
+                            let account = reply_account(config, &backend);
+                            backend.send(account, channel, text, false).await
+                        }
+                        Outgoing::Slash { account, slash: Slash::Me(text) } => {
+                            backend.send(&account, channel, text, true).await
+                        }
+                        Outgoing::Summarize { transcript } => {
+                            summarize(config, transcript, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Webhook { url, body } => {
+                            webhook(url, body, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Slash { .. }
+                        | Outgoing::Marker { .. }
+                        | Outgoing::Whisper { .. }
+                        | Outgoing::Ad(_)
+                        | Outgoing::Poll(_)
+                        | Outgoing::Raid(_) => {
+                            Err("this needs Twitch, not the websocket backend".to_owned())
+                        }
+                        // Unwrapped above, a request is only ever wrapped once.
+                        Outgoing::Cancellable { .. } => Ok(()),
+                    }
+                };
+                let result = tokio::select! {
+                    biased;
+                    // Stopped before it was sent, or while it waited to be.
+                    () = cancel.cancelled() => Ok(()),
+                    result = handled => result,
+                };
+
+                if let Err(message) = result {
+                    sender.send(Command::Error(PluginError::error("Send", message)));
+                }
+            }
+        }
+    }
+}
+
+/// The bot account, unless its credentials are missing, in which case the
+/// main account answers rather than nobody.
+fn reply_account<'a>(config: &'a Config, backend: &impl ChatBackend) -> &'a str {
+    config
+        .bot
+        .as_deref()
+        .filter(|bot| backend.can_send_as(bot))
+        .unwrap_or(auth::MAIN)
+}
+
+/// Validates every account's token, refreshing and storing again those that
+/// expired. Accounts whose token can't be renewed are dropped, so the main
+/// account falls back to reading anonymously.
+async fn refresh_credentials(
+    config: &Config,
+    paths: &Paths,
+    accounts: &mut HashMap<String, Credentials>,
+    sender: &CommandSender,
+) {
+    let mut expired = Vec::new();
+
+    for (account, credentials) in accounts.iter_mut() {
+        match oauth::ensure(config.client_id.as_deref(), credentials).await {
+            Ok(oauth::Checked::Valid) => (),
+            Ok(oauth::Checked::Changed) => {
+                // The keychain backends block on their own executor.
+                let (paths, account, credentials) =
+                    (paths.clone(), account.clone(), credentials.clone());
+                let stored = tokio::task::spawn_blocking(move || {
+                    auth::save(&paths, &account, &credentials)
+                        .map_err(|message| format!("{}: {}", account, message))
+                })
+                .await;
+
+                if let Ok(Err(message)) = stored {
+                    sender.send(Command::Error(PluginError::error("Credentials", message)));
+                }
+            }
+            Ok(oauth::Checked::Expired) => {
+                expired.push(account.clone());
+                sender.send(Command::Error(PluginError::error(
+                    "Credentials",
+                    format!("{}: the token expired, log in again", account),
+                )));
+            }
+            Err(message) => {
+                // Without a login there is nothing to connect as, otherwise
+                // the token is given a chance until Twitch rejects it.
+                if credentials.login.is_empty() {
+                    expired.push(account.clone());
+                }
+                sender.send(Command::Error(PluginError::warning(
+                    "Credentials",
+                    format!("{}: {}", account, message),
+                )));
+            }
+        }
+    }
+
+    for account in expired {
+        accounts.remove(&account);
+    }
+}
+
+/// What stays across reconnects.
+struct Kept {
+    /// Along with the gift trains it collects.
+    relay: Relay,
+    primary: Option<Primary>,
+    shared: SharedChat,
+    /// Twitch doesn't reset its count either.
+    limits: HashMap<String, RateLimit>,
+}
+
+/// [`watch_connection`] with all it needs its own, to run on a task of its
+/// own, handing back what it kept.
+async fn watch_twitch(
+    config: Config,
+    mut kept: Kept,
+    accounts: HashMap<String, Credentials>,
+    sender: CommandSender,
+    outgoing: SharedOutgoing,
+) -> (String, Kept) {
+    let mut outgoing = outgoing.lock().await;
+    let reason = watch_connection(&config, &mut kept, &accounts, &sender, &mut outgoing).await;
+
+    (reason, kept)
+}
+
+/// Runs one connection stack until the watchdog decides it is wedged and
+/// returns the reason.
+async fn watch_connection(
+    config: &Config,
+    kept: &mut Kept,
+    accounts: &HashMap<String, Credentials>,
+    sender: &CommandSender,
+    outgoing: &mut UnboundedReceiver<Outgoing>,
+) -> String {
+    let (mut backend, error) = TwitchBackend::connect(
+        accounts,
+        config.channels(),
+        &mut kept.limits,
+        kept.shared.clone(),
+        config.shared_chat,
+    );
+    let (relay, primary) = (&kept.relay, kept.primary.as_ref());
+
+    if let Some(message) = error {
+        sender.send(Command::Error(PluginError::warning("Connection", message)));
+    }
+
+    let mut keepalive = time::interval(KEEPALIVE_INTERVAL);
+    let mut validation = time::interval(oauth::VALIDATE_INTERVAL);
+    // The token was just validated before connecting.
+    validation.tick().await;
+
+    loop {
+        tokio::select! {
+            event = backend.next_event() => {
+                match event {
+                    Some(event) => relay.forward(event),
+                    None => return "channel closed".to_owned(),
+                }
+            }
+            _ = keepalive.tick() => {
+                if backend.silent_for() > KEEPALIVE_TIMEOUT {
+                    return "no keepalives".to_owned();
+                }
+
+                backend.ping(KEEPALIVE_INTERVAL).await;
+            }
+            _ = validation.tick() => {
+                let Some(credentials) = accounts.get(auth::MAIN) else {
+                    continue;
+                };
+
+                // Network errors are left to the keepalive, only a token
+                // Twitch rejects ends the connection.
+                if let Ok(None) = oauth::validate(&credentials.token).await {
+                    return "token expired".to_owned();
+                }
+            }
+            Some(request) = outgoing.recv() => {
+                let (request, cancel) = request.cancellable();
+                let channel = config.channel.clone();
+
+                let handled = async {
+                    match request {
+                        Outgoing::Say { account, text } => {
+                            backend.send(&account, channel, text, false).await
+                        }
+                        Outgoing::Reply { channel, text } => {
+                            let account = reply_account(config, &backend);
+                            backend.send(account, channel, text, false).await
+                        }
+                        Outgoing::Slash { account, slash: Slash::Me(text) } => {
+                            backend.send(&account, channel, text, true).await
+                        }
+                        Outgoing::Slash { account, slash } => {
+                            let acting = match (primary, accounts.get(&account)) {
+                                _ if account == auth::MAIN => Ok(primary.cloned()),
+                                (Some(primary), Some(credentials)) => {
+                                    primary.acting_as(config, credentials).map(Some)
+                                }
+                                _ => Ok(None),
+                            };
+                            acting.map(|acting| moderate(acting.as_ref(), slash, sender, &cancel))
+                        }
+                        Outgoing::Summarize { transcript } => {
+                            summarize(config, transcript, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Webhook { url, body } => {
+                            webhook(url, body, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Marker { description } => {
+                            marker(primary, description, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Whisper { to, text } => {
+                            whisper(primary, to, text, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Ad(action) => {
+                            ad(primary, action, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Poll(action) => {
+                            poll(primary, action, sender, &cancel);
+                            Ok(())
+                        }
+                        Outgoing::Raid(action) => {
+                            raid(primary, action, sender, &cancel);
+                            Ok(())
+                        }
+                        // Unwrapped above, a request is only ever wrapped once.
+                        Outgoing::Cancellable { .. } => Ok(()),
+                    }
+                };
+                let result = tokio::select! {
+                    biased;
+                    // Stopped before it was sent, or while it waited to be.
+                    () = cancel.cancelled() => Ok(()),
+                    result = handled => result,
+                };
+
+                if let Err(message) = result {
+                    sender.send(Command::Error(PluginError::error("Send", message)));
+                }
+            }
+        }
+    }
+}
+
+/// Sends the cached badges right away, then keeps them up to date while
+/// there are credentials and a `client_id` to ask Helix with.
+async fn refresh_badges(
+    config: Config,
+    paths: Paths,
+    credentials: Option<Credentials>,
+    sender: CommandSender,
+) {
+    let path = badges::cache_path(&paths.cache);
+
+    if let Some(cached) = badges::load_cache(&path) {
+        sender.send(Command::Badges(cached));
+    }
+
+    let (Some(client_id), Some(credentials)) = (&config.client_id, &credentials) else {
+        return;
+    };
+
+    let helix = match Helix::new(client_id, credentials) {
+        Ok(helix) => helix,
+        Err(message) => {
+            sender.send(Command::Error(PluginError::error("Badges", message)));
+            return;
+        }
+    };
+
+    let channels: Vec<String> = config.channels().into_iter().collect();
+    let mut interval = time::interval(badges::REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        sender.send(match badges::refresh(&helix, &path, &channels).await {
+            Ok(badges) => Command::Badges(badges),
+            Err(message) => Command::Error(PluginError::warning("Badges", message)),
+        });
+    }
+}
+
+/// Runs on its own task so a slow endpoint doesn't hold up the keepalives.
+fn summarize(
+    config: &Config,
+    transcript: String,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    let options = config.llm.clone();
+    let sender = sender.clone();
+
+    spawn(cancel, async move {
+        let summary = match Llm::new(options) {
+            Ok(llm) => llm.complete(summarize::PROMPT, &transcript).await,
+            Err(message) => Err(message),
+        };
+
+        sender.send(match summary {
+            Ok(text) => Command::Summary { text },
+            Err(message) => Command::Error(PluginError::error("Summary", message)),
+        });
+    });
+}
+
+/// The Helix client for requests from the editor on the primary channel,
+/// with the user ids it needed looked up once for the whole session.
+#[derive(Clone)]
+struct Primary {
+    helix: Helix,
+    channel: String,
+    /// The main account's login.
+    login: String,
+    ids: Arc<tokio::sync::Mutex<HashMap<String, String>>>,
+}
+
+impl Primary {
+    fn new(helix: Helix, channel: String, credentials: &Credentials) -> Self {
+        Primary {
+            helix,
+            channel,
+            login: credentials.login.clone(),
+            ids: Arc::default(),
+        }
+    }
+
+    /// The same for another account, for chat commands sent as it.
+    fn acting_as(&self, config: &Config, credentials: &Credentials) -> Result<Self, String> {
+        let client_id = config.client_id.as_deref().unwrap_or_default();
+
+        Ok(Primary {
+            helix: Helix::new(client_id, credentials)?,
+            login: credentials.login.clone(),
+            ..self.clone()
+        })
+    }
+
+    async fn id(&self, login: &str) -> Result<String, String> {
+        if let Some(id) = self.ids.lock().await.get(login) {
+            return Ok(id.clone());
+        }
+
+        let id = self.helix.user_id(login).await?;
+        self.ids.lock().await.insert(login.to_owned(), id.clone());
+        Ok(id)
+    }
+
+    async fn broadcaster(&self) -> Result<String, String> {
+        self.id(&self.channel).await
+    }
+
+    /// The account the requests are made as.
+    async fn user(&self) -> Result<String, String> {
+        self.id(&self.login).await
+    }
+}
+
+/// Runs `request` on a task of its own, so a slow endpoint doesn't hold up
+/// the keepalives, and passes on what it returns. Without a `client_id`
+/// and a login only the error naming `what` needs them is.
+fn request<F, R>(
+    primary: Option<&Primary>,
+    context: &str,
+    what: &str,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+    request: F,
+) where
+    F: FnOnce(Primary) -> R + Send + 'static,
+    R: Future<Output = Result<Option<Command>, String>> + Send,
+{
+    let context = context.to_owned();
+    let Some(primary) = primary.cloned() else {
+        sender.send(Command::Error(PluginError::error(
+            context,
+            format!("{} need a client_id and :TwitchLogin", what),
+        )));
+        return;
+    };
+
+    let sender = sender.clone();
+
+    spawn(cancel, async move {
+        match request(primary).await {
+            Ok(Some(command)) => sender.send(command),
+            Ok(None) => (),
+            Err(message) => sender.send(Command::Error(PluginError::error(context, message))),
+        }
+    });
+}
+
+/// Runs `task` on a task of its own until it is done or `cancel` fires.
+fn spawn(cancel: &CancellationToken, task: impl Future<Output = ()> + Send + 'static) {
+    let cancel = cancel.clone();
+
+    tokio::spawn(async move {
+        tokio::select! {
+            () = task => (),
+            () = cancel.cancelled() => (),
+        }
+    });
+}
+
+/// Runs a Helix chat command in the configured channel.
+fn moderate(
+    primary: Option<&Primary>,
+    slash: Slash,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    request(
+        primary,
+        "Send",
+        "chat commands",
+        sender,
+        cancel,
+        |primary| async move {
+            let helix = &primary.helix;
+            let broadcaster = primary.broadcaster().await?;
+            let moderator = primary.user().await?;
+
+            match slash {
+                Slash::Announce(message) => {
+                    helix.announce(&broadcaster, &moderator, &message).await?
+                }
+                Slash::Timeout {
+                    user,
+                    seconds,
+                    reason,
+                } => {
+                    let user = primary.id(&user).await?;
+                    helix
+                        .timeout(&broadcaster, &moderator, &user, seconds, &reason)
+                        .await?
+                }
+                Slash::Slow(seconds) => helix.slow_mode(&broadcaster, &moderator, seconds).await?,
+                Slash::Delete(id) => helix.delete_message(&broadcaster, &moderator, &id).await?,
+                Slash::Me(_) => (),
+            }
+
+            Ok(None)
+        },
+    );
+}
+
+fn marker(
+    primary: Option<&Primary>,
+    description: String,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    request(
+        primary,
+        "Marker",
+        "stream markers",
+        sender,
+        cancel,
+        |primary| async move {
+            let broadcaster = primary.broadcaster().await?;
+            let marker = primary
+                .helix
+                .create_marker(&broadcaster, &description)
+                .await?;
+
+            Ok(Some(Command::Marker {
+                position: marker.position_seconds,
+                description: marker.description,
+            }))
+        },
+    );
+}
+
+fn whisper(
+    primary: Option<&Primary>,
+    to: String,
+    text: String,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    request(
+        primary,
+        "Whisper",
+        "whispers",
+        sender,
+        cancel,
+        |primary| async move {
+            let from = primary.user().await?;
+            let recipient = primary.id(&to).await?;
+            primary.helix.whisper(&from, &recipient, &text).await?;
+
+            Ok(Some(Command::Whisper {
+                user: to,
+                text,
+                sent: true,
+            }))
+        },
+    );
+}
+
+/// Fetches the ad schedule whenever it may have changed, passing it on if it
+/// did: right away, when `notices` tell the stream went live or a break
+/// began, after that break, and shortly before the next. A failure is only
+/// reported once until the next success, as a token without the
+/// `channel:read:ads` scope would otherwise fail every time.
+async fn watch_ads(
+    primary: Primary,
+    mut notices: UnboundedReceiver<ads::Notice>,
+    sender: CommandSender,
+) {
+    let mut due = Some(Duration::ZERO);
+    let mut last = None;
+    let mut failing = false;
+
+    loop {
+        let wait = time::sleep(due.unwrap_or_default());
+        tokio::select! {
+            Some(notice) = notices.recv() => {
+                due = Some(match notice {
+                    ads::Notice::Online => Duration::ZERO,
+                    ads::Notice::Began { length } => {
+                        Duration::from_secs(length.into()) + ads::AFTER_BREAK
+                    }
+                });
+                continue;
+            }
+            _ = wait, if due.is_some() => (),
+        }
+
+        let result = async {
+            let id = primary.broadcaster().await?;
+            ads::schedule(&primary.helix, &id).await
+        };
+
+        match result.await {
+            Ok(schedule) => {
+                failing = false;
+                due = ads::recheck_in(&schedule);
+
+                if last.as_ref() != Some(&schedule) {
+                    last = Some(schedule.clone());
+                    sender.send(Command::AdSchedule(schedule));
+                }
+            }
+            Err(message) => {
+                due = Some(ads::RETRY_INTERVAL);
+
+                if !failing {
+                    failing = true;
+                    sender.send(Command::Error(PluginError::warning("Ads", message)));
+                }
+            }
+        }
+    }
+}
+
+/// Looks up which followed channels are live, for `:TwitchRaid` to complete.
+/// Like the ad schedule, a failure is only reported once in a row.
+async fn watch_followed(primary: Primary, sender: CommandSender) {
+    let mut interval = time::interval(raids::FOLLOWED_INTERVAL);
+    let mut last = None;
+    let mut failing = false;
+
+    loop {
+        interval.tick().await;
+
+        let result = async {
+            let id = primary.user().await?;
+            primary.helix.followed_live(&id).await
+        };
+
+        match result.await {
+            Ok(channels) => {
+                failing = false;
+
+                if last.as_ref() != Some(&channels) {
+                    last = Some(channels.clone());
+                    sender.send(Command::LiveChannels { channels });
+                }
+            }
+            Err(message) if !failing => {
+                failing = true;
+                sender.send(Command::Error(PluginError::warning("Raid", message)));
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+/// Passes on the accounts the main account blocked whenever they change. A
+/// token without the `user:read:blocked_users` scope is only reported once.
+async fn watch_blocks(primary: Primary, sender: CommandSender) {
+    let mut interval = time::interval(BLOCKS_INTERVAL);
+    let mut last = None;
+    let mut failing = false;
+
+    loop {
+        interval.tick().await;
+
+        let result = async {
+            let id = primary.user().await?;
+            primary.helix.blocked_users(&id).await
+        };
+
+        match result.await {
+            Ok(users) => {
+                failing = false;
+
+                if last.as_ref() != Some(&users) {
+                    last = Some(users.clone());
+                    sender.send(Command::BlockedUsers { users });
+                }
+            }
+            Err(message) if !failing => {
+                failing = true;
+                sender.send(Command::Error(PluginError::warning("Ignore", message)));
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+fn raid(
+    primary: Option<&Primary>,
+    action: RaidAction,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    request(
+        primary,
+        "Raid",
+        "raids",
+        sender,
+        cancel,
+        |primary| async move {
+            let from = primary.broadcaster().await?;
+
+            Ok(Some(match action {
+                RaidAction::Start(to) => {
+                    let to_id = primary.id(&to).await?;
+                    primary.helix.start_raid(&from, &to_id).await?;
+                    Command::RaidStarted { to }
+                }
+                RaidAction::Cancel => {
+                    primary.helix.cancel_raid(&from).await?;
+                    Command::RaidCanceled
+                }
+            }))
+        },
+    );
+}
+
+fn ad(
+    primary: Option<&Primary>,
+    action: AdAction,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    request(
+        primary,
+        "Ads",
+        "ad breaks",
+        sender,
+        cancel,
+        |primary| async move {
+            let broadcaster = primary.broadcaster().await?;
+
+            match action {
+                AdAction::Snooze => ads::snooze(&primary.helix, &broadcaster)
+                    .await
+                    .map(|schedule| Some(Command::AdSchedule(schedule))),
+                // Announced once EventSub tells it began, like any other.
+                AdAction::Run(length) => primary
+                    .helix
+                    .start_commercial(&broadcaster, length)
+                    .await
+                    .map(|_| None),
+            }
+        },
+    );
+}
+
+fn poll(
+    primary: Option<&Primary>,
+    action: PollAction,
+    sender: &CommandSender,
+    cancel: &CancellationToken,
+) {
+    let follower = sender.clone();
+
+    request(
+        primary,
+        "Polls",
+        "polls and predictions",
+        sender,
+        cancel,
+        |primary| async move {
+            let broadcaster = primary.broadcaster().await?;
+            polls::run(&primary.helix, &broadcaster, action, &follower).await?;
+            Ok(None)
+        },
+    );
+}
+
+fn webhook(url: String, body: String, sender: &CommandSender, cancel: &CancellationToken) {
+    let sender = sender.clone();
+
+    spawn(cancel, async move {
+        if let Err(message) = webhooks::deliver(&url, body).await {
+            sender.send(Command::Error(PluginError::error("Webhook", message)));
+        }
+    });
+}
@@ -0,0 +1,91 @@
+//! Emotes where the `emotes` tag places them, shown as `:Kappa:` in the chat
+//! window or drawn by an image plugin listening for `User TwitchEmote`.
+
+use serde::{Deserialize, Serialize};
+use twitch_irc::message::Emote;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmoteSpan {
+    /// Twitch's emote id, which the image URL is built from.
+    pub id: String,
+    pub code: String,
+    /// Byte range of the emote in the message text.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl EmoteSpan {
+    /// Twitch counts characters, the text is indexed in bytes. `None` for a
+    /// range past the end of the text.
+    pub fn from_emote(text: &str, emote: &Emote) -> Option<Self> {
+        let byte = |index: usize| {
+            text.char_indices()
+                .map(|(byte, _)| byte)
+                .chain([text.len()])
+                .nth(index)
+        };
+
+        Some(EmoteSpan {
+            id: emote.id.clone(),
+            code: emote.code.clone(),
+            start: byte(emote.char_range.start)?,
+            end: byte(emote.char_range.end)?,
+        })
+    }
+
+    /// The emote's image on Twitch's CDN, for image plugins.
+    pub fn url(&self) -> String {
+        format!(
+            "https://static-cdn.jtvnw.net/emoticons/v2/{}/default/dark/1.0",
+            self.id
+        )
+    }
+}
+
+/// The spans that lie in `text[offset..]`, relative to it. Used for the text
+/// after `!nvim `.
+pub fn within(spans: &[EmoteSpan], offset: usize) -> Vec<EmoteSpan> {
+    spans
+        .iter()
+        .filter(|span| span.start >= offset)
+        .map(|span| EmoteSpan {
+            start: span.start - offset,
+            end: span.end - offset,
+            ..span.clone()
+        })
+        .collect()
+}
+
+/// Replaces every emote in `text` with `:code:`, returning the new text and
+/// where the placeholders ended up in it.
+pub fn placeholders(text: &str, spans: &[EmoteSpan]) -> (String, Vec<EmoteSpan>) {
+    let mut spans = spans.to_vec();
+    spans.sort_by_key(|span| span.start);
+
+    let mut replaced = String::with_capacity(text.len());
+    let mut placed = Vec::with_capacity(spans.len());
+    let mut last = 0;
+
+    for span in spans {
+        // Overlapping or out of range, which Twitch shouldn't send.
+        if span.start < last || text.get(span.start..span.end).is_none() {
+            continue;
+        }
+
+        replaced.push_str(&text[last..span.start]);
+        let start = replaced.len();
+        replaced.push(':');
+        replaced.push_str(&span.code);
+        replaced.push(':');
+
+        last = span.end;
+        placed.push(EmoteSpan {
+            start,
+            end: replaced.len(),
+            ..span
+        });
+    }
+
+    replaced.push_str(&text[last..]);
+    (replaced, placed)
+}
@@ -0,0 +1,393 @@
+//! What happens on the primary channel, pushed over an EventSub WebSocket
+//! session: channel point redemptions, which need the
+//! `channel:read:redemptions` scope, polls and predictions, which the
+//! scopes for `:TwitchPollCreate` cover, ad breaks, which need
+//! `channel:read:ads`, and the stream going live. The subscriptions are
+//! created through Helix once Twitch welcomes the session.
+
+use std::{collections::HashSet, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tokio::{sync::mpsc::UnboundedSender, time};
+
+use super::{
+    ads::Notice,
+    connection::backoff,
+    helix::Helix,
+    polls::{Poll, Prediction},
+    websocket::WebSocket,
+};
+use crate::{
+    commands::{Command, CommandSender},
+    ui::PluginError,
+};
+
+const URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+const REDEMPTION: &str = "channel.channel_points_custom_reward_redemption.add";
+const AD_BREAK: &str = "channel.ad_break.begin";
+const ONLINE: &str = "stream.online";
+
+/// Each with the status a Helix poll or prediction has at that point, the
+/// end carries its own.
+const POLLS: [(&str, Option<&str>); 3] = [
+    ("channel.poll.begin", Some("ACTIVE")),
+    ("channel.poll.progress", Some("ACTIVE")),
+    ("channel.poll.end", None),
+];
+const PREDICTIONS: [(&str, Option<&str>); 4] = [
+    ("channel.prediction.begin", Some("ACTIVE")),
+    ("channel.prediction.progress", Some("ACTIVE")),
+    ("channel.prediction.lock", Some("LOCKED")),
+    ("channel.prediction.end", None),
+];
+
+/// On top of the keepalive Twitch promises, before the session counts as
+/// dead.
+const KEEPALIVE_SLACK: Duration = Duration::from_secs(5);
+/// Until the welcome message, which Twitch sends right away.
+const WELCOME_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct Message {
+    metadata: Metadata,
+    #[serde(default)]
+    payload: Value,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    message_type: String,
+    #[serde(default)]
+    subscription_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    id: String,
+    #[serde(default)]
+    keepalive_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    reconnect_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Redemption {
+    broadcaster_user_login: String,
+    user_name: String,
+    #[serde(default)]
+    user_input: String,
+    reward: Reward,
+}
+
+#[derive(Deserialize)]
+struct Reward {
+    title: String,
+    cost: u64,
+}
+
+/// How a session ended.
+enum Ended {
+    /// Twitch moved the session, the subscriptions go along.
+    Moved(String),
+    Failed(String),
+}
+
+/// Where notifications go.
+struct Told<'a> {
+    sender: &'a CommandSender,
+    /// What the ad schedule is fetched again for.
+    ads: &'a UnboundedSender<Notice>,
+}
+
+/// A session Twitch welcomed.
+struct Live {
+    socket: WebSocket,
+    id: String,
+    keepalive: Duration,
+}
+
+/// Keeps a session open for `channel`'s polls, predictions, ad breaks and
+/// going live, and its redemptions with `redemptions`, for as long as the
+/// connection runs, resubscribing whenever a new one has to be started.
+pub async fn watch(
+    helix: Helix,
+    channel: String,
+    redemptions: bool,
+    sender: CommandSender,
+    ads: UnboundedSender<Notice>,
+) {
+    let broadcaster_id = broadcaster_id(&helix, &channel, &sender).await;
+    let told = Told {
+        sender: &sender,
+        ads: &ads,
+    };
+
+    let mut types: Vec<&str> = POLLS
+        .iter()
+        .chain(&PREDICTIONS)
+        .map(|(kind, _)| *kind)
+        .chain([AD_BREAK, ONLINE])
+        .collect();
+    if redemptions {
+        types.push(REDEMPTION);
+    }
+
+    // Reported once, not with every new session.
+    let mut refused = HashSet::new();
+    let mut moved = None;
+    let mut attempt = 0;
+
+    loop {
+        let result = match moved.take() {
+            Some(live) => Ok(live),
+            None => subscribed(&helix, &broadcaster_id, &types, &mut refused, &sender).await,
+        };
+        let ended = match result {
+            Ok(mut live) => {
+                attempt = 0;
+                match listen(&mut live, &told).await {
+                    Ended::Moved(url) => move_to(live, &url, &told).await,
+                    Ended::Failed(message) => Err(message),
+                }
+            }
+            Err(message) => Err(message),
+        };
+
+        match ended {
+            Ok(live) => moved = Some(live),
+            Err(message) => {
+                report(&sender, message);
+
+                attempt += 1;
+                time::sleep(backoff(attempt)).await;
+            }
+        }
+    }
+}
+
+/// The id of `channel`, tried again until Helix answers. Only the first
+/// failure in a row is reported.
+async fn broadcaster_id(helix: &Helix, channel: &str, sender: &CommandSender) -> String {
+    let mut attempt = 0;
+
+    loop {
+        match helix.user_id(channel).await {
+            Ok(id) => return id,
+            Err(message) => {
+                if attempt == 0 {
+                    report(sender, message);
+                }
+                attempt += 1;
+                time::sleep(backoff(attempt)).await;
+            }
+        }
+    }
+}
+
+/// A new session, with whatever of `types` Twitch allows subscribed to. A
+/// type it refuses, say for a missing scope, is reported the first time and
+/// left out; the session fails only without any.
+async fn subscribed<'a>(
+    helix: &Helix,
+    broadcaster_id: &str,
+    types: &[&'a str],
+    refused: &mut HashSet<&'a str>,
+    sender: &CommandSender,
+) -> Result<Live, String> {
+    let live = open(URL).await?;
+    let condition = json!({ "broadcaster_user_id": broadcaster_id });
+
+    let mut failure = None;
+    for kind in types {
+        match helix
+            .subscribe(kind, "1", condition.clone(), &live.id)
+            .await
+        {
+            Ok(()) => {
+                refused.remove(kind);
+            }
+            Err(message) => {
+                let message = format!("{}: {}", kind, message);
+                if refused.insert(kind) {
+                    sender.send(Command::Error(PluginError::error(
+                        "EventSub",
+                        message.clone(),
+                    )));
+                }
+                failure = Some(message);
+            }
+        }
+    }
+
+    match failure {
+        Some(message) if types.iter().all(|kind| refused.contains(kind)) => Err(message),
+        _ => Ok(live),
+    }
+}
+
+/// Connects to `url` and waits for Twitch to welcome the session.
+async fn open(url: &str) -> Result<Live, String> {
+    let mut socket = WebSocket::connect(url).await?;
+
+    let welcome = next(&mut socket, WELCOME_TIMEOUT).await?;
+    if welcome.metadata.message_type != "session_welcome" {
+        return Err(format!(
+            "expected a welcome, got {}",
+            welcome.metadata.message_type
+        ));
+    }
+    let session = Session::deserialize(&welcome.payload["session"]).map_err(|e| e.to_string())?;
+
+    Ok(Live {
+        socket,
+        id: session.id,
+        keepalive: Duration::from_secs(session.keepalive_timeout_seconds.unwrap_or(10))
+            + KEEPALIVE_SLACK,
+    })
+}
+
+/// Opens the session Twitch moved `old` to. Until it is welcomed, what
+/// still arrives on `old` is passed on, then `old` is closed.
+async fn move_to(mut old: Live, url: &str, told: &Told<'_>) -> Result<Live, String> {
+    let opening = open(url);
+    tokio::pin!(opening);
+    let mut reading = true;
+
+    loop {
+        tokio::select! {
+            live = &mut opening => return live,
+            message = next(&mut old.socket, old.keepalive), if reading => match message {
+                // Being moved again before the move is done isn't followed.
+                Ok(message) => {
+                    let _ = handle(message, told);
+                }
+                Err(_) => reading = false,
+            },
+        }
+    }
+}
+
+/// Passes on the notifications of `live` until it ends.
+async fn listen(live: &mut Live, told: &Told<'_>) -> Ended {
+    loop {
+        match next(&mut live.socket, live.keepalive).await {
+            Ok(message) => {
+                if let Some(ended) = handle(message, told) {
+                    return ended;
+                }
+            }
+            Err(message) => return Ended::Failed(message),
+        }
+    }
+}
+
+/// Passes on a notification, or tells how the session ends.
+fn handle(message: Message, told: &Told) -> Option<Ended> {
+    let sender = told.sender;
+    let kind = message.metadata.subscription_type.as_deref();
+
+    match message.metadata.message_type.as_str() {
+        "notification" if kind == Some(AD_BREAK) => {
+            let event = &message.payload["event"];
+            // Documented as numbers and a boolean, shown as strings.
+            let length = match &event["duration_seconds"] {
+                Value::String(text) => text.parse().ok(),
+                value => value.as_u64(),
+            };
+            let automatic = match &event["is_automatic"] {
+                Value::String(text) => text == "true",
+                value => value.as_bool().unwrap_or_default(),
+            };
+
+            if let Some(length) = length.and_then(|length| u32::try_from(length).ok()) {
+                sender.send(Command::AdBreak { length, automatic });
+                let _ = told.ads.send(Notice::Began { length });
+            }
+            None
+        }
+        "notification" if kind == Some(ONLINE) => {
+            let _ = told.ads.send(Notice::Online);
+            None
+        }
+        "notification" if kind.is_some_and(|kind| kind.starts_with("channel.poll.")) => {
+            if let Some(poll) = as_helix::<Poll>(kind, &POLLS, &message.payload["event"]) {
+                sender.send(Command::Poll(poll));
+            }
+            None
+        }
+        "notification" if kind.is_some_and(|kind| kind.starts_with("channel.prediction.")) => {
+            let event = &message.payload["event"];
+            if let Some(prediction) = as_helix::<Prediction>(kind, &PREDICTIONS, event) {
+                sender.send(Command::Prediction(prediction));
+            }
+            None
+        }
+        "notification" if message.metadata.subscription_type.as_deref() == Some(REDEMPTION) => {
+            if let Ok(redemption) = Redemption::deserialize(&message.payload["event"]) {
+                sender.send(Command::Redemption {
+                    channel: redemption.broadcaster_user_login,
+                    user: redemption.user_name,
+                    reward: redemption.reward.title,
+                    cost: redemption.reward.cost,
+                    input: redemption.user_input,
+                });
+            }
+            None
+        }
+        "session_reconnect" => {
+            let url = Session::deserialize(&message.payload["session"])
+                .ok()
+                .and_then(|session| session.reconnect_url);
+
+            Some(match url {
+                Some(url) => Ended::Moved(url),
+                None => Ended::Failed("Twitch asked to reconnect without a URL".to_owned()),
+            })
+        }
+        "revocation" => {
+            let status = message.payload["subscription"]["status"]
+                .as_str()
+                .unwrap_or("revoked");
+            Some(Ended::Failed(format!(
+                "the subscription was revoked: {}",
+                status
+            )))
+        }
+        // Keepalives only reset the timeout.
+        _ => None,
+    }
+}
+
+/// A poll or prediction event as Helix would return it, with the status
+/// Twitch gives in capitals.
+fn as_helix<T: DeserializeOwned>(
+    kind: Option<&str>,
+    statuses: &[(&str, Option<&str>)],
+    event: &Value,
+) -> Option<T> {
+    let (_, status) = statuses.iter().find(|(known, _)| Some(*known) == kind)?;
+    let status = match status {
+        Some(status) => status.to_string(),
+        None => event["status"].as_str()?.to_uppercase(),
+    };
+
+    let mut event = event.clone();
+    event["status"] = Value::String(status);
+    serde_json::from_value(event).ok()
+}
+
+/// The next message, failing when none arrives within `timeout`.
+async fn next(socket: &mut WebSocket, timeout: Duration) -> Result<Message, String> {
+    let text = time::timeout(timeout, socket.next_text())
+        .await
+        .map_err(|_| "no keepalives".to_owned())??
+        .ok_or_else(|| "Twitch closed the session".to_owned())?;
+
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// Reports a failure the session is opened again after.
+fn report(sender: &CommandSender, message: String) {
+    sender.send(Command::Error(PluginError::warning("EventSub", message)));
+}
@@ -0,0 +1,126 @@
+//! A mass gift arrives as one `submysterygift` followed by a `subgift` per
+//! recipient. They are collected into a single [`GiftTrain`] so the burst
+//! becomes one alert.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use crate::commands::{Command, CommandSender};
+
+/// How long a train waits for more gifts before it is sent as is.
+pub const QUIET: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GiftTrain {
+    pub channel: String,
+    /// `None` for anonymous gifts.
+    pub gifter: Option<String>,
+    pub count: u64,
+    /// `1000`, `2000` or `3000` for tier 1 to 3.
+    pub tier: String,
+    pub recipients: Vec<String>,
+}
+
+struct Pending {
+    train: GiftTrain,
+    /// Whether the `submysterygift` with the final count came in.
+    announced: bool,
+    updated: Instant,
+}
+
+/// Gift trains still being collected, by channel and gifter.
+#[derive(Clone)]
+pub struct GiftTrains {
+    pending: Arc<Mutex<HashMap<(String, String), Pending>>>,
+    sender: CommandSender,
+}
+
+impl GiftTrains {
+    pub fn new(sender: CommandSender) -> Self {
+        GiftTrains {
+            pending: Arc::default(),
+            sender,
+        }
+    }
+
+    /// The `submysterygift` announcing `count` gifts.
+    pub fn announce(&self, channel: String, gifter: Option<String>, count: u64, tier: String) {
+        self.update(channel, gifter, tier, |entry| {
+            entry.announced = true;
+            entry.train.count = entry.train.count.max(count);
+        });
+    }
+
+    /// A single `subgift`, either on its own or part of a train.
+    pub fn gift(&self, channel: String, gifter: Option<String>, tier: String, recipient: String) {
+        self.update(channel, gifter, tier, |entry| {
+            entry.train.recipients.push(recipient);
+            entry.train.count = entry.train.count.max(entry.train.recipients.len() as u64);
+        });
+    }
+
+    fn update(
+        &self,
+        channel: String,
+        gifter: Option<String>,
+        tier: String,
+        update: impl FnOnce(&mut Pending),
+    ) {
+        let key = (channel.clone(), gifter.clone().unwrap_or_default());
+        let mut pending = self.pending.lock().unwrap();
+
+        let started = !pending.contains_key(&key);
+        let entry = pending.entry(key.clone()).or_insert_with(|| Pending {
+            train: GiftTrain {
+                channel,
+                gifter,
+                count: 0,
+                tier,
+                recipients: Vec::new(),
+            },
+            announced: false,
+            updated: Instant::now(),
+        });
+
+        update(entry);
+        entry.updated = Instant::now();
+
+        // Complete once every announced gift has arrived.
+        if entry.announced && entry.train.recipients.len() as u64 >= entry.train.count {
+            if let Some(done) = pending.remove(&key) {
+                self.sender.send(Command::GiftSubs(done.train));
+            }
+            return;
+        }
+
+        if started {
+            tokio::spawn(self.clone().flush_when_quiet(key));
+        }
+    }
+
+    /// Sends the train once no gift came in for a while, in case some of
+    /// the announced ones never arrive.
+    async fn flush_when_quiet(self, key: (String, String)) {
+        loop {
+            time::sleep(QUIET).await;
+
+            let mut pending = self.pending.lock().unwrap();
+
+            match pending.get(&key) {
+                Some(entry) if entry.updated.elapsed() >= QUIET => {
+                    let done = pending.remove(&key).unwrap();
+                    self.sender.send(Command::GiftSubs(done.train));
+                    return;
+                }
+                Some(_) => (),
+                None => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,644 @@
+//! The few Helix API calls the plugin needs. They require the user token
+//! from `:TwitchLogin` along with the `client_id` it was issued for.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::polls::{Poll, Prediction};
+use crate::auth::Credentials;
+
+const API: &str = "https://api.twitch.tv/helix";
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone)]
+pub struct Helix {
+    client: reqwest::Client,
+    client_id: String,
+    /// Shared by every clone, so the tasks holding one use the token from
+    /// the latest refresh.
+    token: Arc<RwLock<String>>,
+}
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+    login: String,
+    #[serde(default)]
+    display_name: String,
+}
+
+/// A Twitch account, as shown in chat.
+pub struct Account {
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+}
+
+#[derive(Deserialize)]
+struct Follower {
+    followed_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct Subscriber {
+    user_id: String,
+    tier: String,
+}
+
+/// The broadcast of a live channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveStream {
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub game_name: String,
+    #[serde(default)]
+    pub viewer_count: u64,
+}
+
+/// A stream marker, placed `position_seconds` into the broadcast.
+#[derive(Deserialize)]
+pub struct Marker {
+    pub position_seconds: u64,
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdScheduleData {
+    #[serde(default)]
+    pub next_ad_at: Value,
+    #[serde(default)]
+    pub duration: u32,
+    #[serde(default)]
+    pub snooze_count: u32,
+}
+
+#[derive(Deserialize)]
+pub struct Snoozed {
+    #[serde(default)]
+    pub next_ad_at: Value,
+    #[serde(default)]
+    pub snooze_count: u32,
+}
+
+#[derive(Deserialize)]
+struct Block {
+    user_login: String,
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    user_login: String,
+}
+
+#[derive(Deserialize)]
+struct BadgeSet {
+    set_id: String,
+    versions: Vec<BadgeVersion>,
+}
+
+#[derive(Deserialize)]
+struct BadgeVersion {
+    id: String,
+    title: String,
+}
+
+impl Helix {
+    pub fn new(client_id: &str, credentials: &Credentials) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Helix {
+            client,
+            client_id: client_id.to_owned(),
+            token: Arc::new(RwLock::new(credentials.token.clone())),
+        })
+    }
+
+    /// Makes every clone send `credentials` from now on, once they were
+    /// refreshed.
+    pub fn update(&self, credentials: &Credentials) {
+        if let Ok(mut token) = self.token.write() {
+            token.clone_from(&credentials.token);
+        }
+    }
+
+    fn token(&self) -> String {
+        self.token
+            .read()
+            .map(|token| token.clone())
+            .unwrap_or_default()
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, String> {
+        let response = self
+            .client
+            .get(format!("{}{}", API, path))
+            .query(query)
+            .bearer_auth(self.token())
+            .header("Client-Id", &self.client_id)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+
+        let body = response
+            .json::<Data<T>>()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(body.data)
+    }
+
+    /// Sends a request and returns the `data` of its response.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Value,
+    ) -> Result<Vec<T>, String> {
+        let response = self
+            .client
+            .request(method, format!("{}{}", API, path))
+            .query(query)
+            .bearer_auth(self.token())
+            .header("Client-Id", &self.client_id)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+
+        let body = response
+            .json::<Data<T>>()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(body.data)
+    }
+
+    /// Sends a request whose response the plugin doesn't need.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Value,
+    ) -> Result<(), String> {
+        self.client
+            .request(method, format!("{}{}", API, path))
+            .query(query)
+            .bearer_auth(self.token())
+            .header("Client-Id", &self.client_id)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    pub async fn user_id(&self, login: &str) -> Result<String, String> {
+        self.get::<User>("/users", &[("login", login)])
+            .await?
+            .into_iter()
+            .next()
+            .map(|user| user.id)
+            .ok_or_else(|| format!("no Twitch user named {}", login))
+    }
+
+    pub async fn user_login(&self, id: &str) -> Result<String, String> {
+        self.get::<User>("/users", &[("id", id)])
+            .await?
+            .into_iter()
+            .next()
+            .map(|user| user.login)
+            .ok_or_else(|| format!("no Twitch user with id {}", id))
+    }
+
+    /// The logins of the accounts `user_id` blocked, the first 100 of them.
+    pub async fn blocked_users(&self, user_id: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .get::<Block>(
+                "/users/blocks",
+                &[("broadcaster_id", user_id), ("first", "100")],
+            )
+            .await?
+            .into_iter()
+            .map(|block| block.user_login)
+            .collect())
+    }
+
+    /// The logins of the channels `user_id` follows that are live now.
+    pub async fn followed_live(&self, user_id: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .get::<Stream>(
+                "/streams/followed",
+                &[("user_id", user_id), ("first", "100")],
+            )
+            .await?
+            .into_iter()
+            .map(|stream| stream.user_login)
+            .collect())
+    }
+
+    /// The accounts named `logins`, at most 100 at a time. Names Twitch
+    /// doesn't know are left out.
+    pub async fn users(&self, logins: &[String]) -> Result<Vec<Account>, String> {
+        let query: Vec<(&str, &str)> = logins
+            .iter()
+            .map(|login| ("login", login.as_str()))
+            .collect();
+
+        Ok(self
+            .get::<User>("/users", &query)
+            .await?
+            .into_iter()
+            .map(|user| Account {
+                id: user.id,
+                login: user.login,
+                display_name: user.display_name,
+            })
+            .collect())
+    }
+
+    /// When `user_id` followed `broadcaster_id`, unset if they don't. Needs
+    /// the `moderator:read:followers` scope.
+    pub async fn followed_at(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        Ok(self
+            .get::<Follower>(
+                "/channels/followers",
+                &[("broadcaster_id", broadcaster_id), ("user_id", user_id)],
+            )
+            .await?
+            .into_iter()
+            .next()
+            .map(|follower| follower.followed_at))
+    }
+
+    /// The tier of each of `user_ids` subscribed to `broadcaster_id`, as
+    /// `1000`, `2000` or `3000`. Needs the `channel:read:subscriptions`
+    /// scope, so only the broadcaster's token will do.
+    pub async fn subscriptions(
+        &self,
+        broadcaster_id: &str,
+        user_ids: &[&str],
+    ) -> Result<HashMap<String, String>, String> {
+        let mut query = vec![("broadcaster_id", broadcaster_id)];
+        query.extend(user_ids.iter().map(|id| ("user_id", *id)));
+
+        Ok(self
+            .get::<Subscriber>("/subscriptions", &query)
+            .await?
+            .into_iter()
+            .map(|subscriber| (subscriber.user_id, subscriber.tier))
+            .collect())
+    }
+
+    /// `login`'s broadcast, unset while they are offline.
+    pub async fn stream(&self, login: &str) -> Result<Option<LiveStream>, String> {
+        Ok(self
+            .get::<LiveStream>("/streams", &[("user_login", login)])
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Badge titles keyed by `set/version`, e.g. `bits/1000`.
+    pub async fn global_badges(&self) -> Result<HashMap<String, String>, String> {
+        Ok(flatten(self.get("/chat/badges/global", &[]).await?))
+    }
+
+    pub async fn channel_badges(
+        &self,
+        broadcaster_id: &str,
+    ) -> Result<HashMap<String, String>, String> {
+        Ok(flatten(
+            self.get("/chat/badges", &[("broadcaster_id", broadcaster_id)])
+                .await?,
+        ))
+    }
+}
+
+/// Marks the current moment of `user_id`'s live stream. The logged in user
+/// must be the broadcaster or one of their editors.
+impl Helix {
+    pub async fn create_marker(&self, user_id: &str, description: &str) -> Result<Marker, String> {
+        self.call::<Marker>(
+            Method::POST,
+            "/streams/markers",
+            &[],
+            json!({ "user_id": user_id, "description": description }),
+        )
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Twitch created no marker".to_owned())
+    }
+}
+
+/// Ad calls, the logged in user must be the broadcaster.
+impl Helix {
+    pub async fn ad_schedule(&self, broadcaster_id: &str) -> Result<AdScheduleData, String> {
+        first(
+            self.get("/channels/ads", &[("broadcaster_id", broadcaster_id)])
+                .await?,
+        )
+    }
+
+    pub async fn snooze_ad(&self, broadcaster_id: &str) -> Result<Snoozed, String> {
+        first(
+            self.call(
+                Method::POST,
+                "/channels/ads/schedule/snooze",
+                &[("broadcaster_id", broadcaster_id)],
+                json!({}),
+            )
+            .await?,
+        )
+    }
+
+    pub async fn start_commercial(&self, broadcaster_id: &str, length: u32) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/channels/commercial",
+            &[],
+            json!({ "broadcaster_id": broadcaster_id, "length": length }),
+        )
+        .await
+    }
+}
+
+/// Raid calls, the logged in user must be the broadcaster.
+impl Helix {
+    pub async fn start_raid(&self, from_id: &str, to_id: &str) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/raids",
+            &[
+                ("from_broadcaster_id", from_id),
+                ("to_broadcaster_id", to_id),
+            ],
+            json!({}),
+        )
+        .await
+    }
+
+    pub async fn cancel_raid(&self, broadcaster_id: &str) -> Result<(), String> {
+        self.send(
+            Method::DELETE,
+            "/raids",
+            &[("broadcaster_id", broadcaster_id)],
+            json!({}),
+        )
+        .await
+    }
+}
+
+/// Poll and prediction calls, the logged in user must be the broadcaster.
+impl Helix {
+    pub async fn create_poll(
+        &self,
+        broadcaster_id: &str,
+        title: &str,
+        choices: &[String],
+        seconds: u32,
+    ) -> Result<Poll, String> {
+        let choices: Vec<Value> = choices
+            .iter()
+            .map(|title| json!({ "title": title }))
+            .collect();
+
+        first(
+            self.call(
+                Method::POST,
+                "/polls",
+                &[],
+                json!({
+                    "broadcaster_id": broadcaster_id,
+                    "title": title,
+                    "choices": choices,
+                    "duration": seconds,
+                }),
+            )
+            .await?,
+        )
+    }
+
+    pub async fn create_prediction(
+        &self,
+        broadcaster_id: &str,
+        title: &str,
+        outcomes: &[String],
+        seconds: u32,
+    ) -> Result<Prediction, String> {
+        let outcomes: Vec<Value> = outcomes
+            .iter()
+            .map(|title| json!({ "title": title }))
+            .collect();
+
+        first(
+            self.call(
+                Method::POST,
+                "/predictions",
+                &[],
+                json!({
+                    "broadcaster_id": broadcaster_id,
+                    "title": title,
+                    "outcomes": outcomes,
+                    "prediction_window": seconds,
+                }),
+            )
+            .await?,
+        )
+    }
+
+    /// Resolves or cancels a prediction, `status` being `RESOLVED` or
+    /// `CANCELED`.
+    pub async fn end_prediction(
+        &self,
+        broadcaster_id: &str,
+        id: &str,
+        status: &str,
+        winning_outcome_id: Option<&str>,
+    ) -> Result<Prediction, String> {
+        first(
+            self.call(
+                Method::PATCH,
+                "/predictions",
+                &[],
+                json!({
+                    "broadcaster_id": broadcaster_id,
+                    "id": id,
+                    "status": status,
+                    "winning_outcome_id": winning_outcome_id,
+                }),
+            )
+            .await?,
+        )
+    }
+}
+
+/// Whispers the logged in user, `from_user_id`, sends. Twitch wants the
+/// `user:manage:whispers` scope and a verified phone number for them.
+impl Helix {
+    pub async fn whisper(
+        &self,
+        from_user_id: &str,
+        to_user_id: &str,
+        message: &str,
+    ) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/whispers",
+            &[("from_user_id", from_user_id), ("to_user_id", to_user_id)],
+            json!({ "message": message }),
+        )
+        .await
+    }
+}
+
+/// Moderation calls, made as the logged in user on `broadcaster_id`'s
+/// channel.
+impl Helix {
+    pub async fn announce(
+        &self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        message: &str,
+    ) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/chat/announcements",
+            &[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+            ],
+            json!({ "message": message }),
+        )
+        .await
+    }
+
+    pub async fn timeout(
+        &self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        user_id: &str,
+        seconds: u32,
+        reason: &str,
+    ) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/moderation/bans",
+            &[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+            ],
+            json!({ "data": { "user_id": user_id, "duration": seconds, "reason": reason } }),
+        )
+        .await
+    }
+
+    pub async fn delete_message(
+        &self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        message_id: &str,
+    ) -> Result<(), String> {
+        self.send(
+            Method::DELETE,
+            "/moderation/chat",
+            &[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+                ("message_id", message_id),
+            ],
+            json!({}),
+        )
+        .await
+    }
+
+    pub async fn slow_mode(
+        &self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        seconds: Option<u32>,
+    ) -> Result<(), String> {
+        self.send(
+            Method::PATCH,
+            "/chat/settings",
+            &[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+            ],
+            json!({ "slow_mode": seconds.is_some(), "slow_mode_wait_time": seconds }),
+        )
+        .await
+    }
+}
+
+/// EventSub, whose events then arrive over the WebSocket session.
+impl Helix {
+    pub async fn subscribe(
+        &self,
+        kind: &str,
+        version: &str,
+        condition: Value,
+        session_id: &str,
+    ) -> Result<(), String> {
+        self.send(
+            Method::POST,
+            "/eventsub/subscriptions",
+            &[],
+            json!({
+                "type": kind,
+                "version": version,
+                "condition": condition,
+                "transport": { "method": "websocket", "session_id": session_id },
+            }),
+        )
+        .await
+    }
+}
+
+fn first<T>(data: Vec<T>) -> Result<T, String> {
+    data.into_iter()
+        .next()
+        .ok_or_else(|| "Twitch sent an empty response".to_owned())
+}
+
+fn flatten(sets: Vec<BadgeSet>) -> HashMap<String, String> {
+    sets.into_iter()
+        .flat_map(|set| {
+            set.versions
+                .into_iter()
+                .map(move |version| (format!("{}/{}", set.set_id, version.id), version.title))
+        })
+        .collect()
+}
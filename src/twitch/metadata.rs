@@ -0,0 +1,224 @@
+//! What Helix knows about the chatters and the stream, fetched in the
+//! background. Chatters are looked up in batches and only once in a while
+//! each, the stream once a minute, and everything is handed to the editor
+//! as it comes in, so it never waits on a request.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time,
+};
+
+use super::{helix::Helix, ChatMessage};
+use crate::{
+    commands::{Command, CommandSender},
+    ui::PluginError,
+};
+
+/// How often the stream is checked, which is as stale as `!uptime` gets.
+const STREAM_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a chatter isn't looked up again after they were.
+pub const VIEWER_TTL: Duration = Duration::from_secs(30 * 60);
+/// Chatters seen within this long of each other are looked up together.
+const BATCH_DELAY: Duration = Duration::from_secs(2);
+/// As many as Helix takes in one request.
+const BATCH_SIZE: usize = 100;
+
+/// A chatter as Helix knows them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Viewer {
+    pub login: String,
+    pub display_name: String,
+    /// Unset if they don't follow, or the token may not tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub followed_at: Option<DateTime<Utc>>,
+    /// `1000`, `2000` or `3000`, unset if they aren't subscribed or the
+    /// token may not tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+}
+
+/// Where the connection hands over who it saw chatting. Without Helix it
+/// does nothing.
+#[derive(Clone, Default)]
+pub struct Lookups {
+    queue: Option<UnboundedSender<String>>,
+}
+
+impl Lookups {
+    /// Starts looking up the chatters of `channel`, and the stream.
+    pub fn start(helix: Option<Helix>, channel: String, sender: &CommandSender) -> Self {
+        let Some(helix) = helix else {
+            return Lookups::default();
+        };
+        let (queue, logins) = mpsc::unbounded_channel();
+
+        tokio::spawn(watch_stream(helix.clone(), channel.clone(), sender.clone()));
+        tokio::spawn(watch_viewers(helix, channel, logins, sender.clone()));
+
+        Lookups { queue: Some(queue) }
+    }
+
+    /// Looks up the author of `message`, unless they were lately.
+    pub fn seen(&self, message: &ChatMessage) {
+        if let Some(queue) = &self.queue {
+            let _ = queue.send(message.author.to_lowercase());
+        }
+    }
+}
+
+/// What Helix refused once isn't asked for again, the token most likely
+/// lacks the scope.
+struct Allowed {
+    followers: bool,
+    subscriptions: bool,
+}
+
+/// Looks up the chatters handed over by [`Lookups::seen`] that weren't
+/// within the last [`VIEWER_TTL`], and passes on what was found.
+async fn watch_viewers(
+    helix: Helix,
+    channel: String,
+    mut logins: UnboundedReceiver<String>,
+    sender: CommandSender,
+) {
+    let mut fetched: HashMap<String, Instant> = HashMap::new();
+    let mut broadcaster = None;
+    let mut allowed = Allowed {
+        followers: true,
+        subscriptions: true,
+    };
+    let mut failing = false;
+
+    while let Some(login) = logins.recv().await {
+        time::sleep(BATCH_DELAY).await;
+
+        let mut batch = BTreeSet::from([login]);
+        while let Ok(login) = logins.try_recv() {
+            batch.insert(login);
+        }
+
+        fetched.retain(|_, at| at.elapsed() < VIEWER_TTL);
+        let batch: Vec<String> = batch
+            .into_iter()
+            .filter(|login| !fetched.contains_key(login))
+            .collect();
+
+        for chunk in batch.chunks(BATCH_SIZE) {
+            let result = async {
+                let id = match &broadcaster {
+                    Some(id) => id,
+                    None => broadcaster.insert(helix.user_id(&channel).await?),
+                };
+                lookup(&helix, id, chunk, &mut allowed, &sender).await
+            };
+
+            match result.await {
+                Ok(viewers) => {
+                    failing = false;
+
+                    let now = Instant::now();
+                    fetched.extend(chunk.iter().map(|login| (login.clone(), now)));
+                    sender.send(Command::Viewers { viewers });
+                }
+                // Reported once in a row, like the ad schedule.
+                Err(message) if !failing => {
+                    failing = true;
+                    sender.send(Command::Error(PluginError::warning("Viewers", message)));
+                }
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+/// The accounts named `logins`, with their follow date and tier where the
+/// token allows.
+async fn lookup(
+    helix: &Helix,
+    broadcaster: &str,
+    logins: &[String],
+    allowed: &mut Allowed,
+    sender: &CommandSender,
+) -> Result<Vec<Viewer>, String> {
+    let accounts = helix.users(logins).await?;
+    let mut tiers = HashMap::new();
+
+    if allowed.subscriptions && !accounts.is_empty() {
+        let ids: Vec<&str> = accounts.iter().map(|account| account.id.as_str()).collect();
+
+        match helix.subscriptions(broadcaster, &ids).await {
+            Ok(found) => tiers = found,
+            Err(message) => {
+                allowed.subscriptions = false;
+                refused(sender, "subscriber tiers", message);
+            }
+        }
+    }
+
+    let mut viewers = Vec::with_capacity(accounts.len());
+
+    for account in accounts {
+        let followed_at = match allowed.followers {
+            true => match helix.followed_at(broadcaster, &account.id).await {
+                Ok(at) => at,
+                Err(message) => {
+                    allowed.followers = false;
+                    refused(sender, "follow dates", message);
+                    None
+                }
+            },
+            false => None,
+        };
+
+        viewers.push(Viewer {
+            tier: tiers.remove(&account.id),
+            followed_at,
+            login: account.login,
+            display_name: account.display_name,
+        });
+    }
+
+    Ok(viewers)
+}
+
+fn refused(sender: &CommandSender, what: &str, message: String) {
+    sender.send(Command::Error(PluginError::error(
+        "Viewers",
+        format!("no more {} for this session: {}", what, message),
+    )));
+}
+
+/// Passes on whether `channel` is live whenever that changes. Like the ad
+/// schedule, a failure is only reported once in a row.
+async fn watch_stream(helix: Helix, channel: String, sender: CommandSender) {
+    let mut interval = time::interval(STREAM_INTERVAL);
+    let mut last = None;
+    let mut failing = false;
+
+    loop {
+        interval.tick().await;
+
+        match helix.stream(&channel).await {
+            Ok(stream) => {
+                failing = false;
+
+                if last.as_ref() != Some(&stream) {
+                    last = Some(stream.clone());
+                    sender.send(Command::Stream { stream });
+                }
+            }
+            Err(message) if !failing => {
+                failing = true;
+                sender.send(Command::Error(PluginError::warning("Stream", message)));
+            }
+            Err(_) => (),
+        }
+    }
+}
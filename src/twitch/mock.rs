@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use twitch_irc::message::{IRCMessage, ServerMessage};
+
+use super::{
+    backend,
+    parse::{parse_command, ChatMessage},
+};
+use crate::{
+    backend::{ChatBackend, ChatEvent},
+    commands::{Command, Syntax},
+    config::Config,
+};
+
+/// A scripted chat source standing in for a live connection in tests,
+/// either parsed right away with [`MockChat::commands`] or read as a
+/// backend. Besides plain messages it takes IRC lines as Twitch sends them,
+/// which go through the same parsing as the real connection.
+pub struct MockChat {
+    channel: String,
+    events: VecDeque<ChatEvent>,
+    syntax: Syntax,
+    /// What was sent through the backend, as channel and text.
+    sent: Vec<(String, String)>,
+    /// Whether reading past the script panics rather than ending.
+    crashes: bool,
+}
+
+impl Default for MockChat {
+    fn default() -> Self {
+        MockChat {
+            channel: Config::default().channel,
+            events: VecDeque::new(),
+            syntax: Syntax::default(),
+            sent: Vec::new(),
+            crashes: false,
+        }
+    }
+}
+
+impl MockChat {
+    /// Attributes the following messages to `channel`.
+    pub fn in_channel(mut self, channel: &str) -> Self {
+        self.channel = channel.to_owned();
+        self
+    }
+
+    /// Parses the commands with `syntax` rather than the default one.
+    pub fn written_in(mut self, syntax: Syntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn say(mut self, author: &str, text: &str) -> Self {
+        let message = ChatMessage {
+            id: String::new(),
+            channel: self.channel.clone(),
+            author: author.to_owned(),
+            color: None,
+            text: text.to_owned(),
+            badges: Vec::new(),
+            emotes: Vec::new(),
+            emote_spans: Vec::new(),
+            bits: None,
+            reply: None,
+            paid: None,
+            source: None,
+            risk: None,
+        };
+        self.events.push_back(ChatEvent::Message(Box::new(message)));
+        self
+    }
+
+    /// A `PRIVMSG` from `author` with `badges` (as `set/version`), tagged
+    /// like Twitch tags chat.
+    pub fn privmsg(self, author: &str, badges: &[&str], text: &str) -> Self {
+        let line = format!(
+            "@badge-info=;badges={badges};color=;display-name={author};emotes=;flags=;id={id};\
+             mod=0;room-id=1;subscriber=0;tmi-sent-ts=1717243200000;turbo=0;user-id=2;user-type= \
+             :{author}!{author}@{author}.tmi.twitch.tv PRIVMSG #{channel} :{text}",
+            badges = badges.join(","),
+            author = author,
+            id = self.events.len(),
+            channel = self.channel,
+            text = text,
+        );
+        self.irc(&line)
+    }
+
+    /// A raw IRC line, such as a `PRIVMSG` or a `USERNOTICE`. Lines that
+    /// stand for no event are left out, like on a live connection.
+    ///
+    /// # Panics
+    ///
+    /// If `line` isn't a message Twitch could send.
+    pub fn irc(mut self, line: &str) -> Self {
+        let message = IRCMessage::parse(line)
+            .map_err(|e| e.to_string())
+            .and_then(|message| ServerMessage::try_from(message).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| panic!("invalid IRC line {:?}: {}", line, e));
+
+        self.events.extend(backend::event(message));
+        self
+    }
+
+    /// Panics once the scripted events are read, the way a bug in a backend
+    /// would, rather than ending.
+    pub fn crashing(mut self) -> Self {
+        self.crashes = true;
+        self
+    }
+
+    /// The commands the scripted messages produce, in order.
+    pub fn commands(&self) -> Vec<Command> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                ChatEvent::Message(message) => parse_command(message, &self.syntax),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn sent(&self) -> &[(String, String)] {
+        &self.sent
+    }
+}
+
+/// Reads the scripted events one after the other, then ends as if the
+/// connection dropped, or panics if it is [`MockChat::crashing`].
+impl ChatBackend for MockChat {
+    async fn next_event(&mut self) -> Option<ChatEvent> {
+        let event = self.events.pop_front();
+        if event.is_none() && self.crashes {
+            panic!("the mock chat crashed");
+        }
+        event
+    }
+
+    async fn send(
+        &mut self,
+        _account: &str,
+        channel: String,
+        text: String,
+        _action: bool,
+    ) -> Result<(), String> {
+        self.sent.push((channel, text));
+        Ok(())
+    }
+
+    fn can_send_as(&self, _account: &str) -> bool {
+        true
+    }
+}
@@ -0,0 +1,35 @@
+pub mod ads;
+pub mod args;
+mod backend;
+mod badges;
+pub mod cheer;
+mod connection;
+pub mod emotes;
+mod eventsub;
+mod gifts;
+mod helix;
+mod metadata;
+#[cfg(feature = "integration")]
+mod mock;
+pub mod oauth;
+mod paid;
+mod parse;
+pub mod polls;
+pub mod raids;
+mod ratelimit;
+mod shared;
+mod slash;
+mod websocket;
+
+pub use badges::{Badge, BadgeSets};
+#[cfg(feature = "integration")]
+pub use connection::serve;
+pub use connection::{connect, ConnectionStatus, Outgoing, OutgoingSender};
+pub use gifts::{GiftTrain, GiftTrains, QUIET as GIFT_QUIET};
+pub use helix::LiveStream;
+pub use metadata::{Lookups, Viewer, VIEWER_TTL};
+#[cfg(feature = "integration")]
+pub use mock::MockChat;
+pub use paid::PaidMessage;
+pub use parse::{parse_command, ChatMessage, ReplyParent};
+pub use slash::Slash;
@@ -0,0 +1,108 @@
+//! Token validation and refresh against `id.twitch.tv`. Twitch asks that
+//! every token is validated when connecting and hourly after that.
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::auth::Credentials;
+
+const VALIDATE: &str = "https://id.twitch.tv/oauth2/validate";
+const TOKEN: &str = "https://id.twitch.tv/oauth2/token";
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often the main token is validated while connected.
+pub const VALIDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub enum Checked {
+    Valid,
+    /// A new token was issued, the credentials should be stored again.
+    Changed,
+    /// The token expired and can't be renewed.
+    Expired,
+}
+
+#[derive(Deserialize)]
+struct Validation {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct Refreshed {
+    access_token: String,
+    refresh_token: String,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// The login the token belongs to, `None` once it expired or was revoked.
+pub async fn validate(token: &str) -> Result<Option<String>, String> {
+    let response = client()?
+        .get(VALIDATE)
+        .header("Authorization", format!("OAuth {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+
+    let validation = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .json::<Validation>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(validation.login))
+}
+
+/// Validates the credentials, trading an expired token for a new one when
+/// there is a refresh token.
+pub async fn ensure(
+    client_id: Option<&str>,
+    credentials: &mut Credentials,
+) -> Result<Checked, String> {
+    if let Some(login) = validate(&credentials.token).await? {
+        // Tokens from the environment come without a nick.
+        credentials.login = login;
+        return Ok(Checked::Valid);
+    }
+
+    let (Some(client_id), Some(refresh_token)) = (client_id, &credentials.refresh_token) else {
+        return Ok(Checked::Expired);
+    };
+
+    // Public clients, such as those using the device code flow, refresh
+    // without a client secret.
+    let refreshed = client()?
+        .post(TOKEN)
+        .form(&[
+            ("client_id", client_id),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("refreshing the token failed: {}", e))?
+        .json::<Refreshed>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    credentials.token = refreshed.access_token;
+    credentials.refresh_token = Some(refreshed.refresh_token);
+
+    if let Some(login) = validate(&credentials.token).await? {
+        credentials.login = login;
+    }
+
+    Ok(Checked::Changed)
+}
@@ -0,0 +1,65 @@
+//! Hype Chat, where viewers pay to have their message pinned for a while.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use twitch_irc::message::PrivmsgMessage;
+
+/// How long each level stays pinned, from `ONE` to `TEN`.
+const DURATIONS: [u64; 10] = [30, 150, 300, 600, 1800, 3600, 7200, 10800, 14400, 18000];
+
+const LEVELS: [&str; 10] = [
+    "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE", "TEN",
+];
+
+/// The `pinned-chat-paid-*` tags of a Hype Chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaidMessage {
+    /// In the currency's minor unit, see `exponent`.
+    pub amount: u64,
+    pub currency: String,
+    /// Decimal places of `amount`, 2 for USD.
+    pub exponent: u32,
+    /// From 1 to 10.
+    pub level: usize,
+}
+
+impl PaidMessage {
+    pub fn from_tags(msg: &PrivmsgMessage) -> Option<Self> {
+        let tag = |name: &str| msg.source.tags.0.get(name).cloned().flatten();
+
+        let level = tag("pinned-chat-paid-level")?;
+
+        Some(PaidMessage {
+            amount: tag("pinned-chat-paid-amount")?.parse().ok()?,
+            currency: tag("pinned-chat-paid-currency")?,
+            exponent: tag("pinned-chat-paid-exponent")?.parse().ok()?,
+            level: LEVELS.iter().position(|name| *name == level)? + 1,
+        })
+    }
+
+    /// E.g. `4.99 USD`.
+    pub fn amount(&self) -> String {
+        let divisor = 10u64.pow(self.exponent);
+        let width = self.exponent as usize;
+
+        match width {
+            0 => format!("{} {}", self.amount, self.currency),
+            _ => format!(
+                "{}.{:0width$} {}",
+                self.amount / divisor,
+                self.amount % divisor,
+                self.currency
+            ),
+        }
+    }
+
+    /// How long Twitch keeps the message pinned.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(DURATIONS[self.level.clamp(1, 10) - 1])
+    }
+
+    pub fn highlight(&self) -> String {
+        format!("TwitchHypeChat{}", self.level.clamp(1, 10))
+    }
+}
@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use twitch_irc::message::PrivmsgMessage;
+
+use super::{
+    args::{schema_of, ArgError, Args},
+    cheer,
+    emotes::{self, EmoteSpan},
+    paid::PaidMessage,
+};
+use crate::{
+    commands::{Command, Syntax},
+    keymaps, suggest,
+};
+
+/// A chat message normalized from a `PRIVMSG`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// Twitch's message id, empty for injected messages.
+    #[serde(default)]
+    pub id: String,
+    /// Empty for injected messages that don't name a channel.
+    #[serde(default)]
+    pub channel: String,
+    pub author: String,
+    /// The author's chat color as `#rrggbb`, unset for viewers who never
+    /// picked one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub text: String,
+    /// As `set/version`, e.g. `subscriber/12`. The version may be left out
+    /// for injected messages.
+    #[serde(default)]
+    pub badges: Vec<String>,
+    /// The code of every emote in the message, once per use.
+    #[serde(default)]
+    pub emotes: Vec<String>,
+    /// Where each of the `emotes` is in the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emote_spans: Vec<EmoteSpan>,
+    /// Bits cheered with this message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bits: Option<u64>,
+    /// The message this one replies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply: Option<ReplyParent>,
+    /// Set on Hype Chat messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paid: Option<PaidMessage>,
+    /// The channel a Shared Chat message was sent in, when it isn't ours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The moderation score, when scoring is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk: Option<f64>,
+}
+
+/// The parent of a reply as sent in the `reply-parent-*` tags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplyParent {
+    pub id: String,
+    pub author: String,
+    pub text: String,
+}
+
+impl ReplyParent {
+    fn from_tags(msg: &PrivmsgMessage) -> Option<Self> {
+        let tag = |name: &str| msg.source.tags.0.get(name).cloned().flatten();
+
+        Some(ReplyParent {
+            id: tag("reply-parent-msg-id")?,
+            author: tag("reply-parent-display-name")
+                .or_else(|| tag("reply-parent-user-login"))
+                .unwrap_or_default(),
+            text: tag("reply-parent-msg-body").unwrap_or_default(),
+        })
+    }
+}
+
+impl ChatMessage {
+    /// The text with cheermotes annotated, for plain text views.
+    pub fn display_text(&self) -> Cow<'_, str> {
+        match self.bits {
+            Some(_) => Cow::Owned(cheer::annotate(&self.text)),
+            None => Cow::Borrowed(&self.text),
+        }
+    }
+}
+
+impl From<PrivmsgMessage> for ChatMessage {
+    fn from(msg: PrivmsgMessage) -> Self {
+        let reply = ReplyParent::from_tags(&msg);
+        let paid = PaidMessage::from_tags(&msg);
+        let emote_spans = msg
+            .emotes
+            .iter()
+            .filter_map(|emote| EmoteSpan::from_emote(&msg.message_text, emote))
+            .collect();
+        let emotes = msg.emotes.into_iter().map(|emote| emote.code).collect();
+
+        ChatMessage {
+            id: msg.message_id,
+            channel: msg.channel_login,
+            author: msg.sender.name,
+            color: msg
+                .name_color
+                .map(|color| format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)),
+            text: msg.message_text,
+            badges: msg
+                .badges
+                .into_iter()
+                .map(|badge| format!("{}/{}", badge.name, badge.version))
+                .collect(),
+            emotes,
+            emote_spans,
+            bits: msg.bits,
+            reply,
+            paid,
+            source: None,
+            risk: None,
+        }
+    }
+}
+
+/// Longer `!banner` texts are cut, far more than the widest editor fits.
+const MAX_BANNER: usize = 60;
+
+/// Longer `!remind` texts are cut, a notification is no place for more.
+const MAX_REMINDER: usize = 200;
+
+/// Parses `message` as a chat command written in `syntax`, e.g. `!nvim` or
+/// `!CS` for a `cs` alias of `colorscheme`. A known command whose arguments
+/// don't parse comes back as [`Command::Malformed`].
+pub fn parse_command(message: &ChatMessage, syntax: &Syntax) -> Option<Command> {
+    let trimmed = message.text.trim();
+    let mut split = trimmed.splitn(2, ' ');
+    let name = syntax.command(split.next()?)?;
+    let rest = split.next();
+
+    match name.as_str() {
+        "nvim" => {
+            let text = rest?;
+            // Where `text` starts in the untrimmed message.
+            let offset =
+                message.text.len() - message.text.trim_start().len() + trimmed.len() - text.len();
+
+            Some(Command::Message {
+                id: message.id.clone(),
+                channel: message.channel.clone(),
+                author: message.author.clone(),
+                color: message.color.clone(),
+                badges: message.badges.clone(),
+                text: text.to_owned(),
+                emotes: emotes::within(&message.emote_spans, offset),
+                bits: message.bits,
+                reply: message.reply.clone(),
+                source: message.source.clone(),
+                risk: message.risk,
+            })
+        }
+        "where" => Some(Command::Where {
+            channel: message.channel.clone(),
+            author: message.author.clone(),
+            badges: message.badges.clone(),
+        }),
+        "uptime" => Some(Command::Uptime {
+            channel: message.channel.clone(),
+            author: message.author.clone(),
+            badges: message.badges.clone(),
+        }),
+        _ => {
+            let parsed = schema_of(&name)?
+                .parse(rest.unwrap_or_default())
+                .and_then(|args| command(message, &name, args));
+
+            Some(parsed.unwrap_or_else(|error| Command::Malformed {
+                channel: message.channel.clone(),
+                author: message.author.clone(),
+                badges: message.badges.clone(),
+                command: name,
+                error,
+            }))
+        }
+    }
+}
+
+/// The command `name` with the `args` that matched its schema.
+fn command(message: &ChatMessage, name: &str, mut args: Args) -> Result<Command, ArgError> {
+    let channel = message.channel.clone();
+    let author = message.author.clone();
+    let badges = message.badges.clone();
+
+    Ok(match name {
+        "colorscheme" => Command::ColorScheme {
+            channel,
+            author,
+            badges,
+            background: args.flag("bg").map(str::to_owned),
+            name: args.text(0),
+        },
+        "trythis" => Command::TryThis {
+            channel,
+            author,
+            badges,
+            name: args.text(0),
+        },
+        // `file:line`, the line may be left out.
+        "goto" => {
+            let target = args.text(0);
+            let (file, line) = match target.rsplit_once(':') {
+                Some((file, line)) => match line.parse().ok().filter(|&line| line > 0) {
+                    Some(line) => (file, line),
+                    None => return Err(ArgError::invalid("line", line)),
+                },
+                None => (target.as_str(), 1),
+            };
+            if file.is_empty() {
+                return Err(ArgError::invalid("file:line", target));
+            }
+
+            Command::Goto {
+                channel,
+                author,
+                badges,
+                file: file.to_owned(),
+                line,
+            }
+        }
+        "vote" => Command::Vote {
+            channel,
+            author,
+            badges,
+            choice: args.text(0),
+        },
+        "keymap" => {
+            let (lhs, rhs) = (args.text(0), args.text(1));
+            if lhs.is_empty() || lhs.len() > keymaps::MAX_LHS {
+                return Err(ArgError::invalid("lhs", lhs));
+            }
+            if rhs.len() > keymaps::MAX_RHS {
+                return Err(ArgError::invalid("rhs", rhs));
+            }
+
+            Command::Keymap {
+                channel,
+                author,
+                badges,
+                lhs,
+                rhs,
+            }
+        }
+        // `12 text` or `12-14 one\ntwo`, with `\n` between new lines.
+        "suggest" => {
+            let (start, end) = args.lines(0);
+            let lines: Vec<String> = args
+                .text(1)
+                .split("\\n")
+                .map(|line| line.trim_end().to_owned())
+                .collect();
+
+            if start == 0 || end < start || end - start >= suggest::MAX_LINES {
+                return Err(ArgError::invalid("lines", format!("{}-{}", start, end)));
+            }
+            if lines.len() > suggest::MAX_LINES {
+                return Err(ArgError::invalid(
+                    "replacement",
+                    format!("{} lines", lines.len()),
+                ));
+            }
+
+            Command::Suggest {
+                channel,
+                author,
+                badges,
+                start,
+                end,
+                lines,
+            }
+        }
+        "banner" => Command::Banner {
+            channel,
+            author,
+            badges,
+            text: args.text(0).chars().take(MAX_BANNER).collect(),
+        },
+        "opt" => Command::SetOption {
+            channel,
+            author,
+            badges,
+            name: args.text(0),
+            value: args.text(1),
+        },
+        "snippet" => Command::Snippet {
+            channel,
+            author,
+            badges,
+            name: args.text(0).to_lowercase(),
+        },
+        "remind" => Command::Remind {
+            channel,
+            author,
+            badges,
+            seconds: args.seconds(0),
+            text: args.text(1).chars().take(MAX_REMINDER).collect(),
+        },
+        "focus" => Command::Focus {
+            channel,
+            author,
+            badges,
+            number: args.number(0),
+        },
+        "help" => Command::Help {
+            channel,
+            author,
+            badges,
+            command: args.optional(0),
+        },
+        "font" => Command::Font {
+            channel,
+            author,
+            badges,
+            font: args.text(0),
+        },
+        "hl" => Command::Highlight {
+            channel,
+            author,
+            badges,
+            group: args.text(0),
+            fg: args.text(1),
+            bg: args.optional(2),
+        },
+        _ => unreachable!("{} has a schema but no command", name),
+    })
+}
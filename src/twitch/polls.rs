@@ -0,0 +1,112 @@
+//! Polls and predictions on the primary channel, created through Helix.
+//! Their progress is pushed over the EventSub session, along with those
+//! started from the dashboard.
+
+use serde::{Deserialize, Serialize};
+
+use super::helix::Helix;
+use crate::commands::{Command, CommandSender};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Poll {
+    pub id: String,
+    pub title: String,
+    pub choices: Vec<Choice>,
+    /// `ACTIVE`, `COMPLETED`, `TERMINATED`, `ARCHIVED`, ...
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+    pub title: String,
+    #[serde(default)]
+    pub votes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prediction {
+    pub id: String,
+    pub title: String,
+    pub outcomes: Vec<Outcome>,
+    /// `ACTIVE`, `LOCKED`, `RESOLVED` or `CANCELED`.
+    pub status: String,
+    #[serde(default)]
+    pub winning_outcome_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub users: u64,
+    #[serde(default)]
+    pub channel_points: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PollAction {
+    CreatePoll {
+        title: String,
+        choices: Vec<String>,
+        seconds: u32,
+    },
+    CreatePrediction {
+        title: String,
+        outcomes: Vec<String>,
+        seconds: u32,
+    },
+    ResolvePrediction {
+        id: String,
+        outcome_id: String,
+    },
+    CancelPrediction {
+        id: String,
+    },
+}
+
+/// Carries out `action` and passes on the poll or prediction Helix returns.
+/// How the votes go comes from EventSub.
+pub async fn run(
+    helix: &Helix,
+    broadcaster_id: &str,
+    action: PollAction,
+    sender: &CommandSender,
+) -> Result<(), String> {
+    match action {
+        PollAction::CreatePoll {
+            title,
+            choices,
+            seconds,
+        } => {
+            let poll = helix
+                .create_poll(broadcaster_id, &title, &choices, seconds)
+                .await?;
+            sender.send(Command::Poll(poll));
+        }
+        PollAction::CreatePrediction {
+            title,
+            outcomes,
+            seconds,
+        } => {
+            let prediction = helix
+                .create_prediction(broadcaster_id, &title, &outcomes, seconds)
+                .await?;
+            sender.send(Command::Prediction(prediction));
+        }
+        PollAction::ResolvePrediction { id, outcome_id } => {
+            let prediction = helix
+                .end_prediction(broadcaster_id, &id, "RESOLVED", Some(&outcome_id))
+                .await?;
+            sender.send(Command::Prediction(prediction));
+        }
+        PollAction::CancelPrediction { id } => {
+            let prediction = helix
+                .end_prediction(broadcaster_id, &id, "CANCELED", None)
+                .await?;
+            sender.send(Command::Prediction(prediction));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,17 @@
+//! Raiding out of the primary channel, and the followed channels that are
+//! live to offer as targets.
+
+use std::time::Duration;
+
+/// How often the followed live channels are looked up for completion.
+pub const FOLLOWED_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long Twitch waits before a raid goes ahead, unless it is started
+/// early from the dashboard.
+pub const COUNTDOWN: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Clone)]
+pub enum RaidAction {
+    Start(String),
+    Cancel,
+}
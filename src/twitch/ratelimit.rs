@@ -0,0 +1,44 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The period Twitch counts messages over.
+const WINDOW: Duration = Duration::from_secs(30);
+
+/// Keeps one account under its messages-per-window limit. Twitch locks
+/// accounts out of chat for a while when they go over.
+pub struct RateLimit {
+    limit: usize,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    pub fn new(limit: u32) -> Self {
+        RateLimit {
+            limit: limit as usize,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Counts a message if there is room for it, otherwise returns how long
+    /// until there is.
+    pub fn acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        while let Some(&oldest) = self.sent.front() {
+            if now - oldest < WINDOW {
+                break;
+            }
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= self.limit {
+            return Err(WINDOW - (now - self.sent[0]));
+        }
+
+        self.sent.push_back(now);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+//! Shared Chat, where several channels' chats are merged for a collab
+//! stream. Messages from the other channels carry their own room in
+//! `source-room-id`, which only holds the id, so the name is looked up.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use twitch_irc::message::PrivmsgMessage;
+
+use super::helix::Helix;
+
+/// Resolves source rooms to channel names, caching them for the session.
+#[derive(Clone, Default)]
+pub struct SharedChat {
+    helix: Option<Helix>,
+    names: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl SharedChat {
+    pub fn new(helix: Option<Helix>) -> Self {
+        SharedChat {
+            helix,
+            names: Arc::default(),
+        }
+    }
+
+    /// The channel for `room_id`, or the id itself when it can't be
+    /// resolved.
+    pub async fn channel(&self, room_id: &str) -> String {
+        if let Some(name) = self.names.lock().unwrap().get(room_id) {
+            return name.clone();
+        }
+
+        let Some(helix) = &self.helix else {
+            return room_id.to_owned();
+        };
+
+        match helix.user_login(room_id).await {
+            Ok(name) => {
+                self.names
+                    .lock()
+                    .unwrap()
+                    .insert(room_id.to_owned(), name.clone());
+                name
+            }
+            Err(_) => room_id.to_owned(),
+        }
+    }
+}
+
+/// The room a message was sent in when it came from another channel of a
+/// shared session.
+pub fn source_room(msg: &PrivmsgMessage) -> Option<String> {
+    let source = msg.source.tags.0.get("source-room-id").cloned().flatten()?;
+
+    (source != msg.channel_id).then_some(source)
+}
@@ -0,0 +1,110 @@
+//! Twitch's chat commands, e.g. `/me waves`. Only `/me` still exists on
+//! IRC, the moderation commands went to Helix.
+
+use crate::i18n::{tr, tr_with};
+
+/// The commands `:TwitchSend` understands.
+pub const COMMANDS: [&str; 5] = ["me", "announce", "timeout", "slow", "slowoff"];
+
+const DEFAULT_TIMEOUT_SECS: u32 = 600;
+const MAX_TIMEOUT_SECS: u32 = 1_209_600;
+const DEFAULT_SLOW_SECS: u32 = 30;
+const MAX_SLOW_SECS: u32 = 120;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Slash {
+    Me(String),
+    Announce(String),
+    Timeout {
+        user: String,
+        seconds: u32,
+        reason: String,
+    },
+    /// Slow mode with the wait between messages, `None` turns it off.
+    Slow(Option<u32>),
+    /// Deletes the message with this id, only sent by `filter.actions`.
+    Delete(String),
+}
+
+impl Slash {
+    /// Parses `text` if it starts with a `/`. Unknown commands and bad
+    /// arguments come back as a message for the user.
+    pub fn parse(text: &str) -> Option<Result<Slash, String>> {
+        let rest = text.strip_prefix('/')?;
+        let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+        let args = args.trim();
+
+        Some(match name {
+            "me" => non_empty(name, args).map(Slash::Me),
+            "announce" => non_empty(name, args).map(Slash::Announce),
+            "timeout" => timeout(args),
+            "slow" => match args {
+                "" => Ok(Slash::Slow(Some(DEFAULT_SLOW_SECS))),
+                seconds => match seconds.parse() {
+                    Ok(seconds @ 3..=MAX_SLOW_SECS) => Ok(Slash::Slow(Some(seconds))),
+                    _ => Err(usage("slow")),
+                },
+            },
+            "slowoff" => Ok(Slash::Slow(None)),
+            _ => Err(tr_with(
+                "send.unknown",
+                &[("command", &name), ("commands", &COMMANDS.join(", /"))],
+            )),
+        })
+    }
+}
+
+fn non_empty(name: &str, args: &str) -> Result<String, String> {
+    match args {
+        "" => Err(usage(name)),
+        args => Ok(args.to_owned()),
+    }
+}
+
+fn timeout(args: &str) -> Result<Slash, String> {
+    let mut words = args.splitn(3, ' ');
+
+    let user = match words.next() {
+        Some(user) if !user.is_empty() => user.trim_start_matches('@').to_lowercase(),
+        _ => return Err(usage("timeout")),
+    };
+
+    let seconds = match words.next() {
+        Some(duration) => match parse_duration(duration) {
+            Some(seconds @ 1..=MAX_TIMEOUT_SECS) => seconds,
+            _ => return Err(usage("timeout")),
+        },
+        None => DEFAULT_TIMEOUT_SECS,
+    };
+
+    Ok(Slash::Timeout {
+        user,
+        seconds,
+        reason: words.next().unwrap_or_default().trim().to_owned(),
+    })
+}
+
+/// Seconds from `90`, `90s`, `10m`, `2h` or `1d`.
+fn parse_duration(duration: &str) -> Option<u32> {
+    let (number, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => duration.split_at(i),
+        None => (duration, "s"),
+    };
+
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    number.parse::<u32>().ok()?.checked_mul(factor)
+}
+
+fn usage(command: &str) -> String {
+    tr_with(
+        "send.usage",
+        &[("usage", &tr(&format!("send.usage_{}", command)))],
+    )
+}
@@ -0,0 +1,66 @@
+//! The WebSocket client for EventSub: text messages from the server over
+//! TLS, pings answered, nothing sent otherwise.
+
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{crypto::ring, ClientConfig, RootCertStore};
+use tokio_tungstenite::{
+    tungstenite::{protocol::WebSocketConfig, Message},
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+
+/// EventSub messages are a few kilobytes, anything near this is broken.
+const MAX_MESSAGE: usize = 1 << 20;
+
+pub struct WebSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl WebSocket {
+    /// Connects to a `wss://` URL and completes the opening handshake.
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        if !url.starts_with("wss://") {
+            return Err(format!("{} isn't a wss:// URL", url));
+        }
+
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        let tls = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let config = WebSocketConfig::default()
+            .max_message_size(Some(MAX_MESSAGE))
+            .max_frame_size(Some(MAX_MESSAGE));
+
+        let (stream, _) = tokio_tungstenite::connect_async_tls_with_config(
+            url,
+            Some(config),
+            false,
+            Some(Connector::Rustls(Arc::new(tls))),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(WebSocket { stream })
+    }
+
+    /// The next text message, `None` once the server closed the connection.
+    /// Pings are answered while reading.
+    pub async fn next_text(&mut self) -> Result<Option<String>, String> {
+        while let Some(message) = self.stream.next().await {
+            match message.map_err(|e| e.to_string())? {
+                Message::Text(text) => return Ok(Some(text.as_str().to_owned())),
+                Message::Close(_) => return Ok(None),
+                // Binary messages aren't sent by EventSub, they are skipped.
+                _ => (),
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    libuv::TimerHandle,
+    schedule, Result,
+};
+
+/// How long an alert stays up.
+const DURATION: Duration = Duration::from_secs(6);
+
+/// Shows `text` in a one-line float in the top right corner that closes by
+/// itself.
+pub fn show(text: &str, zindex: u32) -> Result<()> {
+    let mut buffer = super::float_buffer()?;
+    buffer.set_lines(.., false, [text])?;
+
+    let opts = SetExtmarkOpts::builder()
+        .end_col(text.len())
+        .hl_group("TwitchAlert")
+        .build();
+    buffer.set_extmark(api::create_namespace("nvim_twitch_alert"), 0, 0, &opts)?;
+
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+    let cols = api::get_option_value::<u32>("columns", &opts)?;
+
+    let width: u32 = api::call_function("strdisplaywidth", (text,))?;
+
+    let config = WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .anchor(WindowAnchor::NorthEast)
+        .border(WindowBorder::Rounded)
+        .style(WindowStyle::Minimal)
+        .focusable(false)
+        .width(width.clamp(1, cols.saturating_sub(4).max(1)))
+        .height(1)
+        .col(cols as f32)
+        .row(1.0)
+        .zindex(zindex)
+        .build();
+
+    let window = api::open_win(&buffer, false, &config)?;
+    super::set_border_highlight(&window)?;
+
+    TimerHandle::once(DURATION, move || {
+        schedule(move |_| match window.is_valid() {
+            true => window.close(true),
+            false => Ok(()),
+        });
+        Ok::<_, nvim_oxi::Error>(())
+    })?;
+
+    Ok(())
+}
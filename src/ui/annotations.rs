@@ -0,0 +1,121 @@
+use std::{collections::VecDeque, time::Instant};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer},
+    Result,
+};
+
+use super::{wrap::truncate, MessageView};
+use crate::config::{AnnotationOptions, WindowOptions};
+
+/// Longer messages are cut short rather than running off the window.
+const MAX_WIDTH: usize = 80;
+
+struct Annotation {
+    buffer: Buffer,
+    extmark: u32,
+    /// The line it was put on, which is skipped by the next ones.
+    line: usize,
+    expires: Instant,
+}
+
+/// `display.mode = "annotate"`: `!nvim` messages shown as virtual text at
+/// the end of the cursor line in whatever buffer is current, each for a
+/// while, instead of in the chat window.
+pub struct Annotations {
+    namespace: u32,
+    /// The oldest first.
+    shown: VecDeque<Annotation>,
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Annotations {
+            namespace: api::create_namespace("nvim_twitch_annotations"),
+            shown: VecDeque::new(),
+        }
+    }
+
+    /// Annotates the cursor line with `message`, or the first line below
+    /// it without an annotation yet, dropping the oldest beyond `max`.
+    pub fn show(
+        &mut self,
+        message: &MessageView,
+        window: &WindowOptions,
+        options: &AnnotationOptions,
+    ) -> Result<()> {
+        while self.shown.len() >= options.max {
+            let Some(annotation) = self.shown.pop_front() else {
+                break;
+            };
+            self.remove(annotation)?;
+        }
+
+        let mut buffer = api::get_current_buf();
+        let (cursor, _) = api::get_current_win().get_cursor()?;
+        let last = buffer.line_count()?.saturating_sub(1);
+
+        let taken = |line: usize| {
+            self.shown
+                .iter()
+                .any(|annotation| annotation.buffer == buffer && annotation.line == line)
+        };
+        let line = (cursor - 1..=last)
+            .find(|&line| !taken(line))
+            .unwrap_or(cursor - 1);
+
+        let author = match window.author_colors {
+            true => {
+                super::author_highlight(&message.author, message.color.as_deref(), &window.palette)?
+            }
+            false => "TwitchAuthor".to_owned(),
+        };
+        let text = truncate(&message.text, MAX_WIDTH);
+
+        let opts = SetExtmarkOpts::builder()
+            .virt_text([
+                (format!("{}: ", message.author), author),
+                (text, options.highlight.clone()),
+            ])
+            .virt_text_pos(ExtmarkVirtTextPosition::Eol)
+            .build();
+        let extmark = buffer.set_extmark(self.namespace, line, 0, &opts)?;
+
+        self.shown.push_back(Annotation {
+            buffer,
+            extmark,
+            line,
+            expires: Instant::now() + options.duration,
+        });
+
+        Ok(())
+    }
+
+    /// Removes what ran out, called every second or so.
+    pub fn expire(&mut self) -> Result<()> {
+        let now = Instant::now();
+
+        while self
+            .shown
+            .front()
+            .is_some_and(|annotation| annotation.expires <= now)
+        {
+            if let Some(annotation) = self.shown.pop_front() {
+                self.remove(annotation)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, mut annotation: Annotation) -> Result<()> {
+        // Its buffer may have been wiped since.
+        if annotation.buffer.is_valid() {
+            annotation
+                .buffer
+                .del_extmark(self.namespace, annotation.extmark)?;
+        }
+
+        Ok(())
+    }
+}
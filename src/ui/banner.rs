@@ -0,0 +1,165 @@
+//! `!banner <text>`: large block letters typed out one at a time across the
+//! top of the editor, for calls to action.
+
+use std::time::Duration;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    libuv::TimerHandle,
+    schedule, Result,
+};
+
+/// Rows in every glyph of [`glyph`].
+const HEIGHT: usize = 5;
+
+type Glyph = [&'static str; HEIGHT];
+
+/// Shows `text` in large letters in a float spanning the top of the editor,
+/// adding a letter every `step` and closing `duration` after the last.
+/// Letters that don't fit the width are left out.
+pub fn show(text: &str, step: Duration, duration: Duration, zindex: u32) -> Result<()> {
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+    let cols = api::get_option_value::<u32>("columns", &opts)? as usize;
+
+    let mut glyphs: Vec<Glyph> = Vec::new();
+    let mut width = 0;
+    for ch in text.trim().chars() {
+        let glyph = glyph(ch.to_ascii_uppercase());
+        let added = glyph[0].len() + usize::from(!glyphs.is_empty());
+        if width + added > cols {
+            break;
+        }
+        width += added;
+        glyphs.push(glyph);
+    }
+    if glyphs.is_empty() {
+        return Ok(());
+    }
+
+    // Centered for the whole text, so typing it out doesn't shift it.
+    let indent = " ".repeat((cols - width) / 2);
+
+    let buffer = super::float_buffer()?;
+    let config = WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .style(WindowStyle::Minimal)
+        .focusable(false)
+        .width(cols.max(1) as u32)
+        .height(HEIGHT as u32)
+        .col(0.0)
+        .row(0.0)
+        .zindex(zindex)
+        .build();
+    let window = api::open_win(&buffer, false, &config)?;
+
+    let opts = OptionOpts::builder().window(window.clone()).build();
+    api::set_option_value("winhighlight", "NormalFloat:TwitchBanner", &opts)?;
+
+    let held = (duration.as_millis() / step.as_millis().max(1)) as usize;
+    let mut shown = 0;
+
+    TimerHandle::start(Duration::ZERO, step, move |timer| {
+        shown += 1;
+
+        if shown > glyphs.len() + held {
+            let _ = timer.stop();
+            let window = window.clone();
+            schedule(move |_| match window.is_valid() {
+                true => window.close(true),
+                false => Ok(()),
+            });
+        } else if shown <= glyphs.len() {
+            let lines = rows(&glyphs[..shown], &indent);
+            let mut buffer = buffer.clone();
+            schedule(move |_| match buffer.is_valid() {
+                true => buffer.set_lines(.., false, lines),
+                false => Ok(()),
+            });
+        }
+    })?;
+
+    Ok(())
+}
+
+/// The rows of `glyphs` side by side, one column apart.
+fn rows(glyphs: &[Glyph], indent: &str) -> Vec<String> {
+    (0..HEIGHT)
+        .map(|row| {
+            let line = glyphs
+                .iter()
+                .map(|glyph| glyph[row])
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{}{}", indent, line).trim_end().to_owned()
+        })
+        .collect()
+}
+
+/// The block letter for `ch`, which is expected in uppercase. Anything
+/// without one is shown as `?`.
+fn glyph(ch: char) -> Glyph {
+    match ch {
+        'A' => [" ### ", "#   #", "#####", "#   #", "#   #"],
+        'B' => ["#### ", "#   #", "#### ", "#   #", "#### "],
+        'C' => [" ####", "#    ", "#    ", "#    ", " ####"],
+        'D' => ["#### ", "#   #", "#   #", "#   #", "#### "],
+        'E' => ["#####", "#    ", "#### ", "#    ", "#####"],
+        'F' => ["#####", "#    ", "#### ", "#    ", "#    "],
+        'G' => [" ####", "#    ", "#  ##", "#   #", " ####"],
+        'H' => ["#   #", "#   #", "#####", "#   #", "#   #"],
+        'I' => ["###", " # ", " # ", " # ", "###"],
+        'J' => ["  ###", "   # ", "   # ", "#  # ", " ##  "],
+        'K' => ["#   #", "#  # ", "###  ", "#  # ", "#   #"],
+        'L' => ["#    ", "#    ", "#    ", "#    ", "#####"],
+        'M' => ["#   #", "## ##", "# # #", "#   #", "#   #"],
+        'N' => ["#   #", "##  #", "# # #", "#  ##", "#   #"],
+        'O' => [" ### ", "#   #", "#   #", "#   #", " ### "],
+        'P' => ["#### ", "#   #", "#### ", "#    ", "#    "],
+        'Q' => [" ### ", "#   #", "# # #", "#  # ", " ## #"],
+        'R' => ["#### ", "#   #", "#### ", "#  # ", "#   #"],
+        'S' => [" ####", "#    ", " ### ", "    #", "#### "],
+        'T' => ["#####", "  #  ", "  #  ", "  #  ", "  #  "],
+        'U' => ["#   #", "#   #", "#   #", "#   #", " ### "],
+        'V' => ["#   #", "#   #", "#   #", " # # ", "  #  "],
+        'W' => ["#   #", "#   #", "# # #", "## ##", "#   #"],
+        'X' => ["#   #", " # # ", "  #  ", " # # ", "#   #"],
+        'Y' => ["#   #", " # # ", "  #  ", "  #  ", "  #  "],
+        'Z' => ["#####", "   # ", "  #  ", " #   ", "#####"],
+        '0' => [" ### ", "#  ##", "# # #", "##  #", " ### "],
+        '1' => [" # ", "## ", " # ", " # ", "###"],
+        '2' => [" ### ", "#   #", "  ## ", " #   ", "#####"],
+        '3' => ["#### ", "    #", " ### ", "    #", "#### "],
+        '4' => ["#   #", "#   #", "#####", "    #", "    #"],
+        '5' => ["#####", "#    ", "#### ", "    #", "#### "],
+        '6' => [" ### ", "#    ", "#### ", "#   #", " ### "],
+        '7' => ["#####", "    #", "   # ", "  #  ", "  #  "],
+        '8' => [" ### ", "#   #", " ### ", "#   #", " ### "],
+        '9' => [" ### ", "#   #", " ####", "    #", " ### "],
+        '!' => ["#", "#", "#", " ", "#"],
+        '?' => [" ### ", "#   #", "  ## ", "     ", "  #  "],
+        '.' => [" ", " ", " ", " ", "#"],
+        ',' => ["  ", "  ", "  ", " #", "# "],
+        ':' => [" ", "#", " ", "#", " "],
+        '-' => ["    ", "    ", "####", "    ", "    "],
+        '+' => ["   ", " # ", "###", " # ", "   "],
+        '\'' => ["#", "#", " ", " ", " "],
+        '#' => [" # # ", "#####", " # # ", "#####", " # # "],
+        '@' => [" ### ", "# ###", "# # #", "# ###", " ### "],
+        '/' => ["    #", "   # ", "  #  ", " #   ", "#    "],
+        '<' => ["  #", " # ", "#  ", " # ", "  #"],
+        '>' => ["#  ", " # ", "  #", " # ", "#  "],
+        '=' => ["    ", "####", "    ", "####", "    "],
+        '_' => ["    ", "    ", "    ", "    ", "####"],
+        '(' => [" #", "# ", "# ", "# ", " #"],
+        ')' => ["# ", " #", " #", " #", "# "],
+        '*' => ["     ", " # # ", "  #  ", " # # ", "     "],
+        '&' => [" ##  ", "#  # ", " ## #", "#  # ", " ## #"],
+        '$' => [" ####", "# #  ", " ### ", "  # #", "#### "],
+        '%' => ["#   #", "   # ", "  #  ", " #   ", "#   #"],
+        ' ' => ["  ", "  ", "  ", "  ", "  "],
+
+        _ => glyph('?'),
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    libuv::TimerHandle,
+    schedule, Result,
+};
+
+/// What is being celebrated, each with a banner of its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Celebration {
+    Sub,
+    Resub,
+    Raid,
+}
+
+impl Celebration {
+    /// The key of its template in `celebrations.templates`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Celebration::Sub => "sub",
+            Celebration::Resub => "resub",
+            Celebration::Raid => "raid",
+        }
+    }
+
+    fn banner(self) -> &'static [&'static str] {
+        match self {
+            Celebration::Sub => &[
+                r" ___ _   _ ___ ",
+                r"/ __| | | | _ )",
+                r"\__ \ |_| | _ \",
+                r"|___/\___/|___/",
+            ],
+            Celebration::Resub => &[
+                r" ___ ___ ___ _   _ ___ ",
+                r"| _ \ __/ __| | | | _ )",
+                r"|   / _|\__ \ |_| | _ \",
+                r"|_|_\___|___/\___/|___/",
+            ],
+            Celebration::Raid => &[
+                r" ___    _   ___ ___  ",
+                r"| _ \  /_\ |_ _|   \ ",
+                r"|   / / _ \ | || |) |",
+                r"|_|_\/_/ \_\___|___/ ",
+            ],
+        }
+    }
+}
+
+/// Shows `text` in a float at the top center of the editor, below the
+/// banner if `banner` is set, and closes it after `duration`. Unlike alerts
+/// these take the whole width they need and stand out with
+/// `TwitchCelebration`.
+pub fn show(
+    kind: Celebration,
+    text: &str,
+    banner: bool,
+    duration: Duration,
+    zindex: u32,
+) -> Result<()> {
+    let mut lines: Vec<&str> = Vec::new();
+    if banner {
+        lines.extend(kind.banner());
+        lines.push("");
+    }
+    lines.extend(text.lines());
+
+    let mut widths = Vec::with_capacity(lines.len());
+    for line in &lines {
+        widths.push(api::call_function::<_, usize>("strdisplaywidth", (*line,))?);
+    }
+    let width = widths.iter().copied().max().unwrap_or(0).max(1);
+
+    // Centered within the float.
+    let centered: Vec<String> = lines
+        .iter()
+        .zip(&widths)
+        .map(|(line, &line_width)| format!("{}{}", " ".repeat((width - line_width) / 2), line))
+        .collect();
+
+    let mut buffer = super::float_buffer()?;
+    buffer.set_lines(.., false, centered.iter().map(String::as_str))?;
+
+    let namespace = api::create_namespace("nvim_twitch_celebration");
+    for (row, line) in centered.iter().enumerate() {
+        let opts = SetExtmarkOpts::builder()
+            .end_col(line.len())
+            .hl_group("TwitchCelebration")
+            .build();
+        buffer.set_extmark(namespace, row, 0, &opts)?;
+    }
+
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+    let cols = api::get_option_value::<u32>("columns", &opts)?;
+    let rows = api::get_option_value::<u32>("lines", &opts)?;
+
+    let width = (width as u32).min(cols.saturating_sub(4).max(1));
+    let height = (centered.len() as u32).min(rows.saturating_sub(4).max(1));
+
+    let config = WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .border(WindowBorder::Double)
+        .style(WindowStyle::Minimal)
+        .focusable(false)
+        .width(width)
+        .height(height)
+        .col(cols.saturating_sub(width) as f32 / 2.0)
+        .row(2.0)
+        .zindex(zindex)
+        .build();
+
+    let window = api::open_win(&buffer, false, &config)?;
+
+    TimerHandle::once(duration, move || {
+        schedule(move |_| match window.is_valid() {
+            true => window.close(true),
+            false => Ok(()),
+        });
+        Ok::<_, nvim_oxi::Error>(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Code blocks in messages, fenced like in Markdown: ```` ```rust fn main() {}``` ````.
+//! Each starts on a line of its own without the fences, and is highlighted
+//! with the treesitter parser of the language named after the opening
+//! fence when Neovim has one installed.
+
+use nvim_oxi::{api, serde::Deserializer, Array, Object, ObjectKind, Result};
+use serde::Deserialize;
+
+const FENCE: &str = "```";
+
+/// Names chat uses for languages whose parser goes by another.
+const ALIASES: [(&str, &str); 9] = [
+    ("js", "javascript"),
+    ("ts", "typescript"),
+    ("py", "python"),
+    ("rs", "rust"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("c++", "cpp"),
+    ("yml", "yaml"),
+    ("md", "markdown"),
+];
+
+/// Parses the code with a string parser, as an injection would, and
+/// returns the captures of the language's highlights query in bytes of the
+/// code. Nothing comes back for languages without a parser or query.
+const CAPTURES: &str = r#"(function(code, lang)
+  local ts = vim.treesitter
+  lang = ts.language.get_lang and ts.language.get_lang(lang) or lang
+  local ok, parser = pcall(ts.get_string_parser, code, lang)
+  if not ok then return {} end
+  local get = ts.query.get or ts.query.get_query
+  local found, query = pcall(get, lang, "highlights")
+  if not found or not query then return {} end
+  local captures = {}
+  for id, node in query:iter_captures(parser:parse()[1]:root(), code, 0, -1) do
+    local name = query.captures[id]
+    if name:sub(1, 1) ~= "_" and name ~= "spell" and name ~= "nospell" then
+      local _, _, start, _, _, stop = node:range(true)
+      table.insert(captures, { start = start, stop = stop, group = "@" .. name .. "." .. lang })
+    end
+  end
+  return captures
+end)(_A[1], _A[2])"#;
+
+/// A part of the text that starts on a new line, in bytes.
+pub struct Section {
+    pub start: usize,
+    pub end: usize,
+    /// Whether it is the inside of a code block.
+    pub code: bool,
+    /// The language named after the opening fence.
+    pub lang: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Capture {
+    start: usize,
+    stop: usize,
+    group: String,
+}
+
+/// Splits `text` into its code blocks and the text around them, leaving
+/// out the fences and blank sections. A fence that is never closed is
+/// shown as it is.
+pub fn sections(text: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut push = |start: usize, end: usize, code: bool, lang: Option<String>| {
+        let part = &text[start..end];
+        let leading = match code {
+            // The indentation of the first line is part of the code.
+            true => part.len() - part.trim_start_matches(['\r', '\n']).len(),
+            false => part.len() - part.trim_start().len(),
+        };
+        let end = start + part.trim_end().len();
+        let start = start + leading;
+
+        if start < end {
+            sections.push(Section {
+                start,
+                end,
+                code,
+                lang,
+            });
+        }
+    };
+
+    let mut from = 0;
+    while let Some(open) = text[from..].find(FENCE).map(|open| from + open) {
+        let inside = open + FENCE.len();
+        let Some(close) = text[inside..].find(FENCE).map(|close| inside + close) else {
+            break;
+        };
+
+        // The language is only a word right after the fence that the code
+        // follows after a space or line break.
+        let word_end = text[inside..close]
+            .find(char::is_whitespace)
+            .map(|end| inside + end);
+        let (lang, code_start) = match word_end {
+            Some(end) if end > inside => {
+                let space = text[end..].chars().next().map_or(0, char::len_utf8);
+                (Some(text[inside..end].to_lowercase()), end + space)
+            }
+            _ => (None, inside),
+        };
+
+        push(from, open, false, None);
+        push(code_start, close, true, lang);
+        from = close + FENCE.len();
+    }
+    push(from, text.len(), false, None);
+
+    sections
+}
+
+/// The highlight groups for the code blocks in `text`, in bytes of the
+/// text, as treesitter would highlight them in a buffer of their language.
+pub fn highlights(text: &str) -> Result<Vec<(usize, usize, String)>> {
+    let mut highlights = Vec::new();
+
+    for section in sections(text) {
+        let Some(lang) = section.lang.filter(|_| section.code) else {
+            continue;
+        };
+        let lang = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == lang)
+            .map_or(lang.as_str(), |(_, name)| name);
+
+        // The name ends up in the path the parser is loaded from, chat
+        // mustn't point it anywhere else.
+        if !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let code = &text[section.start..section.end];
+        let args = Array::from_iter([Object::from(code), Object::from(lang)]);
+        let captures: Object = api::call_function("luaeval", (CAPTURES, args))?;
+
+        // An empty table comes back as a dictionary.
+        if captures.kind() != ObjectKind::Array {
+            continue;
+        }
+
+        let captures = Vec::<Capture>::deserialize(Deserializer::new(captures))?;
+        highlights.extend(captures.into_iter().map(|capture| {
+            (
+                section.start + capture.start,
+                section.start + capture.stop,
+                capture.group,
+            )
+        }));
+    }
+
+    Ok(highlights)
+}
@@ -0,0 +1,232 @@
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::VecDeque,
+    fmt::Display,
+};
+
+use chrono::{DateTime, Local};
+use nvim_oxi::{
+    api::{self, opts::NotifyOpts, types::LogLevel, Buffer},
+    Function, Object, Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{hooks, i18n::tr_with, logging, metrics};
+
+/// An error on its way to the [`ErrorLog`], from the editor or through
+/// `Command::Error` from the connection thread. How loudly it is told
+/// depends on which it is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "severity", rename_all = "snake_case")]
+pub enum PluginError {
+    /// Worked around or tried again, like an invalid setting left at its
+    /// default or a connection that is retried.
+    Warning { context: String, message: String },
+    /// Something the plugin gave up on.
+    Error { context: String, message: String },
+}
+
+impl PluginError {
+    pub fn warning(context: impl Into<String>, message: impl Into<String>) -> Self {
+        PluginError::Warning {
+            context: context.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn error(context: impl Into<String>, message: impl Into<String>) -> Self {
+        PluginError::Error {
+            context: context.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Where it happened, e.g. `Connection`.
+    pub fn context(&self) -> &str {
+        match self {
+            PluginError::Warning { context, .. } | PluginError::Error { context, .. } => context,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            PluginError::Warning { message, .. } | PluginError::Error { message, .. } => message,
+        }
+    }
+
+    /// The `vim.notify` level it is told at.
+    pub fn level(&self) -> LogLevel {
+        match self {
+            PluginError::Warning { .. } => LogLevel::Warn,
+            PluginError::Error { .. } => LogLevel::Error,
+        }
+    }
+
+    fn severity(&self) -> &'static str {
+        match self {
+            PluginError::Warning { .. } => "warning",
+            PluginError::Error { .. } => "error",
+        }
+    }
+}
+
+struct ErrorEntry {
+    timestamp: DateTime<Local>,
+    error: PluginError,
+    backtrace: Option<String>,
+}
+
+/// What `on_error()` callbacks are called with.
+#[derive(Serialize)]
+struct Reported<'a> {
+    context: &'a str,
+    message: &'a str,
+    /// `warning` or `error`.
+    severity: &'a str,
+}
+
+/// The errors reported by the plugin, viewable with `:TwitchErrors`. Only
+/// the most recent `limit` are kept.
+pub struct ErrorLog {
+    entries: VecDeque<ErrorEntry>,
+    buffer: Option<Buffer>,
+    limit: usize,
+    callbacks: Vec<Function<Object, ()>>,
+}
+
+impl ErrorLog {
+    pub fn new(limit: usize) -> Self {
+        ErrorLog {
+            entries: VecDeque::new(),
+            buffer: None,
+            limit,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Calls `callback` with every error reported from now on.
+    pub fn on_error(&mut self, callback: Function<Object, ()>) {
+        self.callbacks.push(callback);
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+
+        while self.entries.len() > limit {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Reports something the plugin gave up on.
+    pub fn push(&mut self, context: &str, error: &dyn Display) {
+        self.report(PluginError::error(context, error.to_string()));
+    }
+
+    /// Reports something worked around or tried again.
+    pub fn warn(&mut self, context: &str, error: &dyn Display) {
+        self.report(PluginError::warning(context, error.to_string()));
+    }
+
+    /// Records the error and tells the user through `vim.notify`: errors
+    /// with where to read them, warnings as they are. Never fails, it is
+    /// what every other failure ends up in.
+    pub fn report(&mut self, error: PluginError) {
+        metrics::ERRORS.inc();
+
+        if self.entries.len() >= self.limit {
+            self.entries.pop_front();
+        }
+
+        let backtrace = Backtrace::capture();
+        let (context, message) = (error.context(), error.message());
+        match error {
+            PluginError::Warning { .. } => logging::warn(context, message),
+            PluginError::Error { .. } => logging::error(context, message),
+        }
+
+        hooks::call(
+            &self.callbacks,
+            &Reported {
+                context,
+                message,
+                severity: error.severity(),
+            },
+            hooks::ON_ERROR,
+        );
+
+        let notice = match error {
+            PluginError::Warning { .. } => tr_with(
+                "errors.warned",
+                &[("context", &context), ("message", &message)],
+            ),
+            PluginError::Error { .. } => tr_with("errors.reported", &[("context", &context)]),
+        };
+
+        if api::notify(&notice, error.level(), &NotifyOpts::default()).is_err() {
+            api::err_writeln(&notice);
+        }
+
+        self.entries.push_back(ErrorEntry {
+            timestamp: Local::now(),
+            error,
+            backtrace: match backtrace.status() {
+                BacktraceStatus::Captured => Some(backtrace.to_string()),
+                _ => None,
+            },
+        });
+
+        let open = self.buffer.as_ref().is_some_and(Buffer::is_valid);
+
+        // A buffer that can't be written to is made again by `show`.
+        if open && self.render().is_err() {
+            self.buffer = None;
+        }
+    }
+
+    pub fn show(&mut self) -> Result<()> {
+        let valid = matches!(&self.buffer, Some(buffer) if buffer.is_valid());
+
+        if !valid {
+            let mut buffer = api::create_buf(false, true)?;
+            buffer.set_name("twitch://errors")?;
+            self.buffer = Some(buffer);
+        }
+
+        self.render()?;
+
+        if let Some(buffer) = &self.buffer {
+            api::command("split")?;
+            api::get_current_win().set_buf(buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let mut lines = Vec::new();
+
+        for entry in &self.entries {
+            lines.push(format!(
+                "[{}] {} {}: {}",
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                entry.error.severity(),
+                entry.error.context(),
+                entry.error.message()
+            ));
+
+            if let Some(backtrace) = &entry.backtrace {
+                lines.extend(backtrace.lines().map(|line| format!("    {}", line)));
+            }
+        }
+
+        if let Some(buffer) = &mut self.buffer {
+            buffer.set_lines(.., false, lines)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+use crate::config::Focus;
+
+const MAX_WIDTH: usize = 80;
+
+/// Shows `lines` in a centered scratch float that closes with `q`.
+pub fn show(title: &str, lines: Vec<String>) -> Result<()> {
+    open(title, lines).map(|_| ())
+}
+
+/// Like [`show`], returning the buffer so more mappings can be added.
+pub fn open(title: &str, lines: Vec<String>) -> Result<Buffer> {
+    open_window(title, lines, true).map(|(_, buffer)| buffer)
+}
+
+/// Opens a float asking the streamer about something chat proposed, with
+/// `keys` mapped in it. It only takes the cursor as `focus` allows, so
+/// whatever is being typed can't decide it; otherwise the keys work once
+/// the streamer moves into it.
+pub fn confirm(
+    title: &str,
+    lines: Vec<String>,
+    focus: Focus,
+    keys: &[(Mode, &str, &str)],
+) -> Result<(Window, Buffer)> {
+    let enter = super::window::may_focus(focus)?;
+    let (window, mut buffer) = open_window(title, lines, enter)?;
+
+    let opts = SetKeymapOpts::builder().nowait(true).silent(true).build();
+    for (mode, lhs, rhs) in keys {
+        buffer.set_keymap(*mode, lhs, rhs, &opts)?;
+    }
+
+    Ok((window, buffer))
+}
+
+/// Whether the float from [`confirm`] is still open, it may have been
+/// closed some other way.
+pub fn showing(window: Option<&Window>) -> bool {
+    window.is_some_and(Window::is_valid)
+}
+
+fn open_window(title: &str, lines: Vec<String>, enter: bool) -> Result<(Window, Buffer)> {
+    let mut buffer = super::float_buffer()?;
+    buffer.set_lines(.., false, lines.iter().map(String::as_str))?;
+    buffer.set_keymap(
+        Mode::Normal,
+        "q",
+        "<cmd>close<cr>",
+        &SetKeymapOpts::builder().nowait(true).silent(true).build(),
+    )?;
+
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+
+    let cols = api::get_option_value::<u32>("columns", &opts)?;
+    let rows = api::get_option_value::<u32>("lines", &opts)?;
+
+    let content_width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .chain([title.chars().count() + 2])
+        .max()
+        .unwrap_or(0);
+
+    // Tiny terminals get a window of at least one cell rather than a panic.
+    let width = content_width
+        .clamp(20, MAX_WIDTH)
+        .min(cols.saturating_sub(4) as usize)
+        .max(1) as u32;
+    let height = (lines.len().max(1) as u32)
+        .min(rows.saturating_sub(4))
+        .max(1);
+
+    let config = WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .border(WindowBorder::Rounded)
+        .style(WindowStyle::Minimal)
+        .title(WindowTitle::SimpleString(format!(" {} ", title).into()))
+        .title_pos(WindowTitlePosition::Center)
+        .width(width)
+        .height(height)
+        .col(cols.saturating_sub(width) as f32 / 2.0)
+        .row(rows.saturating_sub(height) as f32 / 2.0)
+        .build();
+
+    let window = api::open_win(&buffer, enter, &config)?;
+    super::set_border_highlight(&window)?;
+
+    Ok((window, buffer))
+}
@@ -0,0 +1,267 @@
+pub mod alert;
+mod annotations;
+pub mod banner;
+pub mod celebration;
+mod code;
+mod errors;
+pub mod float;
+mod pinned;
+mod plain;
+mod render;
+mod results;
+mod status;
+mod window;
+mod wrap;
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+};
+
+use nvim_oxi::{
+    api::{self, opts::*, Buffer, Window},
+    Result,
+};
+
+use crate::{
+    config::{self, HighlightOverride},
+    twitch::cheer::TIERS,
+};
+
+pub use annotations::Annotations;
+pub use errors::{ErrorLog, PluginError};
+pub use pinned::PinnedMessages;
+pub use plain::PlainLog;
+pub use render::MessageView;
+pub use results::ResultsWindow;
+pub use status::StatusFloat;
+pub use window::{map_focus_key, record_activity, ChatWindow, RenderStats};
+
+/// The colors of the Hype Chat levels, from one to ten.
+const HYPE_CHAT_COLORS: [&str; 10] = [
+    "#4e7ac7", "#2f9bd8", "#1fb5a6", "#3cb371", "#b8c21c", "#e6a817", "#ee7b22", "#e8563a",
+    "#d93a6a", "#b23ad9",
+];
+
+/// The colors Twitch uses for each cheer tier.
+const CHEER_COLORS: [&str; 5] = ["#979797", "#9c3ee8", "#1db2a5", "#0099fe", "#f43021"];
+
+/// The badge sets with a highlight group of their own, colored like the
+/// badges themselves. The others use `TwitchBadge`.
+const BADGE_COLORS: [(&str, &str, &str); 4] = [
+    ("broadcaster", "TwitchBadgeBroadcaster", "#e91916"),
+    ("moderator", "TwitchBadgeModerator", "#00ad03"),
+    ("vip", "TwitchBadgeVip", "#e005b9"),
+    ("subscriber", "TwitchBadgeSubscriber", "#8205b4"),
+];
+
+/// Distinct Twitch colors beyond this many are shown from the palette
+/// instead, so a busy chat doesn't define a group for every color picked.
+const MAX_AUTHOR_GROUPS: usize = 256;
+
+thread_local! {
+    /// The `TwitchAuthor_` groups defined since the last `:colorscheme`.
+    static AUTHOR_GROUPS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// Defines the plugin's highlight groups without overriding any a
+/// colorscheme or the user already set.
+pub fn define_highlights() -> Result<()> {
+    // Cleared along with everything else by `:colorscheme`.
+    AUTHOR_GROUPS.with_borrow_mut(HashSet::clear);
+
+    for (tier, color) in TIERS.iter().zip(CHEER_COLORS) {
+        let opts = SetHighlightOpts::builder()
+            .default(true)
+            .foreground(color)
+            .bold(true)
+            .build();
+
+        api::set_hl(0, &format!("TwitchCheer{}", tier), &opts)?;
+    }
+
+    for (level, color) in HYPE_CHAT_COLORS.iter().enumerate() {
+        let opts = SetHighlightOpts::builder()
+            .default(true)
+            .foreground(color)
+            .bold(true)
+            .build();
+
+        api::set_hl(0, &format!("TwitchHypeChat{}", level + 1), &opts)?;
+    }
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("Comment")
+        .build();
+    api::set_hl(0, "TwitchReplyContext", &opts)?;
+    api::set_hl(0, "TwitchTimestamp", &opts)?;
+    api::set_hl(0, "TwitchAnnotation", &opts)?;
+    api::set_hl(0, "TwitchLogDebug", &opts)?;
+
+    for (group, link) in [
+        ("TwitchLogError", "ErrorMsg"),
+        ("TwitchLogWarn", "WarningMsg"),
+        ("TwitchLogInfo", "Identifier"),
+    ] {
+        let opts = SetHighlightOpts::builder().default(true).link(link).build();
+        api::set_hl(0, group, &opts)?;
+    }
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("Title")
+        .build();
+    api::set_hl(0, "TwitchAlert", &opts)?;
+    api::set_hl(0, "TwitchBanner", &opts)?;
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("WarningMsg")
+        .build();
+    api::set_hl(0, "TwitchCelebration", &opts)?;
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("Search")
+        .build();
+    api::set_hl(0, "TwitchSpotlight", &opts)?;
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("Special")
+        .build();
+    api::set_hl(0, "TwitchBadge", &opts)?;
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("Constant")
+        .build();
+    api::set_hl(0, "TwitchEmote", &opts)?;
+
+    for (_, group, color) in BADGE_COLORS {
+        let opts = SetHighlightOpts::builder()
+            .default(true)
+            .foreground(color)
+            .bold(true)
+            .build();
+
+        api::set_hl(0, group, &opts)?;
+    }
+
+    let opts = SetHighlightOpts::builder().default(true).bold(true).build();
+    api::set_hl(0, "TwitchAuthor", &opts)?;
+
+    // Left empty, the text looks like the rest of the window until it is
+    // given colors of its own.
+    let opts = SetHighlightOpts::builder().default(true).build();
+    api::set_hl(0, "TwitchMessage", &opts)?;
+
+    let opts = SetHighlightOpts::builder()
+        .default(true)
+        .link("FloatBorder")
+        .build();
+    api::set_hl(0, "TwitchBorder", &opts)?;
+
+    Ok(())
+}
+
+/// A scratch buffer for a float of its own, wiped once the float closes so
+/// they don't pile up.
+pub fn float_buffer() -> Result<Buffer> {
+    let buffer = api::create_buf(false, true)?;
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    api::set_option_value("bufhidden", "wipe", &opts)?;
+    Ok(buffer)
+}
+
+/// Draws the border of the plugin's float `window` with `TwitchBorder`.
+pub fn set_border_highlight(window: &Window) -> Result<()> {
+    let opts = OptionOpts::builder().window(window.clone()).build();
+    api::set_option_value("winhighlight", "FloatBorder:TwitchBorder", &opts)?;
+    Ok(())
+}
+
+/// Applies the `highlights` from the config over the defaults, and over
+/// whatever the colorscheme set.
+pub fn override_highlights(overrides: &BTreeMap<String, HighlightOverride>) -> Result<()> {
+    for (group, highlight) in overrides {
+        let mut opts = SetHighlightOpts::builder();
+
+        if let Some(link) = &highlight.link {
+            opts.link(link);
+        }
+        if let Some(fg) = &highlight.fg {
+            opts.foreground(fg);
+        }
+        if let Some(bg) = &highlight.bg {
+            opts.background(bg);
+        }
+
+        opts.bold(highlight.bold)
+            .italic(highlight.italic)
+            .underline(highlight.underline);
+
+        api::set_hl(0, group, &opts.build())?;
+    }
+
+    Ok(())
+}
+
+/// E.g. `TwitchBadgeModerator`, see [`define_highlights`].
+pub fn badge_highlight(set: &str) -> &'static str {
+    // Founders are subscribers from the first days of a channel.
+    let set = match set {
+        "founder" => "subscriber",
+        set => set,
+    };
+
+    BADGE_COLORS
+        .iter()
+        .find(|(name, _, _)| *name == set)
+        .map_or("TwitchBadge", |(_, group, _)| group)
+}
+
+/// The group coloring `author` in their chat `color`, or in the color of
+/// the `palette` their name picks when they have none, as do injected
+/// messages with anything but `#rrggbb`. Each group is defined on first use
+/// and reused after, `TwitchAuthor` is left for an empty palette.
+pub fn author_highlight(author: &str, color: Option<&str>, palette: &[String]) -> Result<String> {
+    let group = |color: &str| format!("TwitchAuthor_{}", color[1..].to_lowercase());
+    let room = |group: &String| {
+        AUTHOR_GROUPS
+            .with_borrow(|groups| groups.len() < MAX_AUTHOR_GROUPS || groups.contains(group))
+    };
+
+    let twitch = color
+        .filter(|color| config::is_hex_color(color))
+        .map(|color| (group(color), color))
+        .filter(|(group, _)| room(group));
+    let (group, color) = match twitch {
+        Some(twitch) => twitch,
+        None if palette.is_empty() => return Ok("TwitchAuthor".to_owned()),
+        None => {
+            let color = &palette[name_hash(author) as usize % palette.len()];
+            (group(color), color.as_str())
+        }
+    };
+
+    if !AUTHOR_GROUPS.with_borrow(|groups| groups.contains(&group)) {
+        let opts = SetHighlightOpts::builder()
+            .foreground(color)
+            .bold(true)
+            .build();
+        api::set_hl(0, &group, &opts)?;
+        AUTHOR_GROUPS.with_borrow_mut(|groups| groups.insert(group.clone()));
+    }
+
+    Ok(group)
+}
+
+/// FNV-1a of the lowercase name, the same in every session unlike the
+/// standard library's hasher.
+fn name_hash(name: &str) -> u32 {
+    name.to_lowercase().bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
@@ -0,0 +1,164 @@
+use std::time::Instant;
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+use super::wrap::truncate;
+use crate::twitch::PaidMessage;
+
+const MAX_WIDTH: usize = 60;
+
+struct Pin {
+    line: String,
+    highlight: String,
+    expires: Instant,
+}
+
+/// Paid messages, kept in a float at the top of the editor for as long as
+/// they were paid for.
+pub struct PinnedMessages {
+    buffer: Buffer,
+    namespace: u32,
+    window: Option<Window>,
+    pins: Vec<Pin>,
+    zindex: u32,
+}
+
+impl PinnedMessages {
+    pub fn new(zindex: u32) -> Result<Self> {
+        Ok(PinnedMessages {
+            buffer: api::create_buf(false, true)?,
+            namespace: api::create_namespace("nvim_twitch_pinned"),
+            window: None,
+            pins: Vec::new(),
+            zindex,
+        })
+    }
+
+    /// Follows the chat window's stacking order.
+    pub fn set_zindex(&mut self, zindex: u32) -> Result<()> {
+        if zindex == self.zindex {
+            return Ok(());
+        }
+
+        self.zindex = zindex;
+        self.redraw()
+    }
+
+    pub fn pin(&mut self, author: &str, text: &str, paid: &PaidMessage) -> Result<()> {
+        self.pins.push(Pin {
+            line: format!("[{}] {}: {}", paid.amount(), author, text),
+            highlight: paid.highlight(),
+            expires: Instant::now() + paid.duration(),
+        });
+
+        self.redraw()
+    }
+
+    /// Unpins what ran out, called every second or so.
+    pub fn expire(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let count = self.pins.len();
+
+        self.pins.retain(|pin| pin.expires > now);
+
+        if self.pins.len() == count {
+            return Ok(());
+        }
+
+        self.redraw()
+    }
+
+    /// Re-centers the float after the editor was resized.
+    pub fn resize(&mut self) -> Result<()> {
+        self.redraw()
+    }
+
+    /// Brings the float along to the tabpage that was entered, pins are
+    /// never kept per tab.
+    pub fn tab_entered(&mut self) -> Result<()> {
+        let Some(window) = self.window.take() else {
+            return Ok(());
+        };
+
+        if window.is_valid() {
+            window.close(true)?;
+        }
+
+        self.redraw()
+    }
+
+    fn redraw(&mut self) -> Result<()> {
+        // Replaced if the user wiped it.
+        if !self.buffer.is_valid() {
+            self.buffer = api::create_buf(false, true)?;
+            self.window = None;
+        }
+
+        if self.pins.is_empty() {
+            if let Some(window) = self.window.take() {
+                if window.is_valid() {
+                    window.close(true)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let lines: Vec<String> = self
+            .pins
+            .iter()
+            .map(|pin| truncate(&pin.line, MAX_WIDTH))
+            .collect();
+
+        self.buffer
+            .set_lines(.., false, lines.iter().map(String::as_str))?;
+        self.buffer.clear_namespace(self.namespace, ..)?;
+
+        for (i, (pin, line)) in self.pins.iter().zip(&lines).enumerate() {
+            let opts = SetExtmarkOpts::builder()
+                .end_col(line.len())
+                .hl_group(&pin.highlight)
+                .build();
+
+            self.buffer.set_extmark(self.namespace, i, 0, &opts)?;
+        }
+
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(1);
+        let config = layout(width as u32, lines.len() as u32, self.zindex)?;
+
+        match &mut self.window {
+            Some(window) if window.is_valid() => window.set_config(&config)?,
+            _ => self.window = Some(api::open_win(&self.buffer, false, &config)?),
+        }
+
+        Ok(())
+    }
+}
+
+/// Centered at the top of the editor.
+fn layout(width: u32, height: u32, zindex: u32) -> Result<WindowConfig> {
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+    let cols = api::get_option_value::<u32>("columns", &opts)?;
+
+    let width = width.clamp(1, cols.saturating_sub(4).max(1));
+
+    Ok(WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .border(WindowBorder::Rounded)
+        .style(WindowStyle::Minimal)
+        .focusable(false)
+        .width(width)
+        .height(height)
+        .col((cols.saturating_sub(width)) as f32 / 2.0)
+        .row(0.0)
+        .zindex(zindex)
+        .build())
+}
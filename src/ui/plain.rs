@@ -0,0 +1,72 @@
+use nvim_oxi::{
+    api::{self, opts::*, Buffer},
+    Result,
+};
+
+const NAME: &str = "twitch-chat";
+
+/// The listed buffer chat is appended to in accessibility mode, one plain
+/// line per message or alert so screen readers read it in order. Channels
+/// with `output = "buffer"` get one of their own. Only the last `limit`
+/// lines are kept.
+pub struct PlainLog {
+    name: String,
+    buffer: Option<Buffer>,
+    limit: usize,
+}
+
+impl PlainLog {
+    pub fn new(limit: usize) -> Self {
+        PlainLog::named(NAME.to_owned(), limit)
+    }
+
+    pub fn named(name: String, limit: usize) -> Self {
+        PlainLog {
+            name,
+            buffer: None,
+            limit,
+        }
+    }
+
+    /// E.g. `twitch-chat://otherstreamer`.
+    pub fn for_channel(channel: &str, limit: usize) -> Self {
+        PlainLog::named(format!("{}://{}", NAME, channel), limit)
+    }
+
+    /// Takes effect with the next line.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub fn append(&mut self, line: &str, echo: bool) -> Result<()> {
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.is_valid() => buffer,
+            _ => create_buffer(&self.name)?,
+        };
+        let buffer = self.buffer.insert(buffer);
+
+        // A new buffer starts out with one empty line, which is replaced.
+        let count = buffer.line_count()?;
+        let empty = count == 1 && buffer.get_lines(0..1, false)?.all(|first| first.is_empty());
+        let start = if empty { 0 } else { count };
+        // Messages sent over the local backend may span lines.
+        buffer.set_lines(start..count, false, line.split('\n'))?;
+
+        let excess = buffer.line_count()?.saturating_sub(self.limit);
+        if excess > 0 {
+            buffer.set_lines(0..excess, false, std::iter::empty::<&str>())?;
+        }
+
+        if echo {
+            api::echo([(line, None)], true, &EchoOpts::default())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn create_buffer(name: &str) -> Result<Buffer> {
+    let mut buffer = api::create_buf(true, true)?;
+    buffer.set_name(name)?;
+    Ok(buffer)
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Local};
+
+use super::wrap::{self, truncate, Wrap, Wrapped};
+use crate::{
+    i18n::tr_with,
+    twitch::{cheer::Cheer, emotes::EmoteSpan, Badge, ReplyParent},
+};
+
+/// Everything the chat window shows for one message.
+pub struct MessageView {
+    /// Twitch's message id, empty for injected messages.
+    pub id: String,
+    pub author: String,
+    /// The author's chat color, see `window.author_colors`.
+    pub color: Option<String>,
+    /// Shown ahead of the author.
+    pub badges: Vec<Badge>,
+    pub text: String,
+    pub cheers: Vec<Cheer>,
+    /// In `text`, after `window.emotes` was applied to it.
+    pub emotes: Vec<EmoteSpan>,
+    /// The message this one answers, quoted above it.
+    pub reply: Option<ReplyParent>,
+    /// The other channel of a Shared Chat session it was sent in.
+    pub source: Option<String>,
+    pub received: DateTime<Local>,
+}
+
+/// The width of the `12:34 ` in front of the author.
+pub const TIMESTAMP_WIDTH: usize = 5;
+
+/// A highlighted part of the header line, in bytes.
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub kind: SpanKind,
+}
+
+pub enum SpanKind {
+    /// Holds the badge set.
+    Badge(String),
+    Author,
+}
+
+/// A message laid out as buffer lines.
+pub struct Rendered {
+    pub lines: Vec<String>,
+    /// The text, in `lines` from `text_line()` on.
+    pub text: Wrapped,
+}
+
+impl MessageView {
+    /// Lays out the message as buffer lines, with the text wrapped as
+    /// `wrap` says.
+    pub fn lines(&self, wrap: &Wrap, timestamp: bool) -> Rendered {
+        let mut lines = Vec::new();
+
+        if let Some(reply) = &self.reply {
+            lines.push(truncate(
+                &format!("↳ {}: {}", reply.author, reply.text.replace('\n', " ")),
+                wrap.width,
+            ));
+        }
+
+        let text = wrap::wrap(&self.text, wrap);
+        lines.push(self.header(timestamp).0);
+        lines.extend(text.lines.iter().cloned());
+
+        Rendered { lines, text }
+    }
+
+    /// The badges and author in the header line, for highlighting.
+    pub fn header_spans(&self, timestamp: bool) -> Vec<Span> {
+        self.header(timestamp).1
+    }
+
+    fn header(&self, timestamp: bool) -> (String, Vec<Span>) {
+        let mut header = String::new();
+        let mut spans = Vec::new();
+
+        if timestamp {
+            header.push_str(&self.received.format("%H:%M ").to_string());
+        }
+
+        for badge in &self.badges {
+            let start = header.len();
+            header.push('[');
+            header.push_str(&badge.title);
+            header.push(']');
+            spans.push(Span {
+                start,
+                end: header.len(),
+                kind: SpanKind::Badge(badge.set.clone()),
+            });
+            header.push(' ');
+        }
+
+        let start = header.len();
+        header.push_str(&self.author);
+        spans.push(Span {
+            start,
+            end: header.len(),
+            kind: SpanKind::Author,
+        });
+
+        if let Some(source) = &self.source {
+            header.push_str(" (#");
+            header.push_str(source);
+            header.push(')');
+        }
+
+        (header, spans)
+    }
+
+    /// The message as one undecorated sentence, for accessibility mode.
+    pub fn plain_line(&self) -> String {
+        let line = match &self.reply {
+            Some(reply) => tr_with(
+                "plain.reply",
+                &[
+                    ("author", &self.author),
+                    ("parent", &reply.author),
+                    ("text", &self.text),
+                ],
+            ),
+            None => tr_with(
+                "plain.message",
+                &[("author", &self.author), ("text", &self.text)],
+            ),
+        };
+
+        match &self.source {
+            Some(source) => tr_with("plain.source", &[("line", &line), ("channel", source)]),
+            None => line,
+        }
+    }
+
+    /// The line holding the reply quote, if there is one.
+    pub fn quote_line(&self) -> Option<usize> {
+        self.reply.as_ref().map(|_| 0)
+    }
+
+    /// The line holding the author.
+    pub fn header_line(&self) -> usize {
+        usize::from(self.reply.is_some())
+    }
+
+    /// The first line holding the message text.
+    pub fn text_line(&self) -> usize {
+        self.header_line() + 1
+    }
+}
@@ -0,0 +1,101 @@
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+/// A float on the right that keeps showing the latest results of a poll or
+/// prediction as they come in.
+#[derive(Default)]
+pub struct ResultsWindow {
+    buffer: Option<Buffer>,
+    window: Option<Window>,
+    title: String,
+    lines: Vec<String>,
+    /// Closed by the user with `:TwitchPollResults` until there are new
+    /// results.
+    hidden: bool,
+}
+
+impl ResultsWindow {
+    /// Replaces the results, opening the float if it isn't.
+    pub fn show(&mut self, title: &str, lines: Vec<String>, zindex: u32) -> Result<()> {
+        let changed = title != self.title;
+
+        self.title = title.to_owned();
+        self.lines = lines;
+        self.hidden &= !changed;
+
+        match self.hidden {
+            true => Ok(()),
+            false => self.redraw(zindex),
+        }
+    }
+
+    pub fn toggle(&mut self, zindex: u32) -> Result<()> {
+        match self.window.take() {
+            Some(window) if window.is_valid() => {
+                self.hidden = true;
+                window.close(true)?;
+                Ok(())
+            }
+            _ => {
+                self.hidden = false;
+                self.redraw(zindex)
+            }
+        }
+    }
+
+    fn redraw(&mut self, zindex: u32) -> Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.is_valid() => buffer,
+            _ => api::create_buf(false, true)?,
+        };
+        let buffer = self.buffer.insert(buffer);
+        buffer.set_lines(.., false, self.lines.iter().map(String::as_str))?;
+
+        let opts = OptionOpts::builder()
+            .scope(api::opts::OptionScope::Global)
+            .build();
+        let cols = api::get_option_value::<u32>("columns", &opts)?;
+
+        let width = self
+            .lines
+            .iter()
+            .chain([&self.title])
+            .map(|line| api::call_function::<_, u32>("strdisplaywidth", (line.as_str(),)))
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(1)
+            + 2;
+
+        let config = WindowConfig::builder()
+            .relative(WindowRelativeTo::Editor)
+            .anchor(WindowAnchor::NorthEast)
+            .border(WindowBorder::Rounded)
+            .style(WindowStyle::Minimal)
+            .title(WindowTitle::SimpleString(
+                format!(" {} ", self.title).into(),
+            ))
+            .title_pos(WindowTitlePosition::Center)
+            .focusable(false)
+            .width(width.clamp(1, cols.saturating_sub(4).max(1)))
+            .height(self.lines.len() as u32)
+            .col(cols as f32)
+            // Below where alerts show up.
+            .row(4.0)
+            .zindex(zindex)
+            .build();
+
+        match &mut self.window {
+            Some(window) if window.is_valid() => window.set_config(&config)?,
+            _ => self.window = Some(api::open_win(buffer, false, &config)?),
+        }
+
+        Ok(())
+    }
+}
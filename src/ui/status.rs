@@ -0,0 +1,72 @@
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, Window},
+    Result,
+};
+
+/// A one-line float in the bottom right corner that stays up until closed,
+/// for counters that change while they are shown.
+pub struct StatusFloat {
+    /// Stacked from the bottom, so several don't cover each other.
+    slot: u32,
+    buffer: Option<Buffer>,
+    window: Option<Window>,
+}
+
+impl StatusFloat {
+    pub fn new(slot: u32) -> Self {
+        StatusFloat {
+            slot,
+            buffer: None,
+            window: None,
+        }
+    }
+
+    pub fn show(&mut self, text: &str, zindex: u32) -> Result<()> {
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.is_valid() => buffer,
+            _ => api::create_buf(false, true)?,
+        };
+        let buffer = self.buffer.insert(buffer);
+        buffer.set_lines(.., false, [text])?;
+
+        let opts = OptionOpts::builder()
+            .scope(api::opts::OptionScope::Global)
+            .build();
+        let cols = api::get_option_value::<u32>("columns", &opts)?;
+        let rows = api::get_option_value::<u32>("lines", &opts)?;
+        let cmdheight = api::get_option_value::<u32>("cmdheight", &opts)?;
+
+        let width: u32 = api::call_function("strdisplaywidth", (text,))?;
+
+        let config = WindowConfig::builder()
+            .relative(WindowRelativeTo::Editor)
+            .anchor(WindowAnchor::SouthEast)
+            .border(WindowBorder::Rounded)
+            .style(WindowStyle::Minimal)
+            .focusable(false)
+            .width(width.clamp(1, cols.saturating_sub(4).max(1)))
+            .height(1)
+            .col(cols as f32)
+            // Above the status line, each slot being one line and a border.
+            .row(rows.saturating_sub(cmdheight + 1 + self.slot * 3) as f32)
+            .zindex(zindex)
+            .build();
+
+        match &mut self.window {
+            Some(window) if window.is_valid() => window.set_config(&config)?,
+            _ => self.window = Some(api::open_win(buffer, false, &config)?),
+        }
+
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        if let Some(window) = self.window.take() {
+            if window.is_valid() {
+                window.close(true)?;
+            }
+        }
+
+        Ok(())
+    }
+}
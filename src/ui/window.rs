@@ -0,0 +1,1051 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer, TabPage, Window},
+    Array, Dictionary, Object, Result,
+};
+
+use super::{
+    render::{MessageView, Rendered, SpanKind, TIMESTAMP_WIDTH},
+    wrap::Wrap,
+};
+use crate::{
+    config::{Anchor, Border, Collision, EmoteStyle, Focus, Layout, WindowOptions, WindowScope},
+    twitch::emotes::EmoteSpan,
+};
+
+/// Below the default of every other float.
+const LOWERED_ZINDEX: u32 = 1;
+
+/// Extmarks default to 4096, anything else put on the text wins.
+const MESSAGE_PRIORITY: u32 = 100;
+
+/// How long fading out takes once the window is due to be dismissed.
+const FADE_DURATION: Duration = Duration::from_millis(800);
+/// How often `winblend` changes while fading.
+const FADE_STEP: Duration = Duration::from_millis(50);
+
+/// Folds each message in the sidebar, starting at its header and ending
+/// before the blank line after it.
+const FOLD_EXPR: &str =
+    "getline(v:lnum) ==# '' ? 0 : v:lnum == 1 || getline(v:lnum - 1) ==# '' ? '>1' : 1";
+
+/// The header of a folded message and how many lines are hidden.
+const FOLD_TEXT: &str = "getline(v:foldstart) . '  [+' . (v:foldend - v:foldstart) . ']'";
+
+/// How long without typing or moving the cursor counts as idle, for
+/// `focus = "idle"`.
+const IDLE_AFTER: Duration = Duration::from_secs(5);
+
+thread_local! {
+    /// When the streamer last typed or moved the cursor.
+    static ACTIVE_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Notes that the streamer is busy in the editor, called from the cursor
+/// and text autocommands.
+pub fn record_activity() {
+    ACTIVE_AT.set(Some(Instant::now()));
+}
+
+/// Whether the streamer is in normal mode and left the editor alone for
+/// [`IDLE_AFTER`].
+fn idle() -> Result<bool> {
+    let mode: String = api::call_function("mode", Array::new())?;
+    let quiet = ACTIVE_AT
+        .get()
+        .is_none_or(|active_at| active_at.elapsed() >= IDLE_AFTER);
+
+    Ok(mode == "n" && quiet)
+}
+
+/// Whether a float asking about something from chat may move the cursor
+/// into it under `focus`. Never out of insert mode, where the keys the
+/// streamer is typing would decide it.
+pub fn may_focus(focus: Focus) -> Result<bool> {
+    match focus {
+        Focus::Always => {
+            let mode: String = api::call_function("mode", Array::new())?;
+            Ok(mode == "n")
+        }
+        Focus::Never => Ok(false),
+        Focus::Idle => idle(),
+    }
+}
+
+/// Maps `key` in normal mode to `:TwitchChatFocus`, in place of `previous`.
+pub fn map_focus_key(previous: Option<&str>, key: Option<&str>) -> Result<()> {
+    if previous == key {
+        return Ok(());
+    }
+
+    if let Some(previous) = previous {
+        // Already gone if something else mapped it since.
+        let _ = api::del_keymap(Mode::Normal, previous);
+    }
+    if let Some(key) = key {
+        let opts = SetKeymapOpts::builder()
+            .silent(true)
+            .desc("Jump into the Twitch chat window and back")
+            .build();
+        api::set_keymap(Mode::Normal, key, "<cmd>TwitchChatFocus<cr>", &opts)?;
+    }
+
+    Ok(())
+}
+
+/// Time spent in the Neovim API while rendering, used by `:TwitchBench`.
+#[derive(Debug, Default, Clone)]
+pub struct RenderStats {
+    pub set_lines: Duration,
+    pub extmarks: Duration,
+}
+
+pub struct ChatWindow {
+    buffer: Buffer,
+    namespace: u32,
+    /// The open windows by tabpage, at most one unless `scope = "tab"`.
+    windows: HashMap<TabPage, Window>,
+    options: WindowOptions,
+    stats: RenderStats,
+    /// The messages in the buffer, oldest first, rendered again when the
+    /// editor is resized.
+    log: VecDeque<Entry>,
+    /// Whether a completion menu or the command line is in use.
+    lowered: bool,
+    /// When the latest message arrived, while `dismiss_after` is counting.
+    shown_at: Option<Instant>,
+    /// The `winblend` fading started from, while fading.
+    fading_from: Option<u32>,
+    /// Whether the window was closed by `dismiss_after`, so it comes back
+    /// without taking the focus.
+    dismissed: bool,
+    /// Messages held back while batching, added to the buffer at once.
+    batch: Option<Vec<MessageView>>,
+    /// The window and cursor from before the chat window took the focus,
+    /// gone back to when leaving it with `:TwitchChatFocus` or closing it
+    /// with `:TwitchChatToggle`.
+    returning: Option<(Window, (usize, usize))>,
+}
+
+struct Entry {
+    view: MessageView,
+    /// Buffer lines the message takes up, without the blank line after it.
+    height: usize,
+}
+
+impl ChatWindow {
+    pub fn new(options: WindowOptions) -> Result<Self> {
+        Ok(ChatWindow {
+            buffer: create_buffer()?,
+            namespace: api::create_namespace("nvim_twitch_chat"),
+            windows: HashMap::new(),
+            options,
+            stats: RenderStats::default(),
+            log: VecDeque::new(),
+            lowered: false,
+            shown_at: None,
+            fading_from: None,
+            dismissed: false,
+            batch: None,
+            returning: None,
+        })
+    }
+
+    /// Holds messages back from the buffer until [`end_batch`] adds them
+    /// all with one update.
+    ///
+    /// [`end_batch`]: ChatWindow::end_batch
+    pub fn begin_batch(&mut self) {
+        self.batch.get_or_insert_with(Vec::new);
+    }
+
+    pub fn end_batch(&mut self) -> Result<()> {
+        match self.batch.take() {
+            Some(messages) => self.show_messages(messages),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds what was held back so far and goes on batching.
+    fn flush_batch(&mut self) -> Result<()> {
+        match self.batch.as_mut().map(std::mem::take) {
+            Some(messages) => self.show_messages(messages),
+            None => Ok(()),
+        }
+    }
+
+    /// Appends a message to the chat log and scrolls to it, or holds it
+    /// back while batching.
+    pub fn show_message(&mut self, message: MessageView) -> Result<()> {
+        match &mut self.batch {
+            Some(batch) => {
+                batch.push(message);
+                Ok(())
+            }
+            None => self.show_messages(vec![message]),
+        }
+    }
+
+    fn show_messages(&mut self, messages: Vec<MessageView>) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        self.ensure_buffer()?;
+
+        let wrap = self.wrap()?;
+        let rendered: Vec<Rendered> = messages
+            .iter()
+            .map(|message| message.lines(&wrap, self.options.timestamps))
+            .collect();
+        let end = self.line_count();
+        // Windows whose cursor is up in the log stay where they are.
+        let mut following = self.following(end)?;
+
+        let start = Instant::now();
+        let lines = join(&rendered);
+        match self.log.is_empty() {
+            // Replaces the empty line of a new buffer.
+            true => self.buffer.set_lines(.., false, lines)?,
+            false => self
+                .buffer
+                .set_lines(end..end, false, std::iter::once("").chain(lines))?,
+        }
+        self.stats.set_lines += start.elapsed();
+
+        let mut first = if self.log.is_empty() { 0 } else { end + 1 };
+        for (message, rendered) in messages.into_iter().zip(rendered) {
+            self.highlight(&message, &rendered, first)?;
+            first += rendered.lines.len() + 1;
+
+            self.log.push_back(Entry {
+                view: message,
+                height: rendered.lines.len(),
+            });
+        }
+        self.trim()?;
+
+        if self.current().is_none() {
+            let focus = match self.options.focus {
+                Focus::Always => !self.dismissed,
+                Focus::Never => false,
+                Focus::Idle => !self.dismissed && idle()?,
+            };
+            self.open(focus)?;
+            following.extend(self.current().cloned());
+        }
+
+        self.dismissed = false;
+        self.shown_at = Some(Instant::now());
+        self.unfade()?;
+
+        let last = self.line_count();
+        for mut window in following {
+            if window.is_valid() {
+                window.set_cursor(last, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How long until [`dismiss_tick`] has something to do, unset while
+    /// nothing is counting down.
+    ///
+    /// [`dismiss_tick`]: ChatWindow::dismiss_tick
+    pub fn next_tick(&self) -> Option<Duration> {
+        let (Some(after), Some(shown_at)) = (self.options.dismiss_after, self.shown_at) else {
+            return None;
+        };
+
+        match self.options.layout {
+            Layout::Sidebar => None,
+            _ => Some(after.checked_sub(shown_at.elapsed()).unwrap_or(FADE_STEP)),
+        }
+    }
+
+    /// Fades out and closes the window once `dismiss_after` passed since the
+    /// latest message, a step at a time when [`next_tick`] says. The
+    /// sidebar stays open until it is toggled.
+    ///
+    /// [`next_tick`]: ChatWindow::next_tick
+    pub fn dismiss_tick(&mut self) -> Result<()> {
+        let (Some(after), Some(shown_at)) = (self.options.dismiss_after, self.shown_at) else {
+            return Ok(());
+        };
+
+        if self.options.layout == Layout::Sidebar {
+            return Ok(());
+        }
+
+        // Never pulled away from under the cursor.
+        if self.focused() {
+            self.shown_at = Some(Instant::now());
+            return self.unfade();
+        }
+
+        let Some(fading) = shown_at.elapsed().checked_sub(after) else {
+            return Ok(());
+        };
+
+        if !self.options.fade || fading >= FADE_DURATION {
+            self.unfade()?;
+            self.shown_at = None;
+            self.dismissed = true;
+
+            for (_, window) in self.windows.drain() {
+                if window.is_valid() {
+                    window.close(false)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let from = match self.fading_from {
+            Some(from) => from,
+            None => *self.fading_from.insert(self.blend()?),
+        };
+        let progress = fading.as_secs_f64() / FADE_DURATION.as_secs_f64();
+        let blend = from + ((100 - from.min(100)) as f64 * progress) as u32;
+
+        self.set_blend(blend)
+    }
+
+    fn focused(&self) -> bool {
+        let current = api::get_current_win();
+        self.windows.values().any(|window| *window == current)
+    }
+
+    /// Puts back the `winblend` from before fading.
+    fn unfade(&mut self) -> Result<()> {
+        match self.fading_from.take() {
+            Some(from) => self.set_blend(from),
+            None => Ok(()),
+        }
+    }
+
+    /// The `winblend` the window has when it isn't fading.
+    fn blend(&self) -> Result<u32> {
+        if let Some(blend) = self.options.winblend {
+            return Ok(blend);
+        }
+
+        let opts = OptionOpts::builder()
+            .scope(api::opts::OptionScope::Global)
+            .build();
+        Ok(api::get_option_value("winblend", &opts)?)
+    }
+
+    fn set_blend(&self, blend: u32) -> Result<()> {
+        for window in self.windows.values().filter(|window| window.is_valid()) {
+            let opts = OptionOpts::builder().window(window.clone()).build();
+            api::set_option_value("winblend", blend, &opts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows only `message`, for `display.mode = "queue"`.
+    pub fn replace_message(&mut self, message: MessageView) -> Result<()> {
+        self.ensure_buffer()?;
+        self.log.clear();
+        self.buffer.clear_namespace(self.namespace, ..)?;
+        self.buffer
+            .set_lines(.., false, std::iter::empty::<&str>())?;
+
+        self.show_message(message)
+    }
+
+    /// Lines in the buffer, counting the blank ones between messages.
+    fn line_count(&self) -> usize {
+        let heights: usize = self.log.iter().map(|entry| entry.height).sum();
+        heights + self.log.len().saturating_sub(1)
+    }
+
+    /// The windows with their cursor on the last of `lines`, or that aren't
+    /// focused, which follow new messages.
+    fn following(&self, lines: usize) -> Result<Vec<Window>> {
+        let current = api::get_current_win();
+        let mut following = Vec::new();
+
+        for window in self.windows.values().filter(|window| window.is_valid()) {
+            if *window != current || window.get_cursor()?.0 >= lines {
+                following.push(window.clone());
+            }
+        }
+
+        Ok(following)
+    }
+
+    /// Drops the oldest messages beyond `max_lines`, always keeping the
+    /// latest one.
+    fn trim(&mut self) -> Result<()> {
+        let lines = self.line_count();
+        let mut dropped = 0;
+
+        while self.log.len() > 1 && lines - dropped > self.options.max_lines {
+            let Some(entry) = self.log.pop_front() else {
+                break;
+            };
+            // Along with the blank line that followed it.
+            dropped += entry.height + 1;
+        }
+
+        // All at once, a batch can push out many messages.
+        if dropped > 0 {
+            self.buffer
+                .set_lines(..dropped, false, std::iter::empty::<&str>())?;
+        }
+
+        Ok(())
+    }
+
+    /// Brings a global window along to the tabpage that was entered.
+    pub fn tab_entered(&mut self) -> Result<()> {
+        if self.options.scope != WindowScope::Global || self.current().is_some() {
+            return Ok(());
+        }
+
+        if self.windows.values().any(Window::is_valid) {
+            self.open(false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the windows of tabpages that were closed.
+    pub fn tab_closed(&mut self) {
+        self.windows
+            .retain(|tab, window| tab.is_valid() && window.is_valid());
+    }
+
+    /// Fits the window to the editor again and re-wraps the log to its new
+    /// width, called on `VimResized` and changes to `cmdheight` or
+    /// `laststatus`.
+    pub fn resize(&mut self) -> Result<()> {
+        self.ensure_buffer()?;
+        self.relayout()?;
+        self.render()
+    }
+
+    /// Renders the whole log again.
+    /// Highlights the log again after `:colorscheme` cleared the author
+    /// colors.
+    pub fn colors_changed(&mut self) -> Result<()> {
+        self.render()
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let wrap = self.wrap()?;
+        let timestamps = self.options.timestamps;
+
+        let rendered: Vec<Rendered> = self
+            .log
+            .iter()
+            .map(|entry| entry.view.lines(&wrap, timestamps))
+            .collect();
+
+        let start = Instant::now();
+        self.buffer.set_lines(.., false, join(&rendered))?;
+        self.stats.set_lines += start.elapsed();
+
+        self.buffer.clear_namespace(self.namespace, ..)?;
+
+        let log = std::mem::take(&mut self.log);
+        let mut first = 0;
+
+        for (entry, rendered) in log.iter().zip(&rendered) {
+            self.highlight(&entry.view, rendered, first)?;
+            first += rendered.lines.len() + 1;
+        }
+
+        self.log = log
+            .into_iter()
+            .zip(&rendered)
+            .map(|(entry, rendered)| Entry {
+                height: rendered.lines.len(),
+                ..entry
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    /// How message text is laid out in the window as it is now.
+    fn wrap(&self) -> Result<Wrap> {
+        let (width, _) = size(&self.options)?;
+
+        Ok(Wrap {
+            width: match self.options.wrap {
+                true => width as usize,
+                false => usize::MAX,
+            },
+            indent: self.options.indent,
+            max_height: self.options.max_height,
+        })
+    }
+
+    /// Highlights the message `rendered` from line `first` on.
+    fn highlight(
+        &mut self,
+        message: &MessageView,
+        rendered: &Rendered,
+        first: usize,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let text = first + message.text_line();
+
+        if let Some(line) = message.quote_line() {
+            let opts = SetExtmarkOpts::builder()
+                .end_col(rendered.lines[line].len())
+                .hl_group("TwitchReplyContext")
+                .build();
+
+            self.buffer
+                .set_extmark(self.namespace, first + line, 0, &opts)?;
+        }
+
+        if self.options.timestamps {
+            let opts = SetExtmarkOpts::builder()
+                .end_col(TIMESTAMP_WIDTH)
+                .hl_group("TwitchTimestamp")
+                .build();
+
+            self.buffer
+                .set_extmark(self.namespace, first + message.header_line(), 0, &opts)?;
+        }
+
+        for span in message.header_spans(self.options.timestamps) {
+            let group = match span.kind {
+                SpanKind::Badge(set) => super::badge_highlight(&set).to_owned(),
+                SpanKind::Author if self.options.author_colors => super::author_highlight(
+                    &message.author,
+                    message.color.as_deref(),
+                    &self.options.palette,
+                )?,
+                SpanKind::Author => "TwitchAuthor".to_owned(),
+            };
+            let opts = SetExtmarkOpts::builder()
+                .end_col(span.end)
+                .hl_group(&group)
+                .build();
+
+            self.buffer.set_extmark(
+                self.namespace,
+                first + message.header_line(),
+                span.start,
+                &opts,
+            )?;
+        }
+
+        // Below the cheers and emotes in it.
+        let opts = |end: usize| {
+            SetExtmarkOpts::builder()
+                .end_col(end)
+                .hl_group("TwitchMessage")
+                .priority(MESSAGE_PRIORITY)
+                .build()
+        };
+        for (line, content) in rendered.text.lines.iter().enumerate() {
+            self.buffer
+                .set_extmark(self.namespace, text + line, 0, &opts(content.len()))?;
+        }
+
+        // Spread over as many lines as they were wrapped onto.
+        let cheers = message
+            .cheers
+            .iter()
+            .map(|cheer| (cheer.start, cheer.end, cheer.highlight()));
+        let emotes = message
+            .emotes
+            .iter()
+            .map(|emote| (emote.start, emote.end, "TwitchEmote".to_owned()));
+
+        let code = super::code::highlights(&message.text)?;
+
+        for (start, end, group) in cheers.chain(emotes).chain(code) {
+            for (line, start, end) in rendered.text.locate(start, end) {
+                let opts = SetExtmarkOpts::builder()
+                    .end_col(end)
+                    .hl_group(&group)
+                    .build();
+
+                self.buffer
+                    .set_extmark(self.namespace, text + line, start, &opts)?;
+            }
+        }
+        self.stats.extmarks += start.elapsed();
+
+        if self.options.emotes == EmoteStyle::Image {
+            for emote in &message.emotes {
+                // Only an emote wider than the window is broken, it is
+                // drawn where it starts.
+                if let Some(&(line, start, end)) =
+                    rendered.text.locate(emote.start, emote.end).first()
+                {
+                    self.emit_emote(emote, text + line, start, end)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells image plugins where to draw `emote`. Fired again with the new
+    /// position whenever the log is rendered again.
+    fn emit_emote(&self, emote: &EmoteSpan, line: usize, start: usize, end: usize) -> Result<()> {
+        let data = Dictionary::from_iter([
+            ("buffer", Object::from(&self.buffer)),
+            ("line", Object::from(line as i64)),
+            ("col", Object::from(start as i64)),
+            ("end_col", Object::from(end as i64)),
+            ("id", Object::from(emote.id.as_str())),
+            ("name", Object::from(emote.code.as_str())),
+            ("url", Object::from(emote.url())),
+        ]);
+        let opts = ExecAutocmdsOpts::builder()
+            .patterns("TwitchEmote")
+            .data(data)
+            .build();
+
+        api::exec_autocmds(["User"], &opts)?;
+
+        Ok(())
+    }
+
+    /// Applies new layout options, resizing the window if it is open.
+    pub fn set_options(&mut self, options: WindowOptions) -> Result<()> {
+        if options == self.options {
+            return Ok(());
+        }
+
+        // What was held back is laid out with the options it came with.
+        self.flush_batch()?;
+
+        let rewrap = options.timestamps != self.options.timestamps
+            || options.author_colors != self.options.author_colors
+            || options.palette != self.options.palette
+            || options.width != self.options.width
+            || options.wrap != self.options.wrap
+            || options.indent != self.options.indent
+            || options.max_height != self.options.max_height
+            || options.border != self.options.border
+            || options.layout != self.options.layout;
+        let reblend = options.winblend != self.options.winblend;
+        let reopen = options.layout != self.options.layout;
+        let refold = options.fold_lines != self.options.fold_lines;
+        self.options = options;
+
+        if reopen {
+            self.reopen()?;
+        } else if refold && self.options.layout == Layout::Sidebar {
+            for win in self.windows.values().filter(|window| window.is_valid()) {
+                set_sidebar_options(win, &self.options)?;
+            }
+        }
+        self.relayout()?;
+
+        if reblend && self.fading_from.is_none() {
+            self.set_blend(self.blend()?)?;
+        }
+
+        if rewrap {
+            self.render()?;
+        }
+
+        Ok(())
+    }
+
+    /// Called when a completion menu or the command line opens or closes,
+    /// which lowers the floats if `collision = "lower"`.
+    pub fn set_lowered(&mut self, lowered: bool) -> Result<()> {
+        if lowered == self.lowered {
+            return Ok(());
+        }
+
+        self.lowered = lowered;
+
+        // Splits are below every float anyway.
+        match self.options.layout {
+            Layout::Float => self.relayout(),
+            Layout::Sidebar => Ok(()),
+        }
+    }
+
+    /// The stacking order for the plugin's floats right now.
+    pub fn zindex(&self) -> u32 {
+        match self.options.collision {
+            Collision::Lower if self.lowered => LOWERED_ZINDEX,
+            _ => self.options.zindex,
+        }
+    }
+
+    /// The message under the cursor when the chat window is focused, the
+    /// latest one otherwise.
+    pub fn shown_id(&self) -> &str {
+        let current = api::get_current_win();
+        let cursor = self
+            .windows
+            .values()
+            .find(|window| **window == current)
+            .and_then(|window| window.get_cursor().ok())
+            .map(|(line, _)| line.saturating_sub(1));
+
+        let entry = match cursor {
+            Some(cursor) => {
+                let mut first = 0;
+
+                self.log.iter().find(|entry| {
+                    first += entry.height + 1;
+                    cursor < first
+                })
+            }
+            None => self.log.back(),
+        };
+
+        entry.map_or("", |entry| &entry.view.id)
+    }
+
+    /// Focuses the chat window on the message `id`, opening it if needed.
+    /// Returns whether the message is still in the chat log.
+    pub fn jump(&mut self, id: &str) -> Result<bool> {
+        // A wiped buffer took the log with it.
+        if !self.buffer.is_valid() {
+            return Ok(false);
+        }
+
+        let Some(index) = self
+            .log
+            .iter()
+            .position(|entry| !id.is_empty() && entry.view.id == id)
+        else {
+            return Ok(false);
+        };
+        let first: usize = self
+            .log
+            .iter()
+            .take(index)
+            .map(|entry| entry.height + 1)
+            .sum();
+
+        if self.current().is_none() {
+            self.open(true)?;
+        }
+
+        if let Some(window) = self.current() {
+            let mut window = window.clone();
+            self.enter(&window)?;
+            window.set_cursor(first + 1, 0)?;
+        }
+
+        Ok(true)
+    }
+
+    pub fn take_stats(&mut self) -> RenderStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    pub fn toggle(&mut self) -> Result<()> {
+        self.ensure_buffer()?;
+
+        match self.windows.remove(&api::get_current_tabpage()) {
+            Some(win) if win.is_valid() => {
+                let focused = win == api::get_current_win();
+                win.close(false)?;
+                if focused {
+                    self.leave()?;
+                }
+            }
+            _ => self.open(true)?,
+        }
+
+        Ok(())
+    }
+
+    /// Jumps into the chat window, opening it if needed, or back to where
+    /// the cursor was before from inside it.
+    pub fn toggle_focus(&mut self) -> Result<()> {
+        self.ensure_buffer()?;
+
+        if self.focused() {
+            return self.leave();
+        }
+
+        match self.current().cloned() {
+            Some(window) => self.enter(&window),
+            None => self.open(true),
+        }
+    }
+
+    /// Focuses `window`, remembering the window and cursor to go back to.
+    fn enter(&mut self, window: &Window) -> Result<()> {
+        if !self.focused() {
+            let current = api::get_current_win();
+            self.returning = Some((current.clone(), current.get_cursor()?));
+        }
+
+        api::set_current_win(window)?;
+        Ok(())
+    }
+
+    /// Goes back to the window and cursor from before the chat window was
+    /// entered, or the previous window if that one is gone.
+    fn leave(&mut self) -> Result<()> {
+        match self.returning.take() {
+            Some((mut window, (line, col))) if window.is_valid() => {
+                api::set_current_win(&window)?;
+                // Lines may have been deleted in the meantime.
+                let lines = window.get_buf()?.line_count()?;
+                window.set_cursor(line.clamp(1, lines.max(1)), col)?;
+            }
+            _ if self.focused() => api::command("wincmd p")?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// The window in the current tabpage, if it is open.
+    fn current(&self) -> Option<&Window> {
+        self.windows
+            .get(&api::get_current_tabpage())
+            .filter(|window| window.is_valid())
+    }
+
+    /// Replaces the buffer if the user wiped it, which also closed any
+    /// window showing it.
+    fn ensure_buffer(&mut self) -> Result<()> {
+        if self.buffer.is_valid() {
+            return Ok(());
+        }
+
+        self.buffer = create_buffer()?;
+        self.windows.clear();
+        self.log.clear();
+
+        Ok(())
+    }
+
+    fn relayout(&mut self) -> Result<()> {
+        let zindex = self.zindex();
+
+        for win in self.windows.values_mut() {
+            if !win.is_valid() {
+                continue;
+            }
+
+            match self.options.layout {
+                Layout::Float => win.set_config(&layout(&self.options, zindex)?)?,
+                Layout::Sidebar => win.set_width(size(&self.options)?.0)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens the windows again in the current layout, without the focus,
+    /// since a float can't become a split or the other way around.
+    fn reopen(&mut self) -> Result<()> {
+        let mut open = false;
+
+        for (_, win) in self.windows.drain() {
+            if win.is_valid() {
+                win.close(false)?;
+                open = true;
+            }
+        }
+
+        if open {
+            self.open(false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens the window in the current tabpage, closing the one in any other
+    /// unless `scope = "tab"`.
+    fn open(&mut self, focus: bool) -> Result<()> {
+        if self.options.scope == WindowScope::Global {
+            for (_, win) in self.windows.drain() {
+                if win.is_valid() {
+                    win.close(false)?;
+                }
+            }
+        }
+
+        let window = match self.options.layout {
+            Layout::Float => {
+                let config = layout(&self.options, self.zindex())?;
+                let window = api::open_win(&self.buffer, false, &config)?;
+                super::set_border_highlight(&window)?;
+
+                if let Some(blend) = self.options.winblend {
+                    let opts = OptionOpts::builder().window(window.clone()).build();
+                    api::set_option_value("winblend", blend, &opts)?;
+                }
+                window
+            }
+            Layout::Sidebar => open_sidebar(&self.buffer, &self.options)?,
+        };
+
+        if focus {
+            self.enter(&window)?;
+        }
+
+        self.windows.insert(api::get_current_tabpage(), window);
+
+        Ok(())
+    }
+}
+
+fn create_buffer() -> Result<Buffer> {
+    let mut buffer = api::create_buf(false, true)?;
+    buffer.set_keymap(
+        Mode::Normal,
+        "<CR>",
+        "<cmd>TwitchThread<cr>",
+        &SetKeymapOpts::builder().nowait(true).silent(true).build(),
+    )?;
+
+    let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+    api::set_option_value("filetype", "twitchchat", &opts)?;
+
+    Ok(buffer)
+}
+
+/// Splits the editor for the sidebar, on the left if `anchor` is one of the
+/// left corners and on the right otherwise, and goes back to the window
+/// that was current.
+fn open_sidebar(buffer: &Buffer, options: &WindowOptions) -> Result<Window> {
+    let previous = api::get_current_win();
+
+    let side = match options.anchor {
+        Anchor::TopLeft | Anchor::BottomLeft => "topleft",
+        _ => "botright",
+    };
+    api::command(&format!("{} vsplit", side))?;
+
+    let mut window = api::get_current_win();
+    window.set_buf(buffer)?;
+    window.set_width(size(options)?.0)?;
+    set_sidebar_options(&window, options)?;
+
+    api::set_current_win(&previous)?;
+
+    Ok(window)
+}
+
+/// The sidebar's window-local options. Setting `foldlevel` closes every
+/// fold again, so this is only done when it opens or `fold_lines` changes.
+fn set_sidebar_options(window: &Window, options: &WindowOptions) -> Result<()> {
+    let opts = OptionOpts::builder().window(window.clone()).build();
+    api::set_option_value("wrap", true, &opts)?;
+    api::set_option_value("linebreak", true, &opts)?;
+    api::set_option_value("number", false, &opts)?;
+    api::set_option_value("relativenumber", false, &opts)?;
+    api::set_option_value("signcolumn", "no", &opts)?;
+    api::set_option_value("winfixwidth", true, &opts)?;
+    api::set_option_value("foldmethod", "expr", &opts)?;
+    api::set_option_value("foldexpr", FOLD_EXPR, &opts)?;
+    api::set_option_value("foldtext", FOLD_TEXT, &opts)?;
+    api::set_option_value("foldenable", options.fold_lines > 0, &opts)?;
+    api::set_option_value("foldminlines", options.fold_lines as u32, &opts)?;
+    api::set_option_value("foldlevel", 0u32, &opts)?;
+
+    Ok(())
+}
+
+/// The columns and rows left for windows, without the command line and a
+/// global status line.
+fn editor_size() -> Result<(u32, u32)> {
+    let opts = OptionOpts::builder()
+        .scope(api::opts::OptionScope::Global)
+        .build();
+
+    let cols = api::get_option_value::<u32>("columns", &opts)?;
+    let rows = api::get_option_value::<u32>("lines", &opts)?;
+    let cmdheight = api::get_option_value::<u32>("cmdheight", &opts)?;
+    let laststatus = api::get_option_value::<u32>("laststatus", &opts)?;
+
+    let status = u32::from(laststatus != 0);
+
+    Ok((cols, rows.saturating_sub(cmdheight + status)))
+}
+
+/// The lines of every message, with an empty one between each.
+fn join(rendered: &[Rendered]) -> Vec<&str> {
+    let mut lines = Vec::new();
+
+    for (i, rendered) in rendered.iter().enumerate() {
+        if i > 0 {
+            lines.push("");
+        }
+        lines.extend(rendered.lines.iter().map(String::as_str));
+    }
+
+    lines
+}
+
+/// The configured size, shrunk to fit the editor along with the border,
+/// which the sidebar doesn't have.
+fn size(options: &WindowOptions) -> Result<(u32, u32)> {
+    let (cols, rows) = editor_size()?;
+    let (border_cols, border_rows) = match options.layout {
+        Layout::Float => options.border.size(),
+        Layout::Sidebar => (0, 0),
+    };
+
+    Ok((
+        options.width.min(cols.saturating_sub(border_cols)).max(1),
+        options.height.min(rows.saturating_sub(border_rows)).max(1),
+    ))
+}
+
+/// A float of the configured size at its `anchor`, moved by `row` and `col`
+/// but kept inside the editor.
+fn layout(options: &WindowOptions, zindex: u32) -> Result<WindowConfig> {
+    let (cols, rows) = editor_size()?;
+    let (width, height) = size(options)?;
+    let (border_cols, border_rows) = options.border.size();
+
+    // The room left around the window and its border.
+    let free_cols = cols.saturating_sub(width + border_cols);
+    let free_rows = rows.saturating_sub(height + border_rows);
+
+    let (x, y) = match options.anchor {
+        Anchor::Center => (free_cols / 2, free_rows / 2),
+        Anchor::TopLeft => (0, 0),
+        Anchor::TopRight => (free_cols, 0),
+        Anchor::BottomLeft => (0, free_rows),
+        Anchor::BottomRight => (free_cols, free_rows),
+    };
+
+    let x = (x as i64 + options.col as i64).clamp(0, free_cols as i64) as f32;
+    let y = (y as i64 + options.row as i64).clamp(0, free_rows as i64) as f32;
+
+    let border = match options.border {
+        Border::None => WindowBorder::None,
+        Border::Single => WindowBorder::Single,
+        Border::Double => WindowBorder::Double,
+        Border::Rounded => WindowBorder::Rounded,
+        Border::Solid => WindowBorder::Solid,
+        Border::Shadow => WindowBorder::Shadow,
+    };
+
+    Ok(WindowConfig::builder()
+        .relative(WindowRelativeTo::Editor)
+        .border(border)
+        .style(WindowStyle::Minimal)
+        .height(height)
+        .width(width)
+        .col(x)
+        .row(y)
+        .zindex(zindex)
+        .focusable(true)
+        .build())
+}
@@ -0,0 +1,201 @@
+//! Lays message text out in lines no wider than the chat window, measured
+//! in display cells the way Neovim counts them, so wide characters and
+//! emoji don't run past the border. Every line remembers which part of the
+//! text it holds, so highlights given in bytes of the text still land on
+//! the right words. Line breaks in the text and code blocks always start a
+//! new line.
+
+use nvim_oxi::api::{self, opts::*};
+use unicode_width::UnicodeWidthStr;
+
+use super::code;
+
+/// How message text is laid out, from `window.wrap`, `window.indent` and
+/// `window.max_height`.
+#[derive(Debug, Clone, Copy)]
+pub struct Wrap {
+    /// In cells, `usize::MAX` leaves wrapping to Neovim.
+    pub width: usize,
+    /// Cells in front of every line after the first.
+    pub indent: usize,
+    /// Lines after which the text is cut off with `…`, 0 never cuts it.
+    pub max_height: usize,
+}
+
+/// Text laid out in lines.
+pub struct Wrapped {
+    pub lines: Vec<String>,
+    parts: Vec<Part>,
+}
+
+/// The bytes `start..end` of the text, shown after `indent` spaces.
+struct Part {
+    start: usize,
+    end: usize,
+    indent: usize,
+}
+
+impl Wrapped {
+    /// Where bytes `start..end` of the text ended up, as the line and the
+    /// byte columns in it for each line they span. Parts that were cut off
+    /// aren't anywhere.
+    pub fn locate(&self, start: usize, end: usize) -> Vec<(usize, usize, usize)> {
+        self.parts
+            .iter()
+            .enumerate()
+            .filter_map(|(line, part)| {
+                let (from, to) = (start.max(part.start), end.min(part.end));
+                (from < to).then(|| {
+                    (
+                        line,
+                        part.indent + from - part.start,
+                        part.indent + to - part.start,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Breaks `text` after the last word that fits on each line, or anywhere
+/// in words longer than a line.
+pub fn wrap(text: &str, wrap: &Wrap) -> Wrapped {
+    let cells = Cells::current();
+    let width = wrap.width.max(1);
+    // An indent taking most of the line would leave no room for the text.
+    let indent = wrap.indent.min(width / 2);
+
+    let mut parts: Vec<Part> = Vec::new();
+
+    for section in code::sections(text) {
+        // Cut off where the section ends, so lines never run into the next.
+        let text = &text[..section.end];
+        let mut start = section.start;
+
+        while start < text.len() {
+            let indent = if parts.is_empty() { 0 } else { indent };
+            let end = start + line_end(&text[start..], width - indent, cells);
+            let trimmed = start + text[start..end].trim_end().len();
+            parts.push(Part {
+                start,
+                end: trimmed,
+                // Blank lines stay empty.
+                indent: if trimmed > start { indent } else { 0 },
+            });
+            start = next_line(text, end);
+        }
+    }
+    if parts.is_empty() {
+        parts.push(Part {
+            start: 0,
+            end: 0,
+            indent: 0,
+        });
+    }
+
+    let cut = wrap.max_height > 0 && parts.len() > wrap.max_height;
+    if cut {
+        parts.truncate(wrap.max_height);
+
+        // Room is made for the `…`.
+        let last = parts.last_mut().unwrap();
+        let kept = &text[last.start..last.end];
+        let kept = &kept[..line_end(kept, (width - last.indent).saturating_sub(1), cells)];
+        last.end = last.start + kept.trim_end().len();
+    }
+
+    let lines = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut line = " ".repeat(part.indent);
+            line.push_str(&text[part.start..part.end]);
+            if cut && i + 1 == parts.len() {
+                line.push('…');
+            }
+            line
+        })
+        .collect();
+
+    Wrapped { lines, parts }
+}
+
+/// Cuts `line` off with `…` once it is wider than `width` cells.
+pub fn truncate(line: &str, width: usize) -> String {
+    let cells = Cells::current();
+    if cells.of(line) <= width {
+        return line.to_owned();
+    }
+
+    let mut truncated = line[..line_end(line, width.saturating_sub(1), cells)].to_owned();
+    truncated.push('…');
+    truncated
+}
+
+/// Counts cells as Neovim does: wide characters and emoji, along with the
+/// sequences joined into one, take two and combining marks none. Under
+/// `ambiwidth=double` so do the characters of ambiguous width.
+#[derive(Clone, Copy)]
+struct Cells {
+    double: bool,
+}
+
+impl Cells {
+    fn current() -> Self {
+        let opts = OptionOpts::builder().scope(OptionScope::Global).build();
+        let ambiwidth: String = api::get_option_value("ambiwidth", &opts).unwrap_or_default();
+
+        Cells {
+            double: ambiwidth == "double",
+        }
+    }
+
+    fn of(self, text: &str) -> usize {
+        match self.double {
+            true => text.width_cjk(),
+            false => text.width(),
+        }
+    }
+}
+
+/// Where the first line of `text` ends when it has `width` cells, in bytes.
+fn line_end(text: &str, width: usize, cells: Cells) -> usize {
+    let mut space = None;
+
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            return i;
+        }
+
+        // Measured up to here, as a character may join the one before it.
+        if cells.of(&text[..i + c.len_utf8()]) > width {
+            return match space {
+                Some(space) if space > 0 => space,
+                // At least one character goes on every line.
+                _ if i == 0 => c.len_utf8(),
+                _ => i,
+            };
+        }
+        if c.is_whitespace() {
+            space = Some(i);
+        }
+    }
+
+    text.len()
+}
+
+/// Where the line after the one ending at `from` starts: past the blanks
+/// it was broken at, or past a line break, keeping the indentation after
+/// it that code needs.
+fn next_line(text: &str, from: usize) -> usize {
+    let rest = &text[from..];
+    let blanks = rest.len()
+        - rest
+            .trim_start_matches(|c: char| c.is_whitespace() && c != '\n')
+            .len();
+
+    match rest[blanks..].starts_with('\n') {
+        true => from + blanks + 1,
+        false => from + blanks,
+    }
+}
@@ -0,0 +1,124 @@
+//! What Helix told about the chatters and the stream, for `!uptime`, the
+//! `{followage}` and `{uptime}` of text commands, Lua commands and
+//! `viewer()` from Lua. The connection fetches it in the background, the
+//! editor only reads what already came in.
+
+use std::{collections::HashMap, time::Instant};
+
+use chrono::Utc;
+use nvim_oxi::Result;
+
+use crate::{
+    i18n::{tr, tr_with},
+    twitch::{LiveStream, Outgoing, Viewer, VIEWER_TTL},
+    Plugin,
+};
+
+pub struct Viewers {
+    /// By login, with when Helix told. Past [`VIEWER_TTL`] the connection
+    /// looks them up again the next time they chat.
+    known: HashMap<String, (Instant, Viewer)>,
+    /// Caps memory use in very busy channels.
+    limit: usize,
+    /// Unset until Helix was first asked, then the broadcast if there is
+    /// one.
+    pub stream: Option<Option<LiveStream>>,
+}
+
+impl Viewers {
+    pub fn new(limit: usize) -> Self {
+        Viewers {
+            known: HashMap::new(),
+            limit,
+            stream: None,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    pub fn update(&mut self, viewers: Vec<Viewer>) {
+        let now = Instant::now();
+        self.known.extend(
+            viewers
+                .into_iter()
+                .map(|viewer| (viewer.login.clone(), (now, viewer))),
+        );
+        self.evict();
+    }
+
+    /// The viewer `name`, a login or display name with or without an `@`,
+    /// if they were looked up within the last [`VIEWER_TTL`].
+    pub fn get(&self, name: &str) -> Option<&Viewer> {
+        self.known
+            .get(&name.trim_start_matches('@').to_lowercase())
+            .filter(|(at, _)| at.elapsed() < VIEWER_TTL)
+            .map(|(_, viewer)| viewer)
+    }
+
+    /// Drops the viewers looked up too long ago, then the oldest ones over
+    /// the limit.
+    fn evict(&mut self) {
+        self.known.retain(|_, (at, _)| at.elapsed() < VIEWER_TTL);
+
+        let excess = self.known.len().saturating_sub(self.limit);
+        if excess == 0 {
+            return;
+        }
+
+        let mut oldest: Vec<(Instant, String)> = self
+            .known
+            .iter()
+            .map(|(login, (at, _))| (*at, login.clone()))
+            .collect();
+        oldest.sort_unstable();
+        for (_, login) in oldest.into_iter().take(excess) {
+            self.known.remove(&login);
+        }
+    }
+
+    /// How long `name` follows the channel, for `{followage}`.
+    pub fn followage(&self, name: &str) -> String {
+        let followed_at = self.get(name).and_then(|viewer| viewer.followed_at);
+
+        match followed_at {
+            Some(at) => tr_with(
+                "viewers.followage",
+                &[("days", &(Utc::now() - at).num_days().max(0))],
+            ),
+            None => tr("viewers.not_following"),
+        }
+    }
+
+    /// How long the stream is live, for `{uptime}`.
+    pub fn uptime(&self) -> String {
+        match &self.stream {
+            Some(Some(stream)) => {
+                let minutes = (Utc::now() - stream.started_at).num_minutes().max(0);
+                tr_with(
+                    "viewers.uptime",
+                    &[("hours", &(minutes / 60)), ("minutes", &(minutes % 60))],
+                )
+            }
+            Some(None) => tr("viewers.offline"),
+            None => tr("viewers.unknown"),
+        }
+    }
+}
+
+/// Answers `author`'s `!uptime` in `channel`.
+pub fn uptime(plugin: &Plugin, channel: String, author: &str) -> Result<()> {
+    let text = tr_with(
+        "viewers.uptime_reply",
+        &[("user", &author), ("uptime", &plugin.viewers.uptime())],
+    );
+    let _ = plugin.outgoing.send(Outgoing::Reply { channel, text });
+
+    Ok(())
+}
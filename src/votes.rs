@@ -0,0 +1,374 @@
+//! Chat votes run by the plugin rather than Twitch, started with
+//! `:TwitchPoll`. Viewers vote with `!vote <option>` and the tally is shown
+//! in the same float as Twitch's polls. `:TwitchFocusVote` runs one on the
+//! open buffers instead, which chat picks with `!focus <number>` and which
+//! becomes current once time is up.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*, Buffer},
+    Function, Object, Result,
+};
+
+use crate::{
+    commands::{self, Command},
+    i18n::{tr, tr_with},
+    location, polls,
+    twitch::{
+        polls::{Choice, Poll},
+        Outgoing,
+    },
+    Plugin,
+};
+
+pub const SUBCOMMANDS: [&str; 2] = ["end", "cancel"];
+
+const TITLE_LIMIT: usize = 60;
+const OPTIONS: (usize, usize) = (2, 10);
+const SECONDS: (u32, u32) = (10, 1800);
+
+/// How long a `:TwitchFocusVote` runs without a duration.
+const FOCUS_SECONDS: u32 = 30;
+
+/// Twitch's poll statuses, so the float reads the same for both.
+const ACTIVE: &str = "ACTIVE";
+const COMPLETED: &str = "COMPLETED";
+
+#[derive(Default)]
+pub struct Votes {
+    current: Option<Vote>,
+    /// Set from Lua with `on_vote_result()`, called with the `vote_ended`
+    /// event.
+    pub on_result: Option<Function<Object, ()>>,
+}
+
+struct Vote {
+    title: String,
+    options: Vec<String>,
+    counts: Vec<u64>,
+    /// The option each viewer voted for, so changing one's mind moves the
+    /// vote rather than adding one.
+    voters: HashMap<String, usize>,
+    ends: Instant,
+    /// One per option for a `:TwitchFocusVote`, empty for other votes.
+    buffers: Vec<Buffer>,
+}
+
+impl Vote {
+    fn poll(&self, status: &str) -> Poll {
+        Poll {
+            id: String::new(),
+            title: self.title.clone(),
+            choices: self
+                .options
+                .iter()
+                .zip(&self.counts)
+                .enumerate()
+                .map(|(i, (title, &votes))| Choice {
+                    title: match self.buffers.is_empty() {
+                        true => title.clone(),
+                        false => format!("{}. {}", i + 1, title),
+                    },
+                    votes,
+                })
+                .collect(),
+            status: status.to_owned(),
+        }
+    }
+
+    /// The option with the most votes, `None` on a tie or without votes.
+    fn winner(&self) -> Option<usize> {
+        let most = *self.counts.iter().max()?;
+        let mut leading = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count == most);
+
+        match (leading.next(), leading.next()) {
+            (Some((index, _)), None) if most > 0 => Some(index),
+            _ => None,
+        }
+    }
+
+    /// `choice` as an option's number or name.
+    fn option(&self, choice: &str) -> Option<usize> {
+        let choice = choice.trim();
+
+        match choice.parse::<usize>() {
+            Ok(number) => (1..=self.options.len())
+                .contains(&number)
+                .then(|| number - 1),
+            Err(_) => self
+                .options
+                .iter()
+                .position(|option| option.eq_ignore_ascii_case(choice)),
+        }
+    }
+}
+
+/// Runs `:TwitchPoll "title" option option... [seconds]`, `:TwitchPoll end`
+/// or `:TwitchPoll cancel`.
+pub fn run(plugin: &mut Plugin, args: Option<String>) -> Result<()> {
+    match args.as_deref().map(str::trim).unwrap_or_default() {
+        "end" => return end(plugin),
+        "cancel" => return cancel(plugin),
+        _ => (),
+    }
+
+    let Some((title, options, seconds)) = polls::parse(
+        args.as_deref().unwrap_or_default(),
+        TITLE_LIMIT,
+        OPTIONS,
+        SECONDS,
+    ) else {
+        return polls::usage("votes.usage", TITLE_LIMIT, OPTIONS, SECONDS);
+    };
+
+    let vote = Vote {
+        counts: vec![0; options.len()],
+        title,
+        options,
+        voters: HashMap::new(),
+        ends: Instant::now() + Duration::from_secs(seconds as u64),
+        buffers: Vec::new(),
+    };
+
+    let text = tr_with(
+        "votes.started",
+        &[
+            ("title", &vote.title),
+            ("command", &format!("{}vote", plugin.config.syntax.prefix)),
+            ("options", &vote.options.join(", ")),
+            ("seconds", &seconds),
+        ],
+    );
+    say(plugin, text);
+
+    plugin.votes.current = Some(vote);
+    show(plugin)
+}
+
+/// Runs `:TwitchFocusVote [seconds]`, a vote on which of the listed file
+/// buffers to switch to, or `:TwitchFocusVote end|cancel`.
+pub fn run_focus(plugin: &mut Plugin, args: Option<&str>) -> Result<()> {
+    let seconds = match args.map(str::trim).unwrap_or_default() {
+        "end" => return end(plugin),
+        "cancel" => return cancel(plugin),
+        "" => FOCUS_SECONDS,
+        seconds => match seconds.parse() {
+            Ok(seconds) if (SECONDS.0..=SECONDS.1).contains(&seconds) => seconds,
+            _ => {
+                let text = tr_with(
+                    "votes.focus_usage",
+                    &[("shortest", &SECONDS.0), ("longest", &SECONDS.1)],
+                );
+                return notify(&text, LogLevel::Error);
+            }
+        },
+    };
+
+    if !plugin
+        .config
+        .for_channel(&plugin.config.channel)
+        .command_enabled("focus")
+    {
+        return notify(&tr("votes.focus_disabled"), LogLevel::Error);
+    }
+
+    let (buffers, options): (Vec<_>, Vec<_>) = focusable()?.into_iter().unzip();
+    if buffers.len() < OPTIONS.0 {
+        return notify(&tr("votes.too_few_buffers"), LogLevel::Error);
+    }
+
+    let vote = Vote {
+        title: tr("votes.focus_title"),
+        counts: vec![0; options.len()],
+        options,
+        voters: HashMap::new(),
+        ends: Instant::now() + Duration::from_secs(seconds as u64),
+        buffers,
+    };
+
+    let numbered: Vec<String> = vote
+        .options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| format!("{} {}", i + 1, option))
+        .collect();
+    let text = tr_with(
+        "votes.focus_started",
+        &[
+            ("command", &format!("{}focus", plugin.config.syntax.prefix)),
+            ("options", &numbered.join(", ")),
+            ("seconds", &seconds),
+        ],
+    );
+    say(plugin, text);
+
+    plugin.votes.current = Some(vote);
+    show(plugin)
+}
+
+/// The listed buffers holding files, up to the most a vote can offer.
+fn focusable() -> Result<Vec<(Buffer, String)>> {
+    let mut buffers = Vec::new();
+
+    for buffer in api::list_bufs() {
+        let opts = OptionOpts::builder().buffer(buffer.clone()).build();
+        let listed: bool = api::get_option_value("buflisted", &opts)?;
+        let buftype: String = api::get_option_value("buftype", &opts)?;
+        let name = buffer.get_name()?;
+
+        if !listed || !buftype.is_empty() || name.as_os_str().is_empty() {
+            continue;
+        }
+
+        let shown = location::shown_name(&name)?;
+        buffers.push((buffer, shown));
+        if buffers.len() == OPTIONS.1 {
+            break;
+        }
+    }
+
+    Ok(buffers)
+}
+
+fn cancel(plugin: &mut Plugin) -> Result<()> {
+    match plugin.votes.current.take() {
+        Some(_) => {
+            say(plugin, tr("votes.canceled"));
+            notify(&tr("votes.canceled"), LogLevel::Info)
+        }
+        None => notify(&tr("votes.none"), LogLevel::Error),
+    }
+}
+
+/// Counts a `!vote`, anything but an option of the running vote is left
+/// out. Focus votes only take `!focus`, so its permissions hold.
+pub fn cast(plugin: &mut Plugin, channel: &str, author: &str, choice: &str) -> Result<()> {
+    let option = match &plugin.votes.current {
+        Some(vote) if vote.buffers.is_empty() => vote.option(choice),
+        _ => None,
+    };
+
+    match option {
+        Some(option) => count(plugin, channel, author, option),
+        None => Ok(()),
+    }
+}
+
+/// Counts a `!focus`, which only picks the buffers of a
+/// `:TwitchFocusVote`.
+pub fn focus(plugin: &mut Plugin, channel: &str, author: &str, number: usize) -> Result<()> {
+    match &plugin.votes.current {
+        Some(vote) if (1..=vote.buffers.len()).contains(&number) => {
+            count(plugin, channel, author, number - 1)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn count(plugin: &mut Plugin, channel: &str, author: &str, option: usize) -> Result<()> {
+    if channel != plugin.config.channel {
+        return Ok(());
+    }
+
+    let Some(vote) = &mut plugin.votes.current else {
+        return Ok(());
+    };
+
+    if let Some(previous) = vote.voters.insert(author.to_lowercase(), option) {
+        vote.counts[previous] -= 1;
+    }
+    vote.counts[option] += 1;
+
+    show(plugin)
+}
+
+/// Ends the vote once its time is up.
+pub fn tick(plugin: &mut Plugin) -> Result<()> {
+    match &plugin.votes.current {
+        Some(vote) if Instant::now() >= vote.ends => end(plugin),
+        _ => Ok(()),
+    }
+}
+
+fn end(plugin: &mut Plugin) -> Result<()> {
+    let Some(vote) = plugin.votes.current.take() else {
+        return notify(&tr("votes.none"), LogLevel::Error);
+    };
+
+    let poll = vote.poll(COMPLETED);
+    let zindex = plugin.chat.zindex();
+    plugin.polls.show_poll(poll.clone(), zindex)?;
+
+    let winner = vote.winner();
+    if let Some(buffer) = winner.and_then(|index| vote.buffers.get(index)) {
+        if let Err(e) = switch_to(buffer) {
+            plugin.errors.push("Vote", &e);
+        }
+    }
+
+    let winner = winner.map(|index| vote.options[index].clone());
+    let text = match &winner {
+        Some(winner) => tr_with("votes.won", &[("title", &vote.title), ("option", winner)]),
+        None => tr_with("votes.tie", &[("title", &vote.title)]),
+    };
+    say(plugin, text.clone());
+    commands::announce(plugin, &format!("🗳 {}", text))?;
+
+    // Through dispatch, so the result reaches `on_result`, the autocommand and
+    // the webhooks.
+    commands::dispatch(
+        plugin,
+        Command::VoteEnded {
+            title: vote.title,
+            choices: poll.choices,
+            winner,
+        },
+    );
+
+    Ok(())
+}
+
+/// Shows `buffer` in the current window, or the one before it when that's
+/// a float such as the chat window. A buffer wiped since is left be.
+fn switch_to(buffer: &Buffer) -> Result<()> {
+    if !buffer.is_valid() {
+        return Ok(());
+    }
+
+    if api::get_current_win().get_config()?.relative.is_some() {
+        api::command("wincmd p")?;
+    }
+    api::set_current_buf(buffer)?;
+
+    Ok(())
+}
+
+fn show(plugin: &mut Plugin) -> Result<()> {
+    let Some(vote) = &plugin.votes.current else {
+        return Ok(());
+    };
+
+    let poll = vote.poll(ACTIVE);
+    let zindex = plugin.chat.zindex();
+    plugin.polls.show_poll(poll, zindex).map(|_| ())
+}
+
+fn say(plugin: &Plugin, text: String) {
+    let _ = plugin.outgoing.send(Outgoing::Reply {
+        channel: plugin.config.channel.clone(),
+        text,
+    });
+}
+
+fn notify(message: &str, level: LogLevel) -> Result<()> {
+    api::notify(message, level, &NotifyOpts::default())?;
+    Ok(())
+}
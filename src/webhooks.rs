@@ -0,0 +1,87 @@
+//! POSTs events to user-configured URLs. Matching happens on the Neovim
+//! thread, delivery on the connection thread so a slow receiver never
+//! blocks the editor.
+
+use std::time::Duration;
+
+use tokio::time;
+
+use crate::{commands::Command, events::Event, twitch::Outgoing, Plugin};
+
+const ATTEMPTS: u32 = 3;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Queues `event` for every webhook subscribed to its type.
+pub fn fire(plugin: &Plugin, event: &Event) {
+    if plugin.config.webhooks.is_empty() {
+        return;
+    }
+
+    // A failing webhook subscribed to errors would otherwise report its own
+    // failures forever.
+    if matches!(&event.command, Command::Error(error) if error.context() == "Webhook") {
+        return;
+    }
+
+    let Ok(body) = serde_json::to_value(event) else {
+        return;
+    };
+
+    let kind = body["type"].as_str().unwrap_or_default();
+
+    for webhook in &plugin.config.webhooks {
+        if webhook.events.iter().any(|event| event == kind) {
+            let _ = plugin.outgoing.send(Outgoing::Webhook {
+                url: webhook.url.clone(),
+                body: body.to_string(),
+            });
+        }
+    }
+}
+
+/// Retries with a growing delay on network errors, `429` and server errors.
+/// Other client errors mean the request itself is wrong and fail right away.
+pub async fn deliver(url: &str, body: String) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        let result = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+            .await;
+
+        let retryable = match result {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+
+                if !(status.is_server_error() || status.as_u16() == 429) {
+                    return Err(format!("{} answered {}", url, status));
+                }
+
+                format!("{} answered {}", url, status)
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if attempt == ATTEMPTS {
+            return Err(format!(
+                "{} (gave up after {} attempts)",
+                retryable, ATTEMPTS
+            ));
+        }
+
+        time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
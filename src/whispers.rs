@@ -0,0 +1,59 @@
+//! Whispers, Twitch's private messages. They don't belong to a channel, so
+//! rather than the chat window each pops up as a notification titled after
+//! the viewer, and `:TwitchWhisper <user> <text>` answers as the main
+//! account through Helix.
+
+use nvim_oxi::{
+    api::{self, opts::*, types::*},
+    Array, Object, Result,
+};
+
+use crate::{
+    i18n::{tr, tr_with},
+    twitch::Outgoing,
+    Plugin,
+};
+
+/// `vim.notify()` with a title, which notification plugins show apart.
+const NOTIFY: &str = "vim.notify(_A[1], vim.log.levels.INFO, { title = _A[2] })";
+
+/// Shows a whisper from `user`, or one `sent` to them.
+pub fn show(user: &str, text: &str, sent: bool) -> Result<()> {
+    let title = match sent {
+        true => tr_with("whispers.to", &[("user", &user)]),
+        false => tr_with("whispers.from", &[("user", &user)]),
+    };
+
+    let args = Array::from_iter([Object::from(text), Object::from(title)]);
+    api::call_function::<_, Object>("luaeval", (NOTIFY, args))?;
+
+    Ok(())
+}
+
+/// Runs `:TwitchWhisper <user> <text>`, the whisper is shown once Twitch
+/// took it.
+pub fn run(plugin: &Plugin, args: Option<&str>) -> Result<()> {
+    let (user, text) = args
+        .unwrap_or_default()
+        .trim()
+        .split_once(char::is_whitespace)
+        .map(|(user, text)| (user.trim_start_matches('@').to_lowercase(), text.trim()))
+        .unwrap_or_default();
+
+    let valid = !user.is_empty() && user.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || text.is_empty() {
+        api::notify(
+            &tr("whispers.usage"),
+            LogLevel::Error,
+            &NotifyOpts::default(),
+        )?;
+        return Ok(());
+    }
+
+    let _ = plugin.outgoing.send(Outgoing::Whisper {
+        to: user,
+        text: text.to_owned(),
+    });
+
+    Ok(())
+}